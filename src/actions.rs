@@ -0,0 +1,457 @@
+//! Named editor actions and the key bindings that trigger them
+
+use crate::commands::Command;
+use crate::splits::{FocusDirection, SplitDirection};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::fmt;
+
+/// Something the user can ask the editor to do
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// A plain editor command
+    Command(Command),
+    Quit,
+    CommandPalette,
+    ProjectSearch,
+    ReplaceInFiles,
+
+    // Undo tree
+    UndoTree,
+    UndoEarlierBy, // Asks how far back in time to go
+    UndoLaterBy,
+
+    // Kernel and running cells
+    RunCell,
+    RunAllCells,
+    RunStaleCells,
+    CancelExecution,
+    SelectKernel,
+    ToggleOwnKernel,
+
+    // Output pane and inline outputs
+    ToggleOutputPane,
+    FocusOutputPane,
+    ClearOutput,
+    ToggleOutputPlacement,
+    GrowOutputPane,
+    ShrinkOutputPane,
+    ScrollOutputUp,
+    ScrollOutputDown,
+    ToggleInlineOutputs,
+    ToggleCellOutput,
+    ScrollInlineOutputUp,
+    ScrollInlineOutputDown,
+    ExportReport,
+
+    // Entries in the focused output pane
+    FindInOutput,
+    ToggleOutputEntry,
+    CollapseAllOutputs,
+    ToggleErrorsOnly,
+    NextOutputEntry,
+    PrevOutputEntry,
+    OpenFullOutput,
+    CopyOutputEntry,
+
+    // Buffers
+    NewBuffer,
+    OpenFile,
+    QuickOpen,
+    CloseBuffer,
+    NextBuffer,
+    PrevBuffer,
+    MoveBufferLeft,
+    MoveBufferRight,
+    GoToBuffer(usize), // Zero-based position in the tab bar
+
+    // Split windows
+    SplitWindow(SplitDirection),
+    CloseWindow,
+    NextWindow,
+    FocusWindow(FocusDirection),
+}
+
+/// Where a binding applies - the find bar and the focused output pane see keys first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    Editor,
+    Find,
+    Output,
+}
+
+/// An action with its palette title and default keys
+pub struct ActionInfo {
+    pub id: &'static str, // Name used in config files, e.g. "run_cell"
+    pub action: Action,
+    pub category: &'static str,
+    pub title: &'static str,
+    pub scope: Scope,
+    pub keys: &'static [&'static str], // Default bindings, written like "Ctrl+Shift+E"
+}
+
+const fn entry(
+    id: &'static str,
+    category: &'static str,
+    title: &'static str,
+    action: Action,
+    keys: &'static [&'static str],
+) -> ActionInfo {
+    ActionInfo { id, action, category, title, scope: Scope::Editor, keys }
+}
+
+const fn scoped(
+    id: &'static str,
+    scope: Scope,
+    category: &'static str,
+    title: &'static str,
+    action: Action,
+    keys: &'static [&'static str],
+) -> ActionInfo {
+    ActionInfo { id, action, category, title, scope, keys }
+}
+
+/// Every action, in the order the palette lists them
+pub const ACTIONS: &[ActionInfo] = &[
+    // File
+    entry("save", "File", "Save", Action::Command(Command::Save), &["Ctrl+S"]),
+    entry("save_as", "File", "Save as", Action::Command(Command::SaveAs), &["Ctrl+Shift+S"]),
+    entry("export_report", "File", "Export notebook report", Action::ExportReport, &["Alt+X"]),
+    entry("quit", "File", "Quit", Action::Quit, &["Ctrl+Q"]),
+    entry("command_palette", "Help", "Command palette", Action::CommandPalette, &["Ctrl+Shift+P", "F1"]),
+
+    // Editing
+    entry("undo", "Edit", "Undo", Action::Command(Command::Undo), &["Ctrl+Z"]),
+    entry("redo", "Edit", "Redo", Action::Command(Command::Redo), &["Ctrl+Shift+Z"]),
+    entry("undo_earlier", "Edit", "Earlier undo state", Action::Command(Command::UndoEarlier), &["Alt+,"]),
+    entry("undo_later", "Edit", "Later undo state", Action::Command(Command::UndoLater), &["Alt+."]),
+    entry("switch_redo_branch", "Edit", "Switch redo branch", Action::Command(Command::SwitchRedoBranch), &["Alt+B"]),
+    entry("undo_earlier_by", "Edit", "Go back in time...", Action::UndoEarlierBy, &[]),
+    entry("undo_later_by", "Edit", "Go forward in time...", Action::UndoLaterBy, &[]),
+    entry("undo_tree", "Edit", "Undo tree", Action::UndoTree, &["Alt+T"]),
+    entry("cut", "Edit", "Cut", Action::Command(Command::Cut), &["Ctrl+X"]),
+    entry("copy", "Edit", "Copy", Action::Command(Command::Copy), &["Ctrl+C"]),
+    entry("paste", "Edit", "Paste", Action::Command(Command::Paste), &["Ctrl+V"]),
+    entry("select_all", "Edit", "Select all", Action::Command(Command::SelectAll), &["Ctrl+A"]),
+    entry("insert_newline", "Edit", "New line", Action::Command(Command::InsertNewline), &["Enter"]),
+    entry("indent", "Edit", "Indent selection or insert tab", Action::Command(Command::Indent), &["Tab"]),
+    entry("insert_tab", "Edit", "Insert tab", Action::Command(Command::InsertTab), &[]),
+    entry("dedent", "Edit", "Dedent", Action::Command(Command::Dedent), &["Shift+Tab"]),
+    entry("backspace", "Edit", "Delete character before cursor", Action::Command(Command::Backspace), &["Backspace"]),
+    entry("delete", "Edit", "Delete character after cursor", Action::Command(Command::Delete), &["Delete"]),
+
+    // Cursor movement and selection
+    entry("move_up", "Go", "Up", Action::Command(Command::MoveUp), &["Up"]),
+    entry("move_down", "Go", "Down", Action::Command(Command::MoveDown), &["Down"]),
+    entry("move_left", "Go", "Left", Action::Command(Command::MoveLeft), &["Left"]),
+    entry("move_right", "Go", "Right", Action::Command(Command::MoveRight), &["Right"]),
+    entry("move_home", "Go", "Line start", Action::Command(Command::MoveHome), &["Home"]),
+    entry("move_end", "Go", "Line end", Action::Command(Command::MoveEnd), &["End"]),
+    entry("page_up", "Go", "Page up", Action::Command(Command::PageUp), &["PageUp"]),
+    entry("page_down", "Go", "Page down", Action::Command(Command::PageDown), &["PageDown"]),
+    entry("move_word_left", "Go", "Previous word", Action::Command(Command::MoveWordLeft), &["Ctrl+Left"]),
+    entry("move_word_right", "Go", "Next word", Action::Command(Command::MoveWordRight), &["Ctrl+Right"]),
+    entry("move_paragraph_up", "Go", "Previous paragraph", Action::Command(Command::MoveParagraphUp), &["Ctrl+Up"]),
+    entry("move_paragraph_down", "Go", "Next paragraph", Action::Command(Command::MoveParagraphDown), &["Ctrl+Down"]),
+    entry("select_up", "Select", "Up", Action::Command(Command::SelectUp), &["Shift+Up"]),
+    entry("select_down", "Select", "Down", Action::Command(Command::SelectDown), &["Shift+Down"]),
+    entry("select_left", "Select", "Left", Action::Command(Command::SelectLeft), &["Shift+Left"]),
+    entry("select_right", "Select", "Right", Action::Command(Command::SelectRight), &["Shift+Right"]),
+    entry("select_home", "Select", "To line start", Action::Command(Command::SelectHome), &["Shift+Home"]),
+    entry("select_end", "Select", "To line end", Action::Command(Command::SelectEnd), &["Shift+End"]),
+    entry("select_word_left", "Select", "Previous word", Action::Command(Command::SelectWordLeft), &["Ctrl+Shift+Left"]),
+    entry("select_word_right", "Select", "Next word", Action::Command(Command::SelectWordRight), &["Ctrl+Shift+Right"]),
+    entry("select_paragraph_up", "Select", "Previous paragraph", Action::Command(Command::SelectParagraphUp), &["Ctrl+Shift+Up"]),
+    entry("select_paragraph_down", "Select", "Next paragraph", Action::Command(Command::SelectParagraphDown), &["Ctrl+Shift+Down"]),
+    entry("add_cursor_above", "Select", "Add cursor above", Action::Command(Command::AddCursorAbove), &["Alt+Shift+Up"]),
+    entry("add_cursor_below", "Select", "Add cursor below", Action::Command(Command::AddCursorBelow), &["Alt+Shift+Down"]),
+    entry("add_next_occurrence", "Select", "Add next occurrence", Action::Command(Command::AddNextOccurrence), &["Ctrl+D"]),
+    entry("clear_extra_cursors", "Select", "Back to one cursor", Action::Command(Command::ClearExtraCursors), &[]),
+
+    // Folding
+    entry("toggle_fold", "Fold", "Fold / unfold", Action::Command(Command::ToggleFold), &["Alt+Z"]),
+    entry("fold_all_cells", "Fold", "Fold all cells", Action::Command(Command::FoldAllCells), &["Alt+Shift+Z"]),
+    entry("unfold_all", "Fold", "Unfold all", Action::Command(Command::UnfoldAll), &["Alt+U"]),
+
+    // View
+    entry("toggle_soft_wrap", "View", "Soft wrap on / off", Action::Command(Command::ToggleSoftWrap), &["Alt+Shift+W"]),
+
+    // Find and replace; the bar's own keys only apply while it is open
+    entry("find_replace", "Find", "Find and replace", Action::Command(Command::FindReplace), &["Ctrl+F"]),
+    scoped("find_next", Scope::Find, "Find", "Next match", Action::Command(Command::FindNext), &["Ctrl+F"]),
+    scoped("find_prev", Scope::Find, "Find", "Previous match", Action::Command(Command::FindPrev), &["Ctrl+Shift+F"]),
+    scoped("replace", Scope::Find, "Find", "Replace match", Action::Command(Command::Replace), &["Ctrl+H"]),
+    scoped("replace_all", Scope::Find, "Find", "Replace all", Action::Command(Command::ReplaceAll), &["Ctrl+Shift+H"]),
+    scoped("toggle_find_regex", Scope::Find, "Find", "Toggle regular expression", Action::Command(Command::ToggleFindRegex), &["Alt+R"]),
+    scoped("toggle_find_match_case", Scope::Find, "Find", "Toggle match case", Action::Command(Command::ToggleFindMatchCase), &["Alt+C"]),
+    scoped("toggle_find_whole_word", Scope::Find, "Find", "Toggle whole word", Action::Command(Command::ToggleFindWholeWord), &["Alt+W"]),
+    scoped("toggle_find_in_selection", Scope::Find, "Find", "Toggle find in selection", Action::Command(Command::ToggleFindInSelection), &["Alt+L"]),
+    entry("project_search", "Find", "Search in project", Action::ProjectSearch, &["Ctrl+Shift+F", "Alt+F"]),
+    entry("replace_in_files", "Find", "Replace in files", Action::ReplaceInFiles, &["Ctrl+Shift+H"]),
+
+    // Kernel
+    entry("run_cell", "Kernel", "Run cell", Action::RunCell, &["Ctrl+Enter", "Ctrl+E"]),
+    entry("run_all_cells", "Kernel", "Run all cells", Action::RunAllCells, &["Ctrl+Shift+E"]),
+    entry("run_stale_cells", "Kernel", "Run stale cells", Action::RunStaleCells, &["Ctrl+R"]),
+    entry("cancel_execution", "Kernel", "Cancel execution (resets kernel)", Action::CancelExecution, &["Ctrl+Backspace", "Ctrl+H"]),
+    entry("select_kernel", "Kernel", "Select kernel", Action::SelectKernel, &["Ctrl+K"]),
+    entry("toggle_own_kernel", "Kernel", "Toggle own kernel for buffer", Action::ToggleOwnKernel, &["Alt+K"]),
+
+    // Output
+    entry("toggle_output_pane", "Output", "Toggle output pane", Action::ToggleOutputPane, &["Ctrl+O"]),
+    entry("focus_output_pane", "Output", "Focus output pane / editor", Action::FocusOutputPane, &["Esc"]),
+    entry("clear_output", "Output", "Clear output pane", Action::ClearOutput, &["Ctrl+L"]),
+    entry("toggle_output_placement", "Output", "Output pane below / beside", Action::ToggleOutputPlacement, &["Alt+V"]),
+    entry("grow_output_pane", "Output", "Grow output pane", Action::GrowOutputPane, &["Alt+Up", "Alt+Left"]),
+    entry("shrink_output_pane", "Output", "Shrink output pane", Action::ShrinkOutputPane, &["Alt+Down", "Alt+Right"]),
+    entry("scroll_output_up", "Output", "Scroll output pane up", Action::ScrollOutputUp, &["Shift+PageUp"]),
+    entry("scroll_output_down", "Output", "Scroll output pane down", Action::ScrollOutputDown, &["Shift+PageDown"]),
+    entry("toggle_inline_outputs", "Output", "Toggle inline outputs", Action::ToggleInlineOutputs, &["Ctrl+Shift+O"]),
+    entry("toggle_cell_output", "Output", "Collapse / expand cell output", Action::ToggleCellOutput, &["Alt+O"]),
+    entry("scroll_inline_output_up", "Output", "Scroll cell output up", Action::ScrollInlineOutputUp, &["Alt+PageUp"]),
+    entry("scroll_inline_output_down", "Output", "Scroll cell output down", Action::ScrollInlineOutputDown, &["Alt+PageDown"]),
+    scoped("find_in_output", Scope::Output, "Output pane", "Find in output", Action::FindInOutput, &["Ctrl+F", "/"]),
+    scoped("toggle_output_entry", Scope::Output, "Output pane", "Collapse / expand entry", Action::ToggleOutputEntry, &["Enter"]),
+    scoped("collapse_all_outputs", Scope::Output, "Output pane", "Collapse / expand all entries", Action::CollapseAllOutputs, &["c"]),
+    scoped("toggle_errors_only", Scope::Output, "Output pane", "Show errors only", Action::ToggleErrorsOnly, &["e"]),
+    scoped("next_output_entry", Scope::Output, "Output pane", "Next entry", Action::NextOutputEntry, &["n"]),
+    scoped("prev_output_entry", Scope::Output, "Output pane", "Previous entry", Action::PrevOutputEntry, &["p"]),
+    scoped("open_full_output", Scope::Output, "Output pane", "Open full output in a tab", Action::OpenFullOutput, &["o"]),
+    scoped("copy_output_entry", Scope::Output, "Output pane", "Copy entry", Action::CopyOutputEntry, &["y"]),
+
+    // Buffers
+    entry("new_buffer", "Buffers", "New buffer", Action::NewBuffer, &["Ctrl+N"]),
+    entry("open_file", "Buffers", "Open file", Action::OpenFile, &["Ctrl+T"]),
+    entry("quick_open", "Buffers", "Quick open", Action::QuickOpen, &["Ctrl+P"]),
+    entry("close_buffer", "Buffers", "Close buffer", Action::CloseBuffer, &["Ctrl+W"]),
+    entry("next_buffer", "Buffers", "Next buffer", Action::NextBuffer, &["Ctrl+PageDown"]),
+    entry("prev_buffer", "Buffers", "Previous buffer", Action::PrevBuffer, &["Ctrl+PageUp"]),
+    entry("move_buffer_left", "Buffers", "Move buffer left", Action::MoveBufferLeft, &["Ctrl+Shift+PageUp"]),
+    entry("move_buffer_right", "Buffers", "Move buffer right", Action::MoveBufferRight, &["Ctrl+Shift+PageDown"]),
+    entry("go_to_buffer_1", "Buffers", "Go to buffer 1", Action::GoToBuffer(0), &["Alt+1"]),
+    entry("go_to_buffer_2", "Buffers", "Go to buffer 2", Action::GoToBuffer(1), &["Alt+2"]),
+    entry("go_to_buffer_3", "Buffers", "Go to buffer 3", Action::GoToBuffer(2), &["Alt+3"]),
+    entry("go_to_buffer_4", "Buffers", "Go to buffer 4", Action::GoToBuffer(3), &["Alt+4"]),
+    entry("go_to_buffer_5", "Buffers", "Go to buffer 5", Action::GoToBuffer(4), &["Alt+5"]),
+    entry("go_to_buffer_6", "Buffers", "Go to buffer 6", Action::GoToBuffer(5), &["Alt+6"]),
+    entry("go_to_buffer_7", "Buffers", "Go to buffer 7", Action::GoToBuffer(6), &["Alt+7"]),
+    entry("go_to_buffer_8", "Buffers", "Go to buffer 8", Action::GoToBuffer(7), &["Alt+8"]),
+    entry("go_to_buffer_9", "Buffers", "Go to buffer 9", Action::GoToBuffer(8), &["Alt+9"]),
+
+    // Split windows
+    entry("split_below", "Windows", "Split below", Action::SplitWindow(SplitDirection::Horizontal), &["Alt+-"]),
+    entry("split_right", "Windows", "Split right", Action::SplitWindow(SplitDirection::Vertical), &["Alt+\\", "Alt+|"]),
+    entry("close_window", "Windows", "Close window", Action::CloseWindow, &["Alt+Q"]),
+    entry("next_window", "Windows", "Next window", Action::NextWindow, &["Alt+W"]),
+    entry("focus_window_up", "Windows", "Focus window above", Action::FocusWindow(FocusDirection::Up), &["Ctrl+Alt+Up"]),
+    entry("focus_window_down", "Windows", "Focus window below", Action::FocusWindow(FocusDirection::Down), &["Ctrl+Alt+Down"]),
+    entry("focus_window_left", "Windows", "Focus window to the left", Action::FocusWindow(FocusDirection::Left), &["Ctrl+Alt+Left"]),
+    entry("focus_window_right", "Windows", "Focus window to the right", Action::FocusWindow(FocusDirection::Right), &["Ctrl+Alt+Right"]),
+];
+
+/// A key with its modifiers, normalised so bindings compare equal however the terminal
+/// reports them (Shift+letter as an uppercase char, Shift+Tab as BackTab, ...)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBinding {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let mut modifiers = modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        let code = match code {
+            KeyCode::Char(c) if c.is_uppercase() => {
+                modifiers |= KeyModifiers::SHIFT;
+                KeyCode::Char(c.to_lowercase().next().unwrap_or(c))
+            }
+            // Shift is already part of symbols like '|' or '?'
+            KeyCode::Char(c) if !c.is_alphabetic() => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::Char(c)
+            }
+            KeyCode::BackTab => {
+                modifiers |= KeyModifiers::SHIFT;
+                KeyCode::Tab
+            }
+            code => code,
+        };
+        KeyBinding { code, modifiers }
+    }
+
+    /// Parse a binding written like "Ctrl+Shift+E", "Alt+-" or "F1"
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        // The key itself may be '+', so split the modifiers off the front
+        let (prefix, key) = match text.strip_suffix("++") {
+            Some(prefix) => (prefix, "+"),
+            None => match text.rsplit_once('+') {
+                Some((prefix, key)) => (prefix, key),
+                None => ("", text),
+            },
+        };
+
+        let mut modifiers = KeyModifiers::NONE;
+        for part in prefix.split('+').filter(|part| !part.is_empty()) {
+            modifiers |= match part.trim().to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "option" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return None,
+            };
+        }
+
+        let code = match key.trim().to_lowercase().as_str() {
+            "enter" | "return" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" | "ins" => KeyCode::Insert,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" | "pgup" => KeyCode::PageUp,
+            "pagedown" | "pgdn" => KeyCode::PageDown,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "space" => KeyCode::Char(' '),
+            name => {
+                let mut chars = key.trim().chars();
+                match (chars.next(), chars.next()) {
+                    // Letters are lowercase here - an uppercase one needs "Shift+"
+                    (Some(c), None) => KeyCode::Char(c.to_lowercase().next().unwrap_or(c)),
+                    _ => KeyCode::F(name.strip_prefix('f')?.parse().ok().filter(|n| (1..=24).contains(n))?),
+                }
+            }
+        };
+        Some(KeyBinding::new(code, modifiers))
+    }
+}
+
+impl From<&KeyEvent> for KeyBinding {
+    fn from(key: &KeyEvent) -> Self {
+        KeyBinding::new(key.code, key.modifiers)
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "Shift+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            // Letters read better in capitals when a modifier comes first
+            KeyCode::Char(c) if !self.modifiers.is_empty() => write!(f, "{}", c.to_uppercase()),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::Enter => write!(f, "Enter"),
+            KeyCode::Esc => write!(f, "Esc"),
+            KeyCode::Tab => write!(f, "Tab"),
+            KeyCode::Backspace => write!(f, "Backspace"),
+            KeyCode::Delete => write!(f, "Delete"),
+            KeyCode::Insert => write!(f, "Insert"),
+            KeyCode::Home => write!(f, "Home"),
+            KeyCode::End => write!(f, "End"),
+            KeyCode::PageUp => write!(f, "PageUp"),
+            KeyCode::PageDown => write!(f, "PageDown"),
+            KeyCode::Up => write!(f, "Up"),
+            KeyCode::Down => write!(f, "Down"),
+            KeyCode::Left => write!(f, "Left"),
+            KeyCode::Right => write!(f, "Right"),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+/// Which action each key triggers, per scope
+pub struct Keymap {
+    bindings: Vec<(Scope, KeyBinding, Action)>,
+}
+
+impl Default for Keymap {
+    /// The default keys of every action
+    fn default() -> Self {
+        let bindings = ACTIONS
+            .iter()
+            .flat_map(|info| {
+                info.keys
+                    .iter()
+                    .filter_map(|key| KeyBinding::parse(key))
+                    .map(|binding| (info.scope, binding, info.action.clone()))
+            })
+            .collect();
+        Keymap { bindings }
+    }
+}
+
+impl Keymap {
+    /// Action bound to a key in a scope
+    pub fn lookup(&self, scope: Scope, key: &KeyEvent) -> Option<Action> {
+        let pressed = KeyBinding::from(key);
+        self.bindings
+            .iter()
+            .find(|(bound_scope, binding, _)| *bound_scope == scope && *binding == pressed)
+            .map(|(_, _, action)| action.clone())
+    }
+
+    /// Keys bound to an action in any scope, for display
+    pub fn keys_for(&self, action: &Action) -> Vec<KeyBinding> {
+        self.bindings
+            .iter()
+            .filter(|(_, _, bound)| bound == action)
+            .map(|(_, binding, _)| *binding)
+            .collect()
+    }
+
+    /// Make a key trigger an action in a scope, replacing whatever it did there before
+    pub fn bind(&mut self, scope: Scope, binding: KeyBinding, action: Action) {
+        self.unbind(scope, binding);
+        self.bindings.push((scope, binding, action));
+    }
+
+    /// Make a key do nothing special in a scope
+    pub fn unbind(&mut self, scope: Scope, binding: KeyBinding) {
+        self.bindings.retain(|(bound_scope, bound, _)| !(*bound_scope == scope && *bound == binding));
+    }
+}
+
+/// Look an action up by its id - case, `_` and `-` don't matter, so the `Command` variant
+/// names ("MoveWordLeft") work too
+pub fn find_action(name: &str) -> Option<&'static ActionInfo> {
+    let simplify = |name: &str| name.chars().filter(|c| *c != '_' && *c != '-').collect::<String>().to_lowercase();
+    let wanted = simplify(name.trim());
+    ACTIONS.iter().find(|info| simplify(info.id) == wanted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_keys_dont_collide_within_a_scope() {
+        let mut seen: Vec<(Scope, KeyBinding, &str)> = Vec::new();
+        for info in ACTIONS {
+            for key in info.keys {
+                let binding = KeyBinding::parse(key).unwrap_or_else(|| panic!("{} has an unreadable key {}", info.id, key));
+                if let Some((_, _, other)) = seen.iter().find(|(scope, bound, _)| *scope == info.scope && *bound == binding) {
+                    panic!("{} is bound to both {} and {}", key, other, info.id);
+                }
+                seen.push((info.scope, binding, info.id));
+            }
+        }
+    }
+
+    #[test]
+    fn esc_focuses_the_output_pane() {
+        let esc = KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE);
+        assert_eq!(Keymap::default().lookup(Scope::Editor, &esc), Some(Action::FocusOutputPane));
+    }
+}
@@ -0,0 +1,232 @@
+//! Terminal-style interpretation of kernel output: SGR colors, `\r` and backspace
+
+use crossterm::style::{Attribute, Color, ContentStyle};
+
+/// One line of output as it would appear on a terminal
+#[derive(Debug, Clone, Default)]
+pub struct StyledLine {
+    /// Visible text, escape sequences removed
+    pub text: String,
+    /// Styled character ranges as (start_char, end_char, style); unstyled text has no span
+    pub spans: Vec<(usize, usize, ContentStyle)>,
+}
+
+impl StyledLine {
+    /// Style of the character at `char_idx`, if it has one
+    pub fn style_at(&self, char_idx: usize) -> Option<ContentStyle> {
+        self.spans.iter()
+            .find(|(start, end, _)| char_idx >= *start && char_idx < *end)
+            .map(|(_, _, style)| *style)
+    }
+}
+
+/// Split output into lines the way a terminal would show them
+/// `\r` returns to the start of the line and later text overwrites it (progress bars),
+/// backspace steps back one column, and SGR sequences become styled spans
+pub fn parse_output(text: &str) -> Vec<StyledLine> {
+    let mut lines = Vec::new();
+    let mut style = ContentStyle::default();
+    let mut cells: Vec<(char, ContentStyle)> = Vec::new();
+    let mut col: usize = 0;
+    let mut chars = text.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\n' => {
+                lines.push(finish_line(&cells));
+                cells.clear();
+                col = 0;
+            }
+            '\r' => col = 0,
+            '\x08' => col = col.saturating_sub(1),
+            '\x1b' => match chars.peek() {
+                Some('[') => {
+                    chars.next();
+                    // Parameters run until the final byte (@ through ~)
+                    let mut params = String::new();
+                    let mut final_byte = None;
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            final_byte = Some(c);
+                            break;
+                        }
+                        params.push(c);
+                    }
+                    match (final_byte, params.as_str()) {
+                        (Some('m'), _) => apply_sgr(&mut style, &params),
+                        // Erase to end of line / whole line
+                        (Some('K'), "" | "0") => cells.truncate(col),
+                        (Some('K'), "2") => cells.clear(),
+                        _ => {}
+                    }
+                }
+                Some(']') => {
+                    // OSC (window titles, hyperlinks) - skip to BEL or ESC \
+                    chars.next();
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' {
+                            break;
+                        }
+                        if c == '\x1b' && chars.peek() == Some(&'\\') {
+                            chars.next();
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            },
+            c if c.is_control() && c != '\t' => {}
+            c => {
+                if col < cells.len() {
+                    cells[col] = (c, style);
+                } else {
+                    cells.resize(col, (' ', ContentStyle::default()));
+                    cells.push((c, style));
+                }
+                col += 1;
+            }
+        }
+    }
+
+    if !cells.is_empty() {
+        lines.push(finish_line(&cells));
+    }
+    lines
+}
+
+/// Build a line from its cells, merging runs of the same style into spans
+fn finish_line(cells: &[(char, ContentStyle)]) -> StyledLine {
+    let mut line = StyledLine::default();
+    let mut run_start = 0;
+
+    for (idx, (ch, style)) in cells.iter().enumerate() {
+        line.text.push(*ch);
+        let run_ends = cells.get(idx + 1).is_none_or(|(_, next)| next != style);
+        if run_ends {
+            if *style != ContentStyle::default() {
+                line.spans.push((run_start, idx + 1, *style));
+            }
+            run_start = idx + 1;
+        }
+    }
+    line
+}
+
+/// Apply an SGR parameter list (`1;31`, `38;5;208`, `38;2;255;0;0`, ...) to a style
+fn apply_sgr(style: &mut ContentStyle, params: &str) {
+    let codes: Vec<u16> = params.split(';').map(|p| p.parse().unwrap_or(0)).collect();
+    let mut i = 0;
+
+    while i < codes.len() {
+        match codes[i] {
+            0 => *style = ContentStyle::default(),
+            1 => style.attributes.set(Attribute::Bold),
+            2 => style.attributes.set(Attribute::Dim),
+            3 => style.attributes.set(Attribute::Italic),
+            4 => style.attributes.set(Attribute::Underlined),
+            7 => style.attributes.set(Attribute::Reverse),
+            9 => style.attributes.set(Attribute::CrossedOut),
+            22 => {
+                style.attributes.unset(Attribute::Bold);
+                style.attributes.unset(Attribute::Dim);
+            }
+            23 => style.attributes.unset(Attribute::Italic),
+            24 => style.attributes.unset(Attribute::Underlined),
+            27 => style.attributes.unset(Attribute::Reverse),
+            29 => style.attributes.unset(Attribute::CrossedOut),
+            code @ 30..=37 => style.foreground_color = Some(basic_color(code - 30, false)),
+            code @ 90..=97 => style.foreground_color = Some(basic_color(code - 90, true)),
+            code @ 40..=47 => style.background_color = Some(basic_color(code - 40, false)),
+            code @ 100..=107 => style.background_color = Some(basic_color(code - 100, true)),
+            39 => style.foreground_color = None,
+            49 => style.background_color = None,
+            code @ (38 | 48) => {
+                // Extended colors: 5;n (256-color) or 2;r;g;b (truecolor)
+                let color = match codes.get(i + 1) {
+                    Some(5) => {
+                        let color = codes.get(i + 2).map(|&n| Color::AnsiValue(n.min(255) as u8));
+                        i += 2;
+                        color
+                    }
+                    Some(2) => {
+                        let channel = |offset: usize| codes.get(i + offset).map(|&v| v.min(255) as u8);
+                        let color = match (channel(2), channel(3), channel(4)) {
+                            (Some(r), Some(g), Some(b)) => Some(Color::Rgb { r, g, b }),
+                            _ => None,
+                        };
+                        i += 4;
+                        color
+                    }
+                    _ => None,
+                };
+                if code == 38 {
+                    style.foreground_color = color.or(style.foreground_color);
+                } else {
+                    style.background_color = color.or(style.background_color);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+}
+
+/// One of the 8 standard colors, or its bright variant
+fn basic_color(index: u16, bright: bool) -> Color {
+    match (index, bright) {
+        (0, false) => Color::Black,
+        (1, false) => Color::DarkRed,
+        (2, false) => Color::DarkGreen,
+        (3, false) => Color::DarkYellow,
+        (4, false) => Color::DarkBlue,
+        (5, false) => Color::DarkMagenta,
+        (6, false) => Color::DarkCyan,
+        (7, false) => Color::Grey,
+        (0, true) => Color::DarkGrey,
+        (1, true) => Color::Red,
+        (2, true) => Color::Green,
+        (3, true) => Color::Yellow,
+        (4, true) => Color::Blue,
+        (5, true) => Color::Magenta,
+        (6, true) => Color::Cyan,
+        _ => Color::White,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(text: &str) -> Vec<String> {
+        parse_output(text).into_iter().map(|line| line.text).collect()
+    }
+
+    #[test]
+    fn carriage_return_and_backspace_overwrite() {
+        assert_eq!(texts("10%\r50%\r100%\ndone"), ["100%", "done"]);
+        assert_eq!(texts("long line\rshort"), ["shortline"]);
+        assert_eq!(texts("abc\x08\x08X"), ["aXc"]);
+        assert_eq!(texts("progress\r\x1b[Kok\n"), ["ok"]);
+        assert_eq!(texts("a\n\nb"), ["a", "", "b"]);
+    }
+
+    #[test]
+    fn escape_sequences_are_removed() {
+        assert_eq!(texts("\x1b]0;title\x07hi\x1b]8;;http://x\x1b\\link"), ["hilink"]);
+        assert_eq!(texts("\x1b[1;31mred\x1b[0m plain"), ["red plain"]);
+    }
+
+    #[test]
+    fn sgr_codes_become_spans() {
+        let line = &parse_output("\x1b[1;31mred\x1b[0m plain \x1b[38;5;208mo\x1b[48;2;1;2;3mx")[0];
+        let red = line.style_at(0).unwrap();
+        assert_eq!(red.foreground_color, Some(Color::DarkRed));
+        assert!(red.attributes.has(Attribute::Bold));
+        assert_eq!(line.style_at(4), None);
+        assert_eq!(line.style_at(10).unwrap().foreground_color, Some(Color::AnsiValue(208)));
+        let both = line.style_at(11).unwrap();
+        assert_eq!(both.foreground_color, Some(Color::AnsiValue(208)));
+        assert_eq!(both.background_color, Some(Color::Rgb { r: 1, g: 2, b: 3 }));
+        assert_eq!(line.spans.len(), 3);
+    }
+}
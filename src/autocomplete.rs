@@ -1,7 +1,8 @@
+use crate::theme::{self, Element};
 use crossterm::{
     cursor,
     execute,
-    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+    style::{Print, ResetColor, SetBackgroundColor, SetForegroundColor},
 };
 use std::io::{self, Write};
 
@@ -203,6 +204,7 @@ impl Autocomplete {
         };
 
         // Draw each visible suggestion
+        let theme = theme::current();
         for (display_idx, actual_idx) in (start_idx..end_idx).enumerate() {
             let suggestion = &self.suggestions[actual_idx];
             let row = dropdown_row + display_idx as u16;
@@ -210,20 +212,13 @@ impl Autocomplete {
 
             execute!(writer, cursor::MoveTo(dropdown_col, row))?;
 
-            if is_selected {
-                // Highlight selected item
-                execute!(
-                    writer,
-                    SetBackgroundColor(Color::DarkBlue),
-                    SetForegroundColor(Color::White),
-                )?;
-            } else {
-                execute!(
-                    writer,
-                    SetBackgroundColor(Color::DarkGrey),
-                    SetForegroundColor(Color::White),
-                )?;
-            }
+            // Highlight selected item
+            let style = theme[if is_selected { Element::CompletionSelected } else { Element::Completion }];
+            execute!(
+                writer,
+                SetBackgroundColor(style.background()),
+                SetForegroundColor(style.foreground()),
+            )?;
 
             // Pad to max width
             let padded = format!(" {:<width$} ", suggestion, width = max_width);
@@ -1,8 +1,14 @@
 use ropey::Rope;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use crate::undo_tree::{UndoGroup, UndoTree};
 use std::time::{Duration, Instant};
 
+/// Changes that added or removed lines, kept so line numbers held elsewhere can follow them
+const MAX_LINE_SHIFTS: usize = 4096;
+
 /// Represents a single edit operation for undo/redo
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum EditOp {
     Insert {
         pos: usize,      // byte position
@@ -14,33 +20,49 @@ pub enum EditOp {
     },
 }
 
-/// Groups related edit operations (e.g., continuous typing)
-#[derive(Debug)]
-struct UndoGroup {
-    ops: Vec<EditOp>,
-    cursor_before: usize,
-    cursor_after: usize,
+/// Make an edit again, or take it back. Returns where text went in or came out, the text,
+/// and whether it came out
+fn apply_op<'a>(rope: &mut Rope, op: &'a EditOp, forward: bool) -> (usize, &'a str, bool) {
+    match (op, forward) {
+        (EditOp::Insert { pos, text }, true) | (EditOp::Delete { pos, text }, false) => {
+            let char_pos = rope.byte_to_char(*pos);
+            rope.insert(char_pos, text);
+            (*pos, text, false)
+        }
+        (EditOp::Insert { pos, text }, false) | (EditOp::Delete { pos, text }, true) => {
+            let char_start = rope.byte_to_char(*pos);
+            let char_end = char_start + text.chars().count();
+            rope.remove(char_start..char_end);
+            (*pos, text, true)
+        }
+    }
 }
 
 /// Text buffer with undo/redo support
 pub struct Buffer {
     rope: Rope,
-    undo_stack: Vec<UndoGroup>,
-    redo_stack: Vec<UndoGroup>,
+    undo_tree: UndoTree,
     current_group: Option<UndoGroup>,
     last_edit_time: Option<Instant>,
     group_timeout: Duration,
+    holding_group: bool,              // Every edit joins the current group, however long it takes
+    version: u64,                     // Bumped on every change to the text
+    line_shifts: VecDeque<(u64, usize, isize)>, // (version, first line, lines added) of recent changes
+    line_shifts_from: u64,            // Oldest version the line shifts are complete from
 }
 
 impl Buffer {
     pub fn new() -> Self {
         Self {
             rope: Rope::new(),
-            undo_stack: Vec::new(),
-            redo_stack: Vec::new(),
+            undo_tree: UndoTree::new(),
             current_group: None,
             last_edit_time: None,
             group_timeout: Duration::from_millis(300), // Group edits within 300ms
+            holding_group: false,
+            version: 0,
+            line_shifts: VecDeque::new(),
+            line_shifts_from: 0,
         }
     }
     
@@ -63,14 +85,22 @@ impl Buffer {
         
         Self {
             rope: Rope::from_str(&s),
-            undo_stack: Vec::new(),
-            redo_stack: Vec::new(),
+            undo_tree: UndoTree::new(),
             current_group: None,
             last_edit_time: None,
             group_timeout: Duration::from_millis(300),
+            holding_group: false,
+            version: 0,
+            line_shifts: VecDeque::new(),
+            line_shifts_from: 0,
         }
     }
     
+    /// Counter that changes whenever the text changes
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
     /// Get the rope for reading
     pub fn rope(&self) -> &Rope {
         &self.rope
@@ -132,13 +162,15 @@ impl Buffer {
         let char_pos = self.byte_to_char(pos);
         self.rope.insert(char_pos, text);
         
+        self.record_line_shift(pos, text, false);
+
         let op = EditOp::Insert {
             pos,
             text: text.to_string(),
         };
         
         self.push_op(op, cursor_before, cursor_after);
-        self.redo_stack.clear();
+        self.version += 1;
     }
     
     /// Delete a range of bytes
@@ -153,6 +185,7 @@ impl Buffer {
         let char_start = self.byte_to_char(start);
         let char_end = self.byte_to_char(end);
         self.rope.remove(char_start..char_end);
+        self.record_line_shift(start, &text, true);
         
         let op = EditOp::Delete {
             pos: start,
@@ -160,17 +193,47 @@ impl Buffer {
         };
         
         self.push_op(op, cursor_before, cursor_after);
-        self.redo_stack.clear();
+        self.version += 1;
     }
     
+    /// Note the lines a change at `pos` added or removed, for the change that bumps the version next.
+    /// Lines from the one recorded on move down by the count, or are removed first when it's negative
+    fn record_line_shift(&mut self, pos: usize, text: &str, removed: bool) {
+        let lines = text.matches('\n').count() as isize;
+        if lines == 0 {
+            return;
+        }
+        let line = self.byte_to_line(pos);
+        // A change at the very start of a line moves that line too
+        let first = if self.line_to_byte(line) == pos { line } else { line + 1 };
+        self.line_shifts.push_back((self.version + 1, first, if removed { -lines } else { lines }));
+        if self.line_shifts.len() > MAX_LINE_SHIFTS {
+            if let Some((version, _, _)) = self.line_shifts.pop_front() {
+                self.line_shifts_from = version;
+            }
+        }
+    }
+
+    /// Lines added or removed since `version`, as (first line, lines added) in the order they
+    /// happened; None when the changes go back further than is remembered
+    pub fn line_shifts_since(&self, version: u64) -> Option<Vec<(usize, isize)>> {
+        if version < self.line_shifts_from || version > self.version {
+            return None;
+        }
+        Some(self.line_shifts.iter()
+            .filter(|(changed, _, _)| *changed > version)
+            .map(|&(_, line, lines)| (line, lines))
+            .collect())
+    }
+
     /// Push an operation to the current undo group
     fn push_op(&mut self, op: EditOp, cursor_before: usize, cursor_after: usize) {
         let now = Instant::now();
-        let should_start_new_group = self.last_edit_time
+        let should_start_new_group = !self.holding_group && self.last_edit_time
             .map(|t| now.duration_since(t) > self.group_timeout)
             .unwrap_or(true);
         
-        if should_start_new_group {
+        if should_start_new_group || self.current_group.is_none() {
             self.finalize_undo_group();
             self.current_group = Some(UndoGroup {
                 ops: vec![op],
@@ -185,11 +248,29 @@ impl Buffer {
         self.last_edit_time = Some(now);
     }
     
+    /// Make every edit until `end_undo_group` part of one undo step, e.g. the same edit at
+    /// several cursors. The step still joins quick typing before it, like any other edit
+    pub fn begin_undo_group(&mut self) {
+        let timed_out = self.last_edit_time.is_none_or(|t| t.elapsed() > self.group_timeout);
+        if timed_out {
+            self.finalize_undo_group();
+        }
+        self.holding_group = true;
+    }
+
+    /// Go back to grouping edits by time
+    pub fn end_undo_group(&mut self) {
+        self.holding_group = false;
+        if self.current_group.is_some() {
+            self.last_edit_time = Some(Instant::now());
+        }
+    }
+
     /// Finalize the current undo group
     pub fn finalize_undo_group(&mut self) {
         if let Some(group) = self.current_group.take() {
             if !group.ops.is_empty() {
-                self.undo_stack.push(group);
+                self.undo_tree.push(group);
             }
         }
     }
@@ -197,73 +278,113 @@ impl Buffer {
     /// Undo the last group of operations
     pub fn undo(&mut self) -> Option<usize> {
         self.finalize_undo_group();
-        
-        if let Some(group) = self.undo_stack.pop() {
-            let cursor = group.cursor_before;
-            
-            // Apply operations in reverse
-            for op in group.ops.iter().rev() {
-                match op {
-                    EditOp::Insert { pos, text } => {
-                        // Undo an insert by deleting
-                        let char_start = self.byte_to_char(*pos);
-                        let char_end = char_start + text.chars().count();
-                        self.rope.remove(char_start..char_end);
-                    }
-                    EditOp::Delete { pos, text } => {
-                        // Undo a delete by inserting
-                        let char_pos = self.byte_to_char(*pos);
-                        self.rope.insert(char_pos, text);
-                    }
-                }
-            }
-            
-            self.redo_stack.push(group);
-            return Some(cursor);
-        }
-        
-        None
+        let parent = self.undo_tree.parent(self.undo_tree.current())?;
+        self.go_to_state(parent)
     }
     
-    /// Redo the last undone group
+    /// Redo the last undone group, or the branch picked to redo
     pub fn redo(&mut self) -> Option<usize> {
-        if let Some(group) = self.redo_stack.pop() {
-            let cursor = group.cursor_after;
-            
-            // Apply operations forward
-            for op in &group.ops {
-                match op {
-                    EditOp::Insert { pos, text } => {
-                        let char_pos = self.byte_to_char(*pos);
-                        self.rope.insert(char_pos, text);
-                    }
-                    EditOp::Delete { pos, text } => {
-                        let char_start = self.byte_to_char(*pos);
-                        let char_end = char_start + text.chars().count();
-                        self.rope.remove(char_start..char_end);
-                    }
-                }
+        self.finalize_undo_group();
+        let child = self.undo_tree.redo_child(self.undo_tree.current())?;
+        self.go_to_state(child)
+    }
+
+    /// Undo and redo the way to another state of the undo tree. Returns where the cursor goes:
+    /// before the last edit undone, or after the last one made
+    pub fn go_to_state(&mut self, state: usize) -> Option<usize> {
+        self.finalize_undo_group();
+        if state == self.undo_tree.current() || state >= self.undo_tree.len() {
+            return None;
+        }
+        let (undone, redone) = self.undo_tree.go_to(state);
+        let mut cursor = 0;
+        for node in undone {
+            let group = self.undo_tree.group(node).clone();
+            self.apply_group(&group, false);
+            cursor = group.cursor_before;
+        }
+        for node in redone {
+            let group = self.undo_tree.group(node).clone();
+            self.apply_group(&group, true);
+            cursor = group.cursor_after;
+        }
+        self.version += 1;
+        Some(cursor)
+    }
+
+    /// Make a group's edits again in order, or take them back in reverse
+    fn apply_group(&mut self, group: &UndoGroup, forward: bool) {
+        let ops: Vec<&EditOp> = if forward { group.ops.iter().collect() } else { group.ops.iter().rev().collect() };
+        for op in ops {
+            let (pos, text, removed) = apply_op(&mut self.rope, op, forward);
+            self.record_line_shift(pos, text, removed);
+        }
+    }
+
+    /// The text as it is in another state of the undo tree
+    pub fn text_at_state(&self, state: usize) -> String {
+        let mut rope = self.rope.clone();
+        let (undone, redone) = self.undo_tree.path_to(state);
+        for node in undone {
+            for op in self.undo_tree.group(node).ops.iter().rev() {
+                apply_op(&mut rope, op, false);
             }
-            
-            self.undo_stack.push(group);
-            return Some(cursor);
         }
-        
-        None
+        for node in redone {
+            for op in &self.undo_tree.group(node).ops {
+                apply_op(&mut rope, op, true);
+            }
+        }
+        rope.to_string()
     }
-    
+
+    /// Every state the text has been in, as of the last finished undo step
+    pub fn undo_tree(&self) -> &UndoTree {
+        &self.undo_tree
+    }
+
+    /// Make redo from the current state go to its next branch (or previous), returning the
+    /// branch's position among them and how many there are
+    pub fn switch_redo_branch(&mut self, forward: bool) -> Option<(usize, usize)> {
+        self.finalize_undo_group();
+        self.undo_tree.switch_branch(self.undo_tree.current(), forward)
+    }
+
     /// Get the entire buffer as a string (for saving)
     pub fn to_string(&self) -> String {
         self.rope.to_string()
     }
     
+    /// State of the undo tree the text is in, counting edits still being grouped as the
+    /// state they'll make
+    pub fn undo_state(&self) -> usize {
+        if self.current_group.is_some() {
+            self.undo_tree.len()
+        } else {
+            self.undo_tree.current()
+        }
+    }
+
+    /// The undo tree so far, e.g. to keep with the saved file
+    pub fn history(&mut self) -> UndoTree {
+        self.finalize_undo_group();
+        self.undo_tree.clone()
+    }
+
+    /// Take over an undo tree from an earlier session. It must have been taken from a buffer
+    /// with the same text, or undoing it garbles it
+    pub fn restore_history(&mut self, history: UndoTree) {
+        self.current_group = None;
+        self.undo_tree = history;
+    }
+
     /// Check if there are undo operations available
     pub fn can_undo(&self) -> bool {
-        !self.undo_stack.is_empty() || self.current_group.is_some()
+        self.undo_tree.parent(self.undo_tree.current()).is_some() || self.current_group.is_some()
     }
     
     /// Check if there are redo operations available
     pub fn can_redo(&self) -> bool {
-        !self.redo_stack.is_empty()
+        self.current_group.is_none() && self.undo_tree.redo_child(self.undo_tree.current()).is_some()
     }
 }
\ No newline at end of file
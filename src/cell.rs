@@ -1,5 +1,9 @@
 use crate::kernel::ExecutionResult;
 use ropey::Rope;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::sync::RwLock;
 
 /// Represents a cell in the notebook
 #[derive(Debug, Clone)]
@@ -12,8 +16,36 @@ pub struct Cell {
     pub cell_type: CellType,
     /// Execution output (if executed)
     pub output: Option<ExecutionResult>,
+    /// Hash of the output's text, to find its inline lines already formatted
+    pub output_hash: u64,
     /// Execution count
     pub execution_count: Option<usize>,
+    /// Tags from the delimiter line, e.g. `##$$ [skip, slow] title`
+    pub tags: Vec<String>,
+    /// Hash of the cell's code (delimiter line excluded)
+    pub source_hash: u64,
+    /// Hash of the code as it was last executed
+    pub executed_hash: Option<u64>,
+    /// Wall-clock time of the last run
+    pub elapsed_secs: Option<f64>,
+    /// An upstream cell this one depends on was edited or re-run since it last ran
+    pub stale: bool,
+    /// Inline output folded down to its header line
+    pub output_collapsed: bool,
+    /// First visible line of the inline output when it's taller than the block
+    pub output_scroll: usize,
+}
+
+impl Cell {
+    /// Check whether the delimiter line carries the given tag
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// Check whether the code was edited since the cell was last executed
+    pub fn is_modified(&self) -> bool {
+        self.executed_hash.is_some_and(|hash| hash != self.source_hash)
+    }
 }
 
 /// Type of cell
@@ -25,9 +57,70 @@ pub enum CellType {
     Markdown,
 }
 
+/// Status badge drawn at the right edge of a cell's delimiter line
+#[derive(Debug, Clone, PartialEq)]
+pub enum CellBadge {
+    /// Depends on a cell that was edited or re-run since this one ran
+    Stale,
+    /// Code was edited since the cell last ran
+    Modified,
+    /// Currently executing (`[*]`)
+    Running,
+    /// Waiting for earlier cells in the batch
+    Queued,
+    /// Execution count of the last run (`[7]`)
+    Count(usize),
+    /// Duration of the last run in seconds
+    Elapsed(f64),
+    /// Last run succeeded
+    Ok,
+    /// Last run raised
+    Error,
+}
+
 /// Cell delimiter marker
 pub const CELL_DELIMITER: &str = "##$$";
 
+/// Markers that start a cell, from the config; empty means just `CELL_DELIMITER`
+static DELIMITERS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// Split cells at these markers instead, e.g. `##$$` and the `# %%` of VS Code and Jupytext
+/// The first is the one written when sage adds a delimiter line
+pub fn set_delimiters(delimiters: &[String]) {
+    *DELIMITERS.write().unwrap_or_else(|e| e.into_inner()) = delimiters.to_vec();
+}
+
+/// Marker written when sage adds a delimiter line
+pub fn primary_delimiter() -> String {
+    let delimiters = DELIMITERS.read().unwrap_or_else(|e| e.into_inner());
+    delimiters.first().cloned().unwrap_or_else(|| CELL_DELIMITER.to_string())
+}
+
+/// Length of the delimiter a line starts with, after its indentation
+pub fn delimiter_len(line: &str) -> Option<usize> {
+    let line = line.trim_start();
+    let delimiters = DELIMITERS.read().unwrap_or_else(|e| e.into_inner());
+    if delimiters.is_empty() {
+        return line.starts_with(CELL_DELIMITER).then_some(CELL_DELIMITER.len());
+    }
+    // The longest marker wins where one is a prefix of another
+    delimiters.iter().filter(|marker| line.starts_with(marker.as_str())).map(|marker| marker.len()).max()
+}
+
+/// Check whether a line starts a cell
+pub fn is_delimiter_line(line: &str) -> bool {
+    delimiter_len(line).is_some()
+}
+
+/// Left out of run-all and headless runs
+pub const TAG_SKIP: &str = "skip";
+/// Asks for confirmation before running
+pub const TAG_SLOW: &str = "slow";
+/// Runs automatically after a kernel connects or restarts
+pub const TAG_SETUP: &str = "setup";
+/// Expected to error - doesn't stop a batch
+pub const TAG_RAISES: &str = "raises";
+
 /// Parse buffer into cells
 pub fn parse_cells(buffer: &Rope) -> Vec<Cell> {
     let mut cells = Vec::new();
@@ -37,7 +130,7 @@ pub fn parse_cells(buffer: &Rope) -> Vec<Cell> {
     // Find all cell delimiters
     let mut delimiter_positions = Vec::new();
     for (line_idx, line) in text.lines().enumerate() {
-        if line.trim_start().starts_with(CELL_DELIMITER) {
+        if is_delimiter_line(line) {
             // Calculate byte position of this line
             let byte_pos = buffer.line_to_byte(line_idx);
             delimiter_positions.push(byte_pos);
@@ -46,13 +139,23 @@ pub fn parse_cells(buffer: &Rope) -> Vec<Cell> {
 
     // If no delimiters found, treat entire buffer as one cell
     if delimiter_positions.is_empty() {
-        cells.push(Cell {
+        let mut cell = Cell {
             start: 0,
             end: buffer.len_bytes(),
             cell_type: CellType::Code,
             output: None,
+            output_hash: 0,
             execution_count: None,
-        });
+            tags: Vec::new(),
+            source_hash: 0,
+            executed_hash: None,
+            elapsed_secs: None,
+            stale: false,
+            output_collapsed: false,
+            output_scroll: 0,
+        };
+        cell.source_hash = source_hash(&get_cell_content(buffer, &cell));
+        cells.push(cell);
         return cells;
     }
 
@@ -80,6 +183,7 @@ pub fn parse_cells(buffer: &Rope) -> Vec<Cell> {
         } else {
             CellType::Code
         };
+        let tags = parse_delimiter_tags(&line_text);
 
         // Cell starts after the delimiter line
         let cell_start = if delimiter_line_idx + 1 < buffer.len_lines() {
@@ -88,18 +192,145 @@ pub fn parse_cells(buffer: &Rope) -> Vec<Cell> {
             buffer.len_bytes()
         };
 
-        cells.push(Cell {
+        let mut cell = Cell {
             start: delimiter_pos,
             end: end_pos,
             cell_type,
             output: None,
+            output_hash: 0,
             execution_count: None,
-        });
+            tags,
+            source_hash: 0,
+            executed_hash: None,
+            elapsed_secs: None,
+            stale: false,
+            output_collapsed: false,
+            output_scroll: 0,
+        };
+        cell.source_hash = source_hash(&get_cell_content(buffer, &cell));
+        cells.push(cell);
     }
 
     cells
 }
 
+/// Hash of a cell's code, used to tell whether it changed since it last ran
+pub fn source_hash(code: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    code.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Carry execution state (output, counts, staleness) from the previous parse over to
+/// freshly parsed cells. Unchanged cells are matched from the front and back; whatever
+/// is left in the middle is paired up by position (those are the edited cells).
+pub fn carry_cell_state(old: &[Cell], new: &mut [Cell]) {
+    let prefix = old.iter().zip(new.iter())
+        .take_while(|(a, b)| a.source_hash == b.source_hash)
+        .count();
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let suffix = old.iter().rev().zip(new.iter().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a.source_hash == b.source_hash)
+        .count();
+
+    let (old_len, new_len) = (old.len(), new.len());
+    let pairs = (0..prefix)
+        .map(|i| (i, i))
+        .chain((0..suffix).map(|i| (old_len - 1 - i, new_len - 1 - i)))
+        .chain((prefix..old_len - suffix).zip(prefix..new_len - suffix));
+
+    for (old_idx, new_idx) in pairs {
+        let from = &old[old_idx];
+        let to = &mut new[new_idx];
+        to.output = from.output.clone();
+        to.output_hash = from.output_hash;
+        to.execution_count = from.execution_count;
+        to.executed_hash = from.executed_hash;
+        to.elapsed_secs = from.elapsed_secs;
+        to.stale = from.stale;
+        to.output_collapsed = from.output_collapsed;
+        to.output_scroll = from.output_scroll;
+    }
+}
+
+/// Mark every cell after `changed_idx` that reads (directly or through other cells)
+/// a name the changed cell defines as stale
+pub fn mark_downstream_stale(cells: &mut [Cell], changed_idx: usize) {
+    let mut changed: HashSet<String> = match cells.get(changed_idx).and_then(|c| c.output.as_ref()).and_then(|o| o.names.as_ref()) {
+        Some(names) => names.defines.iter().cloned().collect(),
+        None => return,
+    };
+
+    for cell in cells.iter_mut().skip(changed_idx + 1) {
+        if changed.is_empty() {
+            break;
+        }
+        let Some(names) = cell.output.as_ref().and_then(|o| o.names.as_ref()) else {
+            continue;
+        };
+
+        if names.reads.iter().any(|name| changed.contains(name)) {
+            // Everything this cell defines is now out of date too
+            cell.stale = true;
+            changed.extend(names.defines.iter().cloned());
+        } else {
+            // Redefined without depending on the change - later cells see the new value
+            for name in &names.defines {
+                changed.remove(name);
+            }
+        }
+    }
+}
+
+/// Parse the tags from a delimiter line: `##$$ [skip, slow, setup] title`
+/// A bare `##$$ parameters`, with nothing after it, is also accepted for the parameters cell
+pub fn parse_delimiter_tags(line: &str) -> Vec<String> {
+    let rest = match delimiter_len(line) {
+        Some(len) => line.trim_start()[len..].trim_start(),
+        None => return Vec::new(),
+    };
+
+    if let Some((start, end)) = tag_list_range(line) {
+        return line[start + 1..end - 1]
+            .split(',')
+            .map(|tag| tag.trim().to_lowercase())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+    }
+
+    if rest.trim_end() == crate::parameters::PARAMETERS_TAG {
+        vec![crate::parameters::PARAMETERS_TAG.to_string()]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Byte range of the `[...]` tag list on a delimiter line, brackets included
+pub fn tag_list_range(line: &str) -> Option<(usize, usize)> {
+    let indent = line.len() - line.trim_start().len();
+    let delimiter_len = delimiter_len(line)?;
+    let after_delimiter = &line[indent + delimiter_len..];
+    let offset = indent + delimiter_len + (after_delimiter.len() - after_delimiter.trim_start().len());
+
+    if !line[offset..].starts_with('[') {
+        return None;
+    }
+    let close = line[offset..].find(']')?;
+    Some((offset, offset + close + 1))
+}
+
+/// Get the delimiter line of a cell, if the cell starts with one
+pub fn get_cell_header(buffer: &Rope, cell: &Cell) -> Option<String> {
+    let start_line = buffer.byte_to_line(cell.start);
+    let line = buffer.line(start_line).to_string();
+    if is_delimiter_line(&line) {
+        Some(line.trim_end_matches(['\n', '\r']).to_string())
+    } else {
+        None
+    }
+}
+
 /// Get the cell at a given byte position
 pub fn get_cell_at_position(cells: &[Cell], position: usize) -> Option<usize> {
     cells
@@ -177,8 +408,8 @@ pub fn format_output(result: &ExecutionResult) -> String {
                         continue;
                     }
 
-                    // Skip internal REPL lines (SAGE_EXEC markers)
-                    if trimmed.contains("SAGE_EXEC") || trimmed.contains("<string>") {
+                    // Skip internal REPL lines (SAGE_EXEC markers) and frames of the cell itself
+                    if trimmed.contains("SAGE_EXEC") || trimmed.contains("<string>") || trimmed.contains("<cell>") {
                         skip_internal = true;
                         continue;
                     }
@@ -211,3 +442,58 @@ pub fn format_output(result: &ExecutionResult) -> String {
 
     formatted
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_come_from_the_bracketed_list() {
+        assert_eq!(parse_delimiter_tags("##$$ [skip, Slow] Load data"), ["skip", "slow"]);
+        assert_eq!(parse_delimiter_tags("  ##$$[setup,,raises]"), ["setup", "raises"]);
+        assert_eq!(parse_delimiter_tags("##$$ []"), Vec::<String>::new());
+        assert_eq!(parse_delimiter_tags("##$$ Load data [skip]"), Vec::<String>::new());
+        assert_eq!(parse_delimiter_tags("x = 1  # [skip]"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn bare_parameters_word_is_a_tag() {
+        assert_eq!(parse_delimiter_tags("##$$ parameters"), ["parameters"]);
+        assert_eq!(parse_delimiter_tags("##$$ parameters  \n"), ["parameters"]);
+        assert_eq!(parse_delimiter_tags("##$$ [parameters] Defaults"), ["parameters"]);
+        assert_eq!(parse_delimiter_tags("##$$ Parameters for the run"), Vec::<String>::new());
+        assert_eq!(parse_delimiter_tags("##$$ parameters for the run"), Vec::<String>::new());
+        assert_eq!(parse_delimiter_tags("##$$ parameter sweep"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn tag_list_range_includes_brackets() {
+        assert_eq!(tag_list_range("##$$ [skip] title"), Some((5, 11)));
+        assert_eq!(tag_list_range("  ##$$[a]"), Some((6, 9)));
+        assert_eq!(tag_list_range("##$$ [unclosed"), None);
+        assert_eq!(tag_list_range("##$$ title"), None);
+    }
+
+    /// Cells of `old` given execution counts 1, 2, ... carried over to the cells of `new`
+    fn carried_counts(old: &str, new: &str) -> Vec<Option<usize>> {
+        let mut old_cells = parse_cells(&Rope::from_str(old));
+        for (idx, cell) in old_cells.iter_mut().enumerate() {
+            cell.execution_count = Some(idx + 1);
+        }
+        let mut new_cells = parse_cells(&Rope::from_str(new));
+        carry_cell_state(&old_cells, &mut new_cells);
+        new_cells.iter().map(|cell| cell.execution_count).collect()
+    }
+
+    #[test]
+    fn state_follows_unchanged_cells() {
+        let old = "##$$\na = 1\n##$$\nb = 2\n##$$\nc = 3\n";
+        // An edited cell keeps its place's state
+        assert_eq!(carried_counts(old, "##$$\na = 1\n##$$\nb = 20\n##$$\nc = 3\n"), [Some(1), Some(2), Some(3)]);
+        // A new cell starts without any
+        assert_eq!(carried_counts(old, "##$$\nx = 0\n##$$\na = 1\n##$$\nb = 2\n##$$\nc = 3\n"), [None, Some(1), Some(2), Some(3)]);
+        assert_eq!(carried_counts(old, "##$$\na = 1\n##$$\nb = 2\n##$$\nx = 0\n##$$\nc = 3\n"), [Some(1), Some(2), None, Some(3)]);
+        // A deleted cell's state goes with it
+        assert_eq!(carried_counts(old, "##$$\na = 1\n##$$\nc = 3\n"), [Some(1), Some(3)]);
+    }
+}
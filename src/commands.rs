@@ -1,5 +1,5 @@
 /// All possible editor commands
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Command {
     // Movement
     MoveUp,
@@ -52,10 +52,31 @@ pub enum Command {
     FindPrev,
     Replace,
     ReplaceAll,
+    ToggleFindRegex,
+    ToggleFindMatchCase,
+    ToggleFindWholeWord,
+    ToggleFindInSelection,
     
     // Undo/Redo
     Undo,
     Redo,
+    UndoEarlier,      // The state made before the current one, on any branch
+    UndoLater,
+    SwitchRedoBranch, // Make redo take the next branch from here
+
+    // Multiple cursors
+    AddCursorAbove,
+    AddCursorBelow,
+    AddNextOccurrence,
+    ClearExtraCursors,
+
+    // Folding
+    ToggleFold,
+    FoldAllCells,
+    UnfoldAll,
+
+    // View
+    ToggleSoftWrap,
     
     // No operation
     None,
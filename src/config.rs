@@ -0,0 +1,378 @@
+//! User and project configuration files
+
+use crate::actions::{self, Keymap, KeyBinding, Scope};
+use crate::cell;
+use crate::editor::{self, Editor, LineNumbers};
+use crate::layout::PanePlacement;
+use crate::output_pane;
+use crate::swap::AutoSave;
+use crate::theme::{self, ColorDepth};
+use serde::Deserialize;
+use unicode_width::UnicodeWidthStr;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+/// Name of the per-project config file
+pub const PROJECT_FILE: &str = ".sage.toml";
+/// Where the debug log goes unless the config says otherwise
+const DEFAULT_DEBUG_LOG: &str = "/tmp/sage_debug.log";
+
+/// Debug log path from the config; None until one is loaded
+static DEBUG_LOG: RwLock<Option<String>> = RwLock::new(None);
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub editor: EditorConfig,
+    pub output: OutputConfig,
+    pub kernel: KernelConfig,
+    pub cells: CellsConfig,
+    /// Key chord to action id, with `[keys.find]` and `[keys.output]` for those scopes
+    pub keys: toml::Table,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EditorConfig {
+    pub tab_width: usize,  // Spaces per indent level, and per tab when loading files
+    pub line_numbers: LineNumbers, // "absolute", "relative" or "off" for no gutter
+    pub soft_wrap: bool,   // Wrap long lines at the window width instead of scrolling sideways
+    pub wrap_column: usize, // Wrap here instead if the window is wider; 0 for the window width
+    pub wrap_indicator: String, // Drawn at the start of continuation rows
+    pub persistent_undo: bool, // Keep undo history with saved files, to undo past reopening them
+    pub undo_history_kb: usize, // Most undo history kept per file, in KiB of edited text
+    pub swap_interval: u64,     // Seconds between writes of unsaved text to swap files; 0 for none
+    pub auto_save: AutoSave,    // "off", "focus" when the terminal loses focus, or "delay"
+    pub auto_save_delay: u64,   // Seconds the text is left alone before saving with "delay"
+    pub debug_log: String, // Empty to turn the debug log off
+    pub theme: String,     // Built-in theme, theme file in the themes directory, or path to one
+    pub colors: String,    // "auto", "truecolor", "256" or "16"
+}
+
+impl Default for EditorConfig {
+    fn default() -> Self {
+        EditorConfig {
+            tab_width: editor::DEFAULT_TAB_WIDTH,
+            line_numbers: LineNumbers::default(),
+            soft_wrap: false,
+            wrap_column: 0,
+            wrap_indicator: editor::DEFAULT_WRAP_INDICATOR.to_string(),
+            persistent_undo: true,
+            undo_history_kb: 1024,
+            swap_interval: 4,
+            auto_save: AutoSave::default(),
+            auto_save_delay: 5,
+            debug_log: DEFAULT_DEBUG_LOG.to_string(),
+            theme: theme::BUILTIN_THEMES[0].to_string(),
+            colors: "auto".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    pub visible: bool,
+    pub placement: PanePlacement,
+    pub height: usize, // Rows when at the bottom
+    pub width: usize,  // Columns when on the right
+    pub lines: usize,  // Lines kept from each end of long outputs
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        OutputConfig {
+            visible: true,
+            placement: PanePlacement::Bottom,
+            height: 8,
+            width: 60,
+            lines: output_pane::DEFAULT_OUTPUT_LINES,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct KernelConfig {
+    /// Python interpreter to connect to at start-up, and to run files with when
+    /// neither `--python` nor a shebang names one
+    pub python: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CellsConfig {
+    /// Markers that start a cell, e.g. "##$$" or "# %%"; the first is used for new cells
+    pub delimiters: Vec<String>,
+}
+
+impl Default for CellsConfig {
+    fn default() -> Self {
+        CellsConfig { delimiters: vec![cell::CELL_DELIMITER.to_string()] }
+    }
+}
+
+impl Config {
+    /// Problems serde can't catch, as messages without a location
+    fn check(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if !(1..=16).contains(&self.editor.tab_width) {
+            problems.push(format!("editor.tab_width must be between 1 and 16, not {}", self.editor.tab_width));
+        }
+        let indicator = &self.editor.wrap_indicator;
+        if indicator.width() > 4 || indicator.chars().any(char::is_control) {
+            problems.push(format!("editor.wrap_indicator must be at most 4 columns of text, not '{}'", indicator));
+        }
+        if self.editor.auto_save == AutoSave::Delay && self.editor.auto_save_delay == 0 {
+            problems.push("editor.auto_save_delay must be at least 1 second".to_string());
+        }
+        if ColorDepth::from_setting(&self.editor.colors).is_none() {
+            problems.push(format!("editor.colors must be auto, truecolor, 256 or 16, not '{}'", self.editor.colors));
+        }
+        if self.output.height == 0 || self.output.width == 0 {
+            problems.push("output.height and output.width must be at least 1".to_string());
+        }
+        if self.cells.delimiters.is_empty() {
+            problems.push("cells.delimiters needs at least one marker".to_string());
+        }
+        for marker in &self.cells.delimiters {
+            // Anything else would stop the file from being plain Python
+            if !marker.starts_with('#') || marker.trim() != marker {
+                problems.push(format!("cell delimiter '{}' must start with '#' and have no surrounding spaces", marker));
+            }
+        }
+        problems
+    }
+}
+
+/// The user config file: `$XDG_CONFIG_HOME/sage/config.toml`, `~/.config/sage/config.toml`,
+/// or `%APPDATA%\sage\config.toml` on Windows
+pub fn user_config_path() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+    base.map(|dir| dir.join("sage").join("config.toml"))
+}
+
+/// Directory for what sage keeps between sessions, such as undo history:
+/// `$XDG_CACHE_HOME/sage` or `~/.cache/sage`, and `%LOCALAPPDATA%\sage` on Windows
+pub fn cache_dir() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_CACHE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+    };
+    base.map(|dir| dir.join("sage"))
+}
+
+/// Directory of theme files, next to the user config file
+fn themes_dir() -> Option<PathBuf> {
+    user_config_path().and_then(|path| path.parent().map(|dir| dir.join("themes")))
+}
+
+/// The nearest `.sage.toml` at or above a directory
+fn project_config_path(project_dir: &Path) -> Option<PathBuf> {
+    project_dir.ancestors().map(|dir| dir.join(PROJECT_FILE)).find(|path| path.is_file())
+}
+
+/// Where debug messages go, or None when the log is turned off
+pub fn debug_log_path() -> Option<PathBuf> {
+    match DEBUG_LOG.read().unwrap_or_else(|e| e.into_inner()).as_deref() {
+        None => Some(PathBuf::from(DEFAULT_DEBUG_LOG)),
+        Some("") => None,
+        Some(path) => Some(PathBuf::from(path)),
+    }
+}
+
+/// "file:line: message" for a TOML error, with the line worked out from its span
+fn describe_error(path: &Path, text: &str, error: &toml::de::Error) -> String {
+    // Messages can run over several lines; the status bar has one
+    let message = error.message().lines().map(str::trim).filter(|line| !line.is_empty()).collect::<Vec<_>>().join(", ");
+    match error.span() {
+        Some(span) => {
+            format!("{}:{}: {}", path.display(), theme::line_at(text, span.start), message)
+        }
+        None => format!("{}: {}", path.display(), message),
+    }
+}
+
+/// Copy `overrides` into `base`, merging tables that both have
+fn merge_tables(base: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(existing)), toml::Value::Table(table)) => merge_tables(existing, table),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Apply a `[keys]` table to the keymap; returns the entries that made no sense
+fn apply_keys(keymap: &mut Keymap, keys: &toml::Table, scope: Scope, path: &Path) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (chord, value) in keys {
+        let name = match (value, scope) {
+            (toml::Value::String(name), _) => name,
+            (toml::Value::Table(table), Scope::Editor) if chord == "find" || chord == "output" => {
+                let scope = if chord == "find" { Scope::Find } else { Scope::Output };
+                problems.extend(apply_keys(keymap, table, scope, path));
+                continue;
+            }
+            _ => {
+                problems.push(format!("{}: [keys] '{}' should be an action name", path.display(), chord));
+                continue;
+            }
+        };
+
+        let Some(binding) = KeyBinding::parse(chord) else {
+            problems.push(format!("{}: [keys] unknown key '{}'", path.display(), chord));
+            continue;
+        };
+        if name.eq_ignore_ascii_case("none") {
+            keymap.unbind(scope, binding);
+        } else if let Some(info) = actions::find_action(name) {
+            keymap.bind(scope, binding, info.action.clone());
+        } else {
+            problems.push(format!("{}: [keys] '{}' is bound to unknown action '{}'", path.display(), chord, name));
+        }
+    }
+    problems
+}
+
+/// The settings in effect, and the files they came from
+pub struct Settings {
+    pub config: Config,
+    pub keymap: Keymap,
+    pub errors: Vec<String>, // Problems found in the files, reported in the status bar
+    project_dir: PathBuf,
+    files: Vec<(PathBuf, Option<SystemTime>)>, // Files read and their modification times
+    theme_file: Option<PathBuf>,               // Theme file in use, watched with the config files
+}
+
+impl Settings {
+    /// Read the user config, then the project config for files in `project_dir`
+    pub fn load(project_dir: &Path) -> Self {
+        let mut settings = Settings {
+            config: Config::default(),
+            keymap: Keymap::default(),
+            errors: Vec::new(),
+            project_dir: project_dir.to_path_buf(),
+            files: Vec::new(),
+            theme_file: None,
+        };
+        settings.read_files();
+        settings
+    }
+
+    /// Read the files again if one of them was changed, created or removed
+    pub fn reload_if_changed(&mut self) -> bool {
+        if self.watched_files() == self.files {
+            return false;
+        }
+        self.read_files();
+        true
+    }
+
+    /// One line for the status bar about any problems
+    pub fn error_summary(&self) -> Option<String> {
+        let first = self.errors.first()?;
+        Some(match self.errors.len() {
+            1 => format!("Config: {}", first),
+            n => format!("Config: {} (+{} more)", first, n - 1),
+        })
+    }
+
+    /// An empty editor using these settings
+    pub fn new_editor(&self) -> Editor {
+        let mut editor = Editor::new();
+        editor.set_tab_width(self.config.editor.tab_width);
+        editor.set_line_numbers(self.config.editor.line_numbers);
+        let config = &self.config.editor;
+        editor.set_soft_wrap(config.soft_wrap, config.wrap_column, &config.wrap_indicator);
+        editor.set_persistent_undo(config.persistent_undo, config.undo_history_kb);
+        editor
+    }
+
+    /// Config files that apply, in order, with their modification times
+    fn watched_files(&self) -> Vec<(PathBuf, Option<SystemTime>)> {
+        user_config_path()
+            .into_iter()
+            .chain(project_config_path(&self.project_dir))
+            .chain(self.theme_file.clone())
+            .map(|path| {
+                let modified = std::fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+                (path, modified)
+            })
+            .collect()
+    }
+
+    fn read_files(&mut self) {
+        self.files = self.watched_files();
+        self.errors.clear();
+        let mut merged = toml::Table::new();
+        let mut keymap = Keymap::default();
+
+        for (path, modified) in &self.files {
+            if modified.is_none() || Some(path) == self.theme_file.as_ref() {
+                continue;
+            }
+            let text = match std::fs::read_to_string(path) {
+                Ok(text) => text,
+                Err(e) => {
+                    self.errors.push(format!("{}: {}", path.display(), e));
+                    continue;
+                }
+            };
+
+            // A file with any mistake is left out entirely, so half of it never applies
+            let config = match toml::from_str::<Config>(&text) {
+                Ok(config) => config,
+                Err(e) => {
+                    self.errors.push(describe_error(path, &text, &e));
+                    continue;
+                }
+            };
+            let problems = config.check();
+            if !problems.is_empty() {
+                self.errors.extend(problems.into_iter().map(|problem| format!("{}: {}", path.display(), problem)));
+                continue;
+            }
+
+            self.errors.extend(apply_keys(&mut keymap, &config.keys, Scope::Editor, path));
+            if let Ok(table) = toml::from_str::<toml::Table>(&text) {
+                merge_tables(&mut merged, table);
+            }
+        }
+
+        // Each file was valid on its own, so the two together are too
+        self.config = toml::Value::Table(merged).try_into().unwrap_or_default();
+        self.keymap = keymap;
+
+        cell::set_delimiters(&self.config.cells.delimiters);
+        let depth = ColorDepth::from_setting(&self.config.editor.colors).unwrap_or_else(ColorDepth::detect);
+        let (theme, theme_file) = match theme::load(&self.config.editor.theme, themes_dir().as_deref()) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                self.errors.push(e);
+                (theme::Theme::default(), None)
+            }
+        };
+        theme::install(theme, depth);
+        // Watch the theme file too, from the next check on
+        if theme_file != self.theme_file {
+            self.theme_file = theme_file;
+            self.files = self.watched_files();
+        }
+        *DEBUG_LOG.write().unwrap_or_else(|e| e.into_inner()) = Some(self.config.editor.debug_log.clone());
+    }
+}
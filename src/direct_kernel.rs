@@ -37,6 +37,8 @@ import json
 import os
 import io
 import contextlib
+import ast
+import builtins
 
 # Ensure we're not in interactive mode
 sys.ps1 = sys.ps2 = ''
@@ -52,6 +54,121 @@ except (AttributeError, OSError):
 # Ensure TERM is set to dumb to avoid escape codes
 os.environ['TERM'] = 'dumb'
 
+def _sage_bound_names(node):
+    # Names a function, lambda, class or comprehension binds in its own scope
+    bound, declared = set(), set()
+    if isinstance(node, (ast.FunctionDef, ast.AsyncFunctionDef, ast.Lambda)):
+        args = node.args
+        for arg in getattr(args, 'posonlyargs', []) + args.args + args.kwonlyargs + [args.vararg, args.kwarg]:
+            if arg is not None:
+                bound.add(arg.arg)
+        todo = list(node.body) if isinstance(node.body, list) else [node.body]
+    elif isinstance(node, ast.ClassDef):
+        todo = list(node.body)
+    else:
+        todo = [gen.target for gen in node.generators]
+    while todo:
+        child = todo.pop()
+        if isinstance(child, ast.Name) and not isinstance(child.ctx, ast.Load):
+            bound.add(child.id)
+        elif isinstance(child, (ast.Global, ast.Nonlocal)):
+            declared.update(child.names)
+        elif isinstance(child, (ast.Import, ast.ImportFrom)):
+            bound.update((alias.asname or alias.name).split('.')[0] for alias in child.names if alias.name != '*')
+        elif isinstance(child, ast.ExceptHandler) and child.name:
+            bound.add(child.name)
+        if isinstance(child, (ast.FunctionDef, ast.AsyncFunctionDef, ast.ClassDef)):
+            bound.add(child.name)
+            continue
+        if isinstance(child, (ast.Lambda, ast.ListComp, ast.SetComp, ast.DictComp, ast.GeneratorExp)):
+            continue
+        todo.extend(ast.iter_child_nodes(child))
+    return bound - declared
+
+class _SageNameVisitor(ast.NodeVisitor):
+    # Collects the top-level names a cell defines and the top-level names it reads
+    def __init__(self):
+        self.defines = set()
+        self.reads = set()
+        self.scopes = []  # (names bound, is a class) for each scope being visited, innermost last
+
+    def _is_local(self, name):
+        # A class body's names aren't seen from the functions inside it
+        for depth, (bound, is_class) in enumerate(reversed(self.scopes)):
+            if name in bound and (depth == 0 or not is_class):
+                return True
+        return False
+
+    def _visit_scope(self, node, outside, inside):
+        # `outside` is evaluated where the node is, `inside` in the node's own scope
+        for child in outside:
+            self.visit(child)
+        self.scopes.append((_sage_bound_names(node), isinstance(node, ast.ClassDef)))
+        for child in inside:
+            self.visit(child)
+        self.scopes.pop()
+
+    def visit_Name(self, node):
+        if isinstance(node.ctx, ast.Load):
+            if not self._is_local(node.id):
+                self.reads.add(node.id)
+        elif not self.scopes:
+            self.defines.add(node.id)
+
+    def visit_AugAssign(self, node):
+        if isinstance(node.target, ast.Name) and not self._is_local(node.target.id):
+            self.reads.add(node.target.id)
+        self.generic_visit(node)
+
+    def visit_Global(self, node):
+        self.defines.update(node.names)
+
+    def visit_Import(self, node):
+        if self.scopes:
+            return
+        for alias in node.names:
+            if alias.name != '*':
+                self.defines.add((alias.asname or alias.name).split('.')[0])
+
+    visit_ImportFrom = visit_Import
+
+    def visit_FunctionDef(self, node):
+        if not self.scopes:
+            self.defines.add(node.name)
+        args = node.args
+        outside = node.decorator_list + args.defaults + [default for default in args.kw_defaults if default is not None]
+        self._visit_scope(node, outside, node.body)
+
+    visit_AsyncFunctionDef = visit_FunctionDef
+
+    def visit_Lambda(self, node):
+        args = node.args
+        outside = args.defaults + [default for default in args.kw_defaults if default is not None]
+        self._visit_scope(node, outside, [node.body])
+
+    def visit_ClassDef(self, node):
+        if not self.scopes:
+            self.defines.add(node.name)
+        outside = node.decorator_list + node.bases + [keyword.value for keyword in node.keywords]
+        self._visit_scope(node, outside, node.body)
+
+    def _visit_comprehension(self, node):
+        # The first iterable is evaluated outside the comprehension's scope
+        first, rest = node.generators[0], node.generators[1:]
+        inside = [first.target] + first.ifs
+        for gen in rest:
+            inside += [gen.target, gen.iter] + gen.ifs
+        inside += [node.key, node.value] if isinstance(node, ast.DictComp) else [node.elt]
+        self._visit_scope(node, [first.iter], inside)
+
+    visit_ListComp = visit_SetComp = visit_DictComp = visit_GeneratorExp = _visit_comprehension
+
+def _sage_name_usage(code):
+    visitor = _SageNameVisitor()
+    visitor.visit(ast.parse(code))
+    reads = visitor.reads - set(dir(builtins))
+    return {"defines": sorted(visitor.defines), "reads": sorted(reads)}
+
 print("SAGE_KERNEL_READY", flush=True)
 
 while True:
@@ -79,11 +196,11 @@ while True:
         try:
             # First, try to eval the entire code (for simple expressions)
             with contextlib.redirect_stdout(stdout_capture):
-                _sage_result = eval(code, globals())
+                _sage_result = eval(compile(code, "<cell>", "eval"), globals())
         except SyntaxError:
             # If eval fails, just exec the entire code block
             with contextlib.redirect_stdout(stdout_capture):
-                exec(code, globals())
+                exec(compile(code, "<cell>", "exec"), globals())
 
         # Send captured stdout if any
         captured = stdout_capture.getvalue()
@@ -92,6 +209,16 @@ while True:
             print(json.dumps({"type": "stdout", "data": captured}), flush=True)
             print("SAGE_OUTPUT_END", flush=True)
 
+        # Send the names this cell defines and reads (for stale cell tracking)
+        try:
+            names_data = json.dumps({"type": "names", "data": _sage_name_usage(code)})
+        except Exception:
+            names_data = None
+        if names_data is not None:
+            print("SAGE_OUTPUT_START", flush=True)
+            print(names_data, flush=True)
+            print("SAGE_OUTPUT_END", flush=True)
+
         # Collect namespace completions for autocomplete
         # IMPORTANT: Send completions BEFORE the success/result marker
         try:
@@ -292,6 +419,7 @@ impl Kernel for DirectKernel {
         // Read outputs - there can be multiple output blocks (stdout, result, etc)
         let mut outputs = Vec::new();
         let mut completions = Vec::new();
+        let mut names = None;
         let mut success = false;
         let mut finished = false;
         let mut line = String::new();
@@ -360,6 +488,10 @@ impl Kernel for DirectKernel {
                     }
                     // Don't set finished - continue reading for success/result markers
                 }
+                Some("names") => {
+                    // Names defined/read by the cell, for dependency tracking
+                    names = serde_json::from_value::<crate::kernel::NameUsage>(output_data["data"].clone()).ok();
+                }
                 _ => {
                     finished = true;
                 }
@@ -375,6 +507,7 @@ impl Kernel for DirectKernel {
             execution_count: Some(self.execution_count),
             success,
             completions,
+            names,
         })
     }
 
@@ -410,3 +543,58 @@ impl Drop for DirectKernel {
         let _ = self.disconnect();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Names `code` defines and reads at the top level, worked out by the REPL script's helpers;
+    /// None only if there's no python3 to run them with
+    fn name_usage(code: &str) -> Option<(Vec<String>, Vec<String>)> {
+        let script = DirectKernel::get_repl_script();
+        let start = script.find("def _sage_bound_names").expect("name helpers missing from the REPL script");
+        let end = script.find("print(\"SAGE_KERNEL_READY\"").expect("ready line missing from the REPL script");
+        let helpers = &script[start..end];
+        let program = format!(
+            "import ast, builtins, json, sys\n{}\nprint(json.dumps(_sage_name_usage(sys.stdin.read())))\n",
+            helpers
+        );
+        let mut child = Command::new("python3")
+            .args(["-c", &program])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .ok()?;
+        child.stdin.take().unwrap().write_all(code.as_bytes()).unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success(), "name helpers failed:\n{}", String::from_utf8_lossy(&output.stderr));
+        let usage: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let names = |key: &str| -> Vec<String> {
+            usage[key].as_array().into_iter().flatten().filter_map(|name| name.as_str().map(String::from)).collect()
+        };
+        Some((names("defines"), names("reads")))
+    }
+
+    #[test]
+    #[ignore = "runs python3"]
+    fn parameter_does_not_hide_top_level_read() {
+        // Skipped where there's no python3
+        let Some((defines, reads)) = name_usage("def f(df):\n    return df.head()\n\nf(df)\n") else {
+            return;
+        };
+        assert_eq!(defines, ["f"]);
+        assert_eq!(reads, ["df", "f"]);
+    }
+
+    #[test]
+    #[ignore = "runs python3"]
+    fn scoped_names_stay_local() {
+        let code = "total = sum(x * scale for x in data)\nsquare = lambda n: n * n\ndef g(y):\n    z = y + offset\n    return z\n";
+        let Some((defines, reads)) = name_usage(code) else {
+            return;
+        };
+        assert_eq!(defines, ["g", "square", "total"]);
+        assert_eq!(reads, ["data", "offset", "scale"]);
+    }
+}
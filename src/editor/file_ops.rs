@@ -2,6 +2,7 @@ use crate::buffer::Buffer;
 use crate::syntax::SyntaxHighlighter;
 use crate::kernel::{self, Kernel};
 use crate::direct_kernel::DirectKernel;
+use crate::undo_history;
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
@@ -10,11 +11,11 @@ use super::Editor;
 
 impl Editor {
     /// Normalize text by removing invisible characters and converting line endings/tabs
-    pub(super) fn normalize_text(text: String) -> String {
+    pub(super) fn normalize_text(text: String, tab_width: usize) -> String {
         text.chars()
             .filter_map(|c| match c {
-                // Convert tabs to 4 spaces
-                '\t' => Some("    ".to_string()),
+                // Convert tabs to spaces
+                '\t' => Some(" ".repeat(tab_width)),
                 // Remove carriage returns (handled separately for CRLF)
                 '\r' => None,
                 // Remove zero-width and invisible characters
@@ -70,14 +71,24 @@ impl Editor {
     pub fn load_file(&mut self, path: &str) -> io::Result<()> {
         let content = fs::read_to_string(path)?;
         // Normalize: CRLF → LF, tabs → spaces, remove invisible characters
-        let content = Self::normalize_text(content);
+        let content = Self::normalize_text(content, self.tab_width);
         self.buffer = Buffer::from_string(content);
+        if self.undo_history_limit.is_some() {
+            if let Some(history) = undo_history::load(Path::new(path), &self.buffer.to_string()) {
+                self.buffer.restore_history(history);
+            }
+        }
+        self.cells.clear();
+        self.cells_version = None;
         self.file_path = Some(PathBuf::from(path));
         self.cursor = 0;
         self.selection_start = None;
+        self.clear_extra_cursors();
+        self.folds.clear();
+        self.reload_git_head();
         self.modified = false;
         self.viewport_offset = (0, 0);
-        self.last_saved_undo_len = 0;
+        self.saved_undo_state = self.buffer.undo_state();
         self.mouse_selecting = false;
         self.preferred_column = None;
 
@@ -315,8 +326,9 @@ impl Editor {
             match fs::write(path, self.buffer.to_string()) {
                 Ok(_) => {
                     self.modified = false;
-                    self.last_saved_undo_len = 0; // Reset save point
                     self.status_message = None; // Clear any error messages
+                    self.mark_saved();
+                    self.reload_git_head(); // Picks up commits made since the file was opened
                     Ok(())
                 }
                 Err(e) => {
@@ -346,9 +358,10 @@ impl Editor {
             Ok(_) => {
                 self.file_path = Some(path.clone());
                 self.modified = false;
-                self.last_saved_undo_len = 0; // Reset save point
                 self.read_only = new_read_only;
                 self.status_message = None; // Clear any error messages
+                self.mark_saved();
+                self.reload_git_head();
                 Ok(())
             }
             Err(e) => {
@@ -358,6 +371,44 @@ impl Editor {
         }
     }
 
+    /// Keep this many KiB of undo history with each saved file, to undo past reopening it
+    pub fn set_persistent_undo(&mut self, enabled: bool, max_kb: usize) {
+        self.undo_history_limit = enabled.then_some(max_kb.saturating_mul(1024));
+    }
+
+    /// Make the buffer as it is the save point, and keep its undo history for next time
+    fn mark_saved(&mut self) {
+        // Later typing starts a new undo step, so undoing back to here finds the saved text
+        self.buffer.finalize_undo_group();
+        self.saved_undo_state = self.buffer.undo_state();
+        let (Some(path), Some(limit)) = (&self.file_path, self.undo_history_limit) else {
+            return;
+        };
+        if let Err(e) = undo_history::save(path, &self.buffer.to_string(), self.buffer.history(), limit) {
+            self.status_message = Some((format!("Saved, but keeping undo history failed: {}", e), true));
+        }
+    }
+
+    /// Replace the whole text with unsaved text recovered from a swap file, as one undo step
+    pub fn recover_text(&mut self, text: &str) {
+        let cursor = self.cursor;
+        let cursor_after = cursor.min(text.len());
+        self.clear_extra_cursors();
+        self.selection_start = None;
+        self.folds.clear();
+        self.buffer.finalize_undo_group();
+        self.buffer.begin_undo_group();
+        self.buffer.delete(0, self.buffer.len_bytes(), cursor, 0);
+        self.buffer.insert(0, text, 0, cursor_after);
+        self.buffer.end_undo_group();
+        self.buffer.finalize_undo_group();
+        self.cursor = self.ensure_char_boundary(cursor_after);
+        self.modified = self.buffer.undo_state() != self.saved_undo_state;
+        self.reinit_syntax_highlighting();
+        self.update_viewport_for_cursor();
+        self.update_matching();
+    }
+
     pub fn file_path(&self) -> Option<&Path> {
         self.file_path.as_deref()
     }
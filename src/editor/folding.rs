@@ -0,0 +1,266 @@
+use crate::cell::{get_cell_at_position, is_delimiter_line, CellType};
+use crate::syntax::Language;
+use super::Editor;
+
+/// Leading spaces of a line, or None for a blank line
+fn indent_of(line: &str) -> Option<usize> {
+    let rest = line.trim_start_matches(' ');
+    (!rest.trim().is_empty()).then_some(line.len() - rest.len())
+}
+
+/// Level of a markdown heading line (`## Setup` is 2)
+fn heading_level(line: &str) -> Option<usize> {
+    let line = line.trim();
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let rest = &line[level..];
+    ((1..=6).contains(&level) && (rest.is_empty() || rest.starts_with(' '))).then_some(level)
+}
+
+/// Row a logical line is drawn on once folded lines are left out - a folded line is drawn
+/// as the line that folds it. `hidden` holds buffer lines, as from `folded_ranges`
+pub(super) fn visible_row(hidden: &[(usize, usize)], logical_line: usize) -> usize {
+    let mut hidden_above = 0;
+    for &(first, last) in hidden {
+        // Logical lines count the 2 virtual lines before the buffer
+        let (first, last) = (first + 2, last + 2);
+        if logical_line < first {
+            break;
+        }
+        if logical_line <= last {
+            return first - 1 - hidden_above;
+        }
+        hidden_above += last - first + 1;
+    }
+    logical_line - hidden_above
+}
+
+/// Logical line drawn on a row once folded lines are left out
+pub(super) fn logical_line_at(hidden: &[(usize, usize)], row: usize) -> usize {
+    let mut hidden_above = 0;
+    for &(first, last) in hidden {
+        if row + hidden_above < first + 2 {
+            break;
+        }
+        hidden_above += last - first + 1;
+    }
+    row + hidden_above
+}
+
+impl Editor {
+    /// Last line a fold starting at `header` hides, if the line starts something foldable:
+    /// a cell from its delimiter line, a markdown section from its heading, or an indented block
+    fn fold_end(&self, header: usize) -> Option<usize> {
+        let len_lines = self.buffer.len_lines();
+        if header >= len_lines {
+            return None;
+        }
+        let text = self.buffer.line(header);
+        let ends_region = |stop: &dyn Fn(&str) -> bool| {
+            (header + 1..len_lines)
+                .find(|&line| {
+                    let text = self.buffer.line(line);
+                    is_delimiter_line(&text) || stop(&text)
+                })
+                .unwrap_or(len_lines)
+        };
+
+        let end = if is_delimiter_line(&text) {
+            // The whole cell, down to the next delimiter line
+            ends_region(&|_| false) - 1
+        } else if let Some(level) = heading_level(&text).filter(|_| self.is_markdown_line(header)) {
+            let end = ends_region(&|text| heading_level(text).is_some_and(|other| other <= level));
+            self.last_non_blank(header, end)
+        } else {
+            let indent = indent_of(&text)?;
+            let end = ends_region(&|text| indent_of(text).is_some_and(|other| other <= indent));
+            self.last_non_blank(header, end)
+        };
+        (end > header).then_some(end)
+    }
+
+    /// Last line before `end` that isn't blank, down to `header` - blank lines after a block stay visible
+    fn last_non_blank(&self, header: usize, end: usize) -> usize {
+        (header + 1..end).rev().find(|&line| indent_of(&self.buffer.line(line)).is_some()).unwrap_or(header)
+    }
+
+    /// Whether a line is markdown: in a markdown file, or a markdown cell of a notebook
+    fn is_markdown_line(&self, line: usize) -> bool {
+        self.syntax.language() == Language::Markdown
+            || get_cell_at_position(&self.cells, self.buffer.line_to_byte(line))
+                .is_some_and(|idx| self.cells[idx].cell_type == CellType::Markdown)
+    }
+
+    /// Header of the innermost foldable region holding a line: the line itself if it starts
+    /// one, else the closest line above whose region reaches down to it
+    fn fold_header_around(&self, line: usize) -> Option<usize> {
+        let mut indent = indent_of(&self.buffer.line(line)).unwrap_or(usize::MAX);
+        for header in (0..=line).rev() {
+            let text = self.buffer.line(header);
+            // An indented block can only hold the line if it starts less indented than everything since
+            let candidate = header == line
+                || is_delimiter_line(&text)
+                || heading_level(&text).is_some()
+                || indent_of(&text).is_some_and(|other| other < indent);
+            if let Some(other) = indent_of(&text) {
+                indent = indent.min(other);
+            }
+            if candidate && self.fold_end(header).is_some_and(|end| end >= line) {
+                return Some(header);
+            }
+        }
+        None
+    }
+
+    /// Buffer lines hidden by folds, as sorted (first, last) ranges that don't overlap.
+    /// A fold inside another fold is hidden along with it
+    pub fn folded_ranges(&self) -> Vec<(usize, usize)> {
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for &header in &self.folds {
+            if ranges.last().is_some_and(|&(_, last)| header <= last) {
+                continue;
+            }
+            if let Some(end) = self.fold_end(header) {
+                ranges.push((header + 1, end));
+            }
+        }
+        ranges
+    }
+
+    /// Move the folds along with lines added or removed since they were last looked at.
+    /// Folds whose first line went away, or no longer starts anything foldable, open up
+    pub(super) fn sync_folds(&mut self) {
+        let version = self.buffer.version();
+        if self.folds_version == version {
+            return;
+        }
+        match self.buffer.line_shifts_since(self.folds_version) {
+            Some(shifts) => {
+                for (first, lines) in shifts {
+                    if lines < 0 {
+                        let removed = first..first + lines.unsigned_abs();
+                        self.folds.retain(|header| !removed.contains(header));
+                    }
+                    for header in self.folds.iter_mut().filter(|header| **header >= first) {
+                        *header = header.saturating_add_signed(lines);
+                    }
+                }
+            }
+            None => self.folds.clear(),
+        }
+        self.folds_version = version;
+        let folds: Vec<usize> = self.folds.iter().copied().filter(|&header| self.fold_end(header).is_some()).collect();
+        self.folds = folds;
+        self.folds.sort_unstable();
+        self.folds.dedup();
+    }
+
+    /// Fold the innermost region around the cursor, or unfold the fold the cursor is on
+    pub fn toggle_fold(&mut self) {
+        self.sync_folds();
+        self.refresh_cells();
+        let line = self.buffer.byte_to_line(self.cursor);
+        if let Some(idx) = self.folds.iter().position(|&header| header == line) {
+            self.folds.remove(idx);
+            return;
+        }
+
+        match self.fold_header_around(line) {
+            Some(header) => {
+                let idx = self.folds.partition_point(|&other| other < header);
+                self.folds.insert(idx, header);
+                self.clear_extra_cursors();
+                if header != line {
+                    self.cursor = self.buffer.line_to_byte(header);
+                    self.selection_start = None;
+                    self.preferred_column = None;
+                }
+                self.update_viewport_for_cursor();
+                self.update_matching();
+            }
+            None => self.status_message = Some(("Nothing to fold here".to_string(), false)),
+        }
+    }
+
+    /// Fold every cell down to its delimiter line
+    pub fn fold_all_cells(&mut self) {
+        self.sync_folds();
+        let headers: Vec<usize> = (0..self.buffer.len_lines())
+            .filter(|&line| is_delimiter_line(&self.buffer.line(line)) && self.fold_end(line).is_some())
+            .collect();
+        if headers.is_empty() {
+            self.status_message = Some(("No cells to fold".to_string(), false));
+            return;
+        }
+
+        self.folds.extend(headers);
+        self.folds.sort_unstable();
+        self.folds.dedup();
+        self.clear_extra_cursors();
+        self.keep_cursor_out_of_folds(Some(false));
+        self.update_viewport_for_cursor();
+        self.update_matching();
+    }
+
+    /// Open every fold
+    pub fn unfold_all(&mut self) {
+        self.folds.clear();
+        self.update_viewport_for_cursor();
+    }
+
+    /// Open the folds hiding any part of a range, e.g. a search match being selected
+    pub(super) fn unfold_range(&mut self, start: usize, end: usize) {
+        if self.folds.is_empty() {
+            return;
+        }
+        self.sync_folds();
+        let (first, last) = (self.buffer.byte_to_line(start), self.buffer.byte_to_line(end));
+        let opened: Vec<usize> = self.folds.iter()
+            .copied()
+            .filter(|&header| header < last && self.fold_end(header).is_some_and(|end| end >= first))
+            .collect();
+        self.folds.retain(|header| !opened.contains(header));
+    }
+
+    /// Keep the cursor off folded lines. `Some(forward)` steps over the fold in the direction
+    /// the cursor was moving; None opens the folds around it instead
+    pub(super) fn keep_cursor_out_of_folds(&mut self, step: Option<bool>) {
+        if self.folds.is_empty() {
+            return;
+        }
+        self.sync_folds();
+        let line = self.buffer.byte_to_line(self.cursor);
+        let ranges = self.folded_ranges();
+        let Some(&(first, last)) = ranges.iter().find(|(first, last)| (*first..=*last).contains(&line)) else {
+            return;
+        };
+
+        match step {
+            Some(true) if last + 1 < self.buffer.len_lines() => self.cursor = self.buffer.line_to_byte(last + 1),
+            Some(_) => {
+                let header = first - 1;
+                let end = self.buffer.line(header).trim_end_matches('\n').len();
+                self.cursor = self.buffer.line_to_byte(header) + end;
+            }
+            None => self.unfold_range(self.cursor, self.cursor),
+        }
+    }
+
+    /// The nearest line above `line` that isn't folded away
+    pub(super) fn visible_line_above(&self, line: usize) -> usize {
+        let above = line.saturating_sub(1);
+        self.folded_ranges()
+            .iter()
+            .find(|(first, last)| (*first..=*last).contains(&above))
+            .map_or(above, |&(first, _)| first - 1)
+    }
+
+    /// The nearest line below `line` that isn't folded away, or `line` when a fold runs to the end
+    pub(super) fn visible_line_below(&self, line: usize) -> usize {
+        let below = line + 1;
+        match self.folded_ranges().iter().find(|(first, last)| (*first..=*last).contains(&below)) {
+            Some(&(_, last)) if last + 1 < self.buffer.len_lines() => last + 1,
+            Some(_) => line,
+            None => below,
+        }
+    }
+}
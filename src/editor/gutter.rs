@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use serde::Deserialize;
+use crate::cell::is_delimiter_line;
+use crate::git::{self, LineChange};
+use crate::kernel::ExecutionOutput;
+use super::Editor;
+
+/// How the gutter numbers lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineNumbers {
+    /// No gutter at all
+    Off,
+    /// Each line's own number
+    #[default]
+    Absolute,
+    /// Distance from the cursor's line, which shows its own number
+    Relative,
+}
+
+/// Execution state of a cell, as shown down its side in the gutter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellState {
+    NotRun,
+    Ok,
+    Failed,
+    Running,
+    Queued,
+    /// Edited since it last ran
+    Modified,
+    /// Depends on a cell that was edited or re-run since this one ran
+    Stale,
+}
+
+/// Marker column entry for a line of a notebook
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellMark {
+    /// The cell's delimiter line
+    Start(CellState),
+    /// A line of the cell's code
+    Inside(CellState),
+    /// The line the cell's last traceback points at
+    Error,
+}
+
+/// Line of the innermost cell frame in a traceback, counted from the cell's first line of code.
+/// Cell code runs under the file name `<cell>`
+fn traceback_line(traceback: &[String]) -> Option<usize> {
+    traceback.iter().rev().find_map(|line| {
+        let rest = line.trim().strip_prefix("File \"<cell>\", line ")?;
+        rest.split(|c: char| !c.is_ascii_digit()).next()?.parse().ok()
+    })
+}
+
+impl Editor {
+    pub fn set_line_numbers(&mut self, line_numbers: LineNumbers) {
+        self.line_numbers = line_numbers;
+    }
+
+    /// Columns the gutter takes at the left of the window: the git column, the line number,
+    /// the cell column and a space. None of them when line numbers are off
+    pub fn gutter_width(&self) -> usize {
+        match self.line_numbers {
+            LineNumbers::Off => 0,
+            _ => self.buffer.len_lines().to_string().len().max(3) + 3,
+        }
+    }
+
+    /// Number shown beside a buffer line, and whether it's the cursor's line
+    pub fn line_number_label(&self, line: usize) -> (usize, bool) {
+        let cursor_line = self.buffer.byte_to_line(self.cursor);
+        match self.line_numbers {
+            LineNumbers::Relative if line != cursor_line => (line.abs_diff(cursor_line), false),
+            _ => (line + 1, line == cursor_line),
+        }
+    }
+
+    /// Marker column entries for the buffer lines from `first` to `last`
+    pub fn cell_marks(&self, first: usize, last: usize) -> HashMap<usize, CellMark> {
+        let (running, queued) = self.pending_cells();
+        let mut marks = HashMap::new();
+        for (idx, cell) in self.cells.iter().enumerate() {
+            let start = self.buffer.byte_to_line(cell.start);
+            // A cell ends at the start of the next delimiter line
+            let end = self.buffer.byte_to_line(cell.end.saturating_sub(1).max(cell.start));
+            if end < first || start > last {
+                continue;
+            }
+
+            let state = if running == Some(idx) {
+                CellState::Running
+            } else if queued.contains(&idx) {
+                CellState::Queued
+            } else if cell.stale {
+                CellState::Stale
+            } else if cell.is_modified() {
+                CellState::Modified
+            } else {
+                match &cell.output {
+                    Some(output) if output.success => CellState::Ok,
+                    Some(_) => CellState::Failed,
+                    None => CellState::NotRun,
+                }
+            };
+            // Without delimiter lines the whole buffer is one cell, and there are no boundaries to show
+            if is_delimiter_line(&self.buffer.line(start)) {
+                for line in start.max(first)..=end.min(last) {
+                    marks.insert(line, if line == start { CellMark::Start(state) } else { CellMark::Inside(state) });
+                }
+            }
+
+            // The traceback's line numbers only hold while the code is as it ran
+            if state == CellState::Failed {
+                let error_line = cell.output.iter()
+                    .flat_map(|output| &output.outputs)
+                    .find_map(|output| match output {
+                        ExecutionOutput::Error { traceback, .. } => traceback_line(traceback),
+                        _ => None,
+                    })
+                    .map(|line| start + line);
+                if let Some(line) = error_line.filter(|line| (first..=last).contains(line) && *line <= end) {
+                    marks.insert(line, CellMark::Error);
+                }
+            }
+        }
+        marks
+    }
+
+    /// Read the file's text at git `HEAD` again, e.g. after opening or saving it
+    pub(super) fn reload_git_head(&mut self) {
+        self.git_head = self.file_path.as_deref().and_then(git::head_text);
+        self.git_changes.clear();
+        self.git_changes_version = None;
+    }
+
+    /// Compare the buffer with git `HEAD` again if it changed since the last time. Too slow
+    /// for every keystroke in a big file, so the event loop calls it once input goes quiet.
+    /// Returns whether the markers were worked out again
+    pub fn refresh_git_changes(&mut self) -> bool {
+        let version = self.buffer.version();
+        if self.line_numbers == LineNumbers::Off || self.git_changes_version == Some(version) {
+            return false;
+        }
+        self.git_changes = match &self.git_head {
+            Some(head) => git::line_changes(head, &self.buffer.to_string()),
+            None => HashMap::new(),
+        };
+        self.git_changes_version = Some(version);
+        true
+    }
+
+    /// Work the markers out if they weren't since the file was opened or saved
+    pub fn ensure_git_changes(&mut self) {
+        if self.git_changes_version.is_none() {
+            self.refresh_git_changes();
+        }
+    }
+
+    /// How a buffer line differs from git `HEAD`, as of the last `refresh_git_changes`
+    pub fn git_change(&self, line: usize) -> Option<LineChange> {
+        self.git_changes.get(&line).copied()
+    }
+}
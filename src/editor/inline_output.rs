@@ -0,0 +1,234 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+use crate::ansi::parse_output;
+use crate::cell::{format_output, get_cell_at_position};
+use super::wrap::{last_visual_row, line_at_visual_row, visual_row};
+use super::Editor;
+
+/// Output lines shown under a cell before the block scrolls internally
+pub const INLINE_OUTPUT_MAX_HEIGHT: usize = 10;
+
+/// What a row of the editor view shows once inline outputs are laid out
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DisplayLine {
+    /// Logical line: 0 and 1 are the virtual lines before the buffer, 2+ map to buffer lines 0+
+    Text(usize),
+    /// Row of a soft-wrapped logical line after its first (row 1 is the second row)
+    Wrap { line: usize, row: usize },
+    /// Row of the output block drawn under a cell (row 0 is the header)
+    Output { cell_idx: usize, row: usize },
+}
+
+/// An inline output block: (last row of the line it follows, rows, cell index)
+type Block = (usize, usize, usize);
+
+/// Inline outputs as drawn, kept between redraws
+#[derive(Default)]
+pub(super) struct InlineCache {
+    lines: HashMap<u64, Rc<Vec<String>>>, // Formatted lines, by the output's hash
+    blocks: Option<(u64, Rc<Vec<Block>>)>, // Block layout, with the hash of what it was laid out from
+}
+
+/// Map a display row to what it shows, given the inline output blocks, the folded lines and
+/// the extra rows of wrapped lines
+fn map_display_row(
+    blocks: &[Block],
+    hidden: &[(usize, usize)],
+    wraps: &[(usize, usize)],
+    display_row: usize,
+) -> DisplayLine {
+    let mut output_rows_above = 0;
+    for &(after_logical, rows, cell_idx) in blocks {
+        let block_start = after_logical + 1 + output_rows_above;
+        if display_row < block_start {
+            break;
+        }
+        if display_row < block_start + rows {
+            return DisplayLine::Output { cell_idx, row: display_row - block_start };
+        }
+        output_rows_above += rows;
+    }
+    match line_at_visual_row(hidden, wraps, display_row - output_rows_above) {
+        (line, 0) => DisplayLine::Text(line),
+        (line, row) => DisplayLine::Wrap { line, row },
+    }
+}
+
+impl Editor {
+    /// Toggle drawing each cell's latest output under the cell; returns the new state
+    pub fn toggle_inline_outputs(&mut self) -> bool {
+        self.inline_outputs = !self.inline_outputs;
+        self.inline_outputs
+    }
+
+    /// Check whether outputs are drawn inline under their cells
+    pub fn inline_outputs_enabled(&self) -> bool {
+        self.inline_outputs
+    }
+
+    /// Output lines of a cell as shown inline (escape codes and overwritten text removed)
+    fn inline_output_lines(&self, cell_idx: usize) -> Rc<Vec<String>> {
+        let Some((output, hash)) = self.cells.get(cell_idx).and_then(|cell| Some((cell.output.as_ref()?, cell.output_hash))) else {
+            return Rc::default();
+        };
+        let mut cache = self.inline_cache.borrow_mut();
+        cache.lines.entry(hash)
+            .or_insert_with(|| Rc::new(parse_output(&format_output(output)).into_iter().map(|line| line.text).collect()))
+            .clone()
+    }
+
+    /// Inline output blocks as (last row of the line they follow with folded lines left out,
+    /// rows, cell index), in buffer order. A folded cell's output follows the line that folds it
+    fn inline_blocks(&self, hidden: &[(usize, usize)], wraps: &[(usize, usize)]) -> Rc<Vec<Block>> {
+        if !self.inline_outputs {
+            return Rc::default();
+        }
+
+        // Laid out again only when the text, the cells, their outputs or the folds and wraps change
+        let mut hasher = DefaultHasher::new();
+        (self.buffer.version(), hidden, wraps).hash(&mut hasher);
+        for cell in &self.cells {
+            (cell.start, cell.end, cell.output.is_some(), cell.output_hash, cell.output_collapsed).hash(&mut hasher);
+        }
+        let key = hasher.finish();
+        if let Some((cached, blocks)) = &self.inline_cache.borrow().blocks {
+            if *cached == key {
+                return blocks.clone();
+            }
+        }
+
+        // Outputs no cell shows any more are dropped along the way
+        self.inline_cache.borrow_mut().lines
+            .retain(|hash, _| self.cells.iter().any(|cell| cell.output.is_some() && cell.output_hash == *hash));
+
+        let blocks: Vec<Block> = self.cells.iter().enumerate()
+            .filter_map(|(idx, cell)| {
+                // Nothing to show for cells that ran silently
+                let output = cell.output.as_ref()?;
+                if output.success && output.outputs.is_empty() {
+                    return None;
+                }
+
+                let rows = if cell.output_collapsed {
+                    1
+                } else {
+                    1 + self.inline_output_lines(idx).len().min(INLINE_OUTPUT_MAX_HEIGHT)
+                };
+                let last_line = self.buffer.byte_to_line(cell.end.saturating_sub(1).max(cell.start));
+                Some((last_visual_row(hidden, wraps, last_line + 2), rows, idx))
+            })
+            .collect();
+        let blocks = Rc::new(blocks);
+        self.inline_cache.borrow_mut().blocks = Some((key, blocks.clone()));
+        blocks
+    }
+
+    /// What each of `count` display rows starting at `start` shows
+    pub fn display_lines(&self, start: usize, count: usize) -> Vec<DisplayLine> {
+        let hidden = self.folded_ranges();
+        let wraps = self.wrapped_rows(&hidden);
+        let blocks = self.inline_blocks(&hidden, &wraps);
+        (start..start + count).map(|row| map_display_row(&blocks, &hidden, &wraps, row)).collect()
+    }
+
+    /// What a single display row shows
+    pub fn display_line_at(&self, display_row: usize) -> DisplayLine {
+        let hidden = self.folded_ranges();
+        let wraps = self.wrapped_rows(&hidden);
+        map_display_row(&self.inline_blocks(&hidden, &wraps), &hidden, &wraps, display_row)
+    }
+
+    /// Display row of a logical line, leaving out folded lines and counting the wrapped and
+    /// inline output rows above it. A folded line gets the row of the line that folds it
+    pub fn display_row_of_logical(&self, logical_line: usize) -> usize {
+        let hidden = self.folded_ranges();
+        let wraps = self.wrapped_rows(&hidden);
+        let row = visual_row(&hidden, &wraps, logical_line);
+        row + self.inline_blocks(&hidden, &wraps).iter()
+            .filter(|(after_row, _, _)| *after_row < row)
+            .map(|(_, rows, _)| rows)
+            .sum::<usize>()
+    }
+
+    /// Text of an inline output row as (text, is_header, is_error)
+    pub fn inline_output_row(&self, cell_idx: usize, row: usize) -> (String, bool, bool) {
+        let Some(cell) = self.cells.get(cell_idx) else {
+            return (String::new(), false, false);
+        };
+        let is_error = cell.output.as_ref().is_some_and(|output| !output.success);
+        let lines = self.inline_output_lines(cell_idx);
+        let count = cell.execution_count.map(|n| n.to_string()).unwrap_or_else(|| " ".to_string());
+
+        if row == 0 {
+            let header = if cell.output_collapsed {
+                format!("▸ Out[{}]: {} lines (collapsed)", count, lines.len())
+            } else if lines.len() > INLINE_OUTPUT_MAX_HEIGHT {
+                let first = cell.output_scroll + 1;
+                let last = (cell.output_scroll + INLINE_OUTPUT_MAX_HEIGHT).min(lines.len());
+                format!("▾ Out[{}]: lines {}-{} of {}", count, first, last, lines.len())
+            } else {
+                format!("▾ Out[{}]:", count)
+            };
+            return (header, true, false);
+        }
+
+        let text = lines.get(cell.output_scroll + row - 1).cloned().unwrap_or_default();
+        (text, false, is_error)
+    }
+
+    /// The inline output block under a terminal row, as (cell index, row)
+    pub fn inline_output_at_screen_row(&self, screen_row: usize) -> Option<(usize, usize)> {
+        let screen_row = screen_row.checked_sub(self.view_origin.1)?;
+        match self.display_line_at(self.viewport_offset.0 + screen_row) {
+            DisplayLine::Output { cell_idx, row } => Some((cell_idx, row)),
+            DisplayLine::Text(_) | DisplayLine::Wrap { .. } => None,
+        }
+    }
+
+    /// Collapse or expand a cell's inline output
+    pub fn toggle_inline_output_collapsed(&mut self, cell_idx: usize) {
+        if let Some(cell) = self.cells.get_mut(cell_idx) {
+            cell.output_collapsed = !cell.output_collapsed;
+        }
+    }
+
+    /// Collapse or expand the inline output of the cell under the cursor
+    pub fn toggle_current_inline_output(&mut self) {
+        self.refresh_cells();
+        if let Some(cell_idx) = get_cell_at_position(&self.cells, self.cursor) {
+            self.toggle_inline_output_collapsed(cell_idx);
+        }
+    }
+
+    /// Scroll a cell's inline output by `lines`; returns false if it couldn't move
+    pub fn scroll_inline_output(&mut self, cell_idx: usize, lines: i32) -> bool {
+        let total = self.inline_output_lines(cell_idx).len();
+        let Some(cell) = self.cells.get_mut(cell_idx) else {
+            return false;
+        };
+        if cell.output_collapsed {
+            return false;
+        }
+
+        let max_scroll = total.saturating_sub(INLINE_OUTPUT_MAX_HEIGHT);
+        let new_scroll = if lines > 0 {
+            (cell.output_scroll + lines as usize).min(max_scroll)
+        } else {
+            cell.output_scroll.saturating_sub((-lines) as usize)
+        };
+        let moved = new_scroll != cell.output_scroll;
+        cell.output_scroll = new_scroll;
+        moved
+    }
+
+    /// Scroll the inline output of the cell under the cursor
+    pub fn scroll_current_inline_output(&mut self, lines: i32) -> bool {
+        self.refresh_cells();
+        match get_cell_at_position(&self.cells, self.cursor) {
+            Some(cell_idx) => self.scroll_inline_output(cell_idx, lines),
+            None => false,
+        }
+    }
+}
@@ -1,4 +1,6 @@
 use super::Editor;
+use crate::find_replace::{Search, MAX_FIND_MATCHES};
+use std::borrow::Cow;
 
 impl Editor {
     /// Find matching opening bracket scanning backward
@@ -193,35 +195,128 @@ impl Editor {
         self.current_find_match
     }
 
-    /// Find all occurrences of a string in the buffer
-    pub fn find_all(&self, search_text: &str) -> Vec<(usize, usize)> {
-        // Skip search if text is too short to avoid performance issues
-        // (single characters can match thousands of times in large files)
-        if search_text.len() < 2 {
-            return Vec::new();
-        }
-
-        let buffer_text = self.buffer.to_string();
+    /// Find every match of a search in the buffer, or only in `scope`, as sorted byte ranges.
+    /// Lines are searched one at a time straight out of the rope (borrowed unless a line
+    /// crosses a chunk boundary), so large files are never copied and matches don't span lines
+    pub fn find_all(&self, search: &Search, scope: Option<(usize, usize)>) -> Vec<(usize, usize)> {
+        let rope = self.buffer.rope();
+        let (from, to) = scope.unwrap_or((0, rope.len_bytes()));
+        let to = to.min(rope.len_bytes());
+        let first_line = rope.byte_to_line(from.min(to));
+        let mut line_start = rope.line_to_byte(first_line);
         let mut matches = Vec::new();
-        let mut pos = 0;
-
-        while pos < buffer_text.len() {
-            // Ensure we're on a character boundary
-            if !buffer_text.is_char_boundary(pos) {
-                pos += 1;
-                continue;
-            }
 
-            if let Some(found) = buffer_text[pos..].find(search_text) {
-                let match_start = pos + found;
-                let match_end = match_start + search_text.len();
-                matches.push((match_start, match_end));
-                pos = match_end;
-            } else {
+        for line in rope.lines_at(first_line) {
+            if line_start >= to {
                 break;
             }
+            let text: Cow<str> = line.into();
+            for (start, end) in search.find_in_line(strip_line_ending(&text)) {
+                let (start, end) = (line_start + start, line_start + end);
+                if start >= from && end <= to {
+                    matches.push((start, end));
+                    if matches.len() >= MAX_FIND_MATCHES {
+                        return matches;
+                    }
+                }
+            }
+            line_start += line.len_bytes();
         }
-
         matches
     }
+
+    /// The text to put in place of the match at `start`, with regex groups filled in
+    pub fn find_replacement(&self, search: &Search, start: usize, replace: &str) -> String {
+        let rope = self.buffer.rope();
+        let line_idx = rope.byte_to_line(start);
+        let line_start = rope.line_to_byte(line_idx);
+        let text: Cow<str> = rope.line(line_idx).into();
+        search.replacement(strip_line_ending(&text), start - line_start, replace)
+    }
+
+    /// Replace every match of a search on the given zero-based lines; returns how many were replaced
+    pub fn replace_in_lines(&mut self, search: &Search, lines: &[usize], replace: &str) -> usize {
+        let rope = self.buffer.rope();
+        let mut matches = Vec::new();
+        for &line in lines.iter().filter(|&&line| line < rope.len_lines()) {
+            let scope = (rope.line_to_byte(line), rope.line_to_byte(line + 1));
+            matches.extend(self.find_all(search, Some(scope)));
+        }
+        matches.sort_unstable();
+        matches.dedup();
+        let replacements: Vec<String> =
+            matches.iter().map(|&(start, _)| self.find_replacement(search, start, replace)).collect();
+
+        // One undo step of its own, apart from any typing just before or after it; from last
+        // to first so earlier positions stay put
+        self.buffer.finalize_undo_group();
+        self.buffer.begin_undo_group();
+        for (&(start, end), replacement) in matches.iter().zip(&replacements).rev() {
+            self.replace_at(start, end, replacement);
+        }
+        self.buffer.end_undo_group();
+        self.buffer.finalize_undo_group();
+        matches.len()
+    }
+}
+
+fn strip_line_ending(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::Buffer;
+    use crate::find_replace::SearchOptions;
+
+    fn find(text: &str, query: &str, options: SearchOptions, scope: Option<(usize, usize)>) -> Vec<(usize, usize)> {
+        let mut editor = Editor::new();
+        editor.buffer = Buffer::from_string(text.to_string());
+        editor.find_all(&Search::new(query, options).unwrap(), scope)
+    }
+
+    #[test]
+    fn finds_plain_text_in_every_line() {
+        let options = SearchOptions::default();
+        assert_eq!(find("a.b\nxa.b\r\n", "a.b", options, None), [(0, 3), (5, 8)]);
+        assert_eq!(find("aaaa", "aa", options, None), [(0, 2), (2, 4)]);
+        assert_eq!(find("Foo foo", "foo", options, None), [(4, 7)]);
+        assert_eq!(find("Foo foo", "foo", SearchOptions { match_case: false, ..options }, None), [(0, 3), (4, 7)]);
+    }
+
+    #[test]
+    fn regex_and_whole_word() {
+        let regex = SearchOptions { regex: true, ..SearchOptions::default() };
+        assert_eq!(find("x1 y22\nz333", r"\d+", regex, None), [(1, 2), (4, 6), (8, 11)]);
+        // Matches stay within a line, and empty ones are skipped
+        assert_eq!(find("a\nb", r"a\s*b", regex, None), []);
+        assert_eq!(find("ab", "x*", regex, None), []);
+        let whole_word = SearchOptions { whole_word: true, ..SearchOptions::default() };
+        assert_eq!(find("cat cats _cat cat", "cat", whole_word, None), [(0, 3), (14, 17)]);
+    }
+
+    #[test]
+    fn replacing_in_lines_is_one_undo_step() {
+        let mut editor = Editor::new();
+        editor.buffer = Buffer::from_string("a a\nb\na\n".to_string());
+        editor.buffer.insert(5, "x", 5, 6);
+        let search = Search::new("a", SearchOptions::default()).unwrap();
+        assert_eq!(editor.replace_in_lines(&search, &[0, 2], "cc"), 3);
+        assert_eq!(editor.buffer.to_string(), "cc cc\nbx\ncc\n");
+        editor.buffer.undo();
+        assert_eq!(editor.buffer.to_string(), "a a\nbx\na\n");
+        editor.buffer.undo();
+        assert_eq!(editor.buffer.to_string(), "a a\nb\na\n");
+    }
+
+    #[test]
+    fn scope_limits_the_matches() {
+        let options = SearchOptions::default();
+        let text = "ab ab\nab ab\nab";
+        assert_eq!(find(text, "ab", options, Some((3, 11))), [(3, 5), (6, 8), (9, 11)]);
+        assert_eq!(find(text, "ab", options, Some((4, 10))), [(6, 8)]);
+        assert_eq!(find(text, "ab", options, Some((12, 100))), [(12, 14)]);
+    }
 }
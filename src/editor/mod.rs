@@ -3,10 +3,15 @@ use crate::commands::Command;
 use crate::syntax::SyntaxHighlighter;
 use crate::cell::{Cell, parse_cells};
 use crate::kernel::Kernel;
+use crate::git::LineChange;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use arboard::Clipboard;
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicUsize;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use unicode_width::UnicodeWidthChar;
 
@@ -17,6 +22,17 @@ mod mouse;
 mod viewport;
 mod matching;
 mod notebook;
+mod inline_output;
+mod multi_cursor;
+mod folding;
+mod gutter;
+mod wrap;
+mod undo;
+
+pub use inline_output::{DisplayLine, INLINE_OUTPUT_MAX_HEIGHT};
+pub use viewport::View;
+pub use gutter::{CellMark, CellState, LineNumbers};
+pub use wrap::DEFAULT_WRAP_INDICATOR;
 
 /// Token type for word boundary detection
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -27,6 +43,9 @@ enum TokenType {
     Other,     // Everything else
 }
 
+/// Spaces per indent level unless the config says otherwise
+pub const DEFAULT_TAB_WIDTH: usize = 4;
+
 /// Categorize a character into a token type for word navigation
 fn get_token_type(ch: char) -> TokenType {
     if ch.is_alphanumeric() || ch == '_' {
@@ -40,15 +59,23 @@ fn get_token_type(ch: char) -> TokenType {
     }
 }
 
+/// Cells of a background batch as (index, hash of their code), and how many have finished
+type RunningCells = (Vec<(usize, u64)>, Arc<AtomicUsize>);
+
 pub struct Editor {
     buffer: Buffer,
     cursor: usize,           // Byte position in the buffer
     pub selection_start: Option<usize>,  // Start of selection (if any)
+    extra_cursors: Vec<multi_cursor::ExtraCursor>, // Cursors besides `cursor`, each with its own selection
+    block_anchor: Option<(usize, usize)>, // (line, display column) where an Alt+drag block selection started
     file_path: Option<PathBuf>,
     modified: bool,
     viewport_offset: (usize, usize),  // (row, col) offset for scrolling
-    last_saved_undo_len: usize,       // Track save point for modified flag
-    clipboard: Clipboard,             // System clipboard
+    view_size: Option<(usize, usize)>, // (rows, cols) of the editor area from the last layout
+    view_origin: (usize, usize), // (col, row) of the editor area's top-left corner on screen
+    saved_undo_state: usize,          // Undo tree state of the text as last saved, for the modified flag
+    undo_history_limit: Option<usize>, // Bytes of undo history kept with saved files, None to keep none
+    clipboard: Option<Clipboard>,     // System clipboard, None until it could be reached
     mouse_selecting: bool,            // Track if we're actively selecting with mouse
     last_click_time: Option<Instant>, // Track time of last click for double/triple click
     last_click_position: Option<usize>, // Track position of last click
@@ -64,8 +91,23 @@ pub struct Editor {
     current_find_match: Option<usize>, // Index of the current find match
     // REPL/Notebook fields
     cells: Vec<Cell>,                  // Parsed cells for notebook mode
+    cells_version: Option<u64>,        // Buffer version the cells were parsed from
     kernel: Option<Box<dyn Kernel>>,   // Active Python kernel
     repl_mode: bool,                   // Whether we're in REPL mode
+    running_cells: Option<RunningCells>, // Background batch and how many cells have finished
+    inline_outputs: bool,              // Draw each cell's latest output under the cell
+    inline_cache: RefCell<inline_output::InlineCache>, // Formatted inline outputs and their layout
+    folds: Vec<usize>,                 // First lines of folded regions, sorted
+    folds_version: u64,                // Buffer version the folds were last moved along to
+    tab_width: usize,                  // Spaces inserted for Tab and per indent level
+    line_numbers: LineNumbers,         // How the gutter numbers lines, or Off for no gutter
+    git_head: Option<String>,          // The file's text at git HEAD, if it's tracked
+    git_changes: HashMap<usize, LineChange>, // Lines changed since HEAD, by buffer line
+    git_changes_version: Option<u64>,  // Buffer version git_changes was computed for
+    soft_wrap: bool,                   // Wrap long lines instead of scrolling sideways
+    wrap_column: usize,                // Column to wrap at if narrower than the window, 0 for the window
+    wrap_indicator: String,            // Drawn at the start of continuation rows
+    wrap: wrap::WrapLayout,            // Where lines wrap, for the current buffer version and width
 }
 
 impl Editor {
@@ -75,11 +117,16 @@ impl Editor {
             buffer: Buffer::new(),
             cursor: 0,
             selection_start: None,
+            extra_cursors: Vec::new(),
+            block_anchor: None,
             file_path: None,
             modified: false,
             viewport_offset: (0, 0),
-            last_saved_undo_len: 0,
-            clipboard: Clipboard::new().expect("Failed to access clipboard"),
+            view_size: None,
+            view_origin: (0, 0),
+            saved_undo_state: 0,
+            undo_history_limit: None,
+            clipboard: Clipboard::new().ok(),
             mouse_selecting: false,
             last_click_time: None,
             last_click_position: None,
@@ -93,12 +140,32 @@ impl Editor {
             find_matches: Vec::new(),
             current_find_match: None,
             cells: Vec::new(),
+            cells_version: None,
             kernel: None,
             repl_mode: false,
+            running_cells: None,
+            inline_outputs: false,
+            inline_cache: RefCell::default(),
+            folds: Vec::new(),
+            folds_version: 0,
+            tab_width: DEFAULT_TAB_WIDTH,
+            line_numbers: LineNumbers::default(),
+            git_head: None,
+            git_changes: HashMap::new(),
+            git_changes_version: None,
+            soft_wrap: false,
+            wrap_column: 0,
+            wrap_indicator: DEFAULT_WRAP_INDICATOR.to_string(),
+            wrap: wrap::WrapLayout::default(),
             status_message_persistent: false,
         }
     }
 
+    /// Spaces inserted for Tab and added or removed per indent level
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        self.tab_width = tab_width;
+    }
+
     pub fn execute(&mut self, cmd: Command) -> io::Result<()> {
         // Clear non-persistent status messages on user action
         if !self.status_message_persistent {
@@ -123,8 +190,31 @@ impl Editor {
             }
         }
         
+        // With several cursors, edits and movement happen at each of them
+        if !self.extra_cursors.is_empty() {
+            match cmd {
+                Command::Copy => {
+                    self.copy_at_cursors();
+                    return Ok(());
+                }
+                ref cmd if Self::applies_at_every_cursor(cmd) => {
+                    self.execute_at_cursors(cmd.clone());
+                    self.update_viewport_for_cursor();
+                    self.update_matching();
+                    return Ok(());
+                }
+                Command::PageUp | Command::PageDown | Command::SelectAll |
+                Command::MoveParagraphUp | Command::MoveParagraphDown |
+                Command::SelectParagraphUp | Command::SelectParagraphDown |
+                Command::Undo | Command::Redo |
+                Command::UndoEarlier | Command::UndoLater => self.clear_extra_cursors(),
+                _ => {}
+            }
+        }
+
         // Track if cursor moved to update viewport
         let mut cursor_moved = false;
+        let cursor_before = self.cursor;
         
         // For non-selection movement commands, clear selection
         // Note: MoveLeft, MoveRight, MoveUp, and MoveDown handle their own selection clearing
@@ -147,7 +237,7 @@ impl Editor {
                 
                 // Filter out invisible characters
                 let text = match c {
-                    '\t' => "    ".to_string(), // Convert tabs to 4 spaces
+                    '\t' => " ".repeat(self.tab_width), // Convert tabs to spaces
                     '\r' => return Ok(()), // Skip carriage returns
                     // Skip zero-width and invisible characters
                     '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{200E}' | '\u{200F}' |
@@ -213,8 +303,9 @@ impl Editor {
 
                 let cursor_before = self.cursor;
                 let line = self.buffer.byte_to_line(self.cursor);
-                self.buffer.insert(self.cursor, "    ", cursor_before, self.cursor + 4);
-                self.cursor += 4;
+                let indent = " ".repeat(self.tab_width);
+                self.buffer.insert(self.cursor, &indent, cursor_before, self.cursor + indent.len());
+                self.cursor += indent.len();
                 self.modified = true;
                 self.preferred_column = None; // Clear preferred column
 
@@ -326,7 +417,10 @@ impl Editor {
                     cursor_moved = true;
                 } else {
                     let current_line = self.buffer.byte_to_line(self.cursor);
-                    if current_line > 0 {
+                    if let Some(target) = self.wrap_move_target(true) {
+                        // Soft-wrapped lines are moved through a row at a time
+                        self.cursor = target;
+                    } else if current_line > 0 {
                     // Set preferred column if not already set
                     if self.preferred_column.is_none() {
                         let (_, col) = self.cursor_position();
@@ -336,7 +430,7 @@ impl Editor {
                     // Use preferred column as target
                     let target_display_col = self.preferred_column.unwrap();
                     
-                    let new_line = current_line - 1;
+                    let new_line = self.visible_line_above(current_line);
                     let new_line_start = self.buffer.line_to_byte(new_line);
                     let new_line_text = self.buffer.line(new_line);
                     
@@ -398,7 +492,10 @@ impl Editor {
                     cursor_moved = true;
                 } else {
                     let current_line = self.buffer.byte_to_line(self.cursor);
-                    if current_line < self.buffer.len_lines() - 1 {
+                    if let Some(target) = self.wrap_move_target(false) {
+                        // Soft-wrapped lines are moved through a row at a time
+                        self.cursor = target;
+                    } else if current_line < self.buffer.len_lines() - 1 {
                     // Set preferred column if not already set
                     if self.preferred_column.is_none() {
                         let (_, col) = self.cursor_position();
@@ -408,7 +505,7 @@ impl Editor {
                     // Use preferred column as target
                     let target_display_col = self.preferred_column.unwrap();
                     
-                    let new_line = current_line + 1;
+                    let new_line = self.visible_line_below(current_line);
                     let new_line_start = self.buffer.line_to_byte(new_line);
                     let new_line_text = self.buffer.line(new_line);
                     
@@ -537,7 +634,11 @@ impl Editor {
                     self.selection_start = Some(self.cursor);
                 }
                 let current_line = self.buffer.byte_to_line(self.cursor);
-                if current_line > 0 {
+                if let Some(target) = self.wrap_move_target(true) {
+                    // Soft-wrapped lines are moved through a row at a time
+                    self.cursor = target;
+                    cursor_moved = true;
+                } else if current_line > 0 {
                     // Set preferred column if not already set
                     if self.preferred_column.is_none() {
                         let (_, col) = self.cursor_position();
@@ -547,7 +648,7 @@ impl Editor {
                     // Use preferred column as target
                     let target_display_col = self.preferred_column.unwrap();
                     
-                    let new_line = current_line - 1;
+                    let new_line = self.visible_line_above(current_line);
                     let new_line_start = self.buffer.line_to_byte(new_line);
                     let new_line_text = self.buffer.line(new_line);
                     
@@ -606,7 +707,11 @@ impl Editor {
                     self.selection_start = Some(self.cursor);
                 }
                 let current_line = self.buffer.byte_to_line(self.cursor);
-                if current_line < self.buffer.len_lines() - 1 {
+                if let Some(target) = self.wrap_move_target(false) {
+                    // Soft-wrapped lines are moved through a row at a time
+                    self.cursor = target;
+                    cursor_moved = true;
+                } else if current_line < self.buffer.len_lines() - 1 {
                     // Set preferred column if not already set
                     if self.preferred_column.is_none() {
                         let (_, col) = self.cursor_position();
@@ -616,7 +721,7 @@ impl Editor {
                     // Use preferred column as target
                     let target_display_col = self.preferred_column.unwrap();
                     
-                    let new_line = current_line + 1;
+                    let new_line = self.visible_line_below(current_line);
                     let new_line_start = self.buffer.line_to_byte(new_line);
                     let new_line_text = self.buffer.line(new_line);
                     
@@ -999,7 +1104,7 @@ impl Editor {
             // Clipboard operations
             Command::Copy => {
                 if let Some(text) = self.get_selected_text() {
-                    if let Err(e) = self.clipboard.set_text(text) {
+                    if let Err(e) = self.clipboard().and_then(|clipboard| clipboard.set_text(text)) {
                         eprintln!("Failed to copy to clipboard: {}", e);
                     }
                 }
@@ -1007,7 +1112,7 @@ impl Editor {
             
             Command::Cut => {
                 if let Some(text) = self.get_selected_text() {
-                    if let Err(e) = self.clipboard.set_text(text) {
+                    if let Err(e) = self.clipboard().and_then(|clipboard| clipboard.set_text(text)) {
                         eprintln!("Failed to copy to clipboard: {}", e);
                     } else {
                         self.delete_selection();
@@ -1016,13 +1121,13 @@ impl Editor {
             }
             
             Command::Paste => {
-                match self.clipboard.get_text() {
+                match self.clipboard().and_then(|clipboard| clipboard.get_text()) {
                     Ok(text) => {
                         // Delete selection first if any
                         self.delete_selection();
                         
                         // Normalize: CRLF → LF, tabs → spaces, remove invisible characters
-                        let text = Self::normalize_text(text);
+                        let text = Self::normalize_text(text, self.tab_width);
                         
                         let line_before = self.buffer.byte_to_line(self.cursor);
                         let cursor_before = self.cursor;
@@ -1046,28 +1151,20 @@ impl Editor {
             }
             
             Command::Undo => {
-                if let Some(cursor) = self.buffer.undo() {
-                    let cursor = cursor.min(self.buffer.len_bytes());
-                    self.cursor = self.ensure_char_boundary(cursor);
-                    self.modified = self.buffer.can_undo();
-                    cursor_moved = true;
-
-                    // Reinitialize syntax highlighting after undo
-                    self.reinit_syntax_highlighting();
-                }
+                let cursor = self.buffer.undo();
+                cursor_moved = self.after_undo_jump(cursor);
             }
 
             Command::Redo => {
-                if let Some(cursor) = self.buffer.redo() {
-                    let cursor = cursor.min(self.buffer.len_bytes());
-                    self.cursor = self.ensure_char_boundary(cursor);
-                    self.modified = true;
-                    cursor_moved = true;
-
-                    // Reinitialize syntax highlighting after redo
-                    self.reinit_syntax_highlighting();
-                }
+                let cursor = self.buffer.redo();
+                cursor_moved = self.after_undo_jump(cursor);
             }
+
+            Command::UndoEarlier => cursor_moved = self.step_undo_state(false),
+
+            Command::UndoLater => cursor_moved = self.step_undo_state(true),
+
+            Command::SwitchRedoBranch => self.switch_redo_branch(),
             
             Command::Save => {
                 self.save()?;
@@ -1079,7 +1176,9 @@ impl Editor {
             }
             
             Command::FindReplace | Command::FindNext | Command::FindPrev | 
-            Command::Replace | Command::ReplaceAll => {
+            Command::Replace | Command::ReplaceAll |
+            Command::ToggleFindRegex | Command::ToggleFindMatchCase |
+            Command::ToggleFindWholeWord | Command::ToggleFindInSelection => {
                 // These are handled in main.rs with the find/replace window
                 return Ok(());
             }
@@ -1239,6 +1338,7 @@ impl Editor {
                 };
                 
                 // Track cursor adjustment
+                let indent = " ".repeat(self.tab_width);
                 let mut cursor_adjustment = 0;
                 let mut selection_start_adjustment = 0;
                 
@@ -1246,17 +1346,17 @@ impl Editor {
                 for line_num in (start_line..=end_line).rev() {
                     let line_start = self.buffer.line_to_byte(line_num);
                     
-                    // Insert 4 spaces at the start of the line
+                    // Insert one indent level at the start of the line
                     let cursor_before = self.cursor;
-                    self.buffer.insert(line_start, "    ", cursor_before, cursor_before);
+                    self.buffer.insert(line_start, &indent, cursor_before, cursor_before);
                     
                     // Track adjustments for cursor and selection
                     if self.cursor >= line_start {
-                        cursor_adjustment += 4;
+                        cursor_adjustment += indent.len();
                     }
                     if let Some(sel_start) = self.selection_start {
                         if sel_start >= line_start {
-                            selection_start_adjustment += 4;
+                            selection_start_adjustment += indent.len();
                         }
                     }
                 }
@@ -1301,9 +1401,9 @@ impl Editor {
                     let line_start = self.buffer.line_to_byte(line_num);
                     let line_text = self.buffer.line(line_num);
                     
-                    // Count leading spaces (up to 4)
+                    // Count leading spaces (up to one indent level)
                     let mut spaces_count = 0;
-                    for ch in line_text.chars().take(4) {
+                    for ch in line_text.chars().take(self.tab_width) {
                         if ch == ' ' {
                             spaces_count += 1;
                         } else {
@@ -1342,9 +1442,31 @@ impl Editor {
                 self.modified = true;
             }
             
+            Command::AddCursorAbove => self.add_cursor_vertical(false),
+            Command::AddCursorBelow => self.add_cursor_vertical(true),
+            Command::AddNextOccurrence => self.add_next_occurrence(),
+            Command::ClearExtraCursors => self.clear_extra_cursors(),
+
+            Command::ToggleFold => self.toggle_fold(),
+            Command::FoldAllCells => self.fold_all_cells(),
+            Command::UnfoldAll => self.unfold_all(),
+
+            Command::ToggleSoftWrap => self.toggle_soft_wrap(),
+
             Command::None => {}
         }
         
+        // The cursor never rests on a folded line: moving steps over the fold, anything else opens it
+        let moving = matches!(cmd,
+            Command::MoveUp | Command::MoveDown | Command::MoveLeft | Command::MoveRight |
+            Command::MoveWordLeft | Command::MoveWordRight | Command::PageUp | Command::PageDown |
+            Command::MoveParagraphUp | Command::MoveParagraphDown |
+            Command::SelectUp | Command::SelectDown | Command::SelectLeft | Command::SelectRight |
+            Command::SelectWordLeft | Command::SelectWordRight |
+            Command::SelectParagraphUp | Command::SelectParagraphDown
+        );
+        self.keep_cursor_out_of_folds(moving.then_some(self.cursor >= cursor_before));
+
         // Update viewport if cursor moved (but not for pure viewport scrolling)
         if cursor_moved || matches!(cmd,
             Command::InsertChar(_) | Command::InsertNewline | Command::InsertTab |
@@ -1386,6 +1508,15 @@ impl Editor {
     pub fn is_read_only(&self) -> bool {
         self.read_only
     }
+
+    /// The system clipboard, reached now if it couldn't be when the editor was made, as over
+    /// SSH without a display
+    fn clipboard(&mut self) -> Result<&mut Clipboard, arboard::Error> {
+        if self.clipboard.is_none() {
+            self.clipboard = Some(Clipboard::new()?);
+        }
+        self.clipboard.as_mut().ok_or(arboard::Error::ClipboardNotSupported)
+    }
     
     /// Get the current file path or the current directory for Save As prompt
     pub fn get_save_as_initial_path(&self) -> String {
@@ -1404,9 +1535,11 @@ impl Editor {
     
     /// Move cursor to a specific position
     pub fn move_cursor_to(&mut self, position: usize) {
+        self.clear_extra_cursors();
         self.cursor = position.min(self.buffer.len_bytes());
         self.selection_start = None;
         self.preferred_column = None; // Clear preferred column
+        self.unfold_range(self.cursor, self.cursor);
         self.update_viewport_for_cursor();
     }
     
@@ -1419,11 +1552,15 @@ impl Editor {
     
     /// Direct paste method for bracketed paste support
     pub fn paste_text(&mut self, text: String) {
+        if !self.extra_cursors.is_empty() {
+            return self.paste_at_cursors(text);
+        }
+
         // Delete selection first if any
         self.delete_selection();
         
         // Normalize: CRLF → LF, tabs → spaces, remove invisible characters
-        let text = Self::normalize_text(text);
+        let text = Self::normalize_text(text, self.tab_width);
         
         let line_before = self.buffer.byte_to_line(self.cursor);
         let cursor_before = self.cursor;
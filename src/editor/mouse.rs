@@ -57,6 +57,7 @@ impl Editor {
             }
             _ => {
                 // Single click - start normal selection
+                self.clear_extra_cursors();
                 self.cursor = position;
                 self.selection_start = None;
                 self.mouse_selecting = true;
@@ -100,7 +101,7 @@ impl Editor {
     }
 
     /// Select the word at the given position
-    fn select_word_at(&mut self, position: usize) {
+    pub(super) fn select_word_at(&mut self, position: usize) {
         let content = self.buffer.to_string();
         let chars: Vec<char> = content.chars().collect();
 
@@ -180,6 +181,7 @@ impl Editor {
     /// Finish mouse selection
     pub fn finish_mouse_selection(&mut self) {
         self.mouse_selecting = false;
+        self.block_anchor = None;
         // If selection start equals cursor, clear the selection
         if let Some(start) = self.selection_start {
             if start == self.cursor {
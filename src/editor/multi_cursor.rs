@@ -0,0 +1,349 @@
+use unicode_width::UnicodeWidthChar;
+use super::Editor;
+use crate::commands::Command;
+
+/// A cursor besides the main one, with its own selection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct ExtraCursor {
+    cursor: usize,
+    selection_start: Option<usize>,
+    preferred_column: Option<usize>,
+}
+
+impl ExtraCursor {
+    /// Where its text starts - the selection's start, or the cursor
+    fn start(&self) -> usize {
+        self.selection_start.map_or(self.cursor, |start| start.min(self.cursor))
+    }
+
+    fn shift(&mut self, delta: isize) {
+        self.cursor = self.cursor.saturating_add_signed(delta);
+        self.selection_start = self.selection_start.map(|start| start.saturating_add_signed(delta));
+    }
+}
+
+impl Editor {
+    /// Go back to the main cursor only
+    pub fn clear_extra_cursors(&mut self) {
+        self.extra_cursors.clear();
+        self.block_anchor = None;
+    }
+
+    /// Whether there are cursors besides the main one
+    pub fn has_extra_cursors(&self) -> bool {
+        !self.extra_cursors.is_empty()
+    }
+
+    /// Positions of the cursors besides the main one, for the renderer
+    pub fn extra_cursor_positions(&self) -> Vec<usize> {
+        self.extra_cursors.iter().map(|extra| extra.cursor).collect()
+    }
+
+    /// Selections of the cursors besides the main one, for the renderer
+    pub fn extra_selections(&self) -> Vec<(usize, usize)> {
+        self.extra_cursors
+            .iter()
+            .filter_map(|extra| extra.selection_start.map(|start| (start.min(extra.cursor), start.max(extra.cursor))))
+            .filter(|(start, end)| start < end)
+            .collect()
+    }
+
+    /// Whether a command is carried out at every cursor rather than only the main one
+    pub(super) fn applies_at_every_cursor(cmd: &Command) -> bool {
+        matches!(cmd,
+            Command::InsertChar(_) | Command::InsertNewline | Command::InsertTab |
+            Command::Indent | Command::Dedent | Command::Backspace | Command::Delete |
+            Command::Cut | Command::Paste |
+            Command::MoveUp | Command::MoveDown | Command::MoveLeft | Command::MoveRight |
+            Command::MoveHome | Command::MoveEnd | Command::MoveWordLeft | Command::MoveWordRight |
+            Command::SelectUp | Command::SelectDown | Command::SelectLeft | Command::SelectRight |
+            Command::SelectHome | Command::SelectEnd | Command::SelectWordLeft | Command::SelectWordRight
+        )
+    }
+
+    /// Carry out a command at every cursor, as one undo step
+    pub(super) fn execute_at_cursors(&mut self, cmd: Command) {
+        match cmd {
+            Command::Paste => match self.clipboard().and_then(|clipboard| clipboard.get_text()) {
+                Ok(text) => self.paste_at_cursors(text),
+                Err(e) => eprintln!("Failed to paste from clipboard: {}", e),
+            },
+            Command::Cut => {
+                let text = self.selected_texts();
+                self.for_each_cursor(false, |editor, _| {
+                    editor.delete_selection();
+                });
+                if !text.is_empty() {
+                    if let Err(e) = self.clipboard().and_then(|clipboard| clipboard.set_text(text)) {
+                        eprintln!("Failed to copy to clipboard: {}", e);
+                    }
+                }
+            }
+            Command::Indent | Command::Dedent => {
+                self.for_each_cursor(true, |editor, _| {
+                    let _ = editor.execute(cmd.clone());
+                });
+            }
+            cmd => {
+                self.for_each_cursor(false, |editor, _| {
+                    let _ = editor.execute(cmd.clone());
+                });
+            }
+        }
+    }
+
+    /// Copy every cursor's selection, one per line in document order
+    pub(super) fn copy_at_cursors(&mut self) {
+        let text = self.selected_texts();
+        if !text.is_empty() {
+            if let Err(e) = self.clipboard().and_then(|clipboard| clipboard.set_text(text)) {
+                eprintln!("Failed to copy to clipboard: {}", e);
+            }
+        }
+    }
+
+    /// Paste at every cursor - one line each when the text has a line per cursor
+    pub(super) fn paste_at_cursors(&mut self, text: String) {
+        let text = Self::normalize_text(text, self.tab_width);
+        let lines: Vec<&str> = text.strip_suffix('\n').unwrap_or(&text).split('\n').collect();
+        let one_line_each = lines.len() == self.extra_cursors.len() + 1;
+        self.for_each_cursor(false, |editor, idx| {
+            let piece = if one_line_each { lines[idx] } else { text.as_str() };
+            editor.paste_text(piece.to_string());
+        });
+    }
+
+    /// Selected text of every cursor, joined by newlines in document order
+    fn selected_texts(&self) -> String {
+        let mut selections = self.extra_selections();
+        selections.extend(self.get_selection().filter(|(start, end)| start < end));
+        selections.sort_unstable();
+        selections
+            .iter()
+            .map(|&(start, end)| self.buffer.rope().byte_slice(start..end).to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Run an edit once per cursor with that cursor swapped in as the main one, last in the
+    /// document first, so each edit only moves the cursors after it. The edits make one undo
+    /// step. `once_per_line` skips cursors on a line an earlier run already changed (indenting)
+    fn for_each_cursor(&mut self, once_per_line: bool, mut edit: impl FnMut(&mut Self, usize)) {
+        let main = ExtraCursor {
+            cursor: self.cursor,
+            selection_start: self.selection_start,
+            preferred_column: self.preferred_column,
+        };
+        let mut unsorted = std::mem::take(&mut self.extra_cursors);
+        unsorted.push(main);
+        // Sorted by position; the main cursor, pushed last, is found by its index as others may equal it
+        let mut order: Vec<usize> = (0..unsorted.len()).collect();
+        order.sort_by_key(|&idx| unsorted[idx].start());
+        let main_idx = order.iter().position(|&idx| idx == unsorted.len() - 1).unwrap_or(0);
+        let mut cursors: Vec<ExtraCursor> = order.iter().map(|&idx| unsorted[idx]).collect();
+
+        self.buffer.begin_undo_group();
+        // Lines the last edit changed: the first one, and how much each grew counting from it
+        let mut changed_lines: Option<(usize, Vec<isize>)> = None;
+        for idx in (0..cursors.len()).rev() {
+            let cursor = cursors[idx];
+            let first_line = self.buffer.byte_to_line(cursor.start());
+            if once_per_line {
+                if let Some((from, growth)) = &changed_lines {
+                    if let Some(grown) = first_line.checked_sub(*from).and_then(|offset| growth.get(offset)) {
+                        // Changed along with the cursor after it; only its position moves
+                        let line_start = self.buffer.line_to_byte(first_line);
+                        let mut moved = cursor;
+                        moved.shift(*grown);
+                        moved.cursor = moved.cursor.max(line_start);
+                        moved.selection_start = moved.selection_start.map(|start| start.max(line_start));
+                        cursors[idx] = moved;
+                        continue;
+                    }
+                }
+            }
+
+            let last_line = self.buffer.byte_to_line(cursor.cursor.max(cursor.selection_start.unwrap_or(0)));
+            let line_lens: Vec<usize> = (first_line..=last_line).map(|line| self.buffer.line(line).len()).collect();
+            self.cursor = cursor.cursor;
+            self.selection_start = cursor.selection_start;
+            self.preferred_column = cursor.preferred_column;
+            let len_before = self.buffer.len_bytes() as isize;
+            edit(self, idx);
+            let delta = self.buffer.len_bytes() as isize - len_before;
+
+            if once_per_line {
+                let mut total = 0;
+                let growth = line_lens
+                    .iter()
+                    .enumerate()
+                    .map(|(offset, len)| {
+                        total += self.buffer.line(first_line + offset).len() as isize - *len as isize;
+                        total
+                    })
+                    .collect();
+                changed_lines = Some((first_line, growth));
+            }
+            cursors[idx] = ExtraCursor {
+                cursor: self.cursor,
+                selection_start: self.selection_start,
+                preferred_column: self.preferred_column,
+            };
+            // Cursors after this one move with the text
+            for later in &mut cursors[idx + 1..] {
+                later.shift(delta);
+            }
+        }
+        self.buffer.end_undo_group();
+
+        // Cursors that ran into each other become one
+        let len = self.buffer.len_bytes();
+        let main = cursors[main_idx];
+        cursors.remove(main_idx);
+        cursors.retain(|cursor| cursor.cursor != main.cursor);
+        cursors.dedup_by_key(|cursor| cursor.cursor);
+        for cursor in &mut cursors {
+            cursor.cursor = cursor.cursor.min(len);
+            cursor.selection_start = cursor.selection_start.map(|start| start.min(len));
+        }
+        self.cursor = main.cursor.min(len);
+        self.selection_start = main.selection_start.map(|start| start.min(len));
+        self.preferred_column = main.preferred_column;
+        self.extra_cursors = cursors;
+    }
+
+    /// Add a cursor on the line above the topmost cursor (or below the bottommost), in the
+    /// same column. The new cursor becomes the main one, so the view follows it
+    pub fn add_cursor_vertical(&mut self, below: bool) {
+        let positions = self.extra_cursors.iter().map(|extra| extra.cursor).chain([self.cursor]);
+        let edge = if below { positions.max() } else { positions.min() }.unwrap_or(self.cursor);
+        let (line, col) = self.byte_position_to_display(edge);
+        let col = if edge == self.cursor { self.preferred_column.unwrap_or(col) } else { col };
+        let target = match below {
+            true if line + 1 < self.buffer.len_lines() => self.visible_line_below(line),
+            false if line > 0 => self.visible_line_above(line),
+            _ => return,
+        };
+        if target == line {
+            return;
+        }
+
+        self.push_main_cursor();
+        self.cursor = self.position_at_column(target, col);
+        self.preferred_column = Some(col);
+        self.update_viewport_for_cursor();
+        self.update_matching();
+    }
+
+    /// Select the word at the cursor, or add a cursor selecting the next occurrence of the
+    /// selection after the newest cursor
+    pub fn add_next_occurrence(&mut self) {
+        let Some((start, end)) = self.get_selection().filter(|(start, end)| start < end) else {
+            self.select_word_at(self.cursor);
+            self.update_matching();
+            return;
+        };
+
+        // Short selections aren't highlighted, so their occurrences aren't known yet
+        let mut occurrences = self.matching_text_positions.clone();
+        if occurrences.is_empty() {
+            let text = self.buffer.rope().byte_slice(start..end).to_string();
+            occurrences = self
+                .buffer
+                .to_string()
+                .match_indices(&text)
+                .map(|(pos, _)| (pos, pos + text.len()))
+                .filter(|&(pos, _)| pos != start)
+                .collect();
+        }
+        let taken: Vec<usize> = self.extra_cursors.iter().map(|extra| extra.start()).collect();
+        let next = occurrences
+            .iter()
+            .filter(|(pos, _)| *pos >= end && !taken.contains(pos))
+            .chain(occurrences.iter().filter(|(pos, _)| *pos < start && !taken.contains(pos)))
+            .next()
+            .copied();
+
+        match next {
+            Some((next_start, next_end)) => {
+                self.push_main_cursor();
+                self.selection_start = Some(next_start);
+                self.cursor = next_end;
+                self.update_viewport_for_cursor();
+                self.update_matching();
+            }
+            None => self.status_message = Some(("No more occurrences".to_string(), false)),
+        }
+    }
+
+    /// Start an Alt+drag block selection
+    pub fn start_block_selection(&mut self, position: usize) {
+        self.clear_extra_cursors();
+        self.block_anchor = Some(self.byte_position_to_display(position));
+        self.cursor = position;
+        self.selection_start = None;
+        self.mouse_selecting = false;
+        self.preferred_column = None;
+        self.update_viewport_for_cursor();
+    }
+
+    /// Select the columns between the anchor and `position` on every line between them,
+    /// with a cursor on each line. The line under the mouse gets the main cursor
+    pub fn update_block_selection(&mut self, position: usize) -> bool {
+        let Some((anchor_line, anchor_col)) = self.block_anchor else {
+            return false;
+        };
+        let (line, col) = self.byte_position_to_display(position);
+        let (left, right) = (anchor_col.min(col), anchor_col.max(col));
+
+        self.extra_cursors.clear();
+        let folded = self.folded_ranges();
+        for row in anchor_line.min(line)..=anchor_line.max(line) {
+            if folded.iter().any(|(first, last)| (*first..=*last).contains(&row)) {
+                continue;
+            }
+            let from = self.position_at_column(row, left);
+            let to = self.position_at_column(row, right);
+            // Lines too short to reach the block are left out
+            if row != line && self.byte_position_to_display(from).1 < left {
+                continue;
+            }
+            let (cursor, anchor) = if col < anchor_col { (from, to) } else { (to, from) };
+            let selection_start = (from != to).then_some(anchor);
+            if row == line {
+                self.cursor = cursor;
+                self.selection_start = selection_start;
+            } else {
+                self.extra_cursors.push(ExtraCursor { cursor, selection_start, preferred_column: None });
+            }
+        }
+        self.update_viewport_for_cursor();
+        true
+    }
+
+    /// Keep the main cursor as an extra one, to make room for a new main cursor
+    fn push_main_cursor(&mut self) {
+        self.extra_cursors.push(ExtraCursor {
+            cursor: self.cursor,
+            selection_start: self.selection_start,
+            preferred_column: self.preferred_column,
+        });
+        self.selection_start = None;
+    }
+
+    /// Byte position nearest a display column on a line, but not past its end
+    fn position_at_column(&self, line: usize, col: usize) -> usize {
+        let line_start = self.buffer.line_to_byte(line);
+        let mut offset = 0;
+        let mut display_col = 0;
+        for ch in self.buffer.line(line).chars() {
+            let width = ch.width().unwrap_or(1);
+            if ch == '\n' || display_col + width > col {
+                break;
+            }
+            display_col += width;
+            offset += ch.len_utf8();
+        }
+        line_start + offset
+    }
+}
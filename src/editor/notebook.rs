@@ -1,6 +1,9 @@
-use crate::cell::{Cell, parse_cells, get_cell_at_position, get_cell_content};
-use crate::kernel::Kernel;
+use crate::cell::{Cell, CellBadge, CellType, parse_cells, carry_cell_state, mark_downstream_stale, get_cell_at_position, get_cell_content, get_cell_header, format_output, source_hash, TAG_RAISES, TAG_SETUP, TAG_SKIP, TAG_SLOW};
+use crate::export::ExecutedCell;
+use crate::kernel::{ExecutionResult, Kernel};
 use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
 use super::Editor;
 
@@ -70,9 +73,138 @@ impl Editor {
         }
     }
 
-    /// Update cells by parsing the buffer
+    /// Update cells by parsing the buffer, keeping execution state of cells that survived
     pub fn update_cells(&mut self) {
-        self.cells = parse_cells(self.buffer.rope());
+        let mut cells = parse_cells(self.buffer.rope());
+        carry_cell_state(&self.cells, &mut cells);
+
+        // Edited cells invalidate everything that depends on them
+        let modified: Vec<usize> = cells.iter().enumerate()
+            .filter(|(_, cell)| cell.is_modified())
+            .map(|(idx, _)| idx)
+            .collect();
+        for idx in modified {
+            mark_downstream_stale(&mut cells, idx);
+        }
+
+        self.cells = cells;
+        self.cells_version = Some(self.buffer.version());
+    }
+
+    /// Re-parse cells if the buffer changed since they were last parsed
+    pub fn refresh_cells(&mut self) {
+        if self.repl_mode && self.cells_version != Some(self.buffer.version()) {
+            self.update_cells();
+        }
+    }
+
+    /// Store the result of running a cell and mark the cells that depend on it stale
+    /// `executed_hash` is the `source_hash` of the code that was actually sent to the kernel
+    /// `cell_idx` is where the cell was when it started; cells added or removed above it since
+    /// move it, so it's found by that hash, and the result dropped if it can't be told apart
+    pub fn record_cell_run(&mut self, cell_idx: usize, executed_hash: u64, elapsed_secs: f64, result: ExecutionResult) {
+        self.refresh_cells();
+        let Some(cell_idx) = self.locate_cell(cell_idx, executed_hash) else {
+            return;
+        };
+        let cell = &mut self.cells[cell_idx];
+        cell.execution_count = result.execution_count;
+        cell.executed_hash = Some(executed_hash);
+        cell.elapsed_secs = Some(elapsed_secs);
+        cell.stale = false;
+        cell.output_scroll = 0;
+        cell.output_hash = source_hash(&format_output(&result));
+        cell.output = Some(result);
+        mark_downstream_stale(&mut self.cells, cell_idx);
+    }
+
+    /// Where a cell that was at `cell_idx` with code hashing to `hash` is now: the one cell with
+    /// that code, or `cell_idx` if it's one of several
+    pub(super) fn locate_cell(&self, cell_idx: usize, hash: u64) -> Option<usize> {
+        let mut matching = self.cells.iter().enumerate().filter(|(_, cell)| cell.source_hash == hash).map(|(idx, _)| idx);
+        match (matching.next(), matching.next()) {
+            (Some(idx), None) => Some(idx),
+            (Some(_), Some(_)) => self.cells.get(cell_idx).filter(|cell| cell.source_hash == hash).map(|_| cell_idx),
+            _ => None,
+        }
+    }
+
+    /// Stale code cells, in order (cells tagged `skip` are left out)
+    pub fn stale_cell_indices(&mut self) -> Vec<usize> {
+        self.update_cells();
+        self.cells.iter().enumerate()
+            .filter(|(_, cell)| cell.stale && cell.cell_type == CellType::Code && !cell.has_tag(TAG_SKIP))
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    /// Cell numbers (1-indexed) of all stale cells, for the output pane
+    pub fn stale_cell_numbers(&self) -> Vec<usize> {
+        self.cells.iter().enumerate()
+            .filter(|(_, cell)| cell.stale)
+            .map(|(idx, _)| idx + 1)
+            .collect()
+    }
+
+    /// Mark cells, given as (index, hash of their code), as running in the background;
+    /// `finished` counts the ones already done
+    pub fn start_running_cells(&mut self, cells: Vec<(usize, u64)>, finished: Arc<AtomicUsize>) {
+        self.running_cells = Some((cells, finished));
+    }
+
+    /// Clear the running/queued markers once a batch completes or is cancelled
+    pub fn finish_running_cells(&mut self) {
+        self.running_cells = None;
+    }
+
+    /// The cell of the background batch running now, and those still waiting, wherever edits
+    /// since the batch started have moved them
+    pub(super) fn pending_cells(&self) -> (Option<usize>, Vec<usize>) {
+        let Some((cells, finished)) = &self.running_cells else {
+            return (None, Vec::new());
+        };
+        let pending = &cells[finished.load(Ordering::Relaxed).min(cells.len())..];
+        let running = pending.first().and_then(|&(idx, hash)| self.locate_cell(idx, hash));
+        let queued = pending.iter().skip(1).filter_map(|&(idx, hash)| self.locate_cell(idx, hash)).collect();
+        (running, queued)
+    }
+
+    /// Badges for the right edge of delimiter lines, keyed by buffer line
+    pub fn cell_badges(&self) -> Vec<(usize, Vec<CellBadge>)> {
+        let rope = self.buffer.rope();
+        let (running, queued) = self.pending_cells();
+
+        self.cells.iter().enumerate()
+            .filter_map(|(idx, cell)| {
+                let mut badges = Vec::new();
+                if cell.stale {
+                    badges.push(CellBadge::Stale);
+                }
+                if cell.is_modified() {
+                    badges.push(CellBadge::Modified);
+                }
+                if running == Some(idx) {
+                    badges.push(CellBadge::Running);
+                } else if queued.contains(&idx) {
+                    badges.push(CellBadge::Queued);
+                } else if let Some(count) = cell.execution_count {
+                    badges.push(CellBadge::Count(count));
+                    if let Some(elapsed) = cell.elapsed_secs {
+                        badges.push(CellBadge::Elapsed(elapsed));
+                    }
+                    match &cell.output {
+                        Some(output) if output.success => badges.push(CellBadge::Ok),
+                        Some(_) => badges.push(CellBadge::Error),
+                        None => {}
+                    }
+                }
+                if badges.is_empty() {
+                    None
+                } else {
+                    Some((rope.byte_to_line(cell.start), badges))
+                }
+            })
+            .collect()
     }
 
     /// Get cells for rendering
@@ -80,6 +212,82 @@ impl Editor {
         &self.cells
     }
 
+    /// Cells overlapping the selection, or the cell under the cursor if nothing is selected
+    pub fn cells_to_execute(&mut self) -> Vec<usize> {
+        self.update_cells();
+
+        if let Some((sel_start, sel_end)) = self.get_selection() {
+            self.cells.iter().enumerate()
+                .filter(|(_, cell)| cell.start < sel_end && cell.end > sel_start)
+                .map(|(idx, _)| idx)
+                .collect()
+        } else if let Some(cell_idx) = get_cell_at_position(&self.cells, self.cursor) {
+            vec![cell_idx]
+        } else {
+            vec![]
+        }
+    }
+
+    /// Cells for "run all" - everything except markdown and `skip` cells
+    pub fn run_all_cell_indices(&mut self) -> Vec<usize> {
+        self.update_cells();
+        self.cells.iter().enumerate()
+            .filter(|(_, cell)| cell.cell_type == CellType::Code && !cell.has_tag(TAG_SKIP))
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    /// Cells tagged `setup`, run after a kernel connects or restarts
+    pub fn setup_cell_indices(&mut self) -> Vec<usize> {
+        self.update_cells();
+        self.cells.iter().enumerate()
+            .filter(|(_, cell)| cell.has_tag(TAG_SETUP) && !cell.has_tag(TAG_SKIP))
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    /// Cell numbers (1-indexed) of the `slow` cells among the given cells
+    pub fn slow_cell_numbers(&self, indices: &[usize]) -> Vec<usize> {
+        indices.iter()
+            .filter(|&&idx| self.cells.get(idx).map(|cell| cell.has_tag(TAG_SLOW)).unwrap_or(false))
+            .map(|&idx| idx + 1)
+            .collect()
+    }
+
+    /// Code for the given cells as (cell_number, code, raises) for background execution
+    pub fn cell_batch(&self, indices: &[usize]) -> Vec<(usize, String, bool)> {
+        indices.iter()
+            .filter_map(|&idx| self.cells.get(idx).map(|cell| (idx, cell)))
+            .map(|(idx, cell)| {
+                let code = get_cell_content(self.buffer.rope(), cell);
+                (idx + 1, code, cell.has_tag(TAG_RAISES))
+            })
+            .collect()
+    }
+
+    /// Every cell with its latest output, for writing a report or executed notebook
+    pub fn executed_cells(&mut self) -> Vec<ExecutedCell> {
+        self.update_cells();
+        let rope = self.buffer.rope();
+        self.cells.iter()
+            .map(|cell| {
+                let header = get_cell_header(rope, cell);
+                let source = if header.is_some() {
+                    get_cell_content(rope, cell)
+                } else {
+                    rope.slice(cell.start..cell.end).to_string()
+                };
+                ExecutedCell {
+                    header,
+                    source,
+                    cell_type: cell.cell_type.clone(),
+                    tags: cell.tags.clone(),
+                    result: cell.output.clone(),
+                }
+            })
+            .collect()
+    }
+
     /// Execute all cells within selection (or current cell if no selection)
     /// Returns: Vec<(execution_count, cell_line, output_text, is_error, elapsed_secs)>
     pub fn execute_selected_cells_with_output(&mut self) -> Vec<(usize, usize, String, bool, f64)> {
@@ -89,28 +297,8 @@ impl Editor {
             return results;
         }
 
-        // Parse cells
-        self.update_cells();
-
         // Find cells to execute
-        let cells_to_execute: Vec<usize> = if let Some((sel_start, sel_end)) = self.get_selection() {
-            // Execute all cells that overlap with the selection
-            self.cells.iter().enumerate()
-                .filter(|(_, cell)| {
-                    // Cell overlaps with selection if:
-                    // cell.start < sel_end && cell.end > sel_start
-                    cell.start < sel_end && cell.end > sel_start
-                })
-                .map(|(idx, _)| idx)
-                .collect()
-        } else {
-            // No selection - execute current cell only
-            if let Some(cell_idx) = get_cell_at_position(&self.cells, self.cursor) {
-                vec![cell_idx]
-            } else {
-                vec![]
-            }
-        };
+        let cells_to_execute = self.cells_to_execute();
 
         // Save total cell count before loop consumes the vector
         let total_cells_to_execute = cells_to_execute.len();
@@ -129,17 +317,17 @@ impl Editor {
                     Ok(result) => {
                         let elapsed = start_time.elapsed().as_secs_f64();
 
-                        // Store result in cell
-                        self.cells[cell_idx].output = Some(result.clone());
-
                         let execution_count = result.execution_count.unwrap_or(0);
                         let output_text = crate::cell::format_output(&result);
                         let is_error = !result.success;
 
+                        // Store result in cell
+                        self.record_cell_run(cell_idx, source_hash(&code), elapsed, result);
+
                         results.push((execution_count, cell_number, output_text, is_error, elapsed));
 
-                        // Stop execution if this cell had an error
-                        if is_error {
+                        // Stop execution if this cell had an error (unless it's expected to raise)
+                        if is_error && !self.cells[cell_idx].has_tag(TAG_RAISES) {
                             break;
                         }
                     }
@@ -225,10 +413,12 @@ impl Editor {
 
             // Execute code
             if let Some(kernel) = self.kernel.as_mut() {
+                let start_time = std::time::Instant::now();
                 match kernel.execute(&code) {
                     Ok(result) => {
                         // Store result in cell
-                        self.cells[cell_idx].output = Some(result.clone());
+                        let elapsed = start_time.elapsed().as_secs_f64();
+                        self.record_cell_run(cell_idx, source_hash(&code), elapsed, result.clone());
 
                         if result.success {
                             // Format output for display
@@ -326,3 +516,57 @@ impl Editor {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::Buffer;
+
+    fn notebook(text: &str) -> Editor {
+        let mut editor = Editor::new();
+        editor.buffer = Buffer::from_string(text.to_string());
+        editor.enable_repl_mode();
+        editor
+    }
+
+    fn run(editor: &mut Editor, cell_idx: usize, code: &str, count: usize) {
+        let result = ExecutionResult { outputs: Vec::new(), execution_count: Some(count), success: true, completions: Vec::new(), names: None };
+        editor.record_cell_run(cell_idx, source_hash(code), 0.1, result);
+    }
+
+    fn counts(editor: &Editor) -> Vec<Option<usize>> {
+        editor.cells.iter().map(|cell| cell.execution_count).collect()
+    }
+
+    #[test]
+    fn results_follow_cells_moved_by_edits_above() {
+        let mut editor = notebook("##$$\na = 1\n##$$\nb = 2\n");
+        // A cell is added above the one that was running as cell 1
+        editor.buffer.insert(0, "##$$\nz = 0\n", 0, 0);
+        run(&mut editor, 1, "b = 2\n", 7);
+        assert_eq!(counts(&editor), [None, None, Some(7)]);
+    }
+
+    #[test]
+    fn running_and_queued_cells_follow_edits_above() {
+        let mut editor = notebook("##$$\na = 1\n##$$\nb = 2\n##$$\nc = 3\n");
+        let finished = Arc::new(AtomicUsize::new(0));
+        editor.start_running_cells(vec![(1, source_hash("b = 2\n")), (2, source_hash("c = 3\n"))], finished.clone());
+        editor.buffer.insert(0, "##$$\nz = 0\n", 0, 0);
+        editor.refresh_cells();
+        assert_eq!(editor.pending_cells(), (Some(2), vec![3]));
+        finished.store(1, Ordering::Relaxed);
+        assert_eq!(editor.pending_cells(), (Some(3), vec![]));
+    }
+
+    #[test]
+    fn ambiguous_or_edited_results_are_dropped() {
+        let mut editor = notebook("##$$\nx = 1\n##$$\nx = 1\n##$$\ny = 2\n");
+        run(&mut editor, 1, "x = 1\n", 1);
+        assert_eq!(counts(&editor), [None, Some(1), None]);
+        run(&mut editor, 2, "x = 1\n", 2);
+        assert_eq!(counts(&editor), [None, Some(1), None]);
+        run(&mut editor, 2, "y = 3\n", 3);
+        assert_eq!(counts(&editor), [None, Some(1), None]);
+    }
+}
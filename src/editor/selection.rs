@@ -78,9 +78,16 @@ impl Editor {
         self.get_selection()
     }
 
+    /// Whether the selection reaches past the line it starts on
+    pub fn selection_is_multiline(&self) -> bool {
+        self.get_selection().is_some_and(|(start, end)| self.buffer.byte_to_line(start) != self.buffer.byte_to_line(end))
+    }
+
     pub fn select_range(&mut self, start: usize, end: usize) {
+        self.clear_extra_cursors();
         self.selection_start = Some(start);
         self.cursor = end;
+        self.unfold_range(start.min(end), start.max(end));
     }
 
     pub fn replace_selection(&mut self, replacement: &str) -> bool {
@@ -0,0 +1,89 @@
+use crate::undo_tree;
+use super::Editor;
+
+impl Editor {
+    /// Put the cursor where an undo, a redo or a jump through the undo tree left it.
+    /// Returns whether the text changed
+    pub(super) fn after_undo_jump(&mut self, cursor: Option<usize>) -> bool {
+        let Some(cursor) = cursor else {
+            return false;
+        };
+        let cursor = cursor.min(self.buffer.len_bytes());
+        self.cursor = self.ensure_char_boundary(cursor);
+        self.modified = self.buffer.undo_state() != self.saved_undo_state;
+        self.reinit_syntax_highlighting();
+        true
+    }
+
+    /// Go to the state made just before the current one, or just after, on whichever branch
+    /// it is. Returns whether the text changed
+    pub(super) fn step_undo_state(&mut self, later: bool) -> bool {
+        self.buffer.finalize_undo_group();
+        let tree = self.buffer.undo_tree();
+        let target = match later {
+            true if tree.current() + 1 < tree.len() => tree.current() + 1,
+            false if tree.current() > 0 => tree.current() - 1,
+            _ => {
+                let edge = if later { "newest" } else { "oldest" };
+                self.status_message = Some((format!("Already at the {} undo state", edge), false));
+                return false;
+            }
+        };
+        let cursor = self.buffer.go_to_state(target);
+        let changed = self.after_undo_jump(cursor);
+        self.show_undo_state();
+        changed
+    }
+
+    /// Go back or forward in time through the undo states, from when the current one was made
+    pub fn travel_undo_time(&mut self, seconds: u64, later: bool) {
+        self.buffer.finalize_undo_group();
+        let tree = self.buffer.undo_tree();
+        let time = tree.time(tree.current());
+        let target = if later {
+            tree.state_at(time.saturating_add(seconds))
+        } else {
+            tree.state_at(time.saturating_sub(seconds))
+        };
+        self.go_to_undo_state(target);
+    }
+
+    /// Undo and redo the way to a state of the undo tree, e.g. one picked in the undo tree view
+    pub fn go_to_undo_state(&mut self, state: usize) {
+        self.clear_extra_cursors();
+        let cursor = self.buffer.go_to_state(state);
+        if self.after_undo_jump(cursor) {
+            self.keep_cursor_out_of_folds(None);
+            self.update_viewport_for_cursor();
+            self.update_matching();
+        }
+        self.show_undo_state();
+    }
+
+    /// Make redo take the next branch from the current state
+    pub(super) fn switch_redo_branch(&mut self) {
+        let message = match self.buffer.switch_redo_branch(true) {
+            Some((branch, count)) if count > 1 => format!("Redo takes branch {} of {}", branch, count),
+            Some(_) => "Only one way to redo from here".to_string(),
+            None => "Nothing to redo".to_string(),
+        };
+        self.status_message = Some((message, false));
+    }
+
+    /// The undo state the text is in and when it was made, in the status bar
+    fn show_undo_state(&mut self) {
+        let tree = self.buffer.undo_tree();
+        let state = tree.current();
+        let message = if state == 0 {
+            format!("Undo state 0 of {}, before any edit kept", tree.len() - 1)
+        } else {
+            format!("Undo state {} of {}, made {}", state, tree.len() - 1, undo_tree::age(tree.time(state), undo_tree::now()))
+        };
+        self.status_message = Some((message, false));
+    }
+
+    /// The undo state the text was last saved in, if it was saved in this session or opened
+    pub fn saved_undo_state(&self) -> usize {
+        self.saved_undo_state
+    }
+}
@@ -1,7 +1,44 @@
 use unicode_width::UnicodeWidthChar;
-use super::Editor;
+use crate::layout::Rect;
+use super::{DisplayLine, Editor};
+
+/// Where a window is looking in a buffer - each split window keeps its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct View {
+    cursor: usize,
+    selection_start: Option<usize>,
+    viewport_offset: (usize, usize),
+    preferred_column: Option<usize>,
+}
 
 impl Editor {
+    /// The cursor, selection and scroll position, to be restored with `set_view`
+    pub fn view(&self) -> View {
+        View {
+            cursor: self.cursor,
+            selection_start: self.selection_start,
+            viewport_offset: self.viewport_offset,
+            preferred_column: self.preferred_column,
+        }
+    }
+
+    /// Look at the buffer through another window's view
+    /// Positions are clamped, since the buffer may have been edited from another window
+    pub fn set_view(&mut self, view: View) {
+        self.clear_extra_cursors();
+        self.cursor = self.clamp_position(view.cursor);
+        self.selection_start = view.selection_start.map(|pos| self.clamp_position(pos));
+        self.viewport_offset = view.viewport_offset;
+        self.preferred_column = view.preferred_column;
+        self.unfold_range(self.cursor, self.cursor);
+    }
+
+    /// A byte position within the buffer, on a character boundary
+    fn clamp_position(&self, pos: usize) -> usize {
+        let pos = pos.min(self.buffer.len_bytes());
+        self.buffer.char_to_byte(self.buffer.byte_to_char(pos))
+    }
+
     pub fn cursor_position(&self) -> (usize, usize) {
         let line = self.buffer.byte_to_line(self.cursor);
         let line_start = self.buffer.line_to_byte(line);
@@ -44,29 +81,34 @@ impl Editor {
     }
 
     /// Update viewport to follow cursor - call this when cursor moves
-    /// bottom_window_height: height of any bottom window (output pane, find/replace, etc.)
+    /// Uses the editor area from the last layout, or the whole terminal minus the status bar
     pub fn update_viewport_for_cursor(&mut self) {
-        self.update_viewport_for_cursor_with_bottom(0);
+        if let Some((rows, cols)) = self.view_size {
+            self.update_viewport(rows, cols);
+        } else if let Ok((width, height)) = crossterm::terminal::size() {
+            self.update_viewport((height as usize).saturating_sub(1), width as usize);
+        }
     }
 
-    /// Update viewport to follow cursor with bottom window
-    pub fn update_viewport_for_cursor_with_bottom(&mut self, bottom_window_height: usize) {
-        // Get terminal size
-        if let Ok((width, height)) = crossterm::terminal::size() {
-            // Account for status bar and bottom window (output pane, find/replace, etc.)
-            let viewport_height = (height as usize).saturating_sub(1 + bottom_window_height);
-            let viewport_width = width as usize;
-            self.update_viewport(viewport_height, viewport_width);
-        }
+    /// Update viewport to follow cursor within the editor's area of the layout
+    pub fn update_viewport_for_area(&mut self, area: Rect) {
+        self.view_size = Some((area.height as usize, area.width as usize));
+        self.view_origin = (area.x as usize, area.y as usize);
+        self.update_viewport(area.height as usize, area.width as usize);
     }
 
     /// Update viewport to follow cursor with scrolloff
     pub(super) fn update_viewport(&mut self, viewport_height: usize, viewport_width: usize) {
+        self.sync_folds();
         let scrolloff = 3;
-        let (cursor_line, cursor_col) = self.cursor_position();
+        // Text is drawn to the right of the gutter
+        let viewport_width = viewport_width.saturating_sub(self.gutter_width());
+        self.refresh_wrap(viewport_width);
+        let viewport_width = viewport_width.max(scrolloff + 1);
 
-        // Logical line includes the 2 virtual lines before the buffer
-        let logical_cursor_line = cursor_line + 3;
+        // Display row includes the 2 virtual lines before the buffer (and any wrapped and inline output rows)
+        let (cursor_row, cursor_col) = self.cursor_display_position();
+        let logical_cursor_line = cursor_row + 1;
 
         // Vertical scrolling
         let cursor_screen_row = logical_cursor_line.saturating_sub(self.viewport_offset.0);
@@ -81,6 +123,12 @@ impl Editor {
             self.viewport_offset.0 = logical_cursor_line + scrolloff - viewport_height;
         }
 
+        // Wrapped lines fit the window, so there's nothing to scroll sideways
+        if self.soft_wrap {
+            self.viewport_offset.1 = 0;
+            return;
+        }
+
         // Horizontal scrolling - consider both cursor and selection start
         let mut left_col = cursor_col;
         let mut right_col = cursor_col;
@@ -102,18 +150,32 @@ impl Editor {
         }
     }
 
-    /// Convert screen coordinates to buffer position
+    /// Convert terminal coordinates to buffer position
     pub fn screen_to_buffer_position(&self, screen_col: usize, screen_row: usize) -> Option<usize> {
-        // Get terminal height to check if click is on status bar
-        if let Ok((_, height)) = crossterm::terminal::size() {
-            // Ignore clicks on the status bar (last row)
-            if screen_row >= (height - 1) as usize {
-                return None;
+        // Make the coordinates relative to the editor's area
+        let (origin_col, origin_row) = self.view_origin;
+        let screen_row = screen_row.checked_sub(origin_row)?;
+        let screen_col = screen_col.saturating_sub(origin_col);
+
+        // Ignore clicks below the editor area (status bar and panes)
+        match self.view_size {
+            Some((rows, _)) if screen_row >= rows => return None,
+            None => {
+                if let Ok((_, height)) = crossterm::terminal::size() {
+                    if screen_row >= (height - 1) as usize {
+                        return None;
+                    }
+                }
             }
+            _ => {}
         }
 
-        // Calculate logical line from screen row
-        let logical_line = self.viewport_offset.0 + screen_row;
+        // Calculate logical line from screen row - inline output rows aren't part of the buffer
+        let (logical_line, wrap_row) = match self.display_line_at(self.viewport_offset.0 + screen_row) {
+            DisplayLine::Text(line) => (line, 0),
+            DisplayLine::Wrap { line, row } => (line, row),
+            DisplayLine::Output { .. } => return None,
+        };
 
         // Virtual lines before the buffer (lines 0 and 1)
         if logical_line < 2 {
@@ -129,12 +191,18 @@ impl Editor {
             return Some(self.buffer.len_bytes());
         }
 
+        // Wrapped rows lay the line out differently, and don't scroll sideways
+        if self.soft_wrap {
+            return Some(self.wrap_byte_at(buffer_line, wrap_row, screen_col.saturating_sub(self.gutter_width())));
+        }
+
         // Get the line content
         let line = self.buffer.line(buffer_line);
         let line_start = self.buffer.line_to_byte(buffer_line);
 
-        // Calculate the display column accounting for horizontal scroll
-        let target_display_col = screen_col + self.viewport_offset.1;
+        // Calculate the display column accounting for the gutter and horizontal scroll
+        // A click in the gutter lands at the start of the visible text
+        let target_display_col = screen_col.saturating_sub(self.gutter_width()) + self.viewport_offset.1;
 
         // Find the byte position for the target display column
         let mut byte_pos = 0;
@@ -179,7 +247,7 @@ impl Editor {
             self.viewport_offset.0 = self.viewport_offset.0.saturating_add(lines as usize);
             // Don't scroll past the end of the buffer
             // We have 2 virtual lines before the buffer and allow some after
-            let max_offset = self.buffer.len_lines().saturating_add(2).saturating_add(10);
+            let max_offset = self.display_row_of_logical(self.buffer.len_lines() + 2).saturating_add(10);
             if self.viewport_offset.0 > max_offset {
                 self.viewport_offset.0 = max_offset;
             }
@@ -191,6 +259,9 @@ impl Editor {
 
     /// Scroll viewport horizontally without moving cursor
     pub fn scroll_viewport_horizontal(&mut self, cols: i32) {
+        if self.soft_wrap {
+            return;
+        }
         if cols > 0 {
             // Scrolling right (viewport moves right, content moves left)
             self.viewport_offset.1 = self.viewport_offset.1.saturating_add(cols as usize);
@@ -228,16 +299,14 @@ impl Editor {
         self.viewport_offset = offset;
     }
 
-    /// Get cursor screen position (for drawing overlays like autocomplete)
+    /// Get cursor terminal position (for drawing overlays like autocomplete)
     pub fn cursor_screen_position(&self) -> (usize, usize) {
-        let (cursor_line, cursor_col) = self.cursor_position();
+        let (cursor_row, cursor_col) = self.cursor_display_position();
         let (viewport_row, viewport_col) = self.viewport_offset();
 
-        // Calculate screen position (add 2 for virtual lines before buffer)
-        let logical_cursor_line = cursor_line + 2;
-        let screen_row = logical_cursor_line.saturating_sub(viewport_row);
-        let screen_col = cursor_col.saturating_sub(viewport_col);
+        let screen_row = cursor_row.saturating_sub(viewport_row);
+        let screen_col = self.gutter_width() + cursor_col.saturating_sub(viewport_col);
 
-        (screen_col, screen_row)
+        (self.view_origin.0 + screen_col, self.view_origin.1 + screen_row)
     }
 }
@@ -0,0 +1,331 @@
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+use super::folding::{logical_line_at, visible_row};
+use super::Editor;
+
+/// Drawn at the start of each continuation row unless the config says otherwise
+pub const DEFAULT_WRAP_INDICATOR: &str = "↪ ";
+
+/// Where long lines break into rows, for the buffer version and width it was worked out for
+#[derive(Debug, Clone, Default)]
+pub(super) struct WrapLayout {
+    key: Option<(u64, usize)>, // Buffer version and wrap width
+    width: usize,
+    lines: Vec<(usize, Vec<usize>)>, // Wrapped buffer lines, with the byte offsets their later rows start at
+}
+
+/// Spaces a line starts with
+fn indent_of(text: &str) -> usize {
+    text.len() - text.trim_start_matches(' ').len()
+}
+
+/// Byte offsets where a line's rows after the first start when wrapped at `width` columns.
+/// Later rows lose `continuation` columns to the continuation indent and indicator
+fn row_starts_for(text: &str, width: usize, continuation: usize) -> Vec<usize> {
+    let indent = indent_of(text);
+    let mut starts = Vec::new();
+    let (mut row_start, mut available, mut col) = (0, width, 0);
+    let mut after_space = None; // Just after the last space in the row, past the indentation
+    for (idx, ch) in text.char_indices() {
+        let char_width = ch.width().unwrap_or(1);
+        if col + char_width > available && idx > row_start {
+            // Break after the row's last space, or mid-word when one word fills the row
+            let start = after_space.filter(|&pos| pos > row_start && ch != ' ').unwrap_or(idx);
+            col = text[start..idx].chars().map(|c| c.width().unwrap_or(1)).sum();
+            starts.push(start);
+            row_start = start;
+            available = width.saturating_sub(continuation).max(1);
+            after_space = None;
+        }
+        col += char_width;
+        if ch == ' ' && idx >= indent {
+            after_space = Some(idx + 1);
+        }
+    }
+    starts
+}
+
+/// Row of a logical line once folded lines are left out and wrapped lines take their extra
+/// rows. `wraps` holds the extra rows of visible logical lines, as from `wrapped_rows`
+pub(super) fn visual_row(hidden: &[(usize, usize)], wraps: &[(usize, usize)], logical_line: usize) -> usize {
+    let row = visible_row(hidden, logical_line);
+    row + wraps.iter()
+        .take_while(|(line, _)| visible_row(hidden, *line) < row)
+        .map(|(_, extra)| extra)
+        .sum::<usize>()
+}
+
+/// Last row a logical line takes, or that the line folding it takes
+pub(super) fn last_visual_row(hidden: &[(usize, usize)], wraps: &[(usize, usize)], logical_line: usize) -> usize {
+    let row = visible_row(hidden, logical_line);
+    let extra = wraps.iter().find(|(line, _)| visible_row(hidden, *line) == row).map_or(0, |(_, extra)| *extra);
+    visual_row(hidden, wraps, logical_line) + extra
+}
+
+/// Logical line drawn on a row, and which of the line's rows it is
+pub(super) fn line_at_visual_row(hidden: &[(usize, usize)], wraps: &[(usize, usize)], row: usize) -> (usize, usize) {
+    let mut extra_above = 0;
+    for &(line, extra) in wraps {
+        let first = visible_row(hidden, line) + extra_above;
+        if row < first {
+            break;
+        }
+        if row <= first + extra {
+            return (line, row - first);
+        }
+        extra_above += extra;
+    }
+    (logical_line_at(hidden, row - extra_above), 0)
+}
+
+impl Editor {
+    /// Wrap long lines at the window width, or at `column` if that's narrower (0 for the window)
+    pub fn set_soft_wrap(&mut self, soft_wrap: bool, column: usize, indicator: &str) {
+        self.soft_wrap = soft_wrap;
+        self.wrap_column = column;
+        self.wrap_indicator = indicator.to_string();
+        self.wrap.key = None;
+    }
+
+    /// Drawn at the start of continuation rows
+    pub fn wrap_indicator(&self) -> &str {
+        &self.wrap_indicator
+    }
+
+    /// Turn soft wrap on or off for this buffer
+    pub fn toggle_soft_wrap(&mut self) {
+        self.soft_wrap = !self.soft_wrap;
+        self.wrap.key = None;
+        self.viewport_offset.1 = 0;
+        let state = if self.soft_wrap { "on" } else { "off" };
+        self.status_message = Some((format!("Soft wrap {}", state), false));
+    }
+
+    /// Work out where lines wrap again if the buffer or the width changed.
+    /// `text_width` is the window's width right of the gutter
+    pub fn refresh_wrap(&mut self, text_width: usize) {
+        if !self.soft_wrap {
+            self.wrap.lines.clear();
+            return;
+        }
+        let width = match self.wrap_column {
+            0 => text_width,
+            column => column.min(text_width),
+        }
+        .max(1);
+        let key = Some((self.buffer.version(), width));
+        if self.wrap.key == key {
+            return;
+        }
+
+        self.wrap.width = width;
+        self.wrap.lines.clear();
+        for line in 0..self.buffer.len_lines() {
+            // A line can't be wider than it is long in bytes, so most are skipped without a look
+            if self.buffer.rope().line(line).len_bytes() <= width {
+                continue;
+            }
+            let text = self.buffer.line(line);
+            let text = text.trim_end_matches('\n');
+            let starts = row_starts_for(text, width, self.continuation_width(text));
+            if !starts.is_empty() {
+                self.wrap.lines.push((line, starts));
+            }
+        }
+        self.wrap.key = key;
+    }
+
+    /// Columns taken at the start of a continuation row: the line's indentation, then the
+    /// indicator. Deep indentation is dropped, then the indicator, to leave room for the text
+    fn continuation_width(&self, text: &str) -> usize {
+        let (indent, indicator) = self.continuation(text);
+        indent + indicator
+    }
+
+    /// Indentation and indicator columns at the start of a line's continuation rows
+    fn continuation(&self, text: &str) -> (usize, usize) {
+        let half = self.wrap.width / 2;
+        let indicator = self.wrap_indicator.width();
+        let indent = indent_of(text);
+        if indent + indicator <= half {
+            (indent, indicator)
+        } else if indicator <= half {
+            (0, indicator)
+        } else {
+            (0, 0)
+        }
+    }
+
+    /// Byte offsets where a line's rows start, the first being 0.
+    /// Offsets from before an edit the layout hasn't caught up with are left out
+    fn row_starts(&self, line: usize, text: &str) -> Vec<usize> {
+        let later = match self.wrap.lines.binary_search_by_key(&line, |(wrapped, _)| *wrapped) {
+            Ok(idx) if self.soft_wrap => self.wrap.lines[idx].1.as_slice(),
+            _ => &[],
+        };
+        std::iter::once(0)
+            .chain(later.iter().copied().filter(|&start| start < text.len() && text.is_char_boundary(start)))
+            .collect()
+    }
+
+    /// Extra rows of the wrapped lines that aren't folded away, as (logical line, rows) in order
+    pub(super) fn wrapped_rows(&self, hidden: &[(usize, usize)]) -> Vec<(usize, usize)> {
+        if !self.soft_wrap {
+            return Vec::new();
+        }
+        self.wrap.lines.iter()
+            .filter(|(line, _)| *line < self.buffer.len_lines())
+            .filter(|(line, _)| !hidden.iter().any(|(first, last)| (*first..=*last).contains(line)))
+            .map(|(line, starts)| (line + 2, starts.len()))
+            .collect()
+    }
+
+    /// A row of a buffer line as (start, end) byte offsets within the line, newline left out,
+    /// and the indentation and indicator columns drawn before it (both 0 on the first row)
+    pub fn wrap_row(&self, line: usize, row: usize) -> (usize, usize, (usize, usize)) {
+        let text = self.buffer.line(line);
+        let text = text.trim_end_matches('\n');
+        let starts = self.row_starts(line, text);
+        let start = starts.get(row).copied().unwrap_or(text.len());
+        let end = starts.get(row + 1).copied().unwrap_or(text.len());
+        let prefix = if row == 0 { (0, 0) } else { self.continuation(text) };
+        (start, end, prefix)
+    }
+
+    /// Row within its line and column on that row of a byte position, counting the
+    /// continuation indent and indicator
+    fn wrap_position(&self, pos: usize) -> (usize, usize) {
+        let line = self.buffer.byte_to_line(pos);
+        let text = self.buffer.line(line);
+        let text = text.trim_end_matches('\n');
+        let offset = (pos - self.buffer.line_to_byte(line)).min(text.len());
+        let starts = self.row_starts(line, text);
+        let row = starts.partition_point(|&start| start <= offset) - 1;
+        let prefix = if row == 0 { 0 } else { self.continuation_width(text) };
+        (row, prefix + text[starts[row]..offset].chars().map(|ch| ch.width().unwrap_or(1)).sum::<usize>())
+    }
+
+    /// Byte position nearest a column of one of a line's rows
+    pub(super) fn wrap_byte_at(&self, line: usize, row: usize, col: usize) -> usize {
+        let (start, end, (indent, indicator)) = self.wrap_row(line, row);
+        let text = self.buffer.line(line);
+        let mut target = col.saturating_sub(indent + indicator);
+        let mut offset = start;
+        for ch in text[start..end].chars() {
+            let char_width = ch.width().unwrap_or(1);
+            if target < char_width {
+                // Closer to the end of a wide character lands after it
+                if target * 2 >= char_width && char_width > 1 {
+                    offset += ch.len_utf8();
+                }
+                break;
+            }
+            target -= char_width;
+            offset += ch.len_utf8();
+        }
+        // The end of a row that isn't the line's last is the start of the next row
+        let line_len = text.trim_end_matches('\n').len();
+        if offset == end && end < line_len {
+            offset = text[..end].char_indices().next_back().map_or(start, |(idx, _)| idx.max(start));
+        }
+        self.buffer.line_to_byte(line) + offset
+    }
+
+    /// Display row and column of the cursor, before horizontal scrolling. With soft wrap the
+    /// column is on the row of the line the cursor is on
+    pub fn cursor_display_position(&self) -> (usize, usize) {
+        let (line, col) = self.cursor_position();
+        let row = self.display_row_of_logical(line + 2);
+        if !self.soft_wrap {
+            return (row, col);
+        }
+        let (wrap_row, col) = self.wrap_position(self.cursor);
+        (row + wrap_row, col)
+    }
+
+    /// Where Up or Down takes the cursor with soft wrap on: the same column of the row above
+    /// or below, which may be on the same line. None when soft wrap is off, or at the first
+    /// or last line, which moving by line already handles
+    pub(super) fn wrap_move_target(&mut self, up: bool) -> Option<usize> {
+        if !self.soft_wrap {
+            return None;
+        }
+        let line = self.buffer.byte_to_line(self.cursor);
+        let (row, col) = self.wrap_position(self.cursor);
+        let col = *self.preferred_column.get_or_insert(col);
+
+        let (line, row) = if up {
+            if row > 0 {
+                (line, row - 1)
+            } else if line > 0 {
+                let above = self.visible_line_above(line);
+                let text = self.buffer.line(above);
+                (above, self.row_starts(above, text.trim_end_matches('\n')).len() - 1)
+            } else {
+                return None;
+            }
+        } else {
+            let text = self.buffer.line(line);
+            if row + 1 < self.row_starts(line, text.trim_end_matches('\n')).len() {
+                (line, row + 1)
+            } else if line + 1 < self.buffer.len_lines() && self.visible_line_below(line) != line {
+                (self.visible_line_below(line), 0)
+            } else {
+                return None;
+            }
+        };
+        Some(self.wrap_byte_at(line, row, col))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn breaks_after_spaces_or_mid_word() {
+        assert_eq!(row_starts_for("aaa bbb ccc", 8, 0), [8]);
+        assert_eq!(row_starts_for("abcdefghij", 4, 0), [4, 8]);
+        assert_eq!(row_starts_for("short", 10, 0), Vec::<usize>::new());
+        // Indentation isn't a place to break
+        assert_eq!(row_starts_for("    abc def", 6, 0), [6]);
+        assert_eq!(row_starts_for("    abc def", 6, 2), [6, 8]);
+    }
+
+    #[test]
+    fn wide_characters_take_two_columns() {
+        assert_eq!(row_starts_for("日本語テキスト", 5, 0), [6, 12, 18]);
+        assert_eq!(row_starts_for("ab日本", 3, 0), [2, 5]);
+    }
+
+    #[test]
+    fn wide_continuation_leaves_narrow_rows() {
+        assert_eq!(row_starts_for("abcdefghijklmnop", 10, 7), [10, 13]);
+        // Still a column per row when the continuation takes the whole width
+        assert_eq!(row_starts_for("abcdef", 3, 5), [3, 4, 5]);
+    }
+
+    #[test]
+    fn continuation_over_half_the_width_is_dropped() {
+        let mut editor = Editor::new();
+        editor.wrap.width = 10;
+        assert_eq!(editor.continuation("  x"), (2, 2));
+        assert_eq!(editor.continuation("    x"), (0, 2));
+        editor.wrap.width = 3;
+        assert_eq!(editor.continuation("    x"), (0, 0));
+    }
+
+    #[test]
+    fn folded_and_wrapped_rows() {
+        // Buffer lines 3 to 5 (logical 5 to 7) are folded into logical line 4; logical line 2
+        // wraps onto one extra row and logical line 9 onto two
+        let hidden = [(3, 5)];
+        let wraps = [(2, 1), (9, 2)];
+        let rows: Vec<usize> = [0, 2, 3, 4, 6, 8, 9, 10].iter().map(|&line| visual_row(&hidden, &wraps, line)).collect();
+        assert_eq!(rows, [0, 2, 4, 5, 5, 6, 7, 10]);
+        assert_eq!(last_visual_row(&hidden, &wraps, 2), 3);
+        assert_eq!(last_visual_row(&hidden, &wraps, 9), 9);
+
+        let lines: Vec<(usize, usize)> = (0..=10).map(|row| line_at_visual_row(&hidden, &wraps, row)).collect();
+        assert_eq!(lines, [(0, 0), (1, 0), (2, 0), (2, 1), (3, 0), (4, 0), (8, 0), (9, 0), (9, 1), (9, 2), (10, 0)]);
+    }
+}
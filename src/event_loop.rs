@@ -1,4 +1,4 @@
-use crate::{editor, renderer, find_replace, output_pane, kernel, autocomplete, prompt, exit_prompt, kernel_selector, commands, direct_kernel};
+use crate::{editor, renderer, find_replace, output_pane, layout, kernel, autocomplete, prompt, exit_prompt, kernel_selector, commands, direct_kernel, export, tabs, splits, quick_open, actions, palette, config, project_search, undo_tree, undo_view, swap, recovery_prompt};
 use crossterm::{
     event::{self, Event, KeyCode, KeyModifiers, MouseEventKind, MouseButton},
     execute,
@@ -6,40 +6,300 @@ use crossterm::{
 use std::io::{self, Write};
 use std::time::Duration;
 
+/// Fit the newly active buffer to the layout after switching, opening or closing tabs
+fn refresh_after_tab_change(editor: &mut editor::Editor, renderer: &mut renderer::Renderer, layout: &mut layout::Layout, tabs: &tabs::Tabs) -> io::Result<()> {
+    layout.tab_bar = tabs.len() > 1;
+    layout.splits.show_tab(tabs.active_id());
+    editor.clear_find_matches();
+    editor.update_viewport_for_area(layout.areas(false).editor);
+    execute!(io::stdout(),
+        crossterm::terminal::Clear(crossterm::terminal::ClearType::All),
+        crossterm::cursor::Hide
+    )?;
+    renderer.force_redraw();
+    Ok(())
+}
+
+/// Open a file in a new tab, or switch to the tab already showing it
+/// A path that doesn't exist yet opens as an empty buffer that creates the file on save
+fn open_in_tab(editor: &mut editor::Editor, renderer: &mut renderer::Renderer, layout: &mut layout::Layout, tabs: &mut tabs::Tabs, settings: &config::Settings, path: &std::path::Path) -> io::Result<()> {
+    if let Some(idx) = tabs.find_file(path, editor) {
+        tabs.switch_to(editor, idx);
+        return refresh_after_tab_change(editor, renderer, layout, tabs);
+    }
+
+    let mut opened = settings.new_editor();
+    let loaded = match opened.load_file(&path.to_string_lossy()) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            opened.set_file_path(&path.to_string_lossy());
+            Ok(())
+        }
+        Err(e) => Err(e),
+    };
+    match loaded {
+        Ok(()) => {
+            tabs.open(editor, opened);
+            refresh_after_tab_change(editor, renderer, layout, tabs)
+        }
+        Err(e) => {
+            editor.status_message = Some((format!("Failed to open file: {}", e), true));
+            Ok(())
+        }
+    }
+}
+
+/// Apply project search replacements: open buffers are changed in place, other files are
+/// opened in background tabs, and nothing is saved. Returns the matches and files changed
+fn replace_in_files(
+    editor: &mut editor::Editor,
+    tabs: &mut tabs::Tabs,
+    settings: &config::Settings,
+    search: &find_replace::Search,
+    replace: &str,
+    files: &[(std::path::PathBuf, Vec<usize>)],
+) -> (usize, usize) {
+    let (mut replaced, mut changed_files) = (0, 0);
+    for (path, lines) in files {
+        let count = match tabs.find_file(path, editor) {
+            Some(idx) => tabs.editor_mut(idx, editor).replace_in_lines(search, lines, replace),
+            None => {
+                let mut opened = settings.new_editor();
+                if opened.load_file(&path.to_string_lossy()).is_err() {
+                    continue;
+                }
+                let count = opened.replace_in_lines(search, lines, replace);
+                if count > 0 {
+                    tabs.push(opened);
+                }
+                count
+            }
+        };
+        if count > 0 {
+            replaced += count;
+            changed_files += 1;
+        }
+    }
+    (replaced, changed_files)
+}
+
+/// Bring the open buffers and the output pane in line with the settings
+/// `output_lines` from the command line wins over the config; the pane's placement and size
+/// are only set when `output_changed`, so a reload keeps the user's own resizing
+fn apply_settings(
+    settings: &config::Settings,
+    editor: &mut editor::Editor,
+    tabs: &mut tabs::Tabs,
+    output_pane: &mut output_pane::OutputPane,
+    layout: &mut layout::Layout,
+    output_lines: Option<usize>,
+    output_changed: bool,
+) {
+    for idx in 0..tabs.len() {
+        let tab = tabs.editor_mut(idx, editor);
+        tab.set_tab_width(settings.config.editor.tab_width);
+        tab.set_line_numbers(settings.config.editor.line_numbers);
+        let config = &settings.config.editor;
+        tab.set_soft_wrap(config.soft_wrap, config.wrap_column, &config.wrap_indicator);
+        tab.set_persistent_undo(config.persistent_undo, config.undo_history_kb);
+    }
+    swap::set_interval(settings.config.editor.swap_interval);
+    output_pane.set_keep_lines(output_lines.unwrap_or(settings.config.output.lines));
+    if output_changed {
+        let output = &settings.config.output;
+        layout.output_visible = output.visible;
+        layout.placement = output.placement;
+        layout.set_output_dimensions(output.height, output.width);
+    }
+    if let Some(summary) = settings.error_summary() {
+        editor.status_message = Some((summary, true));
+    }
+}
+
+/// Hand unsaved text over to the swap file writer, and save the buffers auto-save is due for.
+/// Returns whether any buffer was saved
+fn keep_buffers_safe(editor: &mut editor::Editor, tabs: &mut tabs::Tabs, swap_files: &mut swap::SwapFiles, settings: &config::Settings, focus_lost: bool) -> bool {
+    let config = &settings.config.editor;
+    let mut saved = false;
+    for idx in 0..tabs.len() {
+        let tab = tabs.editor_mut(idx, editor);
+        swap_files.note(tab);
+        let due = match config.auto_save {
+            swap::AutoSave::Off => false,
+            swap::AutoSave::Focus => focus_lost && tab.is_modified(),
+            swap::AutoSave::Delay => swap_files.due_for_auto_save(tab, Duration::from_secs(config.auto_save_delay)),
+        };
+        if due && !tab.is_read_only() && tab.file_path().is_some() {
+            // Saving quietly; a failure still shows in the status bar
+            let message = tab.status_message.take();
+            if tab.save().is_ok() {
+                tab.status_message = message;
+                swap_files.note(tab);
+                saved = true;
+            }
+        }
+    }
+    swap_files.end_pass();
+    saved
+}
+
+/// Offer to restore unsaved text a crash or a closed terminal left for the active buffer's
+/// file, the first time the buffer is shown. Returns whether the prompt was shown
+fn offer_recovery(editor: &mut editor::Editor, renderer: &mut renderer::Renderer, swap_files: &mut swap::SwapFiles) -> io::Result<bool> {
+    let Some(leftover) = swap_files.unchecked_leftover(editor) else {
+        return Ok(false);
+    };
+    let mut prompt = recovery_prompt::RecoveryPrompt::new(editor.file_name(), &editor.buffer().to_string(), &leftover.text, leftover.time);
+    let choice = prompt.run(&mut io::stdout())?;
+    match choice {
+        recovery_prompt::Recovery::Restore => {
+            editor.recover_text(&leftover.text);
+            swap::discard(&leftover.path);
+            editor.status_message = Some(("Restored unsaved changes; undo to go back to the file as saved".to_string(), false));
+        }
+        recovery_prompt::Recovery::Discard => {
+            swap::discard(&leftover.path);
+            editor.status_message = Some(("Discarded the unsaved changes".to_string(), false));
+        }
+        recovery_prompt::Recovery::Later => {
+            swap_files.put_off(&leftover.path);
+            editor.status_message = Some(("Unsaved changes kept to recover next time the file is opened".to_string(), false));
+        }
+    }
+    execute!(io::stdout(), crossterm::terminal::Clear(crossterm::terminal::ClearType::All), crossterm::cursor::Hide)?;
+    renderer.force_redraw();
+    Ok(true)
+}
+
+/// Connect the active buffer to the interpreter named in the config
+fn connect_configured_kernel(editor: &mut editor::Editor, python: &str) -> Result<(), String> {
+    let name = format!("Python ({})", python);
+    let mut kernel: Box<dyn kernel::Kernel> = Box::new(direct_kernel::DirectKernel::new(python.to_string(), name.clone(), name));
+    kernel.connect().map_err(|e| e.to_string())?;
+    editor.set_kernel(kernel);
+    editor.enable_repl_mode();
+    Ok(())
+}
+
+/// Store the focused window's view and move focus to another split window
+fn focus_window(editor: &mut editor::Editor, tabs: &mut tabs::Tabs, layout: &mut layout::Layout, idx: usize) {
+    let focused = layout.splits.focused();
+    layout.splits.set_window(focused, splits::Window { tab: tabs.active_id(), view: editor.view() });
+    layout.splits.focus(idx);
+    enter_focused_window(editor, tabs, layout);
+}
+
+/// Bring the focused window's buffer and view into the active editor
+fn enter_focused_window(editor: &mut editor::Editor, tabs: &mut tabs::Tabs, layout: &mut layout::Layout) {
+    let window = layout.splits.window(layout.splits.focused());
+    if let Some(idx) = tabs.index_of(window.tab) {
+        tabs.switch_to(editor, idx);
+    }
+    editor.set_view(window.view);
+    editor.update_viewport_for_area(layout.areas(false).editor);
+}
+
+/// Check whether focusing a window would change the active buffer
+fn window_changes_buffer(tabs: &tabs::Tabs, layout: &layout::Layout, idx: usize) -> bool {
+    tabs.index_of(layout.splits.window(idx).tab) != Some(tabs.active())
+}
+
+/// Save the active buffer, asking for a path if it has none; returns false if cancelled or failed
+fn save_or_prompt(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io::Result<bool> {
+    if editor.file_path().is_some() {
+        return Ok(editor.save().is_ok());
+    }
+    let initial_path = editor.get_save_as_initial_path();
+    let mut prompt = prompt::Prompt::new("Save As", &initial_path);
+    let path = prompt.run(&mut io::stdout())?;
+    execute!(io::stdout(),
+        crossterm::terminal::Clear(crossterm::terminal::ClearType::All),
+        crossterm::cursor::Hide
+    )?;
+    renderer.force_redraw();
+    Ok(match path {
+        Some(path) => editor.save_as(path).is_ok(),
+        None => false,
+    })
+}
+
 fn debug_log(msg: &str) {
     use std::fs::OpenOptions;
-    if let Ok(mut log) = OpenOptions::new().create(true).append(true).open("/tmp/sage_debug.log") {
+    let Some(path) = crate::config::debug_log_path() else { return };
+    if let Ok(mut log) = OpenOptions::new().create(true).append(true).open(path) {
         let _ = writeln!(log, "{}", msg);
         let _ = log.flush();
     }
 }
 
-pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io::Result<()> {
+pub fn run(
+    editor: &mut editor::Editor,
+    renderer: &mut renderer::Renderer,
+    mut tabs: tabs::Tabs,
+    mut settings: config::Settings,
+    output_lines: Option<usize>,
+) -> io::Result<()> {
     let mut find_replace: Option<find_replace::FindReplace> = None;
     let mut output_pane = output_pane::OutputPane::new();
-    let mut output_pane_visible = true; // Visible by default
-    let mut output_pane_height = 8; // Default height in lines
+    let mut layout = layout::Layout::new(); // Output pane visibility, placement and size
+    layout.tab_bar = tabs.len() > 1;
+    apply_settings(&settings, editor, &mut tabs, &mut output_pane, &mut layout, output_lines, true);
+    editor.update_viewport_for_area(layout.areas(false).editor);
+    tabs.note_recent(editor);
     let mut needs_redraw = true; // Track if we need to redraw
     let mut skip_event_read = false; // Skip event read to force immediate redraw
 
     // State for background execution with live timer
-    let mut execution_rx: Option<std::sync::mpsc::Receiver<(Box<dyn kernel::Kernel>, Vec<(usize, usize, String, bool, f64)>, Vec<kernel::CompletionItem>)>> = None;
+    let mut execution_rx: Option<ExecutionReceiver> = None;
     let mut execution_start_time: Option<std::time::Instant> = None;
     let mut executing_kernel_info: Option<kernel::KernelInfo> = None;
+    let mut pending_run: Option<Vec<usize>> = None; // Cells waiting for "run slow cells?" confirmation
 
     // Autocomplete
     let mut autocomplete = autocomplete::Autocomplete::new();
     let mut suppress_autocomplete_once = false; // Suppress after Tab completion
 
+    // Swap files and auto-save
+    let mut swap_files = swap::SwapFiles::new();
+    let mut focus_lost = false; // Since the last pass, for auto-saving on focus loss
+
+    // Connect the kernel from the config and run its setup cells
+    if let Some(python) = settings.config.kernel.python.clone() {
+        match connect_configured_kernel(editor, &python) {
+            Ok(()) => {
+                let setup_cells = editor.setup_cell_indices();
+                if let Some((rx, kernel_info)) = request_run(editor, setup_cells, &mut pending_run) {
+                    execution_rx = Some(rx);
+                    execution_start_time = Some(std::time::Instant::now());
+                    executing_kernel_info = Some(kernel_info);
+                }
+                if settings.errors.is_empty() {
+                    editor.status_message = Some((format!("Connected to {}", python), false));
+                }
+            }
+            Err(e) => editor.status_message = Some((format!("Failed to connect to {}: {}", python, e), true)),
+        }
+    }
+
     loop {
         debug_log(&format!("Loop iteration start"));
 
+        if keep_buffers_safe(editor, &mut tabs, &mut swap_files, &settings, std::mem::take(&mut focus_lost)) {
+            needs_redraw = true;
+        }
+
         // Check if background execution is complete
         if let Some(ref rx) = execution_rx {
             match rx.try_recv() {
-                Ok((kernel, results, completions)) => {
+                Ok((kernel, results, runs, completions)) => {
                     // Execution complete! Put kernel back and process results
                     editor.set_kernel(kernel);
+
+                    // Record what ran so dependent cells can be marked stale
+                    editor.finish_running_cells();
+                    for (cell_idx, executed_hash, cell_elapsed, result) in runs {
+                        editor.record_cell_run(cell_idx, executed_hash, cell_elapsed, result);
+                    }
                     execution_rx = None;
                     executing_kernel_info = None;
                     let elapsed = execution_start_time.take().map(|t| t.elapsed().as_secs_f64()).unwrap_or(0.0);
@@ -68,9 +328,9 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
 
                     // Show output pane if needed
                     output_pane.set_focused(false);
-                    if !output_pane_visible {
-                        output_pane_visible = true;
-                        editor.update_viewport_for_cursor_with_bottom(output_pane_height);
+                    if !layout.output_visible {
+                        layout.output_visible = true;
+                        editor.update_viewport_for_area(layout.areas(find_replace.is_some()).editor);
                     }
 
                     renderer.force_redraw();
@@ -87,6 +347,7 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                 Err(std::sync::mpsc::TryRecvError::Disconnected) => {
                     // Thread panicked or channel closed unexpectedly
                     editor.status_message = Some(("Execution failed".to_string(), true));
+                    editor.finish_running_cells();
                     execution_rx = None;
                     execution_start_time = None;
                     executing_kernel_info = None;
@@ -97,31 +358,52 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
 
         // Only draw if needed
         if needs_redraw {
+            // Keep cells (and their stale markers) in sync with the buffer
+            editor.refresh_cells();
+            output_pane.set_stale_cells(editor.stale_cell_numbers());
+
             debug_log(&format!("needs_redraw is true, starting draw"));
-            // Calculate bottom window height
-            let bottom_window_height = if find_replace.is_some() {
-                3 // Find/replace pane
-            } else if output_pane_visible {
-                output_pane_height
-            } else {
-                0
-            };
+            // Work out where the editor and each pane go this frame
+            let areas = layout.areas(find_replace.is_some());
+
+            // Tab bar across the top when more than one buffer is open
+            if let Some(area) = areas.tab_bar {
+                tabs.draw(&mut io::stdout(), editor, area)?;
+            }
+
+            // Unfocused split windows, each through its own view, then the separators between them
+            if layout.splits.len() > 1 {
+                let rects = layout.splits.window_rects(areas.windows);
+                for (idx, rect) in rects.into_iter().enumerate() {
+                    if idx == layout.splits.focused() {
+                        continue;
+                    }
+                    let window = layout.splits.window(idx);
+                    let Some(tab_idx) = tabs.index_of(window.tab) else {
+                        continue;
+                    };
+                    let shown = tabs.editor_mut(tab_idx, editor);
+                    let own_view = shown.view();
+                    shown.refresh_cells();
+                    shown.set_view(window.view);
+                    renderer.draw_window(shown, rect)?;
+                    shown.set_view(own_view);
+                }
+                layout.splits.draw_separators(&mut io::stdout(), areas.windows)?;
+            }
 
-            debug_log(&format!("About to call draw_with_bottom_window"));
-            // Draw the editor with bottom window if needed
-            renderer.draw_with_bottom_window(editor, bottom_window_height)?;
-            debug_log(&format!("draw_with_bottom_window completed"));
+            debug_log(&format!("About to call draw_layout"));
+            // Draw the editor and status bar into their areas
+            renderer.draw_layout(editor, &areas)?;
+            debug_log(&format!("draw_layout completed"));
 
             // Draw the appropriate pane
-            if let Some(ref fr) = find_replace {
+            if let (Some(fr), Some(area)) = (&find_replace, areas.find_bar) {
                 debug_log(&format!("Drawing find_replace"));
-                fr.draw(&mut io::stdout())?;
-            } else if output_pane_visible {
+                fr.draw(&mut io::stdout(), area)?;
+            } else if let Some(area) = areas.output {
                 debug_log(&format!("Drawing output_pane"));
-                let (width, height) = crossterm::terminal::size()?;
-                // Output pane starts after the status bar
-                let output_start_row = height.saturating_sub(output_pane_height as u16);
-                output_pane.draw(&mut io::stdout(), output_start_row, output_pane_height, width)?;
+                output_pane.draw(&mut io::stdout(), area)?;
                 // Only reposition cursor to editor if output pane doesn't have focus
                 if !output_pane.is_focused() {
                     renderer.reposition_cursor(editor)?;
@@ -142,6 +424,12 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
             debug_log(&format!("Draw complete, needs_redraw set to false"));
         }
 
+        // Once the buffer shows behind it
+        if offer_recovery(editor, renderer, &mut swap_files)? {
+            needs_redraw = true;
+            continue;
+        }
+
         // Skip event read if we need immediate redraw (after cell execution)
         if skip_event_read {
             debug_log(&format!("Skipping event read, continuing loop"));
@@ -156,12 +444,30 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
             // Poll with 100ms timeout to update timer frequently
             event::poll(std::time::Duration::from_millis(100))?
         } else {
-            // Block waiting for event when not executing
-            event::poll(std::time::Duration::from_secs(3600))? // 1 hour timeout (effectively blocking)
+            // Wake up every second to notice changes to the config files
+            event::poll(std::time::Duration::from_secs(1))?
         };
 
         if !event_available {
-            // No event, continue loop to update timer
+            // Changed-line markers are worked out again once typing pauses
+            for idx in 0..tabs.len() {
+                if tabs.editor_mut(idx, editor).refresh_git_changes() {
+                    needs_redraw = true;
+                }
+            }
+            let previous_output = settings.config.output.clone();
+            if settings.reload_if_changed() {
+                let output_changed = settings.config.output != previous_output;
+                apply_settings(&settings, editor, &mut tabs, &mut output_pane, &mut layout, output_lines, output_changed);
+                if settings.errors.is_empty() {
+                    editor.status_message = Some(("Reloaded config".to_string(), false));
+                }
+                editor.update_viewport_for_area(layout.areas(find_replace.is_some()).editor);
+                renderer.apply_theme()?;
+                renderer.force_redraw();
+                needs_redraw = true;
+            }
+            // Continue the loop to update the timer or redraw
             continue;
         }
 
@@ -188,11 +494,38 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                                 needs_redraw = true;
                             }
 
-                            // Check if click is in output pane area
-                            let (_, height) = crossterm::terminal::size()?;
-                            let output_start_row = height.saturating_sub(output_pane_height as u16 + 1);
-
-                            if output_pane_visible && mouse_event.row >= output_start_row {
+                            // Check if click is on the tab bar, the pane boundary or in the output pane area
+                            let areas = layout.areas(false);
+                            let output_area = areas.output;
+
+                            if areas.tab_bar.is_some_and(|area| area.contains(mouse_event.column, mouse_event.row)) {
+                                // Click on a tab - switch to its buffer
+                                if let Some(idx) = tabs.tab_at(mouse_event.column) {
+                                    if execution_rx.is_some() {
+                                        editor.status_message = Some(("Wait for execution to finish before switching buffers".to_string(), true));
+                                    } else if idx != tabs.active() {
+                                        find_replace = None;
+                                        tabs.switch_to(editor, idx);
+                                        refresh_after_tab_change(editor, renderer, &mut layout, &tabs)?;
+                                    }
+                                    needs_redraw = true;
+                                }
+                            } else if let Some(idx) = layout.splits.window_at(mouse_event.column, mouse_event.row, areas.windows)
+                                .filter(|&idx| idx != layout.splits.focused())
+                            {
+                                // Click in another split window - focus it
+                                if execution_rx.is_some() && window_changes_buffer(&tabs, &layout, idx) {
+                                    editor.status_message = Some(("Wait for execution to finish before switching buffers".to_string(), true));
+                                } else {
+                                    find_replace = None;
+                                    focus_window(editor, &mut tabs, &mut layout, idx);
+                                    renderer.force_redraw();
+                                }
+                                needs_redraw = true;
+                            } else if layout.is_on_boundary(mouse_event.column, mouse_event.row) {
+                                // Grab the status bar (or divider) to resize the output pane
+                                layout.start_drag();
+                            } else if output_area.is_some_and(|area| area.contains(mouse_event.column, mouse_event.row)) {
                                 // Click is in output pane - focus it and start mouse selection
                                 output_pane.set_focused(true);
                                 output_pane.start_mouse_selection(
@@ -200,6 +533,14 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                                     mouse_event.row as usize,
                                 );
                                 needs_redraw = true;
+                            } else if let Some((cell_idx, row)) = editor.inline_output_at_screen_row(mouse_event.row as usize) {
+                                // Click on an inline output - the header line folds it
+                                output_pane.set_focused(false);
+                                if row == 0 {
+                                    editor.toggle_inline_output_collapsed(cell_idx);
+                                    renderer.force_redraw();
+                                    needs_redraw = true;
+                                }
                             } else {
                                 // Click is in editor - unfocus output pane and start selection
                                 output_pane.set_focused(false);
@@ -207,21 +548,33 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                                     mouse_event.column as usize,
                                     mouse_event.row as usize,
                                 ) {
-                                    editor.start_mouse_selection(position);
-                                    // Update viewport with correct bottom window height
-                                    let bottom_height = if output_pane_visible { output_pane_height } else { 0 };
-                                    editor.update_viewport_for_cursor_with_bottom(bottom_height);
+                                    // Alt+drag selects a block of columns, with a cursor on each line
+                                    if mouse_event.modifiers.contains(KeyModifiers::ALT) {
+                                        editor.start_block_selection(position);
+                                    } else {
+                                        editor.start_mouse_selection(position);
+                                    }
+                                    // Update viewport for the editor's area of the layout
+                                    editor.update_viewport_for_area(layout.areas(find_replace.is_some()).editor);
                                     renderer.force_redraw();
                                     needs_redraw = true; // Need to redraw for selection
                                 }
                             }
                         }
                         MouseEventKind::Drag(MouseButton::Left) => {
-                            // Check if we're dragging in the output pane
-                            let (_, height) = crossterm::terminal::size()?;
-                            let output_start_row = height.saturating_sub(output_pane_height as u16 + 1);
+                            // Check if we're resizing the output pane or dragging in it
+                            let output_area = layout.areas(false).output;
 
-                            if output_pane.is_focused() && output_pane_visible && mouse_event.row >= output_start_row {
+                            if layout.is_dragging() {
+                                layout.drag_to(mouse_event.column, mouse_event.row);
+                                editor.update_viewport_for_area(layout.areas(false).editor);
+                                renderer.force_redraw();
+                                needs_redraw = true;
+                            } else if output_pane.is_focused() && output_area.is_some_and(|area| match layout.placement {
+                                // Dragging past the pane's edge keeps selecting (and scrolling) in the pane
+                                layout::PanePlacement::Bottom => mouse_event.row + 1 >= area.y,
+                                layout::PanePlacement::Right => mouse_event.column >= area.x,
+                            }) {
                                 // Update selection in output pane
                                 output_pane.update_mouse_selection(
                                     mouse_event.column as usize,
@@ -234,31 +587,35 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                                     mouse_event.column as usize,
                                     mouse_event.row as usize,
                                 ) {
-                                    editor.update_mouse_selection(position);
-                                    // Update viewport with correct bottom window height
-                                    let bottom_height = if output_pane_visible { output_pane_height } else { 0 };
-                                    editor.update_viewport_for_cursor_with_bottom(bottom_height);
+                                    if !editor.update_block_selection(position) {
+                                        editor.update_mouse_selection(position);
+                                    }
+                                    // Update viewport for the editor's area of the layout
+                                    editor.update_viewport_for_area(layout.areas(find_replace.is_some()).editor);
                                     needs_redraw = true; // Need to redraw for selection update
                                 }
                             }
                         }
                         MouseEventKind::Up(MouseButton::Left) => {
-                            // Finish selection in both editor and output pane
+                            // Finish selection in both editor and output pane, and any pane resize
+                            layout.end_drag();
                             editor.finish_mouse_selection();
                             output_pane.finish_mouse_selection();
-                            // Update viewport with correct bottom window height
-                            let bottom_height = if output_pane_visible { output_pane_height } else { 0 };
-                            editor.update_viewport_for_cursor_with_bottom(bottom_height);
+                            // Update viewport for the editor's area of the layout
+                            editor.update_viewport_for_area(layout.areas(find_replace.is_some()).editor);
                             needs_redraw = true; // Need to redraw to finalize selection
                         }
                         MouseEventKind::ScrollDown => {
                             // Check if mouse is over output pane
-                            let (_, height) = crossterm::terminal::size()?;
-                            let output_start_row = height.saturating_sub(output_pane_height as u16 + 1);
+                            let output_area = layout.areas(false).output;
 
-                            if output_pane_visible && mouse_event.row >= output_start_row {
+                            if output_area.is_some_and(|area| area.contains(mouse_event.column, mouse_event.row)) {
                                 // Scroll output pane
                                 output_pane.scroll_down();
+                            } else if !shift_held && editor.inline_output_at_screen_row(mouse_event.row as usize)
+                                .is_some_and(|(cell_idx, _)| editor.scroll_inline_output(cell_idx, 3))
+                            {
+                                // Scrolled inside an inline output block
                             } else if shift_held {
                                 // Shift+scroll = horizontal scroll right
                                 editor.scroll_viewport_horizontal(5);
@@ -270,12 +627,15 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                         }
                         MouseEventKind::ScrollUp => {
                             // Check if mouse is over output pane
-                            let (_, height) = crossterm::terminal::size()?;
-                            let output_start_row = height.saturating_sub(output_pane_height as u16 + 1);
+                            let output_area = layout.areas(false).output;
 
-                            if output_pane_visible && mouse_event.row >= output_start_row {
+                            if output_area.is_some_and(|area| area.contains(mouse_event.column, mouse_event.row)) {
                                 // Scroll output pane
                                 output_pane.scroll_up();
+                            } else if !shift_held && editor.inline_output_at_screen_row(mouse_event.row as usize)
+                                .is_some_and(|(cell_idx, _)| editor.scroll_inline_output(cell_idx, -3))
+                            {
+                                // Scrolled inside an inline output block
                             } else if shift_held {
                                 // Shift+scroll = horizontal scroll left
                                 editor.scroll_viewport_horizontal(-5);
@@ -349,172 +709,220 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                 }
 
                 needs_redraw = true; // Key events usually need redraw
+
+                // Answer a pending "run slow cells?" confirmation
+                if let Some(cells) = pending_run.take() {
+                    let confirmed = matches!(key.code, KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter);
+                    if confirmed && execution_rx.is_none() {
+                        if let Some((rx, kernel_info)) = spawn_background_execution(editor, &cells) {
+                            execution_rx = Some(rx);
+                            execution_start_time = Some(std::time::Instant::now());
+                            executing_kernel_info = Some(kernel_info);
+                            editor.status_message = Some(("Executing...".to_string(), false));
+                        } else {
+                            editor.status_message = Some(("No kernel connected. Press Ctrl+K to select a kernel.".to_string(), true));
+                        }
+                    } else {
+                        editor.status_message = Some(("Execution cancelled".to_string(), false));
+                    }
+                    continue;
+                }
                 
+                // Work out the action the key asks for. The find bar and the focused output pane
+                // look first; keys they don't bind go to the editor's keymap
+                let mut action = None;
+
                 // If find/replace window is active, handle its input first
                 if let Some(ref mut fr) = find_replace {
-                    // Special handling for find/replace shortcuts
-                    let fr_cmd = match key.code {
-                        // Ctrl+F while find is open = find next
-                        KeyCode::Char('f') | KeyCode::Char('F') if key.modifiers.contains(KeyModifiers::CONTROL) && !key.modifiers.contains(KeyModifiers::SHIFT) => {
-                            Some(commands::Command::FindNext)
-                        }
-                        // Ctrl+Shift+F = find previous
-                        KeyCode::Char('f') | KeyCode::Char('F') if key.modifiers.contains(KeyModifiers::CONTROL) && key.modifiers.contains(KeyModifiers::SHIFT) => {
-                            Some(commands::Command::FindPrev)
-                        }
-                        // Ctrl+H = replace current and find next
-                        KeyCode::Char('h') | KeyCode::Char('H') if key.modifiers.contains(KeyModifiers::CONTROL) && !key.modifiers.contains(KeyModifiers::SHIFT) => {
-                            Some(commands::Command::Replace)
-                        }
-                        // Ctrl+Shift+H = replace all
-                        KeyCode::Char('h') | KeyCode::Char('H') if key.modifiers.contains(KeyModifiers::CONTROL) && key.modifiers.contains(KeyModifiers::SHIFT) => {
-                            Some(commands::Command::ReplaceAll)
-                        }
-
-                        _ => None
-                    };
-                    
-                    // If we have a find/replace command, execute it
-                    if let Some(cmd) = fr_cmd {
-                        match cmd {
-                            commands::Command::FindNext => {
-                                if !fr.is_empty() {
-                                    if let Some((start, end)) = fr.next_match() {
-                                        editor.select_range(start, end);
-                                        // Update current match index for highlighting
-                                        editor.set_find_matches(fr.get_all_matches().to_vec(), fr.get_current_match_index());
-                                    }
+                    action = settings.keymap.lookup(actions::Scope::Find, &key);
+
+                    // Undo/redo and the palette still work while the bar is open; other keys edit it
+                    if action.is_none() {
+                        let editor_action = settings.keymap.lookup(actions::Scope::Editor, &key);
+                        let passes_through = matches!(
+                            editor_action,
+                            Some(actions::Action::Command(commands::Command::Undo | commands::Command::Redo))
+                                | Some(actions::Action::CommandPalette)
+                        );
+                        if !passes_through {
+                            let result = fr.handle_input(key.code, key.modifiers);
+                            match result {
+                                find_replace::InputResult::Close => {
+                                    find_replace = None;
+                                    // Clear selection and find matches when closing find
+                                    editor.selection_start = None;
+                                    editor.clear_find_matches();
+                                    // Force redraw
+                                    execute!(io::stdout(), 
+                                        crossterm::terminal::Clear(crossterm::terminal::ClearType::All),
+                                        crossterm::cursor::Hide
+                                    )?;
+                                    renderer.force_redraw();
                                 }
-                            }
-                            commands::Command::FindPrev => {
-                                if !fr.is_empty() {
-                                    if let Some((start, end)) = fr.prev_match() {
-                                        editor.select_range(start, end);
-                                        // Update current match index for highlighting
-                                        editor.set_find_matches(fr.get_all_matches().to_vec(), fr.get_current_match_index());
-                                    }
+                                find_replace::InputResult::FindTextChanged => {
+                                    fr.reset_current_match();
+                                    search_again(editor, fr);
                                 }
-                            }
-                            commands::Command::Replace => {
-                                if !fr.is_empty() {
-                                    // Replace current selection
-                                    if editor.replace_selection(fr.replace_text()) {
-                                        // Re-search after replacement
-                                        let matches = editor.find_all(fr.find_text());
-                                        fr.update_matches(matches.clone());
-                                        // Update editor's find matches for highlighting
-                                        editor.set_find_matches(matches, fr.get_current_match_index());
-                                        // Move to next match
-                                        if let Some((start, end)) = fr.current_match_position() {
+                                find_replace::InputResult::FindNext => {
+                                    if !fr.is_empty() {
+                                        if let Some((start, end)) = fr.next_match() {
                                             editor.select_range(start, end);
+                                            // Update current match index for highlighting
+                                            editor.set_find_matches(fr.get_all_matches().to_vec(), fr.get_current_match_index());
                                         }
                                     }
                                 }
+                                find_replace::InputResult::Continue => {}
                             }
-                            commands::Command::ReplaceAll => {
-                                if !fr.is_empty() {
-                                    let find_text = fr.find_text().to_string();
-                                    let replace_text = fr.replace_text().to_string();
-                                    let matches = editor.find_all(&find_text);
-
-                                    // Replace all from last to first to maintain positions
-                                    for &(start, end) in matches.iter().rev() {
-                                        editor.replace_at(start, end, &replace_text);
-                                    }
-
-                                    // Clear matches and update
-                                    fr.update_matches(Vec::new());
-                                    editor.clear_find_matches();
-                                }
-                            }
-                            _ => {}
+                            continue; // Skip normal command processing
                         }
+                        action = editor_action;
+                    }
+                }
+
+                // Keys that act on the focused output pane
+                let output_focused = layout.output_visible && output_pane.is_focused();
+                if action.is_none() && output_focused {
+                    if output_pane.is_finding() {
+                        output_pane.handle_find_input(key.code, key.modifiers);
                         continue;
                     }
-                    
-                    // Check if this is an undo/redo command first
-                    let is_undo_redo = match (key.code, key.modifiers.contains(KeyModifiers::CONTROL)) {
-                        (KeyCode::Char('z') | KeyCode::Char('Z'), true) => true,
-                        _ => false,
-                    };
-                    
-                    // If it's not undo/redo, handle it as find/replace input
-                    if !is_undo_redo {
-                        // Handle regular input for find/replace window
-                        let result = fr.handle_input(key.code, key.modifiers);
-                        match result {
-                            find_replace::InputResult::Close => {
-                                find_replace = None;
-                                // Clear selection and find matches when closing find
-                                editor.selection_start = None;
-                                editor.clear_find_matches();
-                                // Force redraw
-                                execute!(io::stdout(), 
-                                    crossterm::terminal::Clear(crossterm::terminal::ClearType::All),
-                                    crossterm::cursor::Hide
-                                )?;
-                                renderer.force_redraw();
-                            }
-                            find_replace::InputResult::FindTextChanged => {
-                                // Update search results
-                                let matches = editor.find_all(fr.find_text());
-                                fr.update_matches(matches.clone());
-                                // Update editor's find matches for highlighting
-                                // Only set current match to 0 if there are actually matches
-                                let current_match = if matches.is_empty() { None } else { Some(0) };
-                                editor.set_find_matches(matches, current_match);
-                                // Select first match if any
-                                if let Some((start, end)) = fr.current_match_position() {
-                                    editor.select_range(start, end);
-                                } else {
-                                    editor.selection_start = None;
+                    action = settings.keymap.lookup(actions::Scope::Output, &key);
+                }
+
+                let alt = key.modifiers.contains(KeyModifiers::ALT);
+                let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+                let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+
+                // The autocomplete dropdown takes the keys that move through, accept and hide it
+                if action.is_none() && autocomplete.is_visible() && !alt {
+                    match key.code {
+                        KeyCode::Up => {
+                            autocomplete.select_previous();
+                            continue;
+                        }
+                        KeyCode::Down => {
+                            autocomplete.select_next();
+                            continue;
+                        }
+                        KeyCode::Tab if !shift => {
+                            // Accept autocomplete suggestion
+                            if let Some(suggestion) = autocomplete.get_selected() {
+                                let prefix = editor.get_word_at_cursor();
+                                // Delete the prefix and insert the full suggestion
+                                for _ in 0..prefix.len() {
+                                    editor.execute(commands::Command::Backspace)?;
                                 }
-                            }
-                            find_replace::InputResult::FindNext => {
-                                if !fr.is_empty() {
-                                    if let Some((start, end)) = fr.next_match() {
-                                        editor.select_range(start, end);
-                                        // Update current match index for highlighting
-                                        editor.set_find_matches(fr.get_all_matches().to_vec(), fr.get_current_match_index());
-                                    }
+                                for ch in suggestion.chars() {
+                                    editor.execute(commands::Command::InsertChar(ch))?;
                                 }
+                                autocomplete.hide();
+                                renderer.force_redraw(); // Force full redraw to clear autocomplete artifacts
+                                suppress_autocomplete_once = true; // Don't show autocomplete on next key
                             }
-                            find_replace::InputResult::Continue => {}
+                            continue;
                         }
-                        continue; // Skip normal command processing
+                        KeyCode::Esc => {
+                            autocomplete.hide();
+                            continue;
+                        }
+                        _ => {}
+                    }
+                }
+
+                // When the output pane is focused, the arrows, Home and End move its cursor
+                if action.is_none() && output_focused && !alt {
+                    let moves = matches!(key.code, KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right | KeyCode::Home | KeyCode::End);
+                    match key.code {
+                        KeyCode::Up if ctrl => output_pane.move_cursor_paragraph_up(shift),
+                        KeyCode::Up => output_pane.move_cursor_up(shift),
+                        KeyCode::Down if ctrl => output_pane.move_cursor_paragraph_down(shift),
+                        KeyCode::Down => output_pane.move_cursor_down(shift),
+                        KeyCode::Left if ctrl => output_pane.move_cursor_word_left(shift),
+                        KeyCode::Left => output_pane.move_cursor_left(shift),
+                        KeyCode::Right if ctrl => output_pane.move_cursor_word_right(shift),
+                        KeyCode::Right => output_pane.move_cursor_right(shift),
+                        KeyCode::Home => output_pane.move_cursor_home(shift),
+                        KeyCode::End => output_pane.move_cursor_end(shift),
+                        _ => {}
+                    }
+                    if moves {
+                        continue;
+                    }
+                }
+
+                let mut action = action.or_else(|| settings.keymap.lookup(actions::Scope::Editor, &key));
+
+                // Command palette: run whichever action is picked instead
+                if action == Some(actions::Action::CommandPalette) {
+                    autocomplete.hide();
+                    execute!(io::stdout(), crossterm::cursor::Hide)?;
+                    let mut palette = palette::CommandPalette::new(&settings.keymap);
+                    action = palette.run(&mut io::stdout())?;
+                    execute!(io::stdout(),
+                        crossterm::terminal::Clear(crossterm::terminal::ClearType::All),
+                        crossterm::cursor::Hide
+                    )?;
+                    renderer.force_redraw();
+                    if action.is_none() {
+                        continue;
                     }
-                    // If it's undo/redo, fall through to normal command processing
                 }
 
                 // Note: suppress_autocomplete_once flag (if set by Tab completion) will be
                 // checked and cleared in the autocomplete update logic below
 
-                let cmd = match key.code {
-                    // Esc - Hide autocomplete, or toggle output pane focus
-                    KeyCode::Esc => {
-                        if autocomplete.is_visible() {
-                            autocomplete.hide();
-                            needs_redraw = true;
-                        } else if output_pane_visible {
-                            output_pane.toggle_focus();
-                            needs_redraw = true;
+                let cmd = match action {
+                    // Copy the output pane's selection while it has focus
+                    Some(actions::Action::Command(commands::Command::Copy)) if output_focused => {
+                        if let Some(selected_text) = output_pane.get_selected_text() {
+                            // Copy to system clipboard
+                            use arboard::Clipboard;
+                            if let Ok(mut clipboard) = Clipboard::new() {
+                                let _ = clipboard.set_text(selected_text);
+                            }
                         }
                         commands::Command::None
                     }
 
-                    // Quit
-                    KeyCode::Char('q') | KeyCode::Char('Q') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        if editor.is_modified() {
-                            // Show exit prompt for unsaved changes
+                    // Tab indents the selected lines, or inserts 4 spaces without a selection
+                    Some(actions::Action::Command(commands::Command::Indent)) if editor.selection().is_none() => {
+                        commands::Command::InsertTab
+                    }
+
+                    // The find bar's commands open the bar when it isn't already
+                    Some(actions::Action::Command(
+                        cmd @ (commands::Command::FindNext
+                        | commands::Command::FindPrev
+                        | commands::Command::Replace
+                        | commands::Command::ReplaceAll
+                        | commands::Command::ToggleFindRegex
+                        | commands::Command::ToggleFindMatchCase
+                        | commands::Command::ToggleFindWholeWord
+                        | commands::Command::ToggleFindInSelection),
+                    )) => match find_replace.as_mut() {
+                        Some(fr) => {
+                            run_find_command(editor, fr, &cmd);
+                            commands::Command::None
+                        }
+                        None => commands::Command::FindReplace,
+                    },
+
+                    Some(actions::Action::Command(cmd)) => cmd,
+
+                    Some(actions::Action::Quit) => {
+                        let modified = tabs.modified_tabs(editor);
+                        if !modified.is_empty() {
+                            // Show exit prompt listing every buffer with unsaved changes
                             let mut exit_prompt = exit_prompt::ExitPrompt::new();
                             
                             // Hide cursor before showing prompt
                             execute!(io::stdout(), crossterm::cursor::Hide)?;
                             
-                            let filename = editor.file_name();
+                            let filenames: Vec<&str> = modified.iter().map(|&idx| tabs.file_name(idx, editor)).collect();
                             
                             // Run the prompt and get result
-                            let result = exit_prompt.run(&mut io::stdout(), filename)?;
+                            let result = exit_prompt.run(&mut io::stdout(), &filenames)?;
                             
                             // Clear the screen and force complete redraw
                             execute!(io::stdout(), 
@@ -525,51 +933,38 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                             
                             match result {
                                 exit_prompt::ExitOption::Save => {
-                                    // Try to save
-                                    if editor.file_path().is_none() {
-                                        // Need Save As
-                                        let initial_path = editor.get_save_as_initial_path();
-                                        let mut prompt = prompt::Prompt::new("Save As", &initial_path);
-                                        
-                                        if let Some(path) = prompt.run(&mut io::stdout())? {
-                                            if editor.save_as(path).is_err() {
-                                                // Clear and redraw
-                                                execute!(io::stdout(), 
-                                                    crossterm::terminal::Clear(crossterm::terminal::ClearType::All),
-                                                    crossterm::cursor::Hide
-                                                )?;
-                                                renderer.force_redraw();
-                                                renderer.draw(editor)?;
-                                                continue; // Don't exit if save failed
-                                            } else {
-                                                return Ok(()); // Successfully saved, exit
+                                    // Save each modified buffer in turn, stopping if one is cancelled or fails
+                                    let mut saved_all = true;
+                                    for idx in modified {
+                                        if idx != tabs.active() {
+                                            if execution_rx.is_some() {
+                                                saved_all = false;
+                                                editor.status_message = Some(("Wait for execution to finish before saving other buffers".to_string(), true));
+                                                break;
                                             }
-                                        } else {
-                                            // User cancelled Save As, don't exit
-                                            execute!(io::stdout(), 
-                                                crossterm::terminal::Clear(crossterm::terminal::ClearType::All),
-                                                crossterm::cursor::Hide
-                                            )?;
-                                            renderer.force_redraw();
-                                            renderer.draw(editor)?;
-                                            continue;
+                                            tabs.switch_to(editor, idx);
+                                            refresh_after_tab_change(editor, renderer, &mut layout, &tabs)?;
+                                            renderer.draw_layout(editor, &layout.areas(false))?;
                                         }
-                                    } else {
-                                        // Normal save
-                                        if editor.save().is_err() {
-                                            renderer.draw(editor)?;
-                                            continue; // Don't exit if save failed
-                                        } else {
-                                            return Ok(()); // Successfully saved, exit
+                                        if !save_or_prompt(editor, renderer)? {
+                                            saved_all = false;
+                                            break;
                                         }
                                     }
+                                    if saved_all {
+                                        return Ok(()); // Everything saved, exit
+                                    }
+                                    // Don't exit if a save failed or was cancelled
+                                    find_replace = None;
+                                    needs_redraw = true;
+                                    continue;
                                 }
                                 exit_prompt::ExitOption::ExitWithoutSaving => {
                                     return Ok(()); // Exit without saving
                                 }
                                 exit_prompt::ExitOption::Cancel => {
                                     // Cancel exit, redraw and continue
-                                    renderer.draw(editor)?;
+                                    needs_redraw = true;
                                     continue;
                                 }
                             }
@@ -578,34 +973,17 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                             return Ok(());
                         }
                     }
-                    
-                    // Save / Save As
-                    KeyCode::Char('s') | KeyCode::Char('S') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        if key.modifiers.contains(KeyModifiers::SHIFT) {
-                            commands::Command::SaveAs
-                        } else {
-                            commands::Command::Save
-                        }
-                    }
-                    
-                    // Undo/Redo
-                    KeyCode::Char('z') | KeyCode::Char('Z') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        if key.modifiers.contains(KeyModifiers::SHIFT) {
-                            commands::Command::Redo
-                        } else {
-                            commands::Command::Undo
-                        }
-                    }
-                    
-                    // Aggressive Cancellation (Ctrl+Backspace)
+
+                    // Aggressive Cancellation (Ctrl+Backspace, or Ctrl+H as many terminals send it)
                     // TODO: Implement graceful interruption (SIGINT) to preserve kernel state
                     // Currently this does a hard reset which loses all Python variables/state
-                    KeyCode::Backspace if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    Some(actions::Action::CancelExecution) => {
                         // Ctrl+Backspace = AGGRESSIVE CANCEL
                         if execution_rx.is_some() {
                             // Drop the channel - abandons the background thread
                             execution_rx = None;
                             execution_start_time = None;
+                            editor.finish_running_cells();
 
                             // Recreate a fresh kernel using stored info
                             if let Some(kernel_info) = executing_kernel_info.take() {
@@ -620,6 +998,13 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                                         if new_kernel.connect().is_ok() {
                                             editor.set_kernel(new_kernel);
                                             editor.status_message = Some(("CANCELLED - Kernel reset (all variables lost)".to_string(), true));
+                                            // Re-run setup cells on the fresh kernel
+                                            let setup_cells = editor.setup_cell_indices();
+                                            if let Some((rx, kernel_info)) = request_run(editor, setup_cells, &mut pending_run) {
+                                                execution_rx = Some(rx);
+                                                execution_start_time = Some(std::time::Instant::now());
+                                                executing_kernel_info = Some(kernel_info);
+                                            }
                                         } else {
                                             editor.status_message = Some(("CANCELLED - Kernel reconnection failed".to_string(), true));
                                         }
@@ -642,94 +1027,547 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                         commands::Command::None
                     }
 
-                    KeyCode::Char('c') | KeyCode::Char('C') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        // Check if output pane has focus and has selected text
-                        if output_pane_visible && output_pane.is_focused() {
-                            if let Some(selected_text) = output_pane.get_selected_text() {
-                                // Copy to system clipboard
-                                use arboard::Clipboard;
-                                if let Ok(mut clipboard) = Clipboard::new() {
-                                    let _ = clipboard.set_text(selected_text);
-                                }
-                            }
-                            commands::Command::None
+                    // Run Stale Cells (Ctrl+R) - re-runs cells whose upstream changed, in order
+                    Some(actions::Action::RunStaleCells) => {
+                        if execution_rx.is_some() {
+                            editor.status_message = Some(("Already executing (Ctrl+Backspace to cancel - WARNING: resets kernel)".to_string(), true));
                         } else {
-                            commands::Command::Copy
+                            let cells = editor.stale_cell_indices();
+                            if cells.is_empty() {
+                                editor.status_message = Some(("No stale cells".to_string(), false));
+                            } else if let Some((rx, kernel_info)) = request_run(editor, cells, &mut pending_run) {
+                                execution_rx = Some(rx);
+                                execution_start_time = Some(std::time::Instant::now());
+                                executing_kernel_info = Some(kernel_info);
+                                editor.status_message = Some(("Executing...".to_string(), false));
+                            }
                         }
+                        needs_redraw = true;
+                        commands::Command::None
                     }
 
-                    KeyCode::Char('x') | KeyCode::Char('X') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        commands::Command::Cut
-                    }
-                    
-                    KeyCode::Char('v') | KeyCode::Char('V') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        commands::Command::Paste
-                    }
-                    
-                    // Find/Replace
-                    KeyCode::Char('f') | KeyCode::Char('F') if key.modifiers.contains(KeyModifiers::CONTROL) && !key.modifiers.contains(KeyModifiers::SHIFT) => {
-                        commands::Command::FindReplace
-                    }
-                    
-                    // Select All
-                    KeyCode::Char('a') | KeyCode::Char('A') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        commands::Command::SelectAll
+                    // Run All Cells (Ctrl+Shift+E) - skips cells tagged `skip`
+                    Some(actions::Action::RunAllCells) => {
+                        if execution_rx.is_some() {
+                            editor.status_message = Some(("Already executing (Ctrl+Backspace to cancel - WARNING: resets kernel)".to_string(), true));
+                        } else {
+                            let cells = editor.run_all_cell_indices();
+                            if let Some((rx, kernel_info)) = request_run(editor, cells, &mut pending_run) {
+                                execution_rx = Some(rx);
+                                execution_start_time = Some(std::time::Instant::now());
+                                executing_kernel_info = Some(kernel_info);
+                                editor.status_message = Some(("Executing...".to_string(), false));
+                            }
+                        }
+                        needs_redraw = true;
+                        commands::Command::None
                     }
 
-                    // Execute Cell (Ctrl+E as alternative)
-                    KeyCode::Char('e') | KeyCode::Char('E') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    // Execute Cell (Ctrl+Enter, or Ctrl+E as alternative)
+                    Some(actions::Action::RunCell) => {
                         // Check if already executing
                         if execution_rx.is_some() {
                             editor.status_message = Some(("Already executing (Ctrl+Backspace to cancel - WARNING: resets kernel)".to_string(), true));
                             needs_redraw = true;
                         } else {
                             // Start background execution
-                            if let Some((rx, kernel_info)) = spawn_background_execution(editor) {
+                            let cells = editor.cells_to_execute();
+                            if let Some((rx, kernel_info)) = request_run(editor, cells, &mut pending_run) {
                                 execution_rx = Some(rx);
                                 execution_start_time = Some(std::time::Instant::now());
                                 executing_kernel_info = Some(kernel_info);
                                 editor.status_message = Some(("Executing...".to_string(), false));
-                                needs_redraw = true;
-                            } else {
-                                // No kernel connected
-                                editor.status_message = Some(("No kernel connected. Press Ctrl+K to select a kernel.".to_string(), true));
-                                needs_redraw = true;
                             }
+                            needs_redraw = true;
                         }
                         commands::Command::None
                     }
 
                     // Clear Output Pane (Ctrl+L)
-                    KeyCode::Char('l') | KeyCode::Char('L') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    Some(actions::Action::ClearOutput) => {
                         output_pane.clear();
                         editor.status_message = Some(("Output cleared".to_string(), false));
                         needs_redraw = true;
                         commands::Command::None
                     }
 
-                    // Toggle Output Pane (Ctrl+O)
-                    KeyCode::Char('o') | KeyCode::Char('O') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        output_pane_visible = !output_pane_visible;
-                        // Update viewport to account for new bottom window height
-                        let bottom_height = if output_pane_visible { output_pane_height } else { 0 };
-                        editor.update_viewport_for_cursor_with_bottom(bottom_height);
+                    // Toggle inline outputs under each cell (Ctrl+Shift+O)
+                    Some(actions::Action::ToggleInlineOutputs) => {
+                        let enabled = editor.toggle_inline_outputs();
+                        let message = if enabled { "Inline outputs on" } else { "Inline outputs off" };
+                        editor.status_message = Some((message.to_string(), false));
+                        editor.update_viewport_for_area(layout.areas(find_replace.is_some()).editor);
                         renderer.force_redraw();
+                        needs_redraw = true;
                         commands::Command::None
                     }
 
-                    // Kernel Selection (Ctrl+K)
-                    KeyCode::Char('k') | KeyCode::Char('K') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        // Show loading message
-                        editor.status_message = Some(("Discovering Python kernels...".to_string(), false));
-                        renderer.draw(editor)?;
-                        use std::io::Write;
-                        let mut stdout = io::stdout();
-                        stdout.flush()?;
+                    // Collapse/expand the current cell's inline output (Alt+O)
+                    Some(actions::Action::ToggleCellOutput) => {
+                        if editor.inline_outputs_enabled() {
+                            editor.toggle_current_inline_output();
+                            needs_redraw = true;
+                        }
+                        commands::Command::None
+                    }
 
-                        // Create selector (this does the discovery)
-                        let mut selector = kernel_selector::KernelSelector::new();
+                    // Scroll the current cell's inline output (Alt+PageUp/PageDown)
+                    Some(actions::Action::ScrollInlineOutputUp) | Some(actions::Action::ScrollInlineOutputDown) => {
+                        let up = action == Some(actions::Action::ScrollInlineOutputUp);
+                        if editor.inline_outputs_enabled() {
+                            let rows = editor::INLINE_OUTPUT_MAX_HEIGHT as i32;
+                            editor.scroll_current_inline_output(if up { -rows } else { rows });
+                            needs_redraw = true;
+                            commands::Command::None
+                        } else if up {
+                            commands::Command::PageUp
+                        } else {
+                            commands::Command::PageDown
+                        }
+                    }
 
-                        execute!(io::stdout(), crossterm::cursor::Hide)?;
+                    // Toggle Output Pane (Ctrl+O)
+                    Some(actions::Action::ToggleOutputPane) => {
+                        layout.output_visible = !layout.output_visible;
+                        // Update viewport to account for the new layout
+                        editor.update_viewport_for_area(layout.areas(find_replace.is_some()).editor);
+                        renderer.force_redraw();
+                        commands::Command::None
+                    }
+
+                    // Esc - back to one cursor, otherwise toggle output pane focus
+                    Some(actions::Action::FocusOutputPane) if !output_focused && editor.has_extra_cursors() => {
+                        commands::Command::ClearExtraCursors
+                    }
+                    Some(actions::Action::FocusOutputPane) => {
+                        if layout.output_visible {
+                            output_pane.toggle_focus();
+                            needs_redraw = true;
+                        }
+                        commands::Command::None
+                    }
+
+                    // Output pane below or beside the editor (Alt+V)
+                    Some(actions::Action::ToggleOutputPlacement) => {
+                        let message = match layout.toggle_placement() {
+                            layout::PanePlacement::Right => "Output pane on the right",
+                            layout::PanePlacement::Bottom => "Output pane at the bottom",
+                        };
+                        editor.status_message = Some((message.to_string(), false));
+                        editor.update_viewport_for_area(layout.areas(find_replace.is_some()).editor);
+                        renderer.force_redraw();
+                        needs_redraw = true;
+                        commands::Command::None
+                    }
+
+                    // Alt+Up/Left grow and Alt+Down/Right shrink the output pane
+                    Some(actions::Action::GrowOutputPane) | Some(actions::Action::ShrinkOutputPane) => {
+                        // Columns move in bigger steps than rows
+                        let step = if layout.placement == layout::PanePlacement::Right { 2 } else { 1 };
+                        let delta = if action == Some(actions::Action::GrowOutputPane) { step } else { -step };
+                        if layout.resize_output(delta) {
+                            // Update viewport to account for the resized editor area
+                            editor.update_viewport_for_area(layout.areas(find_replace.is_some()).editor);
+                            renderer.force_redraw();
+                            needs_redraw = true;
+                        }
+                        commands::Command::None
+                    }
+
+                    // Shift+PageUp/PageDown scroll the output pane
+                    Some(actions::Action::ScrollOutputUp) | Some(actions::Action::ScrollOutputDown) => {
+                        let up = action == Some(actions::Action::ScrollOutputUp);
+                        if layout.output_visible {
+                            if up {
+                                output_pane.scroll_up();
+                            } else {
+                                output_pane.scroll_down();
+                            }
+                            needs_redraw = true;
+                            commands::Command::None
+                        } else if up {
+                            commands::Command::PageUp
+                        } else {
+                            commands::Command::PageDown
+                        }
+                    }
+
+                    // Export the notebook with its outputs as a report (Alt+X)
+                    Some(actions::Action::ExportReport) => {
+                        let initial_path = export::report_path(&editor.get_save_as_initial_path(), export::ReportFormat::Html);
+                        let mut prompt = prompt::Prompt::new("Export (.html, .md, .ipynb or .py)", &initial_path);
+
+                        execute!(io::stdout(), crossterm::cursor::Hide)?;
+                        let result = prompt.run(&mut io::stdout())?;
+                        execute!(io::stdout(),
+                            crossterm::terminal::Clear(crossterm::terminal::ClearType::All),
+                            crossterm::cursor::Hide
+                        )?;
+                        renderer.force_redraw();
+
+                        if let Some(path) = result {
+                            let path = path.to_string_lossy().to_string();
+                            match export::write_executed_notebook(&path, &editor.executed_cells(), None) {
+                                Ok(()) => editor.status_message = Some((format!("Exported to {}", path), false)),
+                                Err(e) => editor.status_message = Some((format!("Export failed: {}", e), true)),
+                            }
+                        }
+                        needs_redraw = true;
+                        commands::Command::None
+                    }
+
+                    // Output pane entries: find, collapse, filter, step through, open and copy
+                    Some(actions::Action::FindInOutput) => {
+                        if !layout.output_visible {
+                            layout.output_visible = true;
+                            editor.update_viewport_for_area(layout.areas(find_replace.is_some()).editor);
+                            renderer.force_redraw();
+                        }
+                        output_pane.set_focused(true);
+                        output_pane.start_find();
+                        commands::Command::None
+                    }
+                    Some(actions::Action::ToggleOutputEntry) => {
+                        output_pane.toggle_entry_at_cursor();
+                        commands::Command::None
+                    }
+                    Some(actions::Action::CollapseAllOutputs) => {
+                        let collapsed = output_pane.toggle_collapse_all();
+                        let message = if collapsed { "Collapsed all outputs" } else { "Expanded all outputs" };
+                        editor.status_message = Some((message.to_string(), false));
+                        commands::Command::None
+                    }
+                    Some(actions::Action::ToggleErrorsOnly) => {
+                        let errors_only = output_pane.toggle_errors_only();
+                        let message = if errors_only { "Showing errors only" } else { "Showing all output" };
+                        editor.status_message = Some((message.to_string(), false));
+                        commands::Command::None
+                    }
+                    Some(actions::Action::NextOutputEntry) => {
+                        output_pane.next_entry();
+                        commands::Command::None
+                    }
+                    Some(actions::Action::PrevOutputEntry) => {
+                        output_pane.prev_entry();
+                        commands::Command::None
+                    }
+                    Some(actions::Action::OpenFullOutput) => {
+                        // Open the complete text of a truncated entry in a new tab
+                        match output_pane.cursor_entry_full_text() {
+                            Some(_) if execution_rx.is_some() => {
+                                editor.status_message = Some(("Wait for execution to finish before opening the full output".to_string(), true));
+                            }
+                            Some(path) => {
+                                let mut opened = settings.new_editor();
+                                match opened.load_file(&path.to_string_lossy()) {
+                                    Ok(_) => {
+                                        output_pane.set_focused(false);
+                                        find_replace = None;
+                                        tabs.open(editor, opened);
+                                        refresh_after_tab_change(editor, renderer, &mut layout, &tabs)?;
+                                        editor.status_message = Some((format!("Opened full output: {}", path.display()), false));
+                                    }
+                                    Err(e) => editor.status_message = Some((format!("Failed to open full output: {}", e), true)),
+                                }
+                            }
+                            None => {
+                                editor.status_message = Some(("This output was not truncated".to_string(), false));
+                            }
+                        }
+                        commands::Command::None
+                    }
+                    Some(actions::Action::CopyOutputEntry) => {
+                        if let Some(text) = output_pane.cursor_entry_text() {
+                            use arboard::Clipboard;
+                            if let Ok(mut clipboard) = Clipboard::new() {
+                                let _ = clipboard.set_text(text);
+                                editor.status_message = Some(("Copied output entry".to_string(), false));
+                            }
+                        }
+                        commands::Command::None
+                    }
+
+                    // Buffers: Ctrl+N new, Ctrl+T open, Ctrl+P quick open, Ctrl+W close,
+                    // Ctrl+PageUp/PageDown switch, Ctrl+Shift+PageUp/PageDown reorder,
+                    // Alt+1..9 go to, Alt+K own/shared kernel
+                    Some(actions::Action::NewBuffer) | Some(actions::Action::OpenFile) | Some(actions::Action::QuickOpen) | Some(actions::Action::CloseBuffer)
+                        | Some(actions::Action::ProjectSearch) | Some(actions::Action::ReplaceInFiles)
+                        if execution_rx.is_some() =>
+                    {
+                        editor.status_message = Some(("Wait for execution to finish before changing buffers".to_string(), true));
+                        needs_redraw = true;
+                        commands::Command::None
+                    }
+                    Some(actions::Action::NewBuffer) => {
+                        find_replace = None;
+                        tabs.open(editor, settings.new_editor());
+                        refresh_after_tab_change(editor, renderer, &mut layout, &tabs)?;
+                        needs_redraw = true;
+                        commands::Command::None
+                    }
+                    Some(actions::Action::OpenFile) => {
+                        // Start from the directory of the current file
+                        let dir = editor.file_path()
+                            .and_then(|path| path.parent())
+                            .map(|dir| dir.to_path_buf())
+                            .or_else(|| std::env::current_dir().ok())
+                            .unwrap_or_default();
+                        let initial_path = format!("{}{}", dir.display(), std::path::MAIN_SEPARATOR);
+                        execute!(io::stdout(), crossterm::cursor::Hide)?;
+                        let mut prompt = prompt::Prompt::new("Open", &initial_path);
+                        let result = prompt.run(&mut io::stdout())?;
+                        execute!(io::stdout(),
+                            crossterm::terminal::Clear(crossterm::terminal::ClearType::All),
+                            crossterm::cursor::Hide
+                        )?;
+                        renderer.force_redraw();
+                        if let Some(path) = result {
+                            find_replace = None;
+                            open_in_tab(editor, renderer, &mut layout, &mut tabs, &settings, &path)?;
+                        }
+                        needs_redraw = true;
+                        commands::Command::None
+                    }
+                    Some(actions::Action::QuickOpen) => {
+                        // Quick-open: fuzzy find a file in the project
+                        let start = editor.file_path()
+                            .and_then(|path| path.parent())
+                            .filter(|dir| !dir.as_os_str().is_empty())
+                            .map(|dir| dir.to_path_buf())
+                            .or_else(|| std::env::current_dir().ok())
+                            .unwrap_or_default();
+                        let mut finder = quick_open::QuickOpen::new(quick_open::project_root(&start), tabs.recent_files());
+                        let result = finder.run(&mut io::stdout())?;
+                        execute!(io::stdout(),
+                            crossterm::terminal::Clear(crossterm::terminal::ClearType::All),
+                            crossterm::cursor::Hide
+                        )?;
+                        renderer.force_redraw();
+                        if let Some(path) = result {
+                            find_replace = None;
+                            open_in_tab(editor, renderer, &mut layout, &mut tabs, &settings, &path)?;
+                        }
+                        needs_redraw = true;
+                        commands::Command::None
+                    }
+                    Some(actions::Action::UndoTree) => {
+                        // Every state the buffer has been in, to jump to one
+                        let mut view = undo_view::UndoTreeView::new(editor.buffer(), editor.saved_undo_state());
+                        let result = view.run(&mut io::stdout())?;
+                        execute!(io::stdout(),
+                            crossterm::terminal::Clear(crossterm::terminal::ClearType::All),
+                            crossterm::cursor::Hide
+                        )?;
+                        renderer.force_redraw();
+                        if let Some(state) = result {
+                            editor.go_to_undo_state(state);
+                        }
+                        needs_redraw = true;
+                        commands::Command::None
+                    }
+                    Some(actions::Action::UndoEarlierBy) | Some(actions::Action::UndoLaterBy) => {
+                        // Go back or forward in time through the undo states, by a span like "5m"
+                        let later = action == Some(actions::Action::UndoLaterBy);
+                        let title = if later { "Go forward by (30s, 5m, 2h, 1d)" } else { "Go back by (30s, 5m, 2h, 1d)" };
+                        let mut prompt = prompt::Prompt::new(title, "5m");
+                        execute!(io::stdout(), crossterm::cursor::Hide)?;
+                        let result = prompt.run(&mut io::stdout())?;
+                        execute!(io::stdout(),
+                            crossterm::terminal::Clear(crossterm::terminal::ClearType::All),
+                            crossterm::cursor::Hide
+                        )?;
+                        renderer.force_redraw();
+                        if let Some(span) = result {
+                            let span = span.to_string_lossy().to_string();
+                            match undo_tree::parse_duration(&span) {
+                                Some(seconds) => editor.travel_undo_time(seconds, later),
+                                None => editor.status_message = Some((format!("Not a span of time: '{}'", span), true)),
+                            }
+                        }
+                        needs_redraw = true;
+                        commands::Command::None
+                    }
+                    Some(actions::Action::ProjectSearch) | Some(actions::Action::ReplaceInFiles) => {
+                        // Search every file under the working directory, starting from a one-line selection
+                        let root = std::env::current_dir().unwrap_or_default();
+                        let replacing = action == Some(actions::Action::ReplaceInFiles);
+                        let mut search = project_search::ProjectSearch::new(root, tabs.file_texts(editor), replacing);
+                        if let Some((start, end)) = editor.selection().filter(|_| !editor.selection_is_multiline()) {
+                            search.set_query(&editor.buffer().rope().byte_slice(start..end).to_string());
+                        }
+                        let result = search.run(&mut io::stdout())?;
+                        execute!(io::stdout(),
+                            crossterm::terminal::Clear(crossterm::terminal::ClearType::All),
+                            crossterm::cursor::Hide
+                        )?;
+                        renderer.force_redraw();
+                        match result {
+                            Some(project_search::ProjectSearchResult::Open { path, line, range }) => {
+                                find_replace = None;
+                                open_in_tab(editor, renderer, &mut layout, &mut tabs, &settings, &path)?;
+                                if tabs.find_file(&path, editor) == Some(tabs.active()) {
+                                    let buffer = editor.buffer();
+                                    let line_start = buffer.line_to_byte(line.min(buffer.len_lines().saturating_sub(1)));
+                                    let len = buffer.len_bytes();
+                                    editor.select_range((line_start + range.0).min(len), (line_start + range.1).min(len));
+                                    editor.update_viewport_for_area(layout.areas(false).editor);
+                                }
+                            }
+                            Some(project_search::ProjectSearchResult::Replace { search, replace, files }) => {
+                                find_replace = None;
+                                let (replaced, changed_files) = replace_in_files(editor, &mut tabs, &settings, &search, &replace, &files);
+                                refresh_after_tab_change(editor, renderer, &mut layout, &tabs)?;
+                                editor.status_message = Some((
+                                    format!("Replaced {} matches in {} files - unsaved, undo in each buffer with Ctrl+Z", replaced, changed_files),
+                                    false,
+                                ));
+                            }
+                            None => {}
+                        }
+                        needs_redraw = true;
+                        commands::Command::None
+                    }
+                    Some(actions::Action::CloseBuffer) => {
+                        let mut close = true;
+                        if editor.is_modified() {
+                            let mut exit_prompt = exit_prompt::ExitPrompt::for_close();
+                            execute!(io::stdout(), crossterm::cursor::Hide)?;
+                            let result = exit_prompt.run(&mut io::stdout(), &[editor.file_name()])?;
+                            execute!(io::stdout(),
+                                crossterm::terminal::Clear(crossterm::terminal::ClearType::All),
+                                crossterm::cursor::Hide
+                            )?;
+                            renderer.force_redraw();
+                            close = match result {
+                                exit_prompt::ExitOption::Save => save_or_prompt(editor, renderer)?,
+                                exit_prompt::ExitOption::ExitWithoutSaving => true,
+                                exit_prompt::ExitOption::Cancel => false,
+                            };
+                        }
+                        if close {
+                            find_replace = None;
+                            if let Some(closed) = tabs.close_active(editor) {
+                                layout.splits.replace_tab(closed, tabs.active_id());
+                            }
+                            // Closing the last tab leaves a fresh empty buffer
+                            editor.set_tab_width(settings.config.editor.tab_width);
+                            editor.set_line_numbers(settings.config.editor.line_numbers);
+                            let config = &settings.config.editor;
+                            editor.set_soft_wrap(config.soft_wrap, config.wrap_column, &config.wrap_indicator);
+                            editor.set_persistent_undo(config.persistent_undo, config.undo_history_kb);
+                            refresh_after_tab_change(editor, renderer, &mut layout, &tabs)?;
+                        }
+                        needs_redraw = true;
+                        commands::Command::None
+                    }
+                    Some(actions::Action::NextBuffer) | Some(actions::Action::PrevBuffer) => {
+                        if execution_rx.is_some() {
+                            editor.status_message = Some(("Wait for execution to finish before switching buffers".to_string(), true));
+                        } else if tabs.len() > 1 {
+                            find_replace = None;
+                            tabs.cycle(editor, action == Some(actions::Action::NextBuffer));
+                            refresh_after_tab_change(editor, renderer, &mut layout, &tabs)?;
+                        }
+                        needs_redraw = true;
+                        commands::Command::None
+                    }
+                    Some(actions::Action::MoveBufferLeft) | Some(actions::Action::MoveBufferRight) => {
+                        // Reordering keeps the same buffer active, so it is fine mid-execution
+                        tabs.move_active(action == Some(actions::Action::MoveBufferRight));
+                        needs_redraw = true;
+                        commands::Command::None
+                    }
+                    Some(actions::Action::GoToBuffer(idx)) => {
+                        if execution_rx.is_some() {
+                            editor.status_message = Some(("Wait for execution to finish before switching buffers".to_string(), true));
+                        } else if idx < tabs.len() && idx != tabs.active() {
+                            find_replace = None;
+                            tabs.switch_to(editor, idx);
+                            refresh_after_tab_change(editor, renderer, &mut layout, &tabs)?;
+                        }
+                        needs_redraw = true;
+                        commands::Command::None
+                    }
+                    Some(actions::Action::ToggleOwnKernel) => {
+                        if execution_rx.is_some() {
+                            editor.status_message = Some(("Wait for execution to finish before changing kernels".to_string(), true));
+                        } else if tabs.toggle_own_kernel(editor) {
+                            editor.status_message = Some(("This buffer now has its own kernel. Press Ctrl+K to select one.".to_string(), false));
+                        } else {
+                            editor.status_message = Some(("This buffer now shares the kernel with other buffers".to_string(), false));
+                        }
+                        needs_redraw = true;
+                        commands::Command::None
+                    }
+
+                    // Split windows: Alt+- below, Alt+\ to the right, Alt+Q close, Alt+W next,
+                    // Ctrl+Alt+arrows move focus
+                    Some(actions::Action::SplitWindow(direction)) => {
+                        layout.splits.split(direction, editor.view());
+                        find_replace = None;
+                        enter_focused_window(editor, &mut tabs, &mut layout);
+                        execute!(io::stdout(), crossterm::terminal::Clear(crossterm::terminal::ClearType::All))?;
+                        renderer.force_redraw();
+                        needs_redraw = true;
+                        commands::Command::None
+                    }
+                    Some(actions::Action::CloseWindow) => {
+                        if let Some(next) = layout.splits.successor() {
+                            // Refuse if the window taking focus shows another buffer mid-execution
+                            if execution_rx.is_some() && window_changes_buffer(&tabs, &layout, next) {
+                                editor.status_message = Some(("Wait for execution to finish before switching buffers".to_string(), true));
+                            } else {
+                                layout.splits.close_focused();
+                                find_replace = None;
+                                enter_focused_window(editor, &mut tabs, &mut layout);
+                                execute!(io::stdout(), crossterm::terminal::Clear(crossterm::terminal::ClearType::All))?;
+                                renderer.force_redraw();
+                            }
+                            needs_redraw = true;
+                        }
+                        commands::Command::None
+                    }
+                    Some(actions::Action::NextWindow) => {
+                        if layout.splits.len() > 1 {
+                            let idx = (layout.splits.focused() + 1) % layout.splits.len();
+                            if execution_rx.is_some() && window_changes_buffer(&tabs, &layout, idx) {
+                                editor.status_message = Some(("Wait for execution to finish before switching buffers".to_string(), true));
+                            } else {
+                                find_replace = None;
+                                focus_window(editor, &mut tabs, &mut layout, idx);
+                                renderer.force_redraw();
+                            }
+                            needs_redraw = true;
+                        }
+                        commands::Command::None
+                    }
+                    Some(actions::Action::FocusWindow(direction)) => {
+                        if let Some(idx) = layout.splits.neighbour(direction, layout.areas(false).windows) {
+                            if execution_rx.is_some() && window_changes_buffer(&tabs, &layout, idx) {
+                                editor.status_message = Some(("Wait for execution to finish before switching buffers".to_string(), true));
+                            } else {
+                                find_replace = None;
+                                focus_window(editor, &mut tabs, &mut layout, idx);
+                                renderer.force_redraw();
+                            }
+                            needs_redraw = true;
+                        }
+                        commands::Command::None
+                    }
+
+                    // Kernel Selection (Ctrl+K)
+                    Some(actions::Action::SelectKernel) => {
+                        // Show loading message
+                        editor.status_message = Some(("Discovering Python kernels...".to_string(), false));
+                        renderer.draw(editor)?;
+                        use std::io::Write;
+                        let mut stdout = io::stdout();
+                        stdout.flush()?;
+
+                        // Create selector (this does the discovery)
+                        let mut selector = kernel_selector::KernelSelector::new();
+
+                        execute!(io::stdout(), crossterm::cursor::Hide)?;
 
                         debug_log(&format!("About to run kernel selector"));
                         let result = match selector.run(&mut io::stdout()) {
@@ -796,6 +1634,17 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                                     editor.set_kernel(kernel);
                                     editor.enable_repl_mode();
                                     editor.status_message = Some(("Connected to kernel".to_string(), false));
+
+                                    // Run setup cells on the new kernel
+                                    if execution_rx.is_none() {
+                                        let setup_cells = editor.setup_cell_indices();
+                                        if let Some((rx, kernel_info)) = request_run(editor, setup_cells, &mut pending_run) {
+                                            execution_rx = Some(rx);
+                                            execution_start_time = Some(std::time::Instant::now());
+                                            executing_kernel_info = Some(kernel_info);
+                                            editor.status_message = Some(("Connected to kernel - running setup cells...".to_string(), false));
+                                        }
+                                    }
                                     debug_log(&format!("Kernel set"));
                                 }
                                 Err(e) => {
@@ -822,272 +1671,17 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                         commands::Command::None
                     }
 
-                    // Movement (with selection support)
-                    KeyCode::Up => {
-                        if autocomplete.is_visible() && !key.modifiers.contains(KeyModifiers::ALT) {
-                            // Navigate autocomplete dropdown
-                            autocomplete.select_previous();
-                            needs_redraw = true;
-                            commands::Command::None
-                        } else if output_pane_visible && output_pane.is_focused() && !key.modifiers.contains(KeyModifiers::ALT) {
-                            // When output pane is focused, Up moves cursor
-                            if key.modifiers.contains(KeyModifiers::CONTROL) {
-                                // Ctrl+Up: move to previous paragraph
-                                let with_selection = key.modifiers.contains(KeyModifiers::SHIFT);
-                                output_pane.move_cursor_paragraph_up(with_selection);
-                            } else {
-                                let with_selection = key.modifiers.contains(KeyModifiers::SHIFT);
-                                output_pane.move_cursor_up(with_selection);
-                            }
-                            needs_redraw = true;
-                            commands::Command::None
-                        } else if key.modifiers.contains(KeyModifiers::ALT) {
-                            // Alt+Up = Increase output pane height
-                            let (_, term_height) = crossterm::terminal::size()?;
-                            let max_height = (term_height as usize).saturating_sub(3); // Leave 3 lines for editor
-                            if output_pane_visible && output_pane_height < max_height {
-                                output_pane_height += 1;
-                                // Update viewport to account for new bottom window height
-                                editor.update_viewport_for_cursor_with_bottom(output_pane_height);
-                                renderer.force_redraw();
-                                needs_redraw = true;
-                            }
-                            commands::Command::None
-                        } else if key.modifiers.contains(KeyModifiers::CONTROL | KeyModifiers::SHIFT) {
-                            commands::Command::SelectParagraphUp
-                        } else if key.modifiers.contains(KeyModifiers::CONTROL) {
-                            commands::Command::MoveParagraphUp
-                        } else if key.modifiers.contains(KeyModifiers::SHIFT) {
-                            commands::Command::SelectUp
-                        } else {
-                            commands::Command::MoveUp
-                        }
-                    }
-                    KeyCode::Down => {
-                        if autocomplete.is_visible() && !key.modifiers.contains(KeyModifiers::ALT) {
-                            // Navigate autocomplete dropdown
-                            autocomplete.select_next();
-                            needs_redraw = true;
-                            commands::Command::None
-                        } else if output_pane_visible && output_pane.is_focused() && !key.modifiers.contains(KeyModifiers::ALT) {
-                            // When output pane is focused, Down moves cursor
-                            if key.modifiers.contains(KeyModifiers::CONTROL) {
-                                // Ctrl+Down: move to next paragraph
-                                let with_selection = key.modifiers.contains(KeyModifiers::SHIFT);
-                                output_pane.move_cursor_paragraph_down(with_selection);
-                            } else {
-                                let with_selection = key.modifiers.contains(KeyModifiers::SHIFT);
-                                output_pane.move_cursor_down(with_selection);
-                            }
-                            needs_redraw = true;
-                            commands::Command::None
-                        } else if key.modifiers.contains(KeyModifiers::ALT) {
-                            // Alt+Down = Decrease output pane height
-                            if output_pane_visible && output_pane_height > 3 {
-                                output_pane_height -= 1;
-                                // Update viewport to account for new bottom window height
-                                editor.update_viewport_for_cursor_with_bottom(output_pane_height);
-                                renderer.force_redraw();
-                                needs_redraw = true;
-                            }
-                            commands::Command::None
-                        } else if key.modifiers.contains(KeyModifiers::CONTROL | KeyModifiers::SHIFT) {
-                            commands::Command::SelectParagraphDown
-                        } else if key.modifiers.contains(KeyModifiers::CONTROL) {
-                            commands::Command::MoveParagraphDown
-                        } else if key.modifiers.contains(KeyModifiers::SHIFT) {
-                            commands::Command::SelectDown
-                        } else {
-                            commands::Command::MoveDown
-                        }
-                    }
-                    KeyCode::Left => {
-                        if output_pane_visible && output_pane.is_focused() {
-                            if key.modifiers.contains(KeyModifiers::CONTROL) {
-                                // Ctrl+Left: move to previous word
-                                let with_selection = key.modifiers.contains(KeyModifiers::SHIFT);
-                                output_pane.move_cursor_word_left(with_selection);
-                            } else {
-                                let with_selection = key.modifiers.contains(KeyModifiers::SHIFT);
-                                output_pane.move_cursor_left(with_selection);
-                            }
-                            needs_redraw = true;
-                            commands::Command::None
-                        } else if key.modifiers.contains(KeyModifiers::CONTROL | KeyModifiers::SHIFT) {
-                            commands::Command::SelectWordLeft
-                        } else if key.modifiers.contains(KeyModifiers::CONTROL) {
-                            commands::Command::MoveWordLeft
-                        } else if key.modifiers.contains(KeyModifiers::SHIFT) {
-                            commands::Command::SelectLeft
-                        } else {
-                            commands::Command::MoveLeft
-                        }
-                    }
-                    KeyCode::Right => {
-                        if output_pane_visible && output_pane.is_focused() {
-                            if key.modifiers.contains(KeyModifiers::CONTROL) {
-                                // Ctrl+Right: move to next word
-                                let with_selection = key.modifiers.contains(KeyModifiers::SHIFT);
-                                output_pane.move_cursor_word_right(with_selection);
-                            } else {
-                                let with_selection = key.modifiers.contains(KeyModifiers::SHIFT);
-                                output_pane.move_cursor_right(with_selection);
-                            }
-                            needs_redraw = true;
-                            commands::Command::None
-                        } else if key.modifiers.contains(KeyModifiers::CONTROL | KeyModifiers::SHIFT) {
-                            commands::Command::SelectWordRight
-                        } else if key.modifiers.contains(KeyModifiers::CONTROL) {
-                            commands::Command::MoveWordRight
-                        } else if key.modifiers.contains(KeyModifiers::SHIFT) {
-                            commands::Command::SelectRight
-                        } else {
-                            commands::Command::MoveRight
-                        }
-                    }
-                    KeyCode::Home => {
-                        if output_pane_visible && output_pane.is_focused() {
-                            let with_selection = key.modifiers.contains(KeyModifiers::SHIFT);
-                            output_pane.move_cursor_home(with_selection);
-                            needs_redraw = true;
-                            commands::Command::None
-                        } else if key.modifiers.contains(KeyModifiers::SHIFT) {
-                            commands::Command::SelectHome
-                        } else {
-                            commands::Command::MoveHome
-                        }
-                    }
-                    KeyCode::End => {
-                        if output_pane_visible && output_pane.is_focused() {
-                            let with_selection = key.modifiers.contains(KeyModifiers::SHIFT);
-                            output_pane.move_cursor_end(with_selection);
-                            needs_redraw = true;
-                            commands::Command::None
-                        } else if key.modifiers.contains(KeyModifiers::SHIFT) {
-                            commands::Command::SelectEnd
-                        } else {
-                            commands::Command::MoveEnd
-                        }
-                    }
-                    KeyCode::PageUp => {
-                        if key.modifiers.contains(KeyModifiers::SHIFT) && output_pane_visible {
-                            // Shift+PageUp = Scroll output pane up
-                            output_pane.scroll_up();
-                            needs_redraw = true;
-                            commands::Command::None
-                        } else {
-                            commands::Command::PageUp
-                        }
-                    }
-                    KeyCode::PageDown => {
-                        if key.modifiers.contains(KeyModifiers::SHIFT) && output_pane_visible {
-                            // Shift+PageDown = Scroll output pane down
-                            output_pane.scroll_down();
-                            needs_redraw = true;
-                            commands::Command::None
-                        } else {
-                            commands::Command::PageDown
-                        }
-                    }
-
-                    // Editing
-                    // Ctrl+H is often sent by terminals for Ctrl+Backspace - handle cancellation
-                    KeyCode::Char('h') if key.modifiers.contains(KeyModifiers::CONTROL) && find_replace.is_none() => {
-                        // Same cancellation logic as Ctrl+Backspace
-                        if execution_rx.is_some() {
-                            execution_rx = None;
-                            execution_start_time = None;
-
-                            if let Some(kernel_info) = executing_kernel_info.take() {
-                                match kernel_info.kernel_type {
-                                    kernel::KernelType::Direct => {
-                                        let mut new_kernel: Box<dyn kernel::Kernel> = Box::new(direct_kernel::DirectKernel::new(
-                                            kernel_info.python_path.clone(),
-                                            kernel_info.name.clone(),
-                                            kernel_info.display_name.clone(),
-                                        ));
-                                        if new_kernel.connect().is_ok() {
-                                            editor.set_kernel(new_kernel);
-                                            editor.status_message = Some(("CANCELLED - Kernel reset (all variables lost)".to_string(), true));
-                                        } else {
-                                            editor.status_message = Some(("CANCELLED - Kernel reconnection failed".to_string(), true));
-                                        }
-                                    }
-                                    _ => {
-                                        editor.status_message = Some(("Execution cancelled - please reconnect kernel".to_string(), true));
-                                    }
-                                }
-                            } else {
-                                editor.status_message = Some(("Execution cancelled".to_string(), true));
-                            }
-
-                            renderer.force_redraw();
-                            needs_redraw = true;
-                        } else {
-                            editor.status_message = Some(("No execution to cancel".to_string(), false));
-                        }
-                        commands::Command::None
-                    }
-                    KeyCode::Char(c) => commands::Command::InsertChar(c),
-                    KeyCode::Enter => {
-                        // Ctrl+Enter = Execute cell (primary binding)
-                        if key.modifiers.contains(KeyModifiers::CONTROL) {
-                            // Check if already executing
-                            if execution_rx.is_some() {
-                                editor.status_message = Some(("Already executing (Ctrl+Backspace to cancel - WARNING: resets kernel)".to_string(), true));
-                                needs_redraw = true;
-                            } else {
-                                // Start background execution
-                                if let Some((rx, kernel_info)) = spawn_background_execution(editor) {
-                                    execution_rx = Some(rx);
-                                    execution_start_time = Some(std::time::Instant::now());
-                                    executing_kernel_info = Some(kernel_info);
-                                    editor.status_message = Some(("Executing...".to_string(), false));
-                                    needs_redraw = true;
-                                }
-                            }
-                            commands::Command::None
-                        } else {
-                            commands::Command::InsertNewline
-                        }
-                    }
-                    KeyCode::Tab => {
-                        if autocomplete.is_visible() && !key.modifiers.contains(KeyModifiers::SHIFT) {
-                            // Accept autocomplete suggestion
-                            if let Some(suggestion) = autocomplete.get_selected() {
-                                let prefix = editor.get_word_at_cursor();
-                                // Delete the prefix and insert the full suggestion
-                                for _ in 0..prefix.len() {
-                                    editor.execute(commands::Command::Backspace)?;
-                                }
-                                for ch in suggestion.chars() {
-                                    editor.execute(commands::Command::InsertChar(ch))?;
-                                }
-                                autocomplete.hide();
-                                renderer.force_redraw(); // Force full redraw to clear autocomplete artifacts
-                                suppress_autocomplete_once = true; // Don't show autocomplete on next key
-                                needs_redraw = true;
-                            }
-                            commands::Command::None
-                        } else if key.modifiers.contains(KeyModifiers::SHIFT) {
-                            // Shift+Tab = dedent
-                            commands::Command::Dedent
-                        } else if editor.selection().is_some() {
-                            // Tab with selection = indent all selected lines
-                            commands::Command::Indent
-                        } else {
-                            // Tab without selection = insert 4 spaces
-                            commands::Command::InsertTab
-                        }
-                    }
-                    KeyCode::BackTab => {
-                        // Some terminals send BackTab for Shift+Tab
-                        commands::Command::Dedent
-                    }
-                    KeyCode::Backspace => commands::Command::Backspace,
-                    KeyCode::Delete => commands::Command::Delete,
-                    
-                    _ => commands::Command::None,
+                    // Replaced by the palette's pick above
+                    Some(actions::Action::CommandPalette) => commands::Command::None,
+
+                    // Unbound keys type, whatever modifiers come with them
+                    None => match key.code {
+                        KeyCode::Char(c) => commands::Command::InsertChar(c),
+                        KeyCode::Enter => commands::Command::InsertNewline,
+                        KeyCode::Backspace => commands::Command::Backspace,
+                        KeyCode::Delete => commands::Command::Delete,
+                        _ => commands::Command::None,
+                    },
                 };
                 
                 
@@ -1151,8 +1745,10 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                         renderer.draw(editor)?;
                     }
                     commands::Command::FindReplace => {
-                        // Open find/replace window
-                        find_replace = Some(find_replace::FindReplace::new());
+                        // Open find/replace window; a selection over several lines is searched in
+                        let mut fr = find_replace::FindReplace::new();
+                        fr.set_scope(editor.selection(), editor.selection_is_multiline());
+                        find_replace = Some(fr);
                     }
                     commands::Command::None => {
                         // No command - don't override needs_redraw flag
@@ -1166,15 +1762,8 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
 
                         // All other commands are handled normally
                         editor.execute(cmd)?;
-                        // Update viewport with correct bottom window height after movement commands
-                        let bottom_height = if find_replace.is_some() {
-                            3
-                        } else if output_pane_visible {
-                            output_pane_height
-                        } else {
-                            0
-                        };
-                        editor.update_viewport_for_cursor_with_bottom(bottom_height);
+                        // Update viewport for the editor's area of the layout after movement commands
+                        editor.update_viewport_for_area(layout.areas(find_replace.is_some()).editor);
 
                         // Apply autocomplete updates based on command type
                         if suppress_autocomplete_once {
@@ -1200,8 +1789,12 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                     }
                 }
             }
+            Event::FocusLost => {
+                focus_lost = true;
+            }
             Event::Resize(_, _) => {
-                // Terminal was resized, force redraw
+                // Terminal was resized, refit the editor area and force redraw
+                editor.update_viewport_for_area(layout.areas(find_replace.is_some()).editor);
                 renderer.force_redraw();
                 needs_redraw = true;
             }
@@ -1212,75 +1805,189 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
     }
 }
 
-fn spawn_background_execution(
-    editor: &mut editor::Editor,
-) -> Option<(std::sync::mpsc::Receiver<(Box<dyn kernel::Kernel>, Vec<(usize, usize, String, bool, f64)>, Vec<kernel::CompletionItem>)>, kernel::KernelInfo)> {
-    // Extract kernel from editor (temporarily)
-    let mut kernel = editor.take_kernel()?;
+/// Search again for the find bar's query, showing the matches and selecting the current one
+fn search_again(editor: &mut editor::Editor, fr: &mut find_replace::FindReplace) {
+    let matches = match fr.search() {
+        Some(search) => editor.find_all(&search, fr.scope()),
+        None => Vec::new(),
+    };
+    fr.update_matches(matches.clone());
+    editor.set_find_matches(matches, fr.get_current_match_index());
+    if let Some((start, end)) = fr.current_match_position() {
+        editor.select_range(start, end);
+    } else {
+        editor.selection_start = None;
+    }
+}
 
-    // Store kernel info for potential recreation
-    let kernel_info = kernel.info().clone();
+/// Run one of the find bar's commands: step through the matches, replace them or change how it searches
+fn run_find_command(editor: &mut editor::Editor, fr: &mut find_replace::FindReplace, cmd: &commands::Command) {
+    match cmd {
+        commands::Command::FindNext => {
+            if !fr.is_empty() {
+                if let Some((start, end)) = fr.next_match() {
+                    editor.select_range(start, end);
+                    // Update current match index for highlighting
+                    editor.set_find_matches(fr.get_all_matches().to_vec(), fr.get_current_match_index());
+                }
+            }
+        }
+        commands::Command::FindPrev => {
+            if !fr.is_empty() {
+                if let Some((start, end)) = fr.prev_match() {
+                    editor.select_range(start, end);
+                    // Update current match index for highlighting
+                    editor.set_find_matches(fr.get_all_matches().to_vec(), fr.get_current_match_index());
+                }
+            }
+        }
+        commands::Command::Replace => {
+            // Replace the current match if it's what is selected, then move on to the next
+            let (Some(search), Some((start, end))) = (fr.search(), fr.current_match_position()) else {
+                return;
+            };
+            if editor.selection() == Some((start, end)) {
+                let replacement = editor.find_replacement(&search, start, fr.replace_text());
+                editor.replace_selection(&replacement);
+                fr.adjust_scope(start, end - start, replacement.len());
+            }
+            search_again(editor, fr);
+        }
+        commands::Command::ReplaceAll => {
+            let Some(search) = fr.search() else {
+                return;
+            };
+            let matches = editor.find_all(&search, fr.scope());
+            // Work out every replacement before changing anything, so groups see the original text
+            let replacements: Vec<String> =
+                matches.iter().map(|&(start, _)| editor.find_replacement(&search, start, fr.replace_text())).collect();
+
+            // Replace all from last to first to maintain positions
+            for (&(start, end), replacement) in matches.iter().zip(&replacements).rev() {
+                editor.replace_at(start, end, replacement);
+                fr.adjust_scope(start, end - start, replacement.len());
+            }
 
-    // Get selection or current cell position
-    let selection = editor.selection();
-    let cursor_offset = editor.cursor();  // Get byte offset, not line/col
-
-    // Clone cell data we need
-    editor.update_cells();
-    let cells: Vec<(usize, usize, String)> = {
-        use crate::cell::{get_cell_at_position, get_cell_content};
-
-        // Find cells to execute (same logic as execute_selected_cells_with_output)
-        let cells_to_execute: Vec<usize> = if let Some((sel_start, sel_end)) = selection {
-            editor.get_cells_ref().iter().enumerate()
-                .filter(|(_, cell)| cell.start < sel_end && cell.end > sel_start)
-                .map(|(idx, _)| idx)
-                .collect()
-        } else {
-            if let Some(cell_idx) = get_cell_at_position(editor.get_cells_ref(), cursor_offset) {
-                vec![cell_idx]
-            } else {
-                vec![]
+            // Clear matches and update
+            fr.update_matches(Vec::new());
+            editor.clear_find_matches();
+            editor.selection_start = None;
+            editor.status_message = Some((format!("Replaced {} matches", matches.len()), false));
+        }
+        commands::Command::ToggleFindRegex => {
+            fr.toggle_regex();
+            search_again(editor, fr);
+        }
+        commands::Command::ToggleFindMatchCase => {
+            fr.toggle_match_case();
+            search_again(editor, fr);
+        }
+        commands::Command::ToggleFindWholeWord => {
+            fr.toggle_whole_word();
+            search_again(editor, fr);
+        }
+        commands::Command::ToggleFindInSelection => {
+            if !fr.toggle_in_selection(editor.selection()) {
+                editor.status_message = Some(("Select the text to search in first".to_string(), true));
             }
-        };
+            search_again(editor, fr);
+        }
+        _ => {}
+    }
+}
 
-        // Extract cell contents
-        cells_to_execute.iter().map(|&idx| {
-            let cell = &editor.get_cells_ref()[idx];
-            let code = get_cell_content(editor.buffer_rope(), cell);
-            let cell_number = idx + 1;
-            (idx, cell_number, code)
-        }).collect()
-    };
+/// Channel the background execution thread reports back on:
+/// (kernel, Vec<(execution_count, cell_number, output_text, is_error, elapsed_secs)>,
+///  Vec<(cell_index, executed_hash, elapsed_secs, result)>, completions)
+type ExecutionReceiver = std::sync::mpsc::Receiver<(
+    Box<dyn kernel::Kernel>,
+    Vec<(usize, usize, String, bool, f64)>,
+    Vec<(usize, u64, f64, kernel::ExecutionResult)>,
+    Vec<kernel::CompletionItem>,
+)>;
+
+/// Ask before running cells tagged `slow` - returns the confirmation prompt if any are included
+fn slow_cells_prompt(editor: &editor::Editor, cell_indices: &[usize]) -> Option<String> {
+    let slow = editor.slow_cell_numbers(cell_indices);
+    if slow.is_empty() {
+        return None;
+    }
+    let numbers: Vec<String> = slow.iter().map(|n| n.to_string()).collect();
+    let label = if slow.len() == 1 { "Cell" } else { "Cells" };
+    Some(format!("{} {} tagged slow - run anyway? (y/n)", label, numbers.join(", ")))
+}
+
+/// Start running cells in the background, asking first if any are tagged `slow`
+/// Returns None (with a status message) if nothing was started
+fn request_run(
+    editor: &mut editor::Editor,
+    cell_indices: Vec<usize>,
+    pending_run: &mut Option<Vec<usize>>,
+) -> Option<(ExecutionReceiver, kernel::KernelInfo)> {
+    if cell_indices.is_empty() {
+        return None;
+    }
+
+    if let Some(prompt) = slow_cells_prompt(editor, &cell_indices) {
+        editor.status_message = Some((prompt, false));
+        *pending_run = Some(cell_indices);
+        return None;
+    }
 
+    let started = spawn_background_execution(editor, &cell_indices);
+    if started.is_none() {
+        editor.status_message = Some(("No kernel connected. Press Ctrl+K to select a kernel.".to_string(), true));
+    }
+    started
+}
+
+fn spawn_background_execution(
+    editor: &mut editor::Editor,
+    cell_indices: &[usize],
+) -> Option<(ExecutionReceiver, kernel::KernelInfo)> {
+    // Clone cell data we need: (cell_number, code, raises)
+    let cells = editor.cell_batch(cell_indices);
     if cells.is_empty() {
-        editor.set_kernel(kernel);
         return None;
     }
 
+    // Extract kernel from editor (temporarily)
+    let mut kernel = editor.take_kernel()?;
+
+    // Store kernel info for potential recreation
+    let kernel_info = kernel.info().clone();
+
+    // Track progress so delimiter lines can show which cell is running and which are queued
+    let finished = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let started: Vec<(usize, u64)> = cells.iter().map(|(cell_number, code, _)| (cell_number - 1, crate::cell::source_hash(code))).collect();
+    editor.start_running_cells(started, finished.clone());
+
     // Spawn background thread
     let (tx, rx) = std::sync::mpsc::channel();
     std::thread::spawn(move || {
         let mut results = Vec::new();
+        let mut runs = Vec::new();
         let mut all_completions = Vec::new();
 
-        for (_cell_idx, cell_number, code) in cells {
+        for (cell_number, code, raises) in cells {
             let start_time = std::time::Instant::now();
 
             match kernel.execute(&code) {
-                Ok(result) => {
+                Ok(mut result) => {
                     let elapsed = start_time.elapsed().as_secs_f64();
                     let execution_count = result.execution_count.unwrap_or(0);
                     let output_text = crate::cell::format_output(&result);
                     let is_error = !result.success;
 
                     // Collect completions from this execution
-                    all_completions.extend(result.completions);
+                    all_completions.append(&mut result.completions);
 
                     results.push((execution_count, cell_number, output_text, is_error, elapsed));
+                    runs.push((cell_number - 1, crate::cell::source_hash(&code), elapsed, result));
+                    finished.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 
-                    // Stop execution if this cell had an error
-                    if is_error {
+                    // Stop execution if this cell had an error, unless it's tagged `raises`
+                    if is_error && !raises {
                         break;
                     }
                 }
@@ -1293,8 +2000,8 @@ fn spawn_background_execution(
             }
         }
 
-        // Send back kernel, results, and completions
-        let _ = tx.send((kernel, results, all_completions));
+        // Send back kernel, results, executed cells, and completions
+        let _ = tx.send((kernel, results, runs, all_completions));
     });
 
     Some((rx, kernel_info))
@@ -2,13 +2,28 @@ use crossterm::{
     cursor::{Hide, MoveTo, Show},
     event::{self, Event, KeyCode, KeyModifiers},
     execute,
-    style::{Attribute, Color, Print, SetAttribute, SetBackgroundColor, SetForegroundColor, ResetColor},
+    style::{Attribute, Print, SetAttribute, SetBackgroundColor, SetForegroundColor, ResetColor},
     terminal,
 };
 use std::io::{self, Write};
 
+use crate::theme::{self, Element, Style, Theme};
+
+const DISCARD_LABEL: &str = " Don't Save ";
+const CANCEL_LABEL: &str = " Cancel";
+
 pub struct ExitPrompt {
     selected_option: ExitOption,
+    save_label: &'static str, // Text of the save button
+}
+
+/// A button in its own colour when selected, otherwise as plain dialog text
+fn button_style(theme: &Theme, selected: bool, element: Element) -> Style {
+    if selected {
+        theme[element]
+    } else {
+        theme[Element::DialogInactive].over(theme[Element::Dialog])
+    }
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -22,26 +37,41 @@ impl ExitPrompt {
     pub fn new() -> Self {
         Self {
             selected_option: ExitOption::Save,
+            save_label: " Save & Exit ",
+        }
+    }
+
+    /// Prompt for closing one buffer rather than quitting
+    pub fn for_close() -> Self {
+        Self {
+            selected_option: ExitOption::Save,
+            save_label: " Save & Close ",
         }
     }
+
+    /// Width of the three buttons and the gaps between them
+    fn buttons_width(&self) -> usize {
+        self.save_label.len() + 2 + DISCARD_LABEL.len() + 2 + CANCEL_LABEL.len()
+    }
     
     /// Draw only the options line (for updates without flickering)
     fn draw_options_line(&self, stdout: &mut io::Stdout, prompt_x: usize, prompt_y: usize, prompt_width: usize) -> io::Result<()> {
+        let theme = theme::current();
         let y = 5; // Options are on line 5
         
         execute!(
             stdout,
             MoveTo(prompt_x as u16, (prompt_y + y) as u16),
-            SetBackgroundColor(Color::Rgb { r: 40, g: 40, b: 45 }),
-            SetForegroundColor(Color::Rgb { r: 200, g: 200, b: 205 }),
+            SetBackgroundColor(theme[Element::Dialog].background()),
+            SetForegroundColor(theme[Element::Dialog].foreground()),
         )?;
         
         write!(stdout, "│ ")?;
         
         // Calculate button positions for centering
-        let buttons_width = 13 + 2 + 12 + 2 + 8; // "Save & Exit" + spaces + "Don't Save" + spaces + "Cancel"
-        let available_width = prompt_width - 2; // 2 for borders
-        let padding_left = (available_width - buttons_width) / 2;
+        let buttons_width = self.buttons_width();
+        let available_width = prompt_width.saturating_sub(2); // 2 for borders
+        let padding_left = available_width.saturating_sub(buttons_width) / 2;
         
         // Add left padding
         for _ in 0..padding_left {
@@ -52,18 +82,10 @@ impl ExitPrompt {
         let save_selected = self.selected_option == ExitOption::Save;
         execute!(
             stdout,
-            SetBackgroundColor(if save_selected {
-                Color::Rgb { r: 60, g: 120, b: 60 }
-            } else {
-                Color::Rgb { r: 40, g: 40, b: 45 }
-            }),
-            SetForegroundColor(if save_selected {
-                Color::White
-            } else {
-                Color::Rgb { r: 150, g: 150, b: 155 }
-            }),
-            Print(" Save & Exit "),
-            SetBackgroundColor(Color::Rgb { r: 40, g: 40, b: 45 }),
+            SetBackgroundColor(button_style(&theme, save_selected, Element::ButtonSave).background()),
+            SetForegroundColor(button_style(&theme, save_selected, Element::ButtonSave).foreground()),
+            Print(self.save_label),
+            SetBackgroundColor(theme[Element::Dialog].background()),
         )?;
         
         write!(stdout, "  ")?;
@@ -72,18 +94,10 @@ impl ExitPrompt {
         let exit_selected = self.selected_option == ExitOption::ExitWithoutSaving;
         execute!(
             stdout,
-            SetBackgroundColor(if exit_selected {
-                Color::Rgb { r: 120, g: 60, b: 60 }
-            } else {
-                Color::Rgb { r: 40, g: 40, b: 45 }
-            }),
-            SetForegroundColor(if exit_selected {
-                Color::White
-            } else {
-                Color::Rgb { r: 150, g: 150, b: 155 }
-            }),
-            Print(" Don't Save "),
-            SetBackgroundColor(Color::Rgb { r: 40, g: 40, b: 45 }),
+            SetBackgroundColor(button_style(&theme, exit_selected, Element::ButtonDiscard).background()),
+            SetForegroundColor(button_style(&theme, exit_selected, Element::ButtonDiscard).foreground()),
+            Print(DISCARD_LABEL),
+            SetBackgroundColor(theme[Element::Dialog].background()),
         )?;
         
         write!(stdout, "  ")?;
@@ -92,24 +106,16 @@ impl ExitPrompt {
         let cancel_selected = self.selected_option == ExitOption::Cancel;
         execute!(
             stdout,
-            SetBackgroundColor(if cancel_selected {
-                Color::Rgb { r: 60, g: 60, b: 120 }
-            } else {
-                Color::Rgb { r: 40, g: 40, b: 45 }
-            }),
-            SetForegroundColor(if cancel_selected {
-                Color::White
-            } else {
-                Color::Rgb { r: 150, g: 150, b: 155 }
-            }),
-            Print(" Cancel"),  // Fixed: removed trailing space
-            SetBackgroundColor(Color::Rgb { r: 40, g: 40, b: 45 }),
-            SetForegroundColor(Color::Rgb { r: 200, g: 200, b: 205 }),
+            SetBackgroundColor(button_style(&theme, cancel_selected, Element::ButtonCancel).background()),
+            SetForegroundColor(button_style(&theme, cancel_selected, Element::ButtonCancel).foreground()),
+            Print(CANCEL_LABEL),
+            SetBackgroundColor(theme[Element::Dialog].background()),
+            SetForegroundColor(theme[Element::Dialog].foreground()),
         )?;
         
         // Fill rest of line to border
         let used = 1 + padding_left + buttons_width; // 1 for initial space after border
-        let remaining = prompt_width - 1 - used; // -1 for closing border
+        let remaining = prompt_width.saturating_sub(1 + used); // -1 for closing border
         for _ in 0..remaining {
             write!(stdout, " ")?;
         }
@@ -126,7 +132,8 @@ impl ExitPrompt {
     }
     
     /// Draw the exit prompt as a floating window
-    pub fn draw(&self, stdout: &mut io::Stdout, filename: &str) -> io::Result<()> {
+    pub fn draw(&self, stdout: &mut io::Stdout, filenames: &[&str]) -> io::Result<()> {
+        let theme = theme::current();
         let (width, height) = terminal::size()?;
         
         // Calculate prompt dimensions and position
@@ -140,7 +147,7 @@ impl ExitPrompt {
             execute!(
                 stdout,
                 MoveTo((prompt_x + 2) as u16, (prompt_y + y) as u16),
-                SetBackgroundColor(Color::Black),
+                SetBackgroundColor(theme[Element::DialogShadow].background()),
                 Print(" ".repeat(prompt_width))
             )?;
         }
@@ -150,8 +157,8 @@ impl ExitPrompt {
             execute!(
                 stdout,
                 MoveTo(prompt_x as u16, (prompt_y + y) as u16),
-                SetBackgroundColor(Color::Rgb { r: 40, g: 40, b: 45 }),
-                SetForegroundColor(Color::Rgb { r: 200, g: 200, b: 205 }),
+                SetBackgroundColor(theme[Element::Dialog].background()),
+                SetForegroundColor(theme[Element::Dialog].foreground()),
             )?;
             
             if y == 0 {
@@ -174,11 +181,11 @@ impl ExitPrompt {
                 execute!(
                     stdout,
                     SetAttribute(Attribute::Bold),
-                    SetForegroundColor(Color::White),
+                    SetForegroundColor(theme[Element::DialogTitle].foreground()),
                     Print("Save changes?"),
                     SetAttribute(Attribute::Reset),
-                    SetBackgroundColor(Color::Rgb { r: 40, g: 40, b: 45 }),
-                    SetForegroundColor(Color::Rgb { r: 200, g: 200, b: 205 }),
+                    SetBackgroundColor(theme[Element::Dialog].background()),
+                    SetForegroundColor(theme[Element::Dialog].foreground()),
                 )?;
                 let title_padding = prompt_width - 2 - 14; // 2 for border+space, 14 for "Save changes?"
                 for _ in 0..title_padding {
@@ -188,7 +195,10 @@ impl ExitPrompt {
             } else if y == 3 {
                 // File info line
                 write!(stdout, "│ ")?;
-                let info = format!("{} has unsaved changes", filename);
+                let info = match filenames {
+                    [filename] => format!("{} has unsaved changes", filename),
+                    _ => format!("{} files have unsaved changes: {}", filenames.len(), filenames.join(", ")),
+                };
                 let truncated_info = if info.len() > prompt_width - 4 {
                     format!("{}...", &info[..prompt_width - 7])
                 } else {
@@ -196,9 +206,9 @@ impl ExitPrompt {
                 };
                 execute!(
                     stdout,
-                    SetForegroundColor(Color::Rgb { r: 180, g: 180, b: 185 }),
+                    SetForegroundColor(theme[Element::Dialog].foreground()),
                     Print(&truncated_info),
-                    SetForegroundColor(Color::Rgb { r: 200, g: 200, b: 205 }),
+                    SetForegroundColor(theme[Element::Dialog].foreground()),
                 )?;
                 let info_padding = prompt_width - 3 - truncated_info.len(); // 2 for "│ ", 1 for closing "│"
                 for _ in 0..info_padding {
@@ -211,9 +221,9 @@ impl ExitPrompt {
                 write!(stdout, "│ ")?;
                 
                 // Calculate button positions for centering
-                let buttons_width = 13 + 2 + 12 + 2 + 8; // "Save & Exit" + spaces + "Don't Save" + spaces + "Cancel"
-                let available_width = prompt_width - 2; // 2 for borders
-                let padding_left = (available_width - buttons_width) / 2;
+                let buttons_width = self.buttons_width();
+                let available_width = prompt_width.saturating_sub(2); // 2 for borders
+                let padding_left = available_width.saturating_sub(buttons_width) / 2;
                 
                 // Add left padding
                 for _ in 0..padding_left {
@@ -224,18 +234,10 @@ impl ExitPrompt {
                 let save_selected = self.selected_option == ExitOption::Save;
                 execute!(
                     stdout,
-                    SetBackgroundColor(if save_selected {
-                        Color::Rgb { r: 60, g: 120, b: 60 }
-                    } else {
-                        Color::Rgb { r: 40, g: 40, b: 45 }
-                    }),
-                    SetForegroundColor(if save_selected {
-                        Color::White
-                    } else {
-                        Color::Rgb { r: 150, g: 150, b: 155 }
-                    }),
-                    Print(" Save & Exit "),
-                    SetBackgroundColor(Color::Rgb { r: 40, g: 40, b: 45 }),
+                    SetBackgroundColor(button_style(&theme, save_selected, Element::ButtonSave).background()),
+                    SetForegroundColor(button_style(&theme, save_selected, Element::ButtonSave).foreground()),
+                    Print(self.save_label),
+                    SetBackgroundColor(theme[Element::Dialog].background()),
                 )?;
                 
                 write!(stdout, "  ")?;
@@ -244,18 +246,10 @@ impl ExitPrompt {
                 let exit_selected = self.selected_option == ExitOption::ExitWithoutSaving;
                 execute!(
                     stdout,
-                    SetBackgroundColor(if exit_selected {
-                        Color::Rgb { r: 120, g: 60, b: 60 }
-                    } else {
-                        Color::Rgb { r: 40, g: 40, b: 45 }
-                    }),
-                    SetForegroundColor(if exit_selected {
-                        Color::White
-                    } else {
-                        Color::Rgb { r: 150, g: 150, b: 155 }
-                    }),
-                    Print(" Don't Save "),
-                    SetBackgroundColor(Color::Rgb { r: 40, g: 40, b: 45 }),
+                    SetBackgroundColor(button_style(&theme, exit_selected, Element::ButtonDiscard).background()),
+                    SetForegroundColor(button_style(&theme, exit_selected, Element::ButtonDiscard).foreground()),
+                    Print(DISCARD_LABEL),
+                    SetBackgroundColor(theme[Element::Dialog].background()),
                 )?;
                 
                 write!(stdout, "  ")?;
@@ -264,24 +258,16 @@ impl ExitPrompt {
                 let cancel_selected = self.selected_option == ExitOption::Cancel;
                 execute!(
                     stdout,
-                    SetBackgroundColor(if cancel_selected {
-                        Color::Rgb { r: 60, g: 60, b: 120 }
-                    } else {
-                        Color::Rgb { r: 40, g: 40, b: 45 }
-                    }),
-                    SetForegroundColor(if cancel_selected {
-                        Color::White
-                    } else {
-                        Color::Rgb { r: 150, g: 150, b: 155 }
-                    }),
-                    Print(" Cancel"),  // Fixed: removed trailing space
-                    SetBackgroundColor(Color::Rgb { r: 40, g: 40, b: 45 }),
-                    SetForegroundColor(Color::Rgb { r: 200, g: 200, b: 205 }),
+                    SetBackgroundColor(button_style(&theme, cancel_selected, Element::ButtonCancel).background()),
+                    SetForegroundColor(button_style(&theme, cancel_selected, Element::ButtonCancel).foreground()),
+                    Print(CANCEL_LABEL),
+                    SetBackgroundColor(theme[Element::Dialog].background()),
+                    SetForegroundColor(theme[Element::Dialog].foreground()),
                 )?;
                 
                 // Fill rest of line to border
                 let used = 1 + padding_left + buttons_width; // 1 for initial space after border
-                let remaining = prompt_width - 1 - used; // -1 for closing border
+                let remaining = prompt_width.saturating_sub(1 + used); // -1 for closing border
                 for _ in 0..remaining {
                     write!(stdout, " ")?;
                 }
@@ -306,10 +292,10 @@ impl ExitPrompt {
         Ok(())
     }
     
-    /// Handle keyboard input and return the selected option
-    pub fn run(&mut self, stdout: &mut io::Stdout, filename: &str) -> io::Result<ExitOption> {
+    /// Handle keyboard input and return the selected option for the files with unsaved changes
+    pub fn run(&mut self, stdout: &mut io::Stdout, filenames: &[&str]) -> io::Result<ExitOption> {
         // Draw the full window once
-        self.draw(stdout, filename)?;
+        self.draw(stdout, filenames)?;
         
         // Store dimensions for partial redraws
         let (width, height) = terminal::size()?;
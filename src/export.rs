@@ -0,0 +1,614 @@
+use crate::ansi::parse_output;
+use crate::cell::CellType;
+use crate::kernel::{ExecutionOutput, ExecutionResult};
+use crate::syntax::{Language, SyntaxHighlighter, SyntaxState};
+use serde_json::{json, Value};
+use std::io;
+use std::path::Path;
+
+/// Document formats for sharing results with people who don't use sage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Html,
+    Markdown,
+}
+
+impl ReportFormat {
+    /// Parse a format name as given to `--export`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "html" | "htm" => Some(ReportFormat::Html),
+            "md" | "markdown" => Some(ReportFormat::Markdown),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ReportFormat::Html => "html",
+            ReportFormat::Markdown => "md",
+        }
+    }
+}
+
+/// Path of the report for a notebook: the same file name with the format's extension
+pub fn report_path(notebook_path: &str, format: ReportFormat) -> String {
+    Path::new(notebook_path)
+        .with_extension(format.extension())
+        .to_string_lossy()
+        .to_string()
+}
+
+/// A cell as it was run, used to write the executed notebook
+pub struct ExecutedCell {
+    /// Delimiter line (None for a file without delimiters)
+    pub header: Option<String>,
+    /// Cell source without the delimiter line
+    pub source: String,
+    pub cell_type: CellType,
+    /// Tags carried over to the notebook metadata
+    pub tags: Vec<String>,
+    /// Execution result (None if the cell was not run)
+    pub result: Option<ExecutionResult>,
+}
+
+/// Lowercased extension of a path, empty if it has none
+fn extension_of(path: &str) -> String {
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Check an output path before anything runs: the writer has to know its extension, and it
+/// has to be the extension of `format` when a report format was asked for
+pub fn check_output_path(path: &str, format: Option<ReportFormat>) -> Result<(), String> {
+    let extension = extension_of(path);
+    match format {
+        Some(format) if ReportFormat::from_name(&extension) != Some(format) => {
+            Err(format!("Output '{}' doesn't match the export format (use a .{} file)", path, format.extension()))
+        }
+        None if !matches!(extension.as_str(), "ipynb" | "py" | "html" | "htm" | "md" | "markdown") => {
+            Err(format!("Unsupported output format '{}' (use .py, .ipynb, .html or .md)", path))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Write the executed notebook as a report in `format`, or in the format the file
+/// extension names when there's none
+pub fn write_executed_notebook(path: &str, cells: &[ExecutedCell], format: Option<ReportFormat>) -> io::Result<()> {
+    check_output_path(path, format).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    let title = Path::new(path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let content = match (format, extension_of(path).as_str()) {
+        (Some(ReportFormat::Html), _) | (None, "html" | "htm") => to_html(&title, cells),
+        (Some(ReportFormat::Markdown), _) | (None, "md" | "markdown") => to_markdown(cells),
+        (None, "ipynb") => to_ipynb(cells)?,
+        _ => to_py(cells),
+    };
+
+    std::fs::write(path, content)
+}
+
+/// Plain text of an execution result, without ANSI styling
+fn plain_output(result: &ExecutionResult) -> String {
+    let mut output = String::new();
+
+    for exec_output in &result.outputs {
+        let text = match exec_output {
+            ExecutionOutput::Stdout(text) | ExecutionOutput::Result(text) => text.clone(),
+            ExecutionOutput::Stderr(text) => format!("stderr: {}", text),
+            ExecutionOutput::Error { ename, evalue, traceback } => {
+                let mut text = format!("{}: {}\n", ename, evalue);
+                for line in traceback {
+                    if !line.trim().is_empty() {
+                        text.push_str(line);
+                        text.push('\n');
+                    }
+                }
+                text
+            }
+            ExecutionOutput::Display { data, .. } => data.clone(),
+        };
+
+        if !text.is_empty() {
+            output.push_str(&text);
+            if !text.ends_with('\n') {
+                output.push('\n');
+            }
+        }
+    }
+
+    output
+}
+
+/// Executed notebook as a cell-delimited Python file, outputs as comments under each cell
+fn to_py(cells: &[ExecutedCell]) -> String {
+    let mut out = String::new();
+
+    for cell in cells {
+        if let Some(header) = &cell.header {
+            out.push_str(header);
+            out.push('\n');
+        }
+
+        out.push_str(&cell.source);
+        if !cell.source.is_empty() && !cell.source.ends_with('\n') {
+            out.push('\n');
+        }
+
+        if let Some(result) = &cell.result {
+            let text = plain_output(result);
+            if !text.is_empty() {
+                out.push_str(&format!("# Out[{}]:\n", result.execution_count.unwrap_or(0)));
+                for line in text.lines() {
+                    if line.is_empty() {
+                        out.push_str("#\n");
+                    } else {
+                        out.push_str(&format!("# {}\n", line));
+                    }
+                }
+                out.push('\n');
+            }
+        }
+    }
+
+    out
+}
+
+/// Split text into the line list nbformat uses for multi-line strings
+fn nb_lines(text: &str) -> Value {
+    Value::Array(
+        text.split_inclusive('\n')
+            .map(|line| Value::String(line.to_string()))
+            .collect(),
+    )
+}
+
+/// Convert an execution result into nbformat output objects
+fn nb_outputs(result: &ExecutionResult) -> Vec<Value> {
+    result.outputs.iter().map(|exec_output| match exec_output {
+        ExecutionOutput::Stdout(text) => json!({
+            "output_type": "stream",
+            "name": "stdout",
+            "text": nb_lines(text),
+        }),
+        ExecutionOutput::Stderr(text) => json!({
+            "output_type": "stream",
+            "name": "stderr",
+            "text": nb_lines(text),
+        }),
+        ExecutionOutput::Result(text) => json!({
+            "output_type": "execute_result",
+            "execution_count": result.execution_count,
+            "data": { "text/plain": nb_lines(text) },
+            "metadata": {},
+        }),
+        ExecutionOutput::Error { ename, evalue, traceback } => json!({
+            "output_type": "error",
+            "ename": ename,
+            "evalue": evalue,
+            "traceback": traceback,
+        }),
+        ExecutionOutput::Display { data, mime_type } => json!({
+            "output_type": "display_data",
+            "data": { (mime_type.as_str()): nb_lines(data) },
+            "metadata": {},
+        }),
+    }).collect()
+}
+
+/// Executed notebook in Jupyter's nbformat 4
+fn to_ipynb(cells: &[ExecutedCell]) -> io::Result<String> {
+    let nb_cells: Vec<Value> = cells.iter().map(|cell| {
+        match cell.cell_type {
+            CellType::Markdown => json!({
+                "cell_type": "markdown",
+                "metadata": { "tags": cell.tags },
+                "source": nb_lines(&cell.source),
+            }),
+            CellType::Code => json!({
+                "cell_type": "code",
+                "execution_count": cell.result.as_ref().and_then(|r| r.execution_count),
+                "metadata": { "tags": cell.tags },
+                "outputs": cell.result.as_ref().map(nb_outputs).unwrap_or_default(),
+                "source": nb_lines(cell.source.trim_end_matches('\n')),
+            }),
+        }
+    }).collect();
+
+    let notebook = json!({
+        "cells": nb_cells,
+        "metadata": {
+            "kernelspec": {
+                "display_name": "Python 3",
+                "language": "python",
+                "name": "python3",
+            },
+            "language_info": { "name": "python" },
+        },
+        "nbformat": 4,
+        "nbformat_minor": 4,
+    });
+
+    serde_json::to_string_pretty(&notebook)
+        .map_err(io::Error::other)
+}
+
+/// Text of one output as it would appear on a terminal (escape codes and overwritten text removed)
+fn output_text(exec_output: &ExecutionOutput) -> String {
+    let raw = match exec_output {
+        ExecutionOutput::Stdout(text) | ExecutionOutput::Stderr(text) | ExecutionOutput::Result(text) => text.clone(),
+        ExecutionOutput::Error { ename, evalue, traceback } => {
+            let lines: Vec<&String> = traceback.iter().filter(|line| !line.trim().is_empty()).collect();
+            let mut text: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+            // Python tracebacks already end with the exception line
+            if !lines.last().is_some_and(|last| parse_output(last).iter().any(|l| l.text.starts_with(ename.as_str()))) {
+                text.push_str(&format!("{}: {}", ename, evalue));
+            }
+            text
+        }
+        ExecutionOutput::Display { data, .. } => data.clone(),
+    };
+    let lines: Vec<String> = parse_output(&raw).into_iter().map(|line| line.text).collect();
+    lines.join("\n")
+}
+
+/// Image MIME types embedded as data URIs (the data is already base64, as Jupyter sends it)
+fn is_embedded_image(mime_type: &str) -> bool {
+    matches!(mime_type, "image/png" | "image/jpeg" | "image/gif")
+}
+
+/// Escape text for HTML element content and attribute values
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// CSS class of a highlighted span (None for plain text)
+fn syntax_class(state: SyntaxState) -> Option<&'static str> {
+    match state {
+        SyntaxState::StringDouble | SyntaxState::StringSingle | SyntaxState::StringTriple | SyntaxState::StringTripleSingle => Some("s"),
+        SyntaxState::LineComment | SyntaxState::BlockComment => Some("c"),
+        SyntaxState::Keyword => Some("k"),
+        SyntaxState::Type => Some("t"),
+        SyntaxState::Function => Some("f"),
+        SyntaxState::Number => Some("n"),
+        SyntaxState::Operator => Some("o"),
+        SyntaxState::MacroOrDecorator => Some("d"),
+        SyntaxState::Punctuation | SyntaxState::Normal => None,
+    }
+}
+
+/// Python source as HTML, highlighted with the editor's own spans
+fn highlight_python(source: &str) -> String {
+    let mut highlighter = SyntaxHighlighter::new();
+    highlighter.set_language(Language::Python);
+
+    let mut html = String::new();
+    for (line_idx, line) in source.lines().enumerate() {
+        highlighter.process_line(line_idx, line);
+        let mut pos = 0;
+        for span in highlighter.get_line_spans(line_idx).unwrap_or(&[]) {
+            let (start, end) = (span.start.min(line.len()), span.end.min(line.len()));
+            if start < pos || !line.is_char_boundary(start) || !line.is_char_boundary(end) {
+                continue;
+            }
+            html.push_str(&escape_html(&line[pos..start]));
+            match syntax_class(span.state) {
+                Some(class) => html.push_str(&format!("<span class=\"{}\">{}</span>", class, escape_html(&line[start..end]))),
+                None => html.push_str(&escape_html(&line[start..end])),
+            }
+            pos = end;
+        }
+        html.push_str(&escape_html(&line[pos..]));
+        html.push('\n');
+    }
+    html
+}
+
+/// Inline markdown (code, bold, italic, links, images) as HTML
+fn markdown_inline(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut html = String::new();
+    let mut i = 0;
+
+    // Position of the closing delimiter, if the run is closed on this line
+    let find_closing = |from: usize, delim: &[char]| -> Option<usize> {
+        (from..chars.len().saturating_sub(delim.len() - 1))
+            .find(|&idx| chars[idx..idx + delim.len()] == *delim && idx > from)
+    };
+
+    while i < chars.len() {
+        let ch = chars[i];
+        let rest = &chars[i..];
+        // Underscores inside words (snake_case names) are not emphasis
+        let word_before = i > 0 && chars[i - 1].is_alphanumeric();
+
+        if ch == '`' {
+            if let Some(end) = find_closing(i + 1, &['`']) {
+                let code: String = chars[i + 1..end].iter().collect();
+                html.push_str(&format!("<code>{}</code>", escape_html(&code)));
+                i = end + 1;
+                continue;
+            }
+        } else if (rest.starts_with(&['*', '*']) || rest.starts_with(&['_', '_'])) && !(ch == '_' && word_before) {
+            if let Some(end) = find_closing(i + 2, &[ch, ch]) {
+                let inner: String = chars[i + 2..end].iter().collect();
+                html.push_str(&format!("<strong>{}</strong>", markdown_inline(&inner)));
+                i = end + 2;
+                continue;
+            }
+        } else if ch == '*' || (ch == '_' && !word_before) {
+            if let Some(end) = find_closing(i + 1, &[ch]) {
+                let inner: String = chars[i + 1..end].iter().collect();
+                html.push_str(&format!("<em>{}</em>", markdown_inline(&inner)));
+                i = end + 1;
+                continue;
+            }
+        } else if ch == '[' || rest.starts_with(&['!', '[']) {
+            // [text](url) or ![alt](src)
+            let is_image = ch == '!';
+            let text_start = if is_image { i + 2 } else { i + 1 };
+            if let Some(text_end) = find_closing(text_start.saturating_sub(1), &[']']) {
+                if chars.get(text_end + 1) == Some(&'(') {
+                    if let Some(url_end) = find_closing(text_end + 1, &[')']) {
+                        let label: String = chars[text_start..text_end].iter().collect();
+                        let url: String = chars[text_end + 2..url_end].iter().collect();
+                        if is_image {
+                            html.push_str(&format!("<img src=\"{}\" alt=\"{}\">", escape_html(&url), escape_html(&label)));
+                        } else {
+                            html.push_str(&format!("<a href=\"{}\">{}</a>", escape_html(&url), markdown_inline(&label)));
+                        }
+                        i = url_end + 1;
+                        continue;
+                    }
+                }
+            }
+        }
+
+        html.push_str(&escape_html(&ch.to_string()));
+        i += 1;
+    }
+    html
+}
+
+/// Markdown cell as HTML: headings, paragraphs, lists, quotes, rules and fenced code
+fn markdown_to_html(source: &str) -> String {
+    let mut html = String::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut list: Option<&str> = None; // "ul" or "ol" while inside a list
+    let mut fence: Option<String> = None; // Code fence being collected
+
+    fn flush_paragraph(html: &mut String, paragraph: &mut Vec<&str>) {
+        if !paragraph.is_empty() {
+            html.push_str(&format!("<p>{}</p>\n", markdown_inline(&paragraph.join(" "))));
+            paragraph.clear();
+        }
+    }
+    fn close_list(html: &mut String, list: &mut Option<&str>) {
+        if let Some(tag) = list.take() {
+            html.push_str(&format!("</{}>\n", tag));
+        }
+    }
+
+    for line in source.lines() {
+        let trimmed = line.trim();
+
+        if let Some(code) = fence.as_mut() {
+            if trimmed.starts_with("```") {
+                html.push_str(&format!("<pre><code>{}</code></pre>\n", escape_html(code)));
+                fence = None;
+            } else {
+                code.push_str(line);
+                code.push('\n');
+            }
+            continue;
+        }
+
+        let heading_level = trimmed.chars().take_while(|c| *c == '#').count();
+        let ordered_item = trimmed.split_once(". ")
+            .filter(|(number, _)| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+            .map(|(_, item)| item);
+        let bullet_item = ["- ", "* ", "+ "].iter().find_map(|marker| trimmed.strip_prefix(marker));
+
+        if trimmed.starts_with("```") {
+            flush_paragraph(&mut html, &mut paragraph);
+            close_list(&mut html, &mut list);
+            fence = Some(String::new());
+        } else if trimmed.is_empty() {
+            flush_paragraph(&mut html, &mut paragraph);
+            close_list(&mut html, &mut list);
+        } else if (1..=6).contains(&heading_level) && trimmed[heading_level..].starts_with(' ') {
+            flush_paragraph(&mut html, &mut paragraph);
+            close_list(&mut html, &mut list);
+            html.push_str(&format!("<h{0}>{1}</h{0}>\n", heading_level, markdown_inline(trimmed[heading_level..].trim())));
+        } else if trimmed.len() >= 3 && ["-", "*", "_"].iter().any(|rule| trimmed.chars().all(|c| c.to_string() == *rule)) {
+            flush_paragraph(&mut html, &mut paragraph);
+            close_list(&mut html, &mut list);
+            html.push_str("<hr>\n");
+        } else if let Some(quote) = trimmed.strip_prefix('>') {
+            flush_paragraph(&mut html, &mut paragraph);
+            close_list(&mut html, &mut list);
+            html.push_str(&format!("<blockquote>{}</blockquote>\n", markdown_inline(quote.trim())));
+        } else if let Some((tag, item)) = bullet_item.map(|item| ("ul", item)).or(ordered_item.map(|item| ("ol", item))) {
+            flush_paragraph(&mut html, &mut paragraph);
+            if list != Some(tag) {
+                close_list(&mut html, &mut list);
+                html.push_str(&format!("<{}>\n", tag));
+                list = Some(tag);
+            }
+            html.push_str(&format!("<li>{}</li>\n", markdown_inline(item.trim())));
+        } else {
+            close_list(&mut html, &mut list);
+            paragraph.push(trimmed);
+        }
+    }
+
+    if let Some(code) = fence {
+        html.push_str(&format!("<pre><code>{}</code></pre>\n", escape_html(&code)));
+    }
+    flush_paragraph(&mut html, &mut paragraph);
+    close_list(&mut html, &mut list);
+    html
+}
+
+/// One output of a code cell as HTML
+fn html_output(exec_output: &ExecutionOutput) -> String {
+    let class = match exec_output {
+        ExecutionOutput::Stdout(_) => "output",
+        ExecutionOutput::Stderr(_) => "output stderr",
+        ExecutionOutput::Result(_) => "output result",
+        ExecutionOutput::Error { .. } => "output error",
+        ExecutionOutput::Display { data, mime_type } => {
+            return match mime_type.as_str() {
+                mime if is_embedded_image(mime) => {
+                    let data: String = data.chars().filter(|c| !c.is_whitespace()).collect();
+                    format!("<div class=\"display\"><img src=\"data:{};base64,{}\" alt=\"output\"></div>\n", mime, data)
+                }
+                "image/svg+xml" | "text/html" => format!("<div class=\"display\">{}</div>\n", data),
+                "text/markdown" => format!("<div class=\"display\">{}</div>\n", markdown_to_html(data)),
+                _ => format!("<pre class=\"output\">{}</pre>\n", escape_html(&output_text(exec_output))),
+            };
+        }
+    };
+    let text = output_text(exec_output);
+    if text.trim().is_empty() {
+        return String::new();
+    }
+    format!("<pre class=\"{}\">{}</pre>\n", class, escape_html(text.trim_end_matches('\n')))
+}
+
+/// Styles for the HTML report; code colors match the editor's
+const HTML_STYLE: &str = "\
+body { font-family: -apple-system, 'Segoe UI', Helvetica, Arial, sans-serif; max-width: 960px; margin: 2em auto; padding: 0 1em; color: #222; line-height: 1.5; }
+.cell { margin: 1.2em 0; }
+.prompt { color: #888; font-family: monospace; font-size: 0.85em; }
+pre { margin: 0.3em 0; padding: 0.7em 1em; overflow-x: auto; border-radius: 4px; font-size: 0.9em; }
+pre.code { background: #262626; color: #d0d0d0; }
+pre.output { background: #f5f5f5; color: #222; }
+pre.stderr { background: #fdf6e3; }
+pre.error { background: #fbeaea; color: #a02020; }
+.display img { max-width: 100%; }
+.s { color: #98b498; } .c { color: #6e6e6e; } .k { color: #87a0b4; } .t { color: #84aaaa; }
+.f { color: #c8be96; } .n { color: #c8aa8c; } .o { color: #a0a0a0; } .d { color: #b496b4; }
+";
+
+/// Executed notebook as a standalone HTML document
+fn to_html(title: &str, cells: &[ExecutedCell]) -> String {
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str(&format!("<title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n", escape_html(title), HTML_STYLE));
+
+    for cell in cells {
+        match cell.cell_type {
+            CellType::Markdown => {
+                out.push_str(&format!("<div class=\"cell markdown\">\n{}</div>\n", markdown_to_html(&cell.source)));
+            }
+            CellType::Code => {
+                let source = cell.source.trim_matches('\n');
+                if source.trim().is_empty() && cell.result.is_none() {
+                    continue;
+                }
+                let count = cell.result.as_ref().and_then(|r| r.execution_count).map(|n| n.to_string()).unwrap_or_else(|| " ".to_string());
+                out.push_str("<div class=\"cell code\">\n");
+                out.push_str(&format!("<div class=\"prompt\">In [{}]:</div>\n", count));
+                out.push_str(&format!("<pre class=\"code\"><code>{}</code></pre>\n", highlight_python(source).trim_end_matches('\n')));
+                if let Some(result) = &cell.result {
+                    for exec_output in &result.outputs {
+                        out.push_str(&html_output(exec_output));
+                    }
+                }
+                out.push_str("</div>\n");
+            }
+        }
+    }
+
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// Fenced code block, with a fence longer than any backtick run inside it
+fn fenced_block(info: &str, text: &str) -> String {
+    let longest_run = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    format!("{}{}\n{}\n{}\n", fence, info, text.trim_end_matches('\n'), fence)
+}
+
+/// Executed notebook as GitHub-flavoured Markdown
+fn to_markdown(cells: &[ExecutedCell]) -> String {
+    let mut blocks: Vec<String> = Vec::new();
+
+    for cell in cells {
+        match cell.cell_type {
+            CellType::Markdown => {
+                let source = cell.source.trim_matches('\n');
+                if !source.trim().is_empty() {
+                    blocks.push(format!("{}\n", source));
+                }
+            }
+            CellType::Code => {
+                let source = cell.source.trim_matches('\n');
+                if source.trim().is_empty() && cell.result.is_none() {
+                    continue;
+                }
+                blocks.push(fenced_block("python", source));
+
+                let Some(result) = &cell.result else {
+                    continue;
+                };
+                for exec_output in &result.outputs {
+                    match exec_output {
+                        ExecutionOutput::Display { data, mime_type } if is_embedded_image(mime_type) => {
+                            let data: String = data.chars().filter(|c| !c.is_whitespace()).collect();
+                            blocks.push(format!("![output](data:{};base64,{})\n", mime_type, data));
+                        }
+                        ExecutionOutput::Display { data, mime_type } if mime_type == "text/html" || mime_type == "text/markdown" => {
+                            blocks.push(format!("{}\n", data.trim_end_matches('\n')));
+                        }
+                        _ => {
+                            let text = output_text(exec_output);
+                            if !text.trim().is_empty() {
+                                blocks.push(fenced_block("text", &text));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    blocks.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_path_must_suit_the_format() {
+        assert!(check_output_path("run.ipynb", None).is_ok());
+        assert!(check_output_path("run.PY", None).is_ok());
+        assert!(check_output_path("run.txt", None).is_err());
+        assert!(check_output_path("run", None).is_err());
+        assert!(check_output_path("report.html", Some(ReportFormat::Html)).is_ok());
+        assert!(check_output_path("report.htm", Some(ReportFormat::Html)).is_ok());
+        assert!(check_output_path("report.md", Some(ReportFormat::Html)).is_err());
+        assert!(check_output_path("report.ipynb", Some(ReportFormat::Markdown)).is_err());
+    }
+}
@@ -2,12 +2,111 @@ use crossterm::{
     cursor::{Hide, MoveTo, Show},
     event::{self, Event, KeyCode, KeyModifiers},
     execute,
-    style::{Attribute, Color, Print, SetAttribute, SetBackgroundColor, SetForegroundColor, ResetColor},
-    terminal,
+    style::{Attribute, Print, SetAttribute, SetBackgroundColor, SetForegroundColor, ResetColor},
 };
 use arboard::Clipboard;
+use crate::layout::Rect;
+use crate::theme::{self, Element};
+use regex::{Regex, RegexBuilder};
 use std::io::{self, Write};
 
+/// Most matches the find bar collects; the counter shows "+" once it's reached
+pub const MAX_FIND_MATCHES: usize = 100_000;
+
+/// Byte ranges of every non-overlapping occurrence of `search_text` in `text`
+pub fn find_matches(text: &str, search_text: &str) -> Vec<(usize, usize)> {
+    let mut matches = Vec::new();
+    if search_text.is_empty() {
+        return matches;
+    }
+    let mut pos = 0;
+
+    while pos < text.len() {
+        // Ensure we're on a character boundary
+        if !text.is_char_boundary(pos) {
+            pos += 1;
+            continue;
+        }
+
+        if let Some(found) = text[pos..].find(search_text) {
+            let match_start = pos + found;
+            let match_end = match_start + search_text.len();
+            matches.push((match_start, match_end));
+            pos = match_end;
+        } else {
+            break;
+        }
+    }
+
+    matches
+}
+
+/// How the find bar matches its query
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchOptions {
+    pub regex: bool,        // The query is a regular expression and replacements can use `$1`
+    pub match_case: bool,
+    pub whole_word: bool,   // Matches can't have a letter, digit or `_` on either side
+    pub in_selection: bool, // Only search the text that was selected
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions { regex: false, match_case: true, whole_word: false, in_selection: false }
+    }
+}
+
+/// A find query compiled for its options. Plain text is escaped into a regex, so every mode
+/// runs through the same engine
+#[derive(Clone)]
+pub struct Search {
+    regex: Regex,
+    whole_word: bool,
+    expand: bool, // Replacements refer to capture groups
+}
+
+impl Search {
+    pub fn new(query: &str, options: SearchOptions) -> Result<Search, String> {
+        let pattern = if options.regex { query.to_string() } else { regex::escape(query) };
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(!options.match_case)
+            .build()
+            .map_err(|e| match e {
+                // The message comes after a copy of the pattern with a caret under the problem
+                regex::Error::Syntax(text) => text.lines().last().unwrap_or_default().trim_start_matches("error: ").to_string(),
+                e => e.to_string(),
+            })?;
+        Ok(Search { regex, whole_word: options.whole_word, expand: options.regex })
+    }
+
+    /// Byte ranges of the matches in one line, given without its line ending
+    pub fn find_in_line<'a>(&'a self, line: &'a str) -> impl Iterator<Item = (usize, usize)> + 'a {
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        self.regex
+            .find_iter(line)
+            .filter(|m| !m.is_empty())
+            .filter(move |m| {
+                !self.whole_word
+                    || (!line[..m.start()].chars().next_back().is_some_and(is_word)
+                        && !line[m.end()..].chars().next().is_some_and(is_word))
+            })
+            .map(|m| (m.start(), m.end()))
+    }
+
+    /// What replaces the match starting at `start` in `line`: `replace` with any `$1` or
+    /// `${name}` groups filled in when the query is a regex
+    pub fn replacement(&self, line: &str, start: usize, replace: &str) -> String {
+        match self.regex.captures_at(line, start) {
+            Some(captures) if self.expand => {
+                let mut text = String::new();
+                captures.expand(replace, &mut text);
+                text
+            }
+            _ => replace.to_string(),
+        }
+    }
+}
+
 pub struct FindReplace {
     find_text: String,
     replace_text: String,
@@ -17,7 +116,10 @@ pub struct FindReplace {
     current_match: usize,
     total_matches: usize,
     matches: Vec<(usize, usize)>, // (start_byte, end_byte) positions
-    clipboard: Clipboard,
+    options: SearchOptions,
+    scope: Option<(usize, usize)>, // Byte range searched in selection mode
+    error: Option<String>,         // Why the query can't be searched, shown on the bar
+    clipboard: Option<Clipboard>,  // None until the system clipboard could be reached
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -37,10 +139,21 @@ impl FindReplace {
             current_match: 0,
             total_matches: 0,
             matches: Vec::new(),
-            clipboard: Clipboard::new().expect("Failed to access clipboard"),
+            options: SearchOptions::default(),
+            scope: None,
+            error: None,
+            clipboard: Clipboard::new().ok(),
         }
     }
     
+    /// The system clipboard, reached now if it couldn't be when the bar was made
+    fn clipboard(&mut self) -> Result<&mut Clipboard, arboard::Error> {
+        if self.clipboard.is_none() {
+            self.clipboard = Some(Clipboard::new()?);
+        }
+        self.clipboard.as_mut().ok_or(arboard::Error::ClipboardNotSupported)
+    }
+
     /// Update the search results
     pub fn update_matches(&mut self, matches: Vec<(usize, usize)>) {
         self.matches = matches;
@@ -88,11 +201,6 @@ impl FindReplace {
         self.current_match = 0;
     }
     
-    /// Get the find text
-    pub fn find_text(&self) -> &str {
-        &self.find_text
-    }
-    
     /// Get the replace text
     pub fn replace_text(&self) -> &str {
         &self.replace_text
@@ -103,6 +211,71 @@ impl FindReplace {
         self.find_text.is_empty()
     }
 
+    /// Compile the query, keeping any error to show on the bar; None when there's nothing to search
+    pub fn search(&mut self) -> Option<Search> {
+        self.error = None;
+        if self.find_text.is_empty() {
+            return None;
+        }
+        match Search::new(&self.find_text, self.options) {
+            Ok(search) => Some(search),
+            Err(e) => {
+                self.error = Some(e);
+                None
+            }
+        }
+    }
+
+    /// The range to search: the scope in selection mode, otherwise everything
+    pub fn scope(&self) -> Option<(usize, usize)> {
+        self.scope.filter(|_| self.options.in_selection)
+    }
+
+    /// Remember the selection the bar was opened with; one spanning several lines is
+    /// searched in straight away
+    pub fn set_scope(&mut self, selection: Option<(usize, usize)>, multiline: bool) {
+        self.scope = selection.filter(|(start, end)| start < end);
+        self.options.in_selection = self.scope.is_some() && multiline;
+    }
+
+    /// Keep the scope around the same text after `old_len` bytes at `at` became `new_len`
+    pub fn adjust_scope(&mut self, at: usize, old_len: usize, new_len: usize) {
+        if let Some((start, end)) = self.scope.as_mut() {
+            if at < *start {
+                *start = (*start + new_len).saturating_sub(old_len);
+            }
+            if at < *end {
+                *end = (*end + new_len).saturating_sub(old_len);
+            }
+        }
+    }
+
+    pub fn toggle_regex(&mut self) {
+        self.options.regex = !self.options.regex;
+    }
+
+    pub fn toggle_match_case(&mut self) {
+        self.options.match_case = !self.options.match_case;
+    }
+
+    pub fn toggle_whole_word(&mut self) {
+        self.options.whole_word = !self.options.whole_word;
+    }
+
+    /// Switch selection mode; without a scope from opening, the current selection becomes it.
+    /// Returns false if there's nothing to search in
+    pub fn toggle_in_selection(&mut self, selection: Option<(usize, usize)>) -> bool {
+        if self.options.in_selection {
+            self.options.in_selection = false;
+            return true;
+        }
+        if self.scope.is_none() {
+            self.scope = selection.filter(|(start, end)| start < end);
+        }
+        self.options.in_selection = self.scope.is_some();
+        self.options.in_selection
+    }
+
     /// Get all match positions
     pub fn get_all_matches(&self) -> &[(usize, usize)] {
         &self.matches
@@ -158,17 +331,17 @@ impl FindReplace {
         })
     }
     
-    /// Draw the find/replace window at bottom of screen
-    pub fn draw(&self, stdout: &mut io::Stdout) -> io::Result<()> {
-        let (width, height) = terminal::size()?;
-        
-        // Position at bottom, above status bar
-        let window_height = 3;  // Still 3 lines but simpler
-        let window_y = height.saturating_sub(window_height + 1) as usize; // -1 for status bar
+    /// Draw the find/replace window into its area of the layout (3 rows, below the status bar)
+    pub fn draw(&self, stdout: &mut io::Stdout, area: Rect) -> io::Result<()> {
+        let width = area.width;
+        let window_height = area.height.min(3);
+        let window_y = area.y as usize;
         
         // Calculate counter string first to know its actual length - we'll use this for both drawing and cursor positioning
         let counter_str = if self.total_matches > 0 || !self.find_text.is_empty() {
-            if self.total_matches > 0 {
+            if self.total_matches >= MAX_FIND_MATCHES {
+                format!(" [{}/{}+]", self.current_match + 1, self.total_matches)
+            } else if self.total_matches > 0 {
                 format!(" [{}/{}]", self.current_match + 1, self.total_matches)
             } else {
                 " [0/0]".to_string()
@@ -183,19 +356,47 @@ impl FindReplace {
         let available_for_fields = total_width.saturating_sub(6 + 9 + actual_counter_len + 4); // "Find: " + "Replace: " + counter + spacing
         let field_width = available_for_fields / 2;
         
+        let theme = theme::current();
+        let dialog = theme[Element::Dialog];
+        let label = |active: bool| {
+            if active { theme[Element::DialogTitle].foreground() } else { theme[Element::DialogInactive].foreground() }
+        };
+        let field = |active: bool| theme[if active { Element::DialogInput } else { Element::DialogInputInactive }];
+
         // Draw window background with border
         for y in 0..window_height as usize {
             execute!(
                 stdout,
                 MoveTo(0, (window_y + y) as u16),
-                SetBackgroundColor(Color::Rgb { r: 40, g: 40, b: 45 }),
-                SetForegroundColor(Color::Rgb { r: 200, g: 200, b: 205 }),
+                SetBackgroundColor(dialog.background()),
+                SetForegroundColor(dialog.foreground()),
             )?;
             
             if y == 0 {
-                // Top border with rounded corners
-                write!(stdout, "╭")?;
-                for _ in 1..width - 1 {
+                // Top border with rounded corners, and the search options lit up when on
+                write!(stdout, "╭─")?;
+                let toggles = [
+                    (".*", self.options.regex),
+                    ("Aa", self.options.match_case),
+                    ("Word", self.options.whole_word),
+                    ("Sel", self.options.in_selection),
+                ];
+                let mut used = 2;
+                for (label, on) in toggles {
+                    if used + label.len() + 4 > width as usize - 1 {
+                        break;
+                    }
+                    let style = if on { theme[Element::DialogAccent] } else { theme[Element::DialogDim] };
+                    execute!(
+                        stdout,
+                        SetForegroundColor(style.foreground()),
+                        Print(format!(" {} ", label)),
+                        SetForegroundColor(dialog.foreground()),
+                        Print("─"),
+                    )?;
+                    used += label.len() + 3;
+                }
+                for _ in used..width as usize - 1 {
                     write!(stdout, "─")?;
                 }
                 write!(stdout, "╮")?;
@@ -206,13 +407,9 @@ impl FindReplace {
                 // Find label and field
                 execute!(
                     stdout,
-                    SetForegroundColor(if self.active_field == Field::Find {
-                        Color::White
-                    } else {
-                        Color::Rgb { r: 150, g: 150, b: 155 }
-                    }),
+                    SetForegroundColor(label(self.active_field == Field::Find)),
                     Print("Find: "),
-                    SetForegroundColor(Color::Rgb { r: 200, g: 200, b: 205 }),
+                    SetForegroundColor(dialog.foreground()),
                 )?;
                 
                 // Find input field
@@ -225,12 +422,8 @@ impl FindReplace {
                 
                 execute!(
                     stdout,
-                    SetBackgroundColor(if self.active_field == Field::Find {
-                        Color::Rgb { r: 20, g: 20, b: 25 }
-                    } else {
-                        Color::Rgb { r: 30, g: 30, b: 35 }
-                    }),
-                    SetForegroundColor(Color::Rgb { r: 220, g: 220, b: 230 }),
+                    SetBackgroundColor(field(self.active_field == Field::Find).background()),
+                    SetForegroundColor(field(self.active_field == Field::Find).foreground()),
                 )?;
                 
                 // Write the field content with selection highlighting
@@ -242,15 +435,15 @@ impl FindReplace {
                     write!(stdout, " ")?;
                 }
                 
-                execute!(stdout, SetBackgroundColor(Color::Rgb { r: 40, g: 40, b: 45 }))?;
+                execute!(stdout, SetBackgroundColor(dialog.background()))?;
                 
                 // Match counter
                 if !counter_str.is_empty() {
                     execute!(
                         stdout,
-                        SetForegroundColor(Color::Rgb { r: 150, g: 200, b: 150 }),
+                        SetForegroundColor(theme[Element::DialogSuccess].foreground()),
                         Print(&counter_str),
-                        SetForegroundColor(Color::Rgb { r: 200, g: 200, b: 205 }),
+                        SetForegroundColor(dialog.foreground()),
                     )?;
                 }
                 
@@ -260,13 +453,9 @@ impl FindReplace {
                 // Replace label and field
                 execute!(
                     stdout,
-                    SetForegroundColor(if self.active_field == Field::Replace {
-                        Color::White
-                    } else {
-                        Color::Rgb { r: 150, g: 150, b: 155 }
-                    }),
+                    SetForegroundColor(label(self.active_field == Field::Replace)),
                     Print("Replace: "),
-                    SetForegroundColor(Color::Rgb { r: 200, g: 200, b: 205 }),
+                    SetForegroundColor(dialog.foreground()),
                 )?;
                 
                 // Replace input field
@@ -279,12 +468,8 @@ impl FindReplace {
                 
                 execute!(
                     stdout,
-                    SetBackgroundColor(if self.active_field == Field::Replace {
-                        Color::Rgb { r: 20, g: 20, b: 25 }
-                    } else {
-                        Color::Rgb { r: 30, g: 30, b: 35 }
-                    }),
-                    SetForegroundColor(Color::Rgb { r: 220, g: 220, b: 230 }),
+                    SetBackgroundColor(field(self.active_field == Field::Replace).background()),
+                    SetForegroundColor(field(self.active_field == Field::Replace).foreground()),
                 )?;
                 
                 // Write the field content with selection highlighting
@@ -298,8 +483,8 @@ impl FindReplace {
                 
                 execute!(
                     stdout,
-                    SetBackgroundColor(Color::Rgb { r: 40, g: 40, b: 45 }),
-                    SetForegroundColor(Color::Rgb { r: 200, g: 200, b: 205 }),
+                    SetBackgroundColor(dialog.background()),
+                    SetForegroundColor(dialog.foreground()),
                 )?;
                 
                 // Fill rest of line
@@ -313,9 +498,21 @@ impl FindReplace {
                 write!(stdout, "│")?;
                 
             } else if y == 2 {
-                // Bottom border - clean and simple
+                // Bottom border, with the reason the query can't be searched if there is one
                 write!(stdout, "╰")?;
-                for _ in 1..width - 1 {
+                let mut used = 1;
+                if let Some(error) = &self.error {
+                    let message: String = format!(" {} ", error).chars().take((width as usize).saturating_sub(4)).collect();
+                    execute!(
+                        stdout,
+                        Print("─"),
+                        SetForegroundColor(theme[Element::DialogError].foreground()),
+                        Print(&message),
+                        SetForegroundColor(dialog.foreground()),
+                    )?;
+                    used += 1 + message.chars().count();
+                }
+                for _ in used..width as usize - 1 {
                     write!(stdout, "─")?;
                 }
                 write!(stdout, "╯")?;
@@ -390,16 +587,17 @@ impl FindReplace {
         
         // Apply selection highlighting if needed
         if let Some((sel_start, sel_end)) = selection {
+            let theme = theme::current();
+            let field = theme[Element::DialogInput];
             let mut result = String::new();
             for (i, ch) in visible.chars().enumerate() {
                 let abs_pos = offset + i;
                 if abs_pos >= sel_start && abs_pos < sel_end {
                     // Selected character - use inverted colors
-                    result.push_str("\x1b[48;2;95;158;160m\x1b[38;2;0;0;0m");
+                    result.push_str(&theme[Element::Selection].over(field).escape());
                     result.push(ch);
-                    result.push_str("\x1b[0m");
                     // Restore the field background color
-                    result.push_str("\x1b[48;2;20;20;25m\x1b[38;2;220;220;230m");
+                    result.push_str(&field.escape());
                 } else {
                     result.push(ch);
                 }
@@ -491,7 +689,7 @@ impl FindReplace {
                     }
                 };
                 
-                if let Err(e) = self.clipboard.set_text(text_to_copy) {
+                if let Err(e) = self.clipboard().and_then(|clipboard| clipboard.set_text(text_to_copy)) {
                     eprintln!("Failed to copy to clipboard: {}", e);
                 }
                 InputResult::Continue
@@ -515,7 +713,7 @@ impl FindReplace {
                     all
                 };
                 
-                if let Err(e) = self.clipboard.set_text(text_to_cut) {
+                if let Err(e) = self.clipboard().and_then(|clipboard| clipboard.set_text(text_to_cut)) {
                     eprintln!("Failed to copy to clipboard: {}", e);
                 } else if self.selection_start.is_some() {
                     // Delete the selection after copying
@@ -531,7 +729,7 @@ impl FindReplace {
             
             // Handle Ctrl+V (Paste)
             KeyCode::Char('v') | KeyCode::Char('V') if modifiers.contains(KeyModifiers::CONTROL) => {
-                match self.clipboard.get_text() {
+                match self.clipboard().and_then(|clipboard| clipboard.get_text()) {
                     Ok(clipboard_text) => {
                         // Truncate at first newline for single-line fields
                         let text_to_paste = clipboard_text
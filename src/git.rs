@@ -0,0 +1,266 @@
+//! Changed-line markers against the committed version of a file
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+
+/// Most lines added and removed that are told apart; a bigger change is marked as modified
+/// throughout, as working it out would take too long
+const MAX_EDITS: usize = 4_000;
+
+/// How a line of the buffer differs from `HEAD`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineChange {
+    Added,
+    Modified,
+    /// Lines were removed just above this one
+    DeletedAbove,
+}
+
+/// A file's text at `HEAD`, or None when it isn't tracked by git (or git isn't there)
+pub fn head_text(path: &Path) -> Option<String> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let name = path.file_name()?.to_str()?;
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .arg("show")
+        .arg(format!("HEAD:./{}", name))
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    // Compared with the buffer, which is loaded with Unix line endings
+    Some(String::from_utf8_lossy(&output.stdout).replace("\r\n", "\n"))
+}
+
+/// Changed lines of `current` against `head`, keyed by zero-based line of `current`
+pub fn line_changes(head: &str, current: &str) -> HashMap<usize, LineChange> {
+    let old: Vec<&str> = head.split('\n').collect();
+    let new: Vec<&str> = current.split('\n').collect();
+
+    // Only the middle between the unchanged start and end needs comparing
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let suffix = old.iter().rev().zip(new.iter().rev()).take(max_suffix).take_while(|(a, b)| a == b).count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    let mut changes = HashMap::new();
+    let mark_run = |changes: &mut HashMap<usize, LineChange>, removed: usize, added: std::ops::Range<usize>| {
+        if added.is_empty() {
+            if removed > 0 {
+                // Shown on the line after the gap, or the last line when the end was removed
+                let line = (prefix + added.start).min(new.len() - 1);
+                changes.entry(line).or_insert(LineChange::DeletedAbove);
+            }
+            return;
+        }
+        let change = if removed > 0 { LineChange::Modified } else { LineChange::Added };
+        for line in added {
+            changes.insert(prefix + line, change);
+        }
+    };
+
+    let Some(matches) = matching_lines(old_mid, new_mid) else {
+        mark_run(&mut changes, old_mid.len(), 0..new_mid.len());
+        return changes;
+    };
+
+    // Lines removed and added between two matched lines form one change
+    let (mut next_old, mut next_new) = (0, 0);
+    let end = (old_mid.len(), new_mid.len());
+    for (old_line, new_line) in matches.into_iter().chain(std::iter::once(end)) {
+        mark_run(&mut changes, old_line - next_old, next_new..new_line);
+        (next_old, next_new) = (old_line + 1, new_line + 1);
+    }
+    changes
+}
+
+/// Lines `old` and `new` have in common, as (line of `old`, line of `new`) in order, found
+/// with Myers' diff in linear space. None if more than `MAX_EDITS` lines differ
+fn matching_lines(old: &[&str], new: &[&str]) -> Option<Vec<(usize, usize)>> {
+    let mut matches = Vec::new();
+    diff_range(old, new, (0, old.len()), (0, new.len()), MAX_EDITS, &mut matches)?;
+    Some(matches)
+}
+
+/// Add the lines `old` and `new` have in common between the given (start, end) lines to
+/// `matches`, unless more than `limit` lines differ
+fn diff_range(
+    old: &[&str],
+    new: &[&str],
+    (mut old_start, mut old_end): (usize, usize),
+    (mut new_start, mut new_end): (usize, usize),
+    limit: usize,
+    matches: &mut Vec<(usize, usize)>,
+) -> Option<()> {
+    while old_start < old_end && new_start < new_end && old[old_start] == new[new_start] {
+        matches.push((old_start, new_start));
+        old_start += 1;
+        new_start += 1;
+    }
+    let mut tail = Vec::new();
+    while old_start < old_end && new_start < new_end && old[old_end - 1] == new[new_end - 1] {
+        old_end -= 1;
+        new_end -= 1;
+        tail.push((old_end, new_end));
+    }
+    if old_start < old_end && new_start < new_end {
+        let (x, y) = split_point(&old[old_start..old_end], &new[new_start..new_end], limit)?;
+        diff_range(old, new, (old_start, old_start + x), (new_start, new_start + y), usize::MAX, matches)?;
+        diff_range(old, new, (old_start + x, old_end), (new_start + y, new_end), usize::MAX, matches)?;
+    }
+    matches.extend(tail.into_iter().rev());
+    Some(())
+}
+
+/// A point a shortest edit script from `old` to `new` passes through with about half its
+/// edits on either side, as (line of `old`, line of `new`), found by searching from both ends
+/// at once. None if more than `limit` lines differ
+fn split_point(old: &[&str], new: &[&str], limit: usize) -> Option<(usize, usize)> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max_d = (n + m + 1) / 2;
+    let last_d = max_d.min((limit / 2) as isize);
+    let offset = max_d;
+    // Furthest x reached on each diagonal k = x - y, indexed by `offset + k`, or -1; the
+    // backward search counts x and y from the ends
+    let len = 2 * max_d as usize + 2;
+    let mut forward = vec![-1isize; len];
+    let mut backward = vec![-1isize; len];
+    forward[offset as usize + 1] = 0;
+    backward[offset as usize + 1] = 0;
+    let delta = n - m;
+    // With an odd difference in length the searches meet on a forward step, otherwise a backward one
+    let meet_forward = delta % 2 != 0;
+    // Diagonals that ran off the grid are skipped from then on
+    let (mut forward_start, mut forward_end, mut backward_start, mut backward_end) = (0, 0, 0, 0);
+
+    for d in 0..=last_d {
+        let mut k = -d + forward_start;
+        while k <= d - forward_end {
+            let idx = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && forward[idx - 1] < forward[idx + 1]) {
+                forward[idx + 1]
+            } else {
+                forward[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[idx] = x;
+            if x > n {
+                forward_end += 2;
+            } else if y > m {
+                forward_start += 2;
+            } else if meet_forward {
+                let other = offset + delta - k;
+                if (0..len as isize).contains(&other) && backward[other as usize] != -1 && x >= n - backward[other as usize] {
+                    return Some((x as usize, y as usize));
+                }
+            }
+            k += 2;
+        }
+
+        let mut k = -d + backward_start;
+        while k <= d - backward_end {
+            let idx = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && backward[idx - 1] < backward[idx + 1]) {
+                backward[idx + 1]
+            } else {
+                backward[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[(n - x - 1) as usize] == new[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[idx] = x;
+            if x > n {
+                backward_end += 2;
+            } else if y > m {
+                backward_start += 2;
+            } else if !meet_forward {
+                let other = offset + delta - k;
+                if (0..len as isize).contains(&other) && forward[other as usize] != -1 {
+                    let forward_x = forward[other as usize];
+                    if forward_x >= n - x {
+                        return Some((forward_x as usize, (forward_x - (other - offset)) as usize));
+                    }
+                }
+            }
+            k += 2;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changes(head: &str, current: &str) -> Vec<(usize, LineChange)> {
+        let mut changes: Vec<_> = line_changes(head, current).into_iter().collect();
+        changes.sort_by_key(|(line, _)| *line);
+        changes
+    }
+
+    #[test]
+    fn marks_added_modified_and_deleted_lines() {
+        assert_eq!(changes("a\nb\nc", "a\nb\nc"), []);
+        assert_eq!(changes("a\nc", "a\nb\nc"), [(1, LineChange::Added)]);
+        assert_eq!(changes("a\nb\nc", "a\nB\nc"), [(1, LineChange::Modified)]);
+        assert_eq!(changes("a\nb\nc", "a\nc"), [(1, LineChange::DeletedAbove)]);
+        assert_eq!(changes("a\nb\nc", "a\nb"), [(1, LineChange::DeletedAbove)]);
+        assert_eq!(
+            changes("x\na\ny\nb\nz", "a\nnew\nb\nz\nw"),
+            [(0, LineChange::DeletedAbove), (1, LineChange::Modified), (4, LineChange::Added)]
+        );
+    }
+
+    /// Length of the longest common subsequence, the slow way
+    fn lcs_len(old: &[&str], new: &[&str]) -> usize {
+        let mut lengths = vec![vec![0; new.len() + 1]; old.len() + 1];
+        for i in (0..old.len()).rev() {
+            for j in (0..new.len()).rev() {
+                lengths[i][j] = if old[i] == new[j] {
+                    lengths[i + 1][j + 1] + 1
+                } else {
+                    lengths[i + 1][j].max(lengths[i][j + 1])
+                };
+            }
+        }
+        lengths[0][0]
+    }
+
+    #[test]
+    fn myers_finds_a_longest_common_subsequence() {
+        // Small alphabets make many ties and repeats
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = |bound: u64| {
+            seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+            (seed >> 33) % bound
+        };
+        let words = ["a", "b", "c", "d"];
+        for _ in 0..500 {
+            let old: Vec<&str> = (0..next(12)).map(|_| words[next(4) as usize]).collect();
+            let new: Vec<&str> = (0..next(12)).map(|_| words[next(4) as usize]).collect();
+            let matches = matching_lines(&old, &new).unwrap();
+            assert_eq!(matches.len(), lcs_len(&old, &new), "{:?} {:?}", old, new);
+            assert!(matches.windows(2).all(|pair| pair[0].0 < pair[1].0 && pair[0].1 < pair[1].1));
+            assert!(matches.iter().all(|&(i, j)| old[i] == new[j]));
+        }
+    }
+
+    #[test]
+    fn too_big_a_change_is_marked_modified() {
+        let head: String = (0..MAX_EDITS).map(|i| format!("old {}\n", i)).collect();
+        let current: String = (0..MAX_EDITS).map(|i| format!("new {}\n", i)).collect();
+        let changes = line_changes(&head, &current);
+        assert_eq!(changes.len(), MAX_EDITS);
+        assert!(changes.values().all(|change| *change == LineChange::Modified));
+    }
+}
@@ -25,6 +25,13 @@ pub struct CompletionItem {
     pub item_type: String,
 }
 
+/// Top-level names a cell defines and reads, from the kernel's AST analysis
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NameUsage {
+    pub defines: Vec<String>,
+    pub reads: Vec<String>,
+}
+
 /// Execution result with combined output
 #[derive(Debug, Clone)]
 pub struct ExecutionResult {
@@ -32,6 +39,7 @@ pub struct ExecutionResult {
     pub execution_count: Option<usize>,
     pub success: bool,
     pub completions: Vec<CompletionItem>,
+    pub names: Option<NameUsage>,
 }
 
 /// Information about an available Python kernel
@@ -1,9 +1,10 @@
 use crate::kernel::{discover_kernels, KernelInfo};
+use crate::theme::{self, Element};
 use crossterm::{
     cursor,
     event::{self, Event, KeyCode, KeyModifiers},
     execute,
-    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+    style::{Print, ResetColor, SetBackgroundColor, SetForegroundColor},
     terminal::{self, Clear, ClearType},
 };
 use std::io::{self, Write};
@@ -54,12 +55,12 @@ impl KernelSelector {
                         KeyCode::Enter => {
                             use std::fs::OpenOptions;
                             use std::io::Write;
-                            if let Ok(mut log) = OpenOptions::new().create(true).append(true).open("/tmp/sage_debug.log") {
+                            if let Some(mut log) = crate::config::debug_log_path().and_then(|path| OpenOptions::new().create(true).append(true).open(path).ok()) {
                                 let _ = writeln!(log, "Enter pressed in kernel selector");
                                 let _ = log.flush();
                             }
                             let kernel = self.kernels[self.selected_index].clone();
-                            if let Ok(mut log) = OpenOptions::new().create(true).append(true).open("/tmp/sage_debug.log") {
+                            if let Some(mut log) = crate::config::debug_log_path().and_then(|path| OpenOptions::new().create(true).append(true).open(path).ok()) {
                                 let _ = writeln!(log, "Returning kernel: {}", kernel.display_name);
                                 let _ = log.flush();
                             }
@@ -77,6 +78,7 @@ impl KernelSelector {
     }
 
     fn draw<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let theme = theme::current();
         let (width, height) = terminal::size()?;
         let box_width = width.min(100);  // Increased width for longer paths
         let max_list_height = (height as usize).saturating_sub(10);  // Leave room for borders
@@ -98,7 +100,7 @@ impl KernelSelector {
         execute!(
             writer,
             cursor::MoveTo(start_col, start_row),
-            SetForegroundColor(Color::Cyan),
+            SetForegroundColor(theme[Element::DialogAccent].foreground()),
             Print("┌"),
             Print("─".repeat((box_width - 2) as usize)),
             Print("┐"),
@@ -109,12 +111,12 @@ impl KernelSelector {
         execute!(
             writer,
             cursor::MoveTo(start_col, start_row + 1),
-            SetForegroundColor(Color::Cyan),
+            SetForegroundColor(theme[Element::DialogAccent].foreground()),
             Print("│"),
             ResetColor,
             Print(" Select Python Kernel"),
             cursor::MoveTo(start_col + box_width - 1, start_row + 1),
-            SetForegroundColor(Color::Cyan),
+            SetForegroundColor(theme[Element::DialogAccent].foreground()),
             Print("│"),
             ResetColor
         )?;
@@ -123,7 +125,7 @@ impl KernelSelector {
         execute!(
             writer,
             cursor::MoveTo(start_col, start_row + 2),
-            SetForegroundColor(Color::Cyan),
+            SetForegroundColor(theme[Element::DialogAccent].foreground()),
             Print("├"),
             Print("─".repeat((box_width - 2) as usize)),
             Print("┤"),
@@ -152,25 +154,25 @@ impl KernelSelector {
             if kernel_idx == self.selected_index {
                 execute!(
                     writer,
-                    SetForegroundColor(Color::Cyan),
+                    SetForegroundColor(theme[Element::DialogAccent].foreground()),
                     Print("│"),
                     ResetColor,
-                    SetBackgroundColor(Color::DarkGrey),
-                    SetForegroundColor(Color::White),
+                    SetBackgroundColor(theme[Element::DialogSelected].background()),
+                    SetForegroundColor(theme[Element::DialogSelected].foreground()),
                     Print(format!(" > {:<width$}", display_text, width = (box_width - 4) as usize)),
                     ResetColor,
-                    SetForegroundColor(Color::Cyan),
+                    SetForegroundColor(theme[Element::DialogAccent].foreground()),
                     Print("│"),
                     ResetColor
                 )?;
             } else {
                 execute!(
                     writer,
-                    SetForegroundColor(Color::Cyan),
+                    SetForegroundColor(theme[Element::DialogAccent].foreground()),
                     Print("│"),
                     ResetColor,
                     Print(format!("   {:<width$}", display_text, width = (box_width - 4) as usize)),
-                    SetForegroundColor(Color::Cyan),
+                    SetForegroundColor(theme[Element::DialogAccent].foreground()),
                     Print("│"),
                     ResetColor
                 )?;
@@ -182,7 +184,7 @@ impl KernelSelector {
         execute!(
             writer,
             cursor::MoveTo(start_col, bottom_row),
-            SetForegroundColor(Color::Cyan),
+            SetForegroundColor(theme[Element::DialogAccent].foreground()),
             Print("└"),
             Print("─".repeat((box_width - 2) as usize)),
             Print("┘"),
@@ -199,7 +201,7 @@ impl KernelSelector {
         execute!(
             writer,
             cursor::MoveTo(start_col, bottom_row + 1),
-            SetForegroundColor(Color::DarkGrey),
+            SetForegroundColor(theme[Element::DialogDim].foreground()),
             Print(instructions),
             ResetColor
         )?;
@@ -209,6 +211,7 @@ impl KernelSelector {
     }
 
     fn show_error<W: Write>(&self, writer: &mut W, message: &str) -> io::Result<()> {
+        let theme = theme::current();
         let (width, height) = terminal::size()?;
         let box_width = width.min(60);
 
@@ -218,7 +221,7 @@ impl KernelSelector {
         execute!(
             writer,
             cursor::MoveTo(start_col, start_row),
-            SetForegroundColor(Color::Red),
+            SetForegroundColor(theme[Element::DialogError].foreground()),
             Print("┌"),
             Print("─".repeat((box_width - 2) as usize)),
             Print("┐"),
@@ -228,11 +231,11 @@ impl KernelSelector {
         execute!(
             writer,
             cursor::MoveTo(start_col, start_row + 1),
-            SetForegroundColor(Color::Red),
+            SetForegroundColor(theme[Element::DialogError].foreground()),
             Print("│"),
             ResetColor,
             Print(format!(" {:<width$}", message, width = (box_width - 3) as usize)),
-            SetForegroundColor(Color::Red),
+            SetForegroundColor(theme[Element::DialogError].foreground()),
             Print("│"),
             ResetColor
         )?;
@@ -240,7 +243,7 @@ impl KernelSelector {
         execute!(
             writer,
             cursor::MoveTo(start_col, start_row + 2),
-            SetForegroundColor(Color::Red),
+            SetForegroundColor(theme[Element::DialogError].foreground()),
             Print("└"),
             Print("─".repeat((box_width - 2) as usize)),
             Print("┘"),
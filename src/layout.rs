@@ -0,0 +1,291 @@
+//! Screen layout shared by the editor, status bar, output pane and find bar
+
+use crate::splits::Splits;
+use crossterm::terminal;
+use serde::Deserialize;
+
+/// Smallest editor area kept when panes grow
+const MIN_EDITOR_ROWS: usize = 3;
+const MIN_EDITOR_COLS: usize = 20;
+/// Smallest output pane (including its title row)
+const MIN_OUTPUT_ROWS: usize = 3;
+const MIN_OUTPUT_COLS: usize = 20;
+/// Height of the find/replace bar
+const FIND_BAR_ROWS: u16 = 3;
+
+/// A rectangle of screen cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rect {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Rect {
+    pub fn new(x: u16, y: u16, width: u16, height: u16) -> Self {
+        Rect { x, y, width, height }
+    }
+
+    /// Check whether a screen cell lies inside the rectangle
+    pub fn contains(&self, col: u16, row: u16) -> bool {
+        col >= self.x && col < self.x + self.width && row >= self.y && row < self.y + self.height
+    }
+}
+
+/// Where the output pane sits relative to the editor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PanePlacement {
+    /// Below the editor and status bar
+    Bottom,
+    /// To the right of the editor, full height
+    Right,
+}
+
+/// Screen areas for one frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Areas {
+    /// The focused editor window
+    pub editor: Rect,
+    /// All the editor windows, split between them
+    pub windows: Rect,
+    pub status: Rect,
+    /// Output pane (its first row is the title line)
+    pub output: Option<Rect>,
+    /// Column between the editor and a right-hand output pane
+    pub divider: Option<Rect>,
+    pub find_bar: Option<Rect>,
+    /// Row of buffer tabs above everything else
+    pub tab_bar: Option<Rect>,
+}
+
+impl Areas {
+    /// Just the editor and status bar, filling the terminal
+    pub fn editor_only(width: u16, height: u16) -> Self {
+        let editor_rows = height.saturating_sub(1);
+        Areas {
+            editor: Rect::new(0, 0, width, editor_rows),
+            windows: Rect::new(0, 0, width, editor_rows),
+            status: Rect::new(0, editor_rows, width, 1),
+            output: None,
+            divider: None,
+            find_bar: None,
+            tab_bar: None,
+        }
+    }
+
+    /// The same areas moved down by `rows`
+    fn shifted_down(self, rows: u16) -> Self {
+        let shift = |rect: Rect| Rect { y: rect.y + rows, ..rect };
+        Areas {
+            editor: shift(self.editor),
+            windows: shift(self.windows),
+            status: shift(self.status),
+            output: self.output.map(shift),
+            divider: self.divider.map(shift),
+            find_bar: self.find_bar.map(shift),
+            tab_bar: self.tab_bar.map(shift),
+        }
+    }
+}
+
+/// Which panes are showing and how big they are
+pub struct Layout {
+    pub output_visible: bool,
+    pub placement: PanePlacement,
+    pub tab_bar: bool, // Show the buffer tabs along the top
+    pub splits: Splits, // Editor windows within the editor area
+    output_height: usize, // Rows of the output pane when at the bottom
+    output_width: usize,  // Columns of the output pane when on the right
+    dragging: bool,       // True while the pane boundary is being dragged
+}
+
+impl Layout {
+    pub fn new() -> Self {
+        Layout {
+            output_visible: true,
+            placement: PanePlacement::Bottom,
+            tab_bar: false,
+            splits: Splits::new(0),
+            output_height: 8,
+            output_width: 60,
+            dragging: false,
+        }
+    }
+
+    /// Set the output pane's size at the bottom (rows) and on the right (columns)
+    pub fn set_output_dimensions(&mut self, height: usize, width: usize) {
+        self.output_height = height;
+        self.output_width = width;
+    }
+
+    /// Areas for the current terminal size
+    pub fn areas(&self, find_bar: bool) -> Areas {
+        let (width, height) = terminal::size().unwrap_or((80, 24));
+        self.areas_for(width, height, find_bar)
+    }
+
+    /// Areas for a terminal of the given size; the find bar takes the output pane's place
+    pub fn areas_for(&self, width: u16, height: u16, find_bar: bool) -> Areas {
+        let areas = if self.tab_bar && height > 1 {
+            let areas = self.areas_below_tabs(width, height - 1, find_bar).shifted_down(1);
+            Areas { tab_bar: Some(Rect::new(0, 0, width, 1)), ..areas }
+        } else {
+            self.areas_below_tabs(width, height, find_bar)
+        };
+        Areas { editor: self.splits.window_rect(self.splits.focused(), areas.windows), ..areas }
+    }
+
+    /// Areas for everything but the tab bar, starting at the top of the given space
+    fn areas_below_tabs(&self, width: u16, height: u16, find_bar: bool) -> Areas {
+        if find_bar {
+            let bar_rows = FIND_BAR_ROWS.min(height.saturating_sub(1));
+            let editor_rows = height.saturating_sub(1 + bar_rows);
+            return Areas {
+                editor: Rect::new(0, 0, width, editor_rows),
+                windows: Rect::new(0, 0, width, editor_rows),
+                status: Rect::new(0, editor_rows, width, 1),
+                output: None,
+                divider: None,
+                find_bar: Some(Rect::new(0, editor_rows + 1, width, bar_rows)),
+                tab_bar: None,
+            };
+        }
+
+        if !self.output_visible {
+            return Areas::editor_only(width, height);
+        }
+
+        match self.placement {
+            PanePlacement::Bottom => {
+                let pane_rows = self.clamped_height(height) as u16;
+                let editor_rows = height.saturating_sub(1 + pane_rows);
+                Areas {
+                    editor: Rect::new(0, 0, width, editor_rows),
+                    windows: Rect::new(0, 0, width, editor_rows),
+                    status: Rect::new(0, editor_rows, width, 1),
+                    output: Some(Rect::new(0, editor_rows + 1, width, pane_rows)),
+                    divider: None,
+                    find_bar: None,
+                    tab_bar: None,
+                }
+            }
+            PanePlacement::Right => {
+                let pane_cols = self.clamped_width(width) as u16;
+                let editor_cols = width.saturating_sub(pane_cols + 1);
+                Areas {
+                    editor: Rect::new(0, 0, editor_cols, height.saturating_sub(1)),
+                    windows: Rect::new(0, 0, editor_cols, height.saturating_sub(1)),
+                    status: Rect::new(0, height.saturating_sub(1), editor_cols, 1),
+                    output: Some(Rect::new(editor_cols + 1, 0, pane_cols, height)),
+                    divider: Some(Rect::new(editor_cols, 0, 1, height)),
+                    find_bar: None,
+                    tab_bar: None,
+                }
+            }
+        }
+    }
+
+    /// Pane height after leaving room for the editor and status bar
+    fn clamped_height(&self, term_height: u16) -> usize {
+        let max = (term_height as usize).saturating_sub(MIN_EDITOR_ROWS + 1).max(MIN_OUTPUT_ROWS);
+        self.output_height.clamp(MIN_OUTPUT_ROWS, max)
+    }
+
+    /// Pane width after leaving room for the editor and divider
+    fn clamped_width(&self, term_width: u16) -> usize {
+        let max = (term_width as usize).saturating_sub(MIN_EDITOR_COLS + 1).max(MIN_OUTPUT_COLS);
+        self.output_width.clamp(MIN_OUTPUT_COLS, max)
+    }
+
+    /// Grow (positive) or shrink (negative) the output pane; returns false if it couldn't change
+    pub fn resize_output(&mut self, delta: i32) -> bool {
+        if !self.output_visible {
+            return false;
+        }
+        let (width, height) = terminal::size().unwrap_or((80, 24));
+        let height = self.rows_below_tabs(height);
+        let current = self.output_size(width, height);
+        let target = if delta >= 0 {
+            current + delta as usize
+        } else {
+            current.saturating_sub((-delta) as usize)
+        };
+        self.set_output_size(target, width, height);
+        self.output_size(width, height) != current
+    }
+
+    /// Terminal rows left once the tab bar is taken off
+    fn rows_below_tabs(&self, height: u16) -> u16 {
+        if self.tab_bar && height > 1 { height - 1 } else { height }
+    }
+
+    /// Pane size along its resizable axis (rows at the bottom, columns on the right)
+    fn output_size(&self, width: u16, height: u16) -> usize {
+        match self.placement {
+            PanePlacement::Bottom => self.clamped_height(height),
+            PanePlacement::Right => self.clamped_width(width),
+        }
+    }
+
+    /// Set the pane size along its resizable axis, keeping it within bounds
+    fn set_output_size(&mut self, size: usize, width: u16, height: u16) {
+        match self.placement {
+            PanePlacement::Bottom => {
+                self.output_height = size;
+                self.output_height = self.clamped_height(height);
+            }
+            PanePlacement::Right => {
+                self.output_width = size;
+                self.output_width = self.clamped_width(width);
+            }
+        }
+    }
+
+    /// Switch the output pane between the bottom and the right of the editor
+    pub fn toggle_placement(&mut self) -> PanePlacement {
+        self.placement = match self.placement {
+            PanePlacement::Bottom => PanePlacement::Right,
+            PanePlacement::Right => PanePlacement::Bottom,
+        };
+        self.output_visible = true;
+        self.placement
+    }
+
+    /// Check whether a screen cell is on the draggable pane boundary
+    /// (the status bar above a bottom pane, or the divider beside a right pane)
+    pub fn is_on_boundary(&self, col: u16, row: u16) -> bool {
+        let areas = self.areas(false);
+        match self.placement {
+            PanePlacement::Bottom => areas.output.is_some() && areas.status.contains(col, row),
+            PanePlacement::Right => areas.divider.is_some_and(|divider| divider.contains(col, row)),
+        }
+    }
+
+    /// Start dragging the pane boundary
+    pub fn start_drag(&mut self) {
+        self.dragging = true;
+    }
+
+    /// Check whether the pane boundary is being dragged
+    pub fn is_dragging(&self) -> bool {
+        self.dragging
+    }
+
+    /// Move the dragged boundary to the mouse position
+    pub fn drag_to(&mut self, col: u16, row: u16) {
+        let (width, height) = terminal::size().unwrap_or((80, 24));
+        match self.placement {
+            // The status bar follows the mouse; the pane takes the rows below it
+            PanePlacement::Bottom => self.set_output_size(height.saturating_sub(row + 1) as usize, width, self.rows_below_tabs(height)),
+            // The divider follows the mouse; the pane takes the columns right of it
+            PanePlacement::Right => self.set_output_size(width.saturating_sub(col + 1) as usize, width, height),
+        }
+    }
+
+    /// Stop dragging the pane boundary
+    pub fn end_drag(&mut self) {
+        self.dragging = false;
+    }
+}
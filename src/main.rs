@@ -13,6 +13,24 @@ mod kernel_selector;
 mod output_pane;
 mod autocomplete;
 mod event_loop;
+mod parameters;
+mod export;
+mod ansi;
+mod layout;
+mod tabs;
+mod splits;
+mod quick_open;
+mod actions;
+mod palette;
+mod config;
+mod theme;
+mod project_search;
+mod git;
+mod undo_history;
+mod undo_tree;
+mod undo_view;
+mod swap;
+mod recovery_prompt;
 
 use kernel::Kernel;
 
@@ -25,7 +43,8 @@ use std::io::{self, Write};
 
 fn debug_log(msg: &str) {
     use std::fs::OpenOptions;
-    if let Ok(mut log) = OpenOptions::new().create(true).append(true).open("/tmp/sage_debug.log") {
+    let Some(path) = crate::config::debug_log_path() else { return };
+    if let Ok(mut log) = OpenOptions::new().create(true).append(true).open(path) {
         let _ = writeln!(log, "{}", msg);
         let _ = log.flush();
     }
@@ -94,13 +113,24 @@ fn find_default_python() -> io::Result<String> {
 }
 
 /// Execute a Python file non-interactively
-fn execute_file(file_path: Option<String>, python_path: Option<String>) -> io::Result<()> {
+/// `params` override the values in the `##$$ parameters` cell, `output_path` receives the executed notebook,
+/// as a report in `report_format` if one was asked for
+/// `config_python` is the interpreter from the config, used when neither `--python` nor a shebang names one
+fn execute_file(
+    file_path: Option<String>,
+    python_path: Option<String>,
+    config_python: Option<String>,
+    params: Vec<(String, String)>,
+    output_path: Option<String>,
+    report_format: Option<export::ReportFormat>,
+) -> io::Result<()> {
     // Check if file path was provided
     let file_path = match file_path {
         Some(path) => path,
         None => {
             eprintln!("Error: No file specified for execution");
-            eprintln!("Usage: sage --execute <file.py> [--python <python_path>]");
+            eprintln!("Usage: sage --execute <file.py> [--python <python_path>] [-p key=value ...] [--output <executed.py|.ipynb|.html|.md>]");
+            eprintln!("       sage --export <html|md> <file.py> [--python <python_path>] [-p key=value ...] [--output <report>]");
             return Err(io::Error::new(io::ErrorKind::InvalidInput, "No file specified"));
         }
     };
@@ -112,6 +142,10 @@ fn execute_file(file_path: Option<String>, python_path: Option<String>) -> io::R
     }
 
     // Determine Python interpreter to use
+    let fallback_python = || match config_python {
+        Some(path) => Ok(path),
+        None => find_default_python(),
+    };
     let python_executable = match python_path {
         Some(path) => path,
         None => {
@@ -123,12 +157,12 @@ fn execute_file(file_path: Option<String>, python_path: Option<String>) -> io::R
                         shebang_python
                     } else {
                         eprintln!("Warning: Shebang interpreter '{}' not found, using system default", shebang_python);
-                        find_default_python()?
+                        fallback_python()?
                     }
                 }
                 None => {
-                    // No shebang found, discover Python interpreters on the system
-                    find_default_python()?
+                    // No shebang found, use the configured interpreter or discover one on the system
+                    fallback_python()?
                 }
             }
         }
@@ -146,7 +180,9 @@ fn execute_file(file_path: Option<String>, python_path: Option<String>) -> io::R
     let cells = cell::parse_cells(&rope);
 
     // If no cells with delimiters, just run the whole file with Python directly
-    if cells.len() == 1 && cells[0].start == 0 && cells[0].end == rope.len_bytes() {
+    // (parameters and executed output need the kernel, so those still go through it)
+    let has_delimiters = !(cells.len() == 1 && cells[0].start == 0 && cells[0].end == rope.len_bytes());
+    if !has_delimiters && params.is_empty() && output_path.is_none() {
         // No cell delimiters found - execute as a regular Python script
         let status = std::process::Command::new(&python_executable)
             .arg(&file_path)
@@ -177,18 +213,61 @@ fn execute_file(file_path: Option<String>, python_path: Option<String>) -> io::R
         return Err(io::Error::new(io::ErrorKind::Other, format!("Failed to connect to kernel: {}", e)));
     }
 
+    // Build the list of cells to run, injecting the parameter overrides if any were given
+    let mut run_cells: Vec<export::ExecutedCell> = Vec::new();
+    for cell in &cells {
+        let header = cell::get_cell_header(&rope, cell);
+        let source = if header.is_some() {
+            cell::get_cell_content(&rope, cell)
+        } else {
+            rope.slice(cell.start..cell.end).to_string()
+        };
+        run_cells.push(export::ExecutedCell {
+            header,
+            source,
+            cell_type: cell.cell_type.clone(),
+            tags: cell.tags.clone(),
+            result: None,
+        });
+    }
+
+    if !params.is_empty() {
+        let injected = export::ExecutedCell {
+            header: Some(format!("{} [{}]", cell::primary_delimiter(), parameters::INJECTED_PARAMETERS_TAG)),
+            source: parameters::parameter_override_code(&params),
+            cell_type: cell::CellType::Code,
+            tags: vec![parameters::INJECTED_PARAMETERS_TAG.to_string()],
+            result: None,
+        };
+
+        // Overrides go right after the parameters cell, or at the top if there is none
+        let insert_at = match cells.iter().position(|c| c.has_tag(parameters::PARAMETERS_TAG)) {
+            Some(idx) => idx + 1,
+            None => {
+                eprintln!("Warning: No '{} {}' cell found, injecting parameters at the top",
+                    cell::primary_delimiter(), parameters::PARAMETERS_TAG);
+                0
+            }
+        };
+        run_cells.insert(insert_at, injected);
+        eprintln!("Parameters: {}", parameters::describe_parameters(&params));
+    }
+
     // Execute each cell in order
-    for (cell_idx, cell) in cells.iter().enumerate() {
+    let mut failed = false;
+    for (cell_idx, run_cell) in run_cells.iter_mut().enumerate() {
         let cell_number = cell_idx + 1;
-        let code = cell::get_cell_content(&rope, cell);
 
-        // Skip empty cells
-        if code.trim().is_empty() {
+        // Skip empty, markdown and skip-tagged cells
+        if run_cell.cell_type == cell::CellType::Markdown
+            || run_cell.source.trim().is_empty()
+            || run_cell.tags.iter().any(|t| t == cell::TAG_SKIP)
+        {
             continue;
         }
 
         // Execute cell
-        match kernel.execute(&code) {
+        match kernel.execute(&run_cell.source) {
             Ok(result) => {
                 // Print outputs
                 for output in &result.outputs {
@@ -216,21 +295,42 @@ fn execute_file(file_path: Option<String>, python_path: Option<String>) -> io::R
                     }
                 }
 
-                // Stop on error
-                if !result.success {
+                let success = result.success;
+                run_cell.result = Some(result);
+
+                // Stop on error, unless the cell is expected to raise
+                if !success && run_cell.tags.iter().any(|t| t == cell::TAG_RAISES) {
+                    eprintln!("Cell {} raised as expected, continuing", cell_number);
+                } else if !success {
                     eprintln!("\nExecution stopped at cell {} due to error", cell_number);
-                    let _ = kernel.disconnect();
-                    std::process::exit(1);
+                    failed = true;
+                    break;
                 }
             }
             Err(e) => {
                 eprintln!("Cell {} kernel error: {}", cell_number, e);
-                let _ = kernel.disconnect();
-                std::process::exit(1);
+                failed = true;
+                break;
             }
         }
     }
 
+    // Write the executed notebook, including the failing cell's traceback
+    if let Some(path) = &output_path {
+        match export::write_executed_notebook(path, &run_cells, report_format) {
+            Ok(()) => eprintln!("Executed notebook written to {}", path),
+            Err(e) => {
+                eprintln!("Error writing executed notebook '{}': {}", path, e);
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        let _ = kernel.disconnect();
+        std::process::exit(1);
+    }
+
     // Disconnect kernel
     let _ = kernel.disconnect();
 
@@ -238,8 +338,6 @@ fn execute_file(file_path: Option<String>, python_path: Option<String>) -> io::R
 }
 
 fn main() -> io::Result<()> {
-    debug_log("=== SAGE DEBUG LOG ===");
-
     // Parse command line arguments
     let args: Vec<String> = std::env::args().collect();
 
@@ -247,6 +345,11 @@ fn main() -> io::Result<()> {
     let mut execute_mode = false;
     let mut python_path: Option<String> = None;
     let mut file_to_execute: Option<String> = None;
+    let mut extra_files: Vec<String> = Vec::new(); // Further files to open in their own tabs
+    let mut params: Vec<(String, String)> = Vec::new();
+    let mut output_path: Option<String> = None;
+    let mut output_lines: Option<usize> = None; // Overrides the config
+    let mut export_format: Option<export::ReportFormat> = None;
 
     let mut i = 1;
     while i < args.len() {
@@ -266,19 +369,95 @@ fn main() -> io::Result<()> {
                     i += 1;
                 }
             }
+            "-p" | "--param" => {
+                // Next argument should be key=value
+                if i + 1 < args.len() {
+                    match parameters::parse_parameter_arg(&args[i + 1]) {
+                        Some(param) => params.push(param),
+                        None => {
+                            eprintln!("Error: Invalid parameter '{}', expected key=value", args[i + 1]);
+                            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid parameter"));
+                        }
+                    }
+                    i += 1;
+                }
+            }
+            "--output" | "-o" => {
+                // Next argument should be the executed notebook path
+                if i + 1 < args.len() {
+                    output_path = Some(args[i + 1].clone());
+                    i += 1;
+                }
+            }
+            "--export" => {
+                // Next argument should be the report format
+                if i + 1 < args.len() {
+                    match export::ReportFormat::from_name(&args[i + 1]) {
+                        Some(format) => export_format = Some(format),
+                        None => {
+                            eprintln!("Error: Unknown export format '{}', expected html or md", args[i + 1]);
+                            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Unknown export format"));
+                        }
+                    }
+                    i += 1;
+                }
+            }
+            "--output-lines" => {
+                // Next argument should be the lines kept from each end of long outputs
+                if i + 1 < args.len() {
+                    match args[i + 1].parse() {
+                        Ok(lines) => output_lines = Some(lines),
+                        Err(_) => {
+                            eprintln!("Error: Invalid line count '{}' for --output-lines", args[i + 1]);
+                            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid line count"));
+                        }
+                    }
+                    i += 1;
+                }
+            }
             _ => {
                 // If not in execute mode and no flags, this is the file to open
                 if !execute_mode && file_to_execute.is_none() {
                     file_to_execute = Some(args[i].clone());
+                } else if !execute_mode {
+                    extra_files.push(args[i].clone());
                 }
             }
         }
         i += 1;
     }
 
+    // Exporting runs the notebook headlessly and writes the report next to it
+    if let Some(format) = export_format {
+        execute_mode = true;
+        if output_path.is_none() {
+            output_path = file_to_execute.as_deref().map(|path| export::report_path(path, format));
+        }
+    }
+
+    // A wrong --output is reported before the notebook spends any time running
+    if let Some(path) = output_path.as_deref().filter(|_| execute_mode) {
+        if let Err(e) = export::check_output_path(path, export_format) {
+            eprintln!("Error: {}", e);
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, e));
+        }
+    }
+
+    // Settings from the user config and the project's, which is found from the first file
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let project_dir = file_to_execute
+        .as_deref()
+        .and_then(|path| cwd.join(path).parent().map(|dir| dir.to_path_buf()))
+        .unwrap_or(cwd);
+    let settings = config::Settings::load(&project_dir);
+    debug_log("=== SAGE DEBUG LOG ===");
+
     // Handle execute mode
     if execute_mode {
-        return execute_file(file_to_execute, python_path);
+        for error in &settings.errors {
+            eprintln!("Warning: Config: {}", error);
+        }
+        return execute_file(file_to_execute, python_path, settings.config.kernel.python.clone(), params, output_path, export_format);
     }
 
     // Check if we're running in a terminal
@@ -356,6 +535,9 @@ fn main() -> io::Result<()> {
     // Enable bracketed paste mode
     execute!(io::stdout(), event::EnableBracketedPaste)?;
 
+    // Report focus changes, for auto-saving when the terminal loses focus
+    let _ = execute!(io::stdout(), event::EnableFocusChange);
+
     // Enable enhanced keyboard protocol for better key combination support
     // This helps disambiguate Ctrl+Backspace from Ctrl+H
     if let Ok(_) = execute!(
@@ -369,7 +551,7 @@ fn main() -> io::Result<()> {
         // Enhanced keyboard mode enabled successfully
     }
     
-    let mut editor = editor::Editor::new();
+    let mut editor = settings.new_editor();
     let mut renderer = renderer::Renderer::new()?;
 
     // Set initial help message
@@ -389,16 +571,49 @@ fn main() -> io::Result<()> {
         }
     }
     
+    // Open any further files in background tabs
+    let mut tabs = tabs::Tabs::new();
+    for path in extra_files {
+        let mut tab_editor = settings.new_editor();
+        match tab_editor.load_file(&path) {
+            Ok(_) => {},
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                tab_editor.set_file_path(&path);
+            },
+            Err(e) => {
+                eprintln!("Failed to load file: {}", e);
+                continue;
+            }
+        }
+        tabs.push(tab_editor);
+    }
+    
     // Initialize viewport to follow cursor
     editor.update_viewport_for_cursor();
     
+    // A panic leaves the terminal usable and writes out unsaved text still waiting for its swap file
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        // Panics on other threads (a kernel's reader, say) leave the editor running
+        if std::thread::current().name() == Some("main") {
+            restore_terminal();
+        }
+        swap::flush_on_panic();
+        default_hook(info);
+    }));
+
     // Main loop
-    let result = event_loop::run(&mut editor, &mut renderer);
+    let result = event_loop::run(&mut editor, &mut renderer, tabs, settings, output_lines);
+    if result.is_ok() {
+        // Every buffer was saved or its changes given up on when quitting
+        swap::remove_own();
+    }
     
     // Cleanup
     renderer.cleanup()?;
     execute!(io::stdout(), crossterm::event::DisableMouseCapture)?;
     execute!(io::stdout(), event::DisableBracketedPaste)?;
+    let _ = execute!(io::stdout(), event::DisableFocusChange);
     // Disable enhanced keyboard protocol
     let _ = execute!(io::stdout(), crossterm::event::PopKeyboardEnhancementFlags);
     disable_raw_mode()?;
@@ -410,6 +625,20 @@ fn main() -> io::Result<()> {
     Ok(())
 }
 
+/// Put the terminal back the way the shell expects it, ignoring what fails, for panics
+fn restore_terminal() {
+    let _ = execute!(
+        io::stdout(),
+        crossterm::cursor::Show,
+        crossterm::terminal::LeaveAlternateScreen,
+        crossterm::event::DisableMouseCapture,
+        event::DisableBracketedPaste,
+        event::DisableFocusChange,
+        crossterm::event::PopKeyboardEnhancementFlags
+    );
+    let _ = disable_raw_mode();
+}
+
 fn launch_in_terminal(terminal: &str, command: &[&str]) -> bool {
     // First check if the terminal exists
     if let Ok(output) = std::process::Command::new("which").arg(terminal).output() {
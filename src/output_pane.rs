@@ -1,12 +1,27 @@
 use crossterm::{
     cursor,
     execute,
-    terminal::{Clear, ClearType},
-    style::{Color, Print, ResetColor, SetForegroundColor},
+    style::{ContentStyle, Print, PrintStyledContent, ResetColor, SetBackgroundColor},
 };
-use std::io::{self, Write};
+use crossterm::event::{KeyCode, KeyModifiers};
+use crate::ansi::{parse_output, StyledLine};
+use crate::find_replace::find_matches;
+use crate::layout::Rect;
+use crate::swap;
+use crate::theme::{self, Element};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fs;
+use std::hash::BuildHasher;
+use std::io::{self, BufWriter, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Once;
 use std::time::{Duration, Instant};
 
+/// Lines kept from each end of an entry before the middle is dropped
+pub const DEFAULT_OUTPUT_LINES: usize = 500;
+
 #[derive(Debug, Clone)]
 pub struct OutputEntry {
     pub execution_count: usize,
@@ -16,8 +31,154 @@ pub struct OutputEntry {
     pub elapsed_secs: f64,
 }
 
+/// Parsed lines of one entry; long outputs keep only their first and last lines
+struct EntryLines {
+    head: Vec<StyledLine>,
+    tail: Vec<StyledLine>,
+    omitted: usize, // Lines dropped between head and tail
+    full_text: Option<PathBuf>, // Complete output, written out when lines were dropped
+}
+
+impl EntryLines {
+    /// Parse an entry's output, keeping `keep` lines from each end (0 keeps everything)
+    fn new(text: &str, keep: usize, write_full_text: impl FnOnce(&str) -> Option<PathBuf>) -> Self {
+        let total = text.lines().count();
+        if keep == 0 || total <= keep * 2 {
+            return EntryLines { head: parse_output(text), tail: Vec::new(), omitted: 0, full_text: None };
+        }
+
+        // Byte offsets where the head ends and the tail starts; colors set in the dropped
+        // middle don't carry into the tail
+        let head_end = text.match_indices('\n').nth(keep - 1).map(|(idx, _)| idx + 1).unwrap_or(text.len());
+        let tail_start = text.trim_end_matches('\n').rmatch_indices('\n').nth(keep - 1).map(|(idx, _)| idx + 1).unwrap_or(head_end);
+        let full_text = write_full_text(text);
+
+        EntryLines {
+            head: parse_output(&text[..head_end]),
+            tail: parse_output(&text[tail_start..]),
+            omitted: total.saturating_sub(keep * 2),
+            full_text,
+        }
+    }
+
+    /// Rows shown when the entry is expanded (the omitted marker counts as one)
+    fn len(&self) -> usize {
+        self.head.len() + usize::from(self.omitted > 0) + self.tail.len()
+    }
+
+    /// Lines of the output, counting the ones that were dropped
+    fn total_lines(&self) -> usize {
+        self.head.len() + self.omitted + self.tail.len()
+    }
+
+    /// Row `idx` of the expanded entry; None for the omitted marker
+    fn get(&self, idx: usize) -> Option<&StyledLine> {
+        if idx < self.head.len() {
+            return self.head.get(idx);
+        }
+        let marker_rows = usize::from(self.omitted > 0);
+        if idx < self.head.len() + marker_rows {
+            return None;
+        }
+        self.tail.get(idx - self.head.len() - marker_rows)
+    }
+
+    fn is_marker(&self, idx: usize) -> bool {
+        self.omitted > 0 && idx == self.head.len()
+    }
+}
+
+/// Directory for full-output files that only this user can get into. Files left there by
+/// sessions that are no longer running are removed the first time it's used
+fn private_temp_dir() -> io::Result<PathBuf> {
+    static SWEPT: Once = Once::new();
+    let user = std::env::var("USER").unwrap_or_default();
+    let dir = std::env::temp_dir().join(format!("sage-{}", user));
+    match fs::DirBuilder::new().mode(0o700).create(&dir) {
+        Err(e) if e.kind() != io::ErrorKind::AlreadyExists => return Err(e),
+        _ => {}
+    }
+    // Someone else's directory, or one others can write to, is left alone
+    let meta = fs::symlink_metadata(&dir)?;
+    if !meta.is_dir() || meta.permissions().mode() & 0o077 != 0 {
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, "temp directory isn't private"));
+    }
+    SWEPT.call_once(|| sweep_stale_files(&dir));
+    Ok(dir)
+}
+
+/// Remove full-output files whose session has ended; they're named `<pid>-<random>.txt`
+fn sweep_stale_files(dir: &Path) {
+    // Other sessions can only be told apart from dead ones on Linux
+    if !cfg!(target_os = "linux") {
+        return;
+    }
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let pid = name.to_str().and_then(|name| name.split('-').next()).and_then(|pid| pid.parse().ok());
+        if pid.is_some_and(|pid| pid != std::process::id() && !swap::still_running(pid)) {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Write an entry's full output to a new file with a name nobody can guess, returning its path
+fn write_full_text(text: &str) -> Option<PathBuf> {
+    let dir = private_temp_dir().ok()?;
+    for _ in 0..8 {
+        let random = RandomState::new().hash_one((std::process::id(), Instant::now()));
+        let path = dir.join(format!("{}-{:016x}.txt", std::process::id(), random));
+        match fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(&path) {
+            Ok(file) => {
+                if write_plain_text(file, text).is_err() {
+                    let _ = fs::remove_file(&path);
+                    return None;
+                }
+                return Some(path);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(_) => return None,
+        }
+    }
+    None
+}
+
+/// Write output as it appears on screen (escape codes and overwritten text removed)
+fn write_plain_text(file: fs::File, text: &str) -> io::Result<()> {
+    let mut writer = BufWriter::new(file);
+    for line in text.strip_suffix('\n').unwrap_or(text).split('\n') {
+        let parsed = parse_output(line);
+        writeln!(writer, "{}", parsed.first().map(|l| l.text.as_str()).unwrap_or(""))?;
+    }
+    writer.flush()
+}
+
+/// What a row of the flattened output list shows
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PaneLine {
+    /// Header of an entry
+    Header(usize),
+    /// Line of an entry's output as (entry index, line index)
+    Output(usize, usize),
+    /// Blank line after an entry
+    Blank(usize),
+}
+
+impl PaneLine {
+    fn entry_idx(self) -> usize {
+        match self {
+            PaneLine::Header(idx) | PaneLine::Output(idx, _) | PaneLine::Blank(idx) => idx,
+        }
+    }
+}
+
 pub struct OutputPane {
     outputs: Vec<OutputEntry>,
+    entry_lines: Vec<EntryLines>, // Parsed (ANSI/\r-applied) lines of each entry
+    row_starts: Vec<usize>, // First flattened row of each entry (hidden entries take no rows)
+    total_rows: usize, // Rows in the flattened list
+    keep_lines: usize, // Lines kept from each end of long outputs (0 keeps everything)
     scroll_offset: usize, // Line offset for scrolling
     horizontal_offset: usize, // Horizontal scroll offset
     focused: bool,
@@ -32,13 +193,25 @@ pub struct OutputPane {
     click_count: usize, // Count consecutive clicks
     last_click_position: Option<(usize, usize)>, // Last click position (line, col)
     output_start_row: u16, // Starting row of output pane on screen
+    output_start_col: u16, // Starting column of output pane on screen
     preferred_column: Option<usize>, // Preferred column for vertical movement
+    stale_cells: Vec<usize>, // Cell numbers whose latest output is out of date
+    collapsed: Vec<bool>, // Folded state of each entry (parallel to outputs)
+    latest_entries: HashMap<usize, usize>, // Index of the latest entry of each cell, by cell_line
+    errors_only: bool, // Only show entries that failed
+    find_query: Option<String>, // Text of the find box while it is open
+    find_results: Vec<(PaneLine, usize, usize)>, // Matches as (line, start_col, end_col)
+    find_current: usize, // Index of the current match in find_results
 }
 
 impl OutputPane {
     pub fn new() -> Self {
         OutputPane {
             outputs: Vec::new(),
+            entry_lines: Vec::new(),
+            row_starts: Vec::new(),
+            total_rows: 0,
+            keep_lines: DEFAULT_OUTPUT_LINES,
             scroll_offset: 0,
             horizontal_offset: 0,
             focused: false,
@@ -53,7 +226,303 @@ impl OutputPane {
             click_count: 0,
             last_click_position: None,
             output_start_row: 0,
+            output_start_col: 0,
             preferred_column: None,
+            stale_cells: Vec::new(),
+            collapsed: Vec::new(),
+            latest_entries: HashMap::new(),
+            errors_only: false,
+            find_query: None,
+            find_results: Vec::new(),
+            find_current: 0,
+        }
+    }
+
+    /// Set which cells are stale so their latest output is marked in the header
+    pub fn set_stale_cells(&mut self, stale_cells: Vec<usize>) {
+        self.stale_cells = stale_cells;
+    }
+
+    /// Header line for an entry - the latest output of a stale cell gets a [stale] marker
+    fn entry_header(&self, entry_idx: usize) -> String {
+        let entry = &self.outputs[entry_idx];
+        let is_latest = self.latest_entries.get(&entry.cell_line) == Some(&entry_idx);
+        let marker = if is_latest && self.stale_cells.contains(&entry.cell_line) { " [stale]" } else { "" };
+        if self.collapsed[entry_idx] {
+            let count = self.entry_lines[entry_idx].total_lines();
+            format!("▸ Cell {} ({:.3}s):{} {} line{}", entry.cell_line, entry.elapsed_secs, marker, count, if count == 1 { "" } else { "s" })
+        } else {
+            format!("▾ Cell {} ({:.3}s):{}", entry.cell_line, entry.elapsed_secs, marker)
+        }
+    }
+
+    /// Check whether an entry passes the errors-only filter
+    fn entry_shown(&self, entry_idx: usize) -> bool {
+        !self.errors_only || self.outputs[entry_idx].is_error
+    }
+
+    /// Rows an entry takes in the flattened list, honouring folds and the errors-only filter
+    fn entry_rows(&self, entry_idx: usize) -> usize {
+        if !self.entry_shown(entry_idx) {
+            0
+        } else if self.collapsed[entry_idx] {
+            2 // Header and blank line
+        } else {
+            2 + self.entry_lines[entry_idx].len()
+        }
+    }
+
+    /// Rebuild the row index after entries are added, folded or filtered
+    fn rebuild_index(&mut self) {
+        self.row_starts.clear();
+        let mut row = 0;
+        for entry_idx in 0..self.outputs.len() {
+            self.row_starts.push(row);
+            row += self.entry_rows(entry_idx);
+        }
+        self.total_rows = row;
+    }
+
+    /// What a row of the flattened list shows - a binary search, so only visible rows cost anything
+    fn line_at(&self, row: usize) -> Option<PaneLine> {
+        if row >= self.total_rows {
+            return None;
+        }
+        // Last entry starting at or before the row (entries that take no rows share a start
+        // with the shown entry after them, so this picks the shown one)
+        let entry_idx = self.row_starts.partition_point(|start| *start <= row).checked_sub(1)?;
+        let offset = row - self.row_starts[entry_idx];
+        let rows = self.entry_rows(entry_idx);
+        Some(if offset == 0 {
+            PaneLine::Header(entry_idx)
+        } else if offset + 1 == rows {
+            PaneLine::Blank(entry_idx)
+        } else {
+            PaneLine::Output(entry_idx, offset - 1)
+        })
+    }
+
+    /// Row of a line in the flattened list, if it is shown
+    fn row_of(&self, line: PaneLine) -> Option<usize> {
+        let entry_idx = line.entry_idx();
+        let rows = self.entry_rows(entry_idx);
+        let offset = match line {
+            PaneLine::Header(_) => 0,
+            PaneLine::Output(_, line_idx) => line_idx + 1,
+            PaneLine::Blank(_) => rows.checked_sub(1)?,
+        };
+        (offset < rows).then(|| self.row_starts[entry_idx] + offset)
+    }
+
+    /// Text of a line in the flattened list
+    fn line_text(&self, line: PaneLine) -> String {
+        match line {
+            PaneLine::Header(entry_idx) => self.entry_header(entry_idx),
+            PaneLine::Output(entry_idx, line_idx) => {
+                let lines = &self.entry_lines[entry_idx];
+                match lines.get(line_idx) {
+                    Some(styled) => styled.text.clone(),
+                    None => format!("··· {} more lines (o to open the full output) ···", lines.omitted),
+                }
+            }
+            PaneLine::Blank(_) => String::new(),
+        }
+    }
+
+    /// Text of a row of the flattened list (empty past the end)
+    fn row_text(&self, row: usize) -> String {
+        self.line_at(row).map(|line| self.line_text(line)).unwrap_or_default()
+    }
+
+    /// Entry the cursor is in
+    fn cursor_entry(&self) -> Option<usize> {
+        self.line_at(self.cursor_line).map(|line| line.entry_idx())
+    }
+
+    /// Put the cursor on an entry's header (if shown), or keep it within the list
+    fn move_cursor_to_entry(&mut self, entry_idx: Option<usize>) {
+        self.rebuild_index();
+        match entry_idx.and_then(|idx| self.row_of(PaneLine::Header(idx))) {
+            Some(header_line) => self.cursor_line = header_line,
+            None => self.cursor_line = self.cursor_line.min(self.total_rows.saturating_sub(1)),
+        }
+        self.cursor_col = 0;
+        self.selection_start = None;
+        self.preferred_column = None;
+        self.auto_scroll = false;
+        self.ensure_cursor_visible();
+    }
+
+    /// Fold or unfold the entry under the cursor
+    pub fn toggle_entry_at_cursor(&mut self) {
+        if let Some(entry_idx) = self.cursor_entry() {
+            self.collapsed[entry_idx] = !self.collapsed[entry_idx];
+            self.move_cursor_to_entry(Some(entry_idx));
+        }
+    }
+
+    /// Fold every entry, or unfold them all if they are already folded; returns true if now folded
+    pub fn toggle_collapse_all(&mut self) -> bool {
+        let entry_idx = self.cursor_entry();
+        let collapse = (0..self.outputs.len()).any(|idx| self.entry_shown(idx) && !self.collapsed[idx]);
+        self.collapsed.iter_mut().for_each(|collapsed| *collapsed = collapse);
+        self.move_cursor_to_entry(entry_idx);
+        collapse
+    }
+
+    /// Show only entries that failed, or everything again; returns the new state
+    pub fn toggle_errors_only(&mut self) -> bool {
+        let entry_idx = self.cursor_entry();
+        self.errors_only = !self.errors_only;
+        self.update_find_results();
+        self.move_cursor_to_entry(entry_idx);
+        self.errors_only
+    }
+
+    /// Move the cursor to the header of the next entry
+    pub fn next_entry(&mut self) {
+        let Some(current) = self.cursor_entry() else {
+            return;
+        };
+        if let Some(next) = (current + 1..self.outputs.len()).find(|idx| self.entry_shown(*idx)) {
+            self.move_cursor_to_entry(Some(next));
+        }
+    }
+
+    /// Move the cursor to the header of the previous entry (or of its own entry, if inside it)
+    pub fn prev_entry(&mut self) {
+        let Some(current) = self.cursor_entry() else {
+            return;
+        };
+        if self.row_of(PaneLine::Header(current)) != Some(self.cursor_line) {
+            self.move_cursor_to_entry(Some(current));
+        } else if let Some(prev) = (0..current).rev().find(|idx| self.entry_shown(*idx)) {
+            self.move_cursor_to_entry(Some(prev));
+        }
+    }
+
+    /// Full text of the entry under the cursor, as shown (escape codes removed)
+    pub fn cursor_entry_text(&self) -> Option<String> {
+        let entry_idx = self.cursor_entry()?;
+        let lines = &self.entry_lines[entry_idx];
+        if let Some(text) = lines.full_text.as_ref().and_then(|path| fs::read_to_string(path).ok()) {
+            return Some(text.trim_end_matches('\n').to_string());
+        }
+        let text: Vec<&str> = lines.head.iter().chain(&lines.tail).map(|line| line.text.as_str()).collect();
+        Some(text.join("\n"))
+    }
+
+    /// File holding the complete output of the entry under the cursor, if it was truncated
+    pub fn cursor_entry_full_text(&self) -> Option<PathBuf> {
+        self.entry_lines[self.cursor_entry()?].full_text.clone()
+    }
+
+    /// Set how many lines to keep from each end of long outputs (0 keeps everything)
+    pub fn set_keep_lines(&mut self, keep_lines: usize) {
+        self.keep_lines = keep_lines;
+    }
+
+    /// Open the find box
+    pub fn start_find(&mut self) {
+        if self.find_query.is_none() {
+            self.find_query = Some(String::new());
+            self.update_find_results();
+        }
+    }
+
+    /// Check whether the find box is open
+    pub fn is_finding(&self) -> bool {
+        self.find_query.is_some()
+    }
+
+    /// Close the find box and drop its highlights
+    pub fn close_find(&mut self) {
+        self.find_query = None;
+        self.find_results.clear();
+        self.find_current = 0;
+    }
+
+    /// Handle a key while the find box is open
+    pub fn handle_find_input(&mut self, code: KeyCode, modifiers: KeyModifiers) {
+        let Some(query) = self.find_query.as_mut() else {
+            return;
+        };
+        match code {
+            KeyCode::Esc => self.close_find(),
+            KeyCode::Enter if modifiers.contains(KeyModifiers::SHIFT) => self.find_step(false),
+            KeyCode::Enter | KeyCode::Down => self.find_step(true),
+            KeyCode::Up => self.find_step(false),
+            KeyCode::Backspace => {
+                query.pop();
+                self.update_find_results();
+                self.jump_to_current_match();
+            }
+            KeyCode::Char(c) if !modifiers.contains(KeyModifiers::CONTROL) && !modifiers.contains(KeyModifiers::ALT) => {
+                query.push(c);
+                self.update_find_results();
+                self.jump_to_current_match();
+            }
+            _ => {}
+        }
+    }
+
+    /// Search every shown entry (folded ones included) for the find box text
+    fn update_find_results(&mut self) {
+        self.find_results.clear();
+        let query = match self.find_query.as_deref() {
+            Some(query) if !query.is_empty() => query.to_string(),
+            _ => return,
+        };
+
+        for entry_idx in 0..self.outputs.len() {
+            if !self.entry_shown(entry_idx) {
+                continue;
+            }
+            let entry_lines = &self.entry_lines[entry_idx];
+            let lines = std::iter::once(PaneLine::Header(entry_idx))
+                .chain((0..entry_lines.len()).filter(|line| !entry_lines.is_marker(*line)).map(|line| PaneLine::Output(entry_idx, line)));
+            for line in lines {
+                let text = self.line_text(line);
+                for (start, end) in find_matches(&text, &query) {
+                    let start_col = text[..start].chars().count();
+                    let end_col = start_col + text[start..end].chars().count();
+                    self.find_results.push((line, start_col, end_col));
+                }
+            }
+        }
+        if self.find_current >= self.find_results.len() {
+            self.find_current = 0;
+        }
+    }
+
+    /// Move to the next (or previous) match
+    fn find_step(&mut self, forward: bool) {
+        let total = self.find_results.len();
+        if total == 0 {
+            return;
+        }
+        self.find_current = if forward {
+            (self.find_current + 1) % total
+        } else {
+            (self.find_current + total - 1) % total
+        };
+        self.jump_to_current_match();
+    }
+
+    /// Put the cursor on the current match, unfolding its entry if needed
+    fn jump_to_current_match(&mut self) {
+        let Some(&(line, start_col, _)) = self.find_results.get(self.find_current) else {
+            return;
+        };
+        self.collapsed[line.entry_idx()] = false;
+        self.rebuild_index();
+        if let Some(line_idx) = self.row_of(line) {
+            self.cursor_line = line_idx;
+            self.cursor_col = start_col;
+            self.selection_start = None;
+            self.preferred_column = None;
+            self.auto_scroll = false;
+            self.ensure_cursor_visible();
         }
     }
 
@@ -98,8 +567,16 @@ impl OutputPane {
         }
     }
 
-    pub fn add_output(&mut self, entry: OutputEntry) {
+    pub fn add_output(&mut self, mut entry: OutputEntry) {
+        // The lines keep what is shown; a long output's full text goes to a temp file
+        let text = std::mem::take(&mut entry.output);
+        let lines = EntryLines::new(&text, self.keep_lines, write_full_text);
+        self.entry_lines.push(lines);
+        self.collapsed.push(false);
+        self.latest_entries.insert(entry.cell_line, self.outputs.len());
         self.outputs.push(entry);
+        self.rebuild_index();
+        self.update_find_results();
         // Auto-scroll to bottom to show newest output
         self.scroll_to_bottom();
     }
@@ -116,9 +593,23 @@ impl OutputPane {
         self.selection_start = None;
     }
 
+    /// Delete the temp files holding truncated outputs
+    fn remove_full_text_files(&mut self) {
+        for path in self.entry_lines.iter_mut().filter_map(|lines| lines.full_text.take()) {
+            let _ = fs::remove_file(path);
+        }
+    }
+
     pub fn clear(&mut self) {
+        self.remove_full_text_files();
         self.outputs.clear();
+        self.latest_entries.clear();
+        self.entry_lines.clear();
+        self.collapsed.clear();
+        self.find_results.clear();
+        self.find_current = 0;
         self.scroll_offset = 0;
+        self.rebuild_index();
     }
 
     pub fn scroll_up(&mut self) {
@@ -266,12 +757,11 @@ impl OutputPane {
             self.selection_start = None;
         }
 
-        let lines = self.get_all_lines();
-        if self.cursor_line >= lines.len() {
+        if self.cursor_line >= self.count_total_lines() {
             return;
         }
 
-        let line_text = &lines[self.cursor_line].0;
+        let line_text = self.row_text(self.cursor_line);
 
         if self.cursor_col > 0 {
             // Find the previous word boundary within the current line
@@ -315,12 +805,11 @@ impl OutputPane {
             self.selection_start = None;
         }
 
-        let lines = self.get_all_lines();
-        if self.cursor_line >= lines.len() {
+        if self.cursor_line >= self.count_total_lines() {
             return;
         }
 
-        let line_text = &lines[self.cursor_line].0;
+        let line_text = self.row_text(self.cursor_line);
         let chars: Vec<char> = line_text.chars().collect();
         let line_len = chars.len();
 
@@ -371,16 +860,14 @@ impl OutputPane {
             self.selection_start = None;
         }
 
-        let lines = self.get_all_lines();
-
         // Search backwards for a non-empty line preceded by an empty line
         let mut target_line = None;
         for line_num in (0..self.cursor_line).rev() {
-            let line_text = &lines[line_num].0;
+            let line_text = self.row_text(line_num);
             let is_empty = line_text.is_empty();
 
             if !is_empty && line_num > 0 {
-                let prev_line = &lines[line_num - 1].0;
+                let prev_line = self.row_text(line_num - 1);
                 if prev_line.is_empty() {
                     target_line = Some(line_num);
                     break;
@@ -410,15 +897,14 @@ impl OutputPane {
             self.selection_start = None;
         }
 
-        let lines = self.get_all_lines();
-        let total_lines = lines.len();
+        let total_lines = self.count_total_lines();
 
         // Search forward for a non-empty line preceded by an empty line
         let mut found_empty = false;
         let mut target_line = None;
 
         for line_num in (self.cursor_line + 1)..total_lines {
-            let line_text = &lines[line_num].0;
+            let line_text = self.row_text(line_num);
             let is_empty = line_text.is_empty();
 
             if is_empty {
@@ -527,31 +1013,13 @@ impl OutputPane {
 
     /// Get the length of a specific line (in characters, not bytes)
     fn get_line_length(&self, line_idx: usize) -> usize {
-        let lines = self.get_all_lines();
-        if line_idx < lines.len() {
-            lines[line_idx].0.chars().count()  // Use char count, not byte length
-        } else {
-            0
-        }
-    }
-
-    /// Get all lines with metadata (for cursor operations)
-    fn get_all_lines(&self) -> Vec<(String, bool, bool)> {
-        let mut all_lines = Vec::new();
-        for entry in &self.outputs {
-            all_lines.push((format!("Cell {} ({:.3}s):", entry.cell_line, entry.elapsed_secs), true, false));
-            for line in entry.output.lines() {
-                all_lines.push((line.to_string(), false, entry.is_error));
-            }
-            all_lines.push((String::new(), false, false));
-        }
-        all_lines
+        self.row_text(line_idx).chars().count()  // Use char count, not byte length
     }
 
     /// Get selected text
     pub fn get_selected_text(&self) -> Option<String> {
         if let Some((start_line, start_col)) = self.selection_start {
-            let lines = self.get_all_lines();
+            let total_lines = self.count_total_lines();
             let (end_line, end_col) = (self.cursor_line, self.cursor_col);
 
             // Normalize selection (start should be before end)
@@ -563,8 +1031,8 @@ impl OutputPane {
                 };
 
             let mut selected = String::new();
-            for line_idx in sel_start_line..=sel_end_line.min(lines.len().saturating_sub(1)) {
-                let line_text = &lines[line_idx].0;
+            for line_idx in sel_start_line..=sel_end_line.min(total_lines.saturating_sub(1)) {
+                let line_text = &self.row_text(line_idx);
                 let line_chars: Vec<char> = line_text.chars().collect();
                 let line_char_count = line_chars.len();
 
@@ -604,72 +1072,81 @@ impl OutputPane {
 
     /// Count total lines across all output entries
     fn count_total_lines(&self) -> usize {
-        let mut total = 0;
-        for entry in &self.outputs {
-            // Header line
-            total += 1;
-            // Output lines
-            total += entry.output.lines().count();
-            // Blank line between entries
-            total += 1;
-        }
-        total
+        self.total_rows
     }
 
     pub fn is_empty(&self) -> bool {
         self.outputs.is_empty()
     }
 
-    pub fn draw<W: Write>(&mut self, writer: &mut W, start_row: u16, height: usize, width: u16) -> io::Result<()> {
-        // Save start row for mouse position calculations
+    /// Draw the pane into its area of the layout; the first row holds the title
+    pub fn draw<W: Write>(&mut self, writer: &mut W, area: Rect) -> io::Result<()> {
+        let (x, start_row, width) = (area.x, area.y, area.width);
+        let height = area.height as usize;
+        if height < 2 || width < 4 {
+            return Ok(());
+        }
+
+        // Save origin for mouse position calculations
         self.output_start_row = start_row;
+        self.output_start_col = x;
+        let theme = theme::current();
+        let base = theme[Element::Output];
 
         // Clear all rows in the output pane area first (to handle resizing)
-        for row in start_row..=(start_row + height as u16) {
+        for row in start_row..start_row + height as u16 {
             execute!(
                 writer,
-                cursor::MoveTo(0, row),
-                Clear(ClearType::CurrentLine)
+                cursor::MoveTo(x, row),
+                SetBackgroundColor(base.background()),
+                Print(" ".repeat(width as usize)),
+                ResetColor
             )?;
         }
 
         // Draw separator line
         execute!(
             writer,
-            cursor::MoveTo(0, start_row),
-            SetForegroundColor(Color::DarkGrey),
-            Print("─".repeat(width as usize)),
-            ResetColor
+            cursor::MoveTo(x, start_row),
+            PrintStyledContent(theme[Element::OutputBorder].over(base).content().apply("─".repeat(width as usize)))
         )?;
 
-        // Draw title
-        let title = if self.outputs.is_empty() {
-            " Output (Esc to focus, Ctrl+O to toggle, Ctrl+L to clear) "
+        // Draw title (the find box takes its place while open)
+        let title = if let Some(query) = &self.find_query {
+            let counter = if query.is_empty() {
+                String::new()
+            } else {
+                let current = if self.find_results.is_empty() { 0 } else { self.find_current + 1 };
+                format!(" [{}/{}]", current, self.find_results.len())
+            };
+            format!(" Find: {}{} (Enter/Shift+Enter next/prev, Esc close) ", query, counter)
+        } else if self.outputs.is_empty() {
+            " Output (Esc to focus, Ctrl+O to toggle, Ctrl+L to clear) ".to_string()
+        } else if self.errors_only {
+            " Output - errors only (e to show all, Enter fold, / find, y copy entry) ".to_string()
         } else {
-            " Output (Esc to focus, arrows/mouse to scroll, Ctrl+O/L to toggle/clear) "
+            " Output (Esc to focus, Enter fold, c/e fold all/errors, n/p entry, / find, y copy) ".to_string()
         };
+        let title: String = title.chars().take(width as usize - 4).collect();
+        let title_element = if self.find_query.is_some() { Element::OutputFindTitle } else { Element::OutputTitle };
         execute!(
             writer,
-            cursor::MoveTo(2, start_row),
-            SetForegroundColor(Color::Cyan),
-            Print(title),
-            ResetColor
+            cursor::MoveTo(x + 2, start_row),
+            PrintStyledContent(theme[title_element].over(base).content().apply(&title))
         )?;
 
         if self.outputs.is_empty() {
             // Show hint
+            let hint: String = "No output yet. Execute a cell with Ctrl+E or Ctrl+Enter".chars().take(width as usize - 4).collect();
             execute!(
                 writer,
-                cursor::MoveTo(2, start_row + 1),
-                SetForegroundColor(Color::DarkGrey),
-                Print("No output yet. Execute a cell with Ctrl+E or Ctrl+Enter"),
-                ResetColor
+                cursor::MoveTo(x + 2, start_row + 1),
+                PrintStyledContent(theme[Element::OutputDim].over(base).content().apply(hint))
             )?;
             return Ok(());
         }
 
         // Draw outputs with line-by-line scrolling
-        let mut current_row = start_row + 1;
         let max_row = start_row + height as u16;
 
         // Update viewport dimensions
@@ -686,19 +1163,13 @@ impl OutputPane {
             self.scroll_offset.min(total_lines.saturating_sub(1))
         };
 
-        // Build a flat list of all lines with their metadata
-        let mut all_lines: Vec<(String, bool, bool)> = Vec::new(); // (line_text, is_header, is_error)
-        for entry in &self.outputs {
-            // Add header line with elapsed time
-            all_lines.push((format!("Cell {} ({:.3}s):", entry.cell_line, entry.elapsed_secs), true, false));
-
-            // Add output lines (no truncation - horizontal scrolling will handle this)
-            for line in entry.output.lines() {
-                all_lines.push((line.to_string(), false, entry.is_error));
-            }
-
-            // Add blank line
-            all_lines.push((String::new(), false, false));
+        if total_lines == 0 {
+            let hint: String = "No errors. Press e to show all output".chars().take(width as usize - 4).collect();
+            execute!(
+                writer,
+                cursor::MoveTo(x + 2, start_row + 1),
+                PrintStyledContent(theme[Element::OutputDim].over(base).content().apply(hint))
+            )?;
         }
 
         // Calculate selection range if exists
@@ -715,18 +1186,27 @@ impl OutputPane {
         let mut cursor_screen_row = None;
         let mut cursor_screen_col = None;
 
-        // Draw lines starting from line_offset
-        for (absolute_line_idx, (line_text, is_header, is_error)) in all_lines.iter().enumerate() {
-            // Skip lines before line_offset
-            if absolute_line_idx < line_offset {
-                continue;
-            }
-
+        // Draw only the rows that fit, starting from line_offset
+        for absolute_line_idx in line_offset..(line_offset + display_lines).min(total_lines) {
+            let current_row = start_row + 1 + (absolute_line_idx - line_offset) as u16;
             if current_row >= max_row {
                 break;
             }
+            let Some(line) = self.line_at(absolute_line_idx) else {
+                break;
+            };
+            let line_text = self.line_text(line);
+            let (is_header, is_marker, is_error, styled) = match line {
+                PaneLine::Header(_) => (true, false, false, None),
+                // No truncation - horizontal scrolling will handle this
+                PaneLine::Output(entry_idx, line_idx) => {
+                    let lines = &self.entry_lines[entry_idx];
+                    (false, lines.is_marker(line_idx), self.outputs[entry_idx].is_error, lines.get(line_idx))
+                }
+                PaneLine::Blank(_) => (false, false, false, None),
+            };
 
-            let indent = if *is_header { 2 } else { 4 };
+            let indent = if is_header { 2 } else { 4 };
 
             // Apply horizontal scrolling - get visible portion of line
             // Use char-based indexing to avoid UTF-8 boundary panics
@@ -751,122 +1231,92 @@ impl OutputPane {
                 } else {
                     indent as u16
                 };
-                cursor_screen_col = Some(screen_col.min(width - 1));
+                cursor_screen_col = Some(x + screen_col.min(width - 1));
             }
 
-            // Draw line with selection highlighting
-            if let Some(((sel_start_line, sel_start_col), (sel_end_line, sel_end_col))) = selection_range {
-                if absolute_line_idx >= sel_start_line && absolute_line_idx <= sel_end_line {
-                    // This line has selection - adjust for horizontal offset
-                    let (sel_from, sel_to) = if absolute_line_idx == sel_start_line && absolute_line_idx == sel_end_line {
-                        (sel_start_col, sel_end_col)
-                    } else if absolute_line_idx == sel_start_line {
-                        (sel_start_col, line_text.len())
-                    } else if absolute_line_idx == sel_end_line {
-                        (0, sel_end_col)
-                    } else {
-                        (0, line_text.len())
-                    };
-
-                    // Translate selection to visible range
-                    let vis_sel_from = if sel_from > h_offset {
-                        sel_from - h_offset
-                    } else if sel_from == h_offset {
-                        0
-                    } else {
-                        // Selection starts before visible area
-                        0
-                    };
-                    let vis_sel_to = if sel_to > h_offset {
-                        (sel_to - h_offset).min(visible_line.len())
-                    } else {
-                        0
-                    };
-
-                    execute!(writer, cursor::MoveTo(indent as u16, current_row))?;
-
-                    // Use char-based slicing to avoid UTF-8 boundary issues
-                    let vis_line_chars: Vec<char> = visible_line.chars().collect();
-                    let vis_line_len = vis_line_chars.len();
-                    let vis_sel_from = vis_sel_from.min(vis_line_len);
-                    let vis_sel_to = vis_sel_to.min(vis_line_len);
-
-                    // Draw before selection
-                    if vis_sel_from > 0 {
-                        let before: String = vis_line_chars[..vis_sel_from].iter().collect();
-                        if *is_header {
-                            execute!(writer, SetForegroundColor(Color::Green), Print(before), ResetColor)?;
-                        } else if *is_error {
-                            execute!(writer, SetForegroundColor(Color::Red), Print(before), ResetColor)?;
-                        } else {
-                            execute!(writer, Print(before))?;
-                        }
-                    }
-
-                    // Draw selection (inverted colors)
-                    if vis_sel_to > vis_sel_from {
-                        let selected: String = vis_line_chars[vis_sel_from..vis_sel_to].iter().collect();
-                        execute!(writer, crossterm::style::SetBackgroundColor(crossterm::style::Color::White),
-                                 crossterm::style::SetForegroundColor(crossterm::style::Color::Black),
-                                 Print(selected),
-                                 crossterm::style::ResetColor)?;
-                    }
-
-                    // Draw after selection
-                    if vis_sel_to < vis_line_len {
-                        let after: String = vis_line_chars[vis_sel_to..].iter().collect();
-                        if *is_header {
-                            execute!(writer, SetForegroundColor(Color::Green), Print(after), ResetColor)?;
-                        } else if *is_error {
-                            execute!(writer, SetForegroundColor(Color::Red), Print(after), ResetColor)?;
-                        } else {
-                            execute!(writer, Print(after))?;
-                        }
-                    }
+            // Selected columns on this line, if any
+            let line_selection = selection_range.and_then(|((sel_start_line, sel_start_col), (sel_end_line, sel_end_col))| {
+                if absolute_line_idx < sel_start_line || absolute_line_idx > sel_end_line {
+                    return None;
+                }
+                let from = if absolute_line_idx == sel_start_line { sel_start_col } else { 0 };
+                let to = if absolute_line_idx == sel_end_line { sel_end_col } else { char_count };
+                Some((from, to))
+            });
+
+            // Find matches on this line as (start_col, end_col, is_current)
+            let line_matches: Vec<(usize, usize, bool)> = self.find_results.iter().enumerate()
+                .filter(|(_, (match_line, _, _))| *match_line == line)
+                .map(|(idx, (_, start, end))| (*start, *end, idx == self.find_current))
+                .collect();
+
+            // Draw the visible part in runs of the same style
+            execute!(writer, cursor::MoveTo(x + indent as u16, current_row))?;
+            let mut run = String::new();
+            let mut run_style = base.content();
+            for (i, ch) in visible_line.chars().enumerate() {
+                let col = h_offset + i;
+                let find_match = line_matches.iter().find(|(start, end, _)| col >= *start && col < *end);
+                let element = if line_selection.is_some_and(|(from, to)| col >= from && col < to) {
+                    Some(Element::OutputSelection)
+                } else if let Some((_, _, is_current)) = find_match {
+                    // Find matches use the editor's highlights
+                    Some(if *is_current { Element::FindMatchCurrent } else { Element::FindMatch })
+                } else if is_header {
+                    Some(Element::OutputHeader)
+                } else if is_marker {
+                    Some(Element::OutputDim)
                 } else {
-                    // No selection on this line
-                    execute!(writer, cursor::MoveTo(indent as u16, current_row))?;
-                    if *is_header {
-                        execute!(writer, SetForegroundColor(Color::Green), Print(visible_line), ResetColor)?;
-                    } else if *is_error {
-                        execute!(writer, SetForegroundColor(Color::Red), Print(visible_line), ResetColor)?;
-                    } else {
-                        execute!(writer, Print(visible_line))?;
+                    None
+                };
+                let style = if let Some(element) = element {
+                    theme[element].over(base).content()
+                } else if let Some(style) = styled.and_then(|line| line.style_at(col)) {
+                    // Colors from the program's own escape codes, on the pane's colours
+                    ContentStyle {
+                        foreground_color: style.foreground_color.or(base.fg),
+                        background_color: style.background_color.or(base.bg),
+                        ..style
                     }
-                }
-            } else {
-                // No selection anywhere
-                execute!(writer, cursor::MoveTo(indent as u16, current_row))?;
-                if *is_header {
-                    execute!(writer, SetForegroundColor(Color::Green), Print(visible_line), ResetColor)?;
-                } else if *is_error {
-                    execute!(writer, SetForegroundColor(Color::Red), Print(visible_line), ResetColor)?;
+                } else if is_error {
+                    theme[Element::OutputError].over(base).content()
                 } else {
-                    execute!(writer, Print(visible_line))?;
+                    base.content()
+                };
+
+                if style != run_style && !run.is_empty() {
+                    execute!(writer, PrintStyledContent(run_style.apply(run.as_str())))?;
+                    run.clear();
                 }
+                run_style = style;
+                run.push(ch);
+            }
+            if !run.is_empty() {
+                execute!(writer, PrintStyledContent(run_style.apply(run.as_str())))?;
             }
-
-            current_row += 1;
         }
 
         // Show scroll indicator if not showing all lines
-        if line_offset > 0 || line_offset + display_lines < all_lines.len() {
+        if line_offset > 0 || line_offset + display_lines < total_lines {
             let scroll_info = format!(" {}-{}/{} ",
                 line_offset + 1,
-                (line_offset + display_lines).min(all_lines.len()),
-                all_lines.len()
+                (line_offset + display_lines).min(total_lines),
+                total_lines
             );
             execute!(
                 writer,
-                cursor::MoveTo(width.saturating_sub(scroll_info.len() as u16 + 2), start_row),
-                SetForegroundColor(Color::DarkGrey),
-                Print(scroll_info),
-                ResetColor
+                cursor::MoveTo(x + width.saturating_sub(scroll_info.len() as u16 + 2), start_row),
+                PrintStyledContent(theme[Element::OutputDim].over(base).content().apply(scroll_info))
             )?;
         }
 
         // Show cursor if focused and visible (AFTER drawing scroll indicator)
-        if self.focused {
+        if let Some(query) = &self.find_query {
+            // Cursor sits at the end of the find box text
+            let col = 2 + " Find: ".len() + query.chars().count();
+            write!(writer, "\x1b[2 q")?;
+            execute!(writer, cursor::MoveTo(x + (col as u16).min(width.saturating_sub(1)), start_row), crossterm::cursor::Show)?;
+        } else if self.focused {
             if let (Some(row), Some(col)) = (cursor_screen_row, cursor_screen_col) {
                 // Set cursor style based on whether we have a selection
                 if self.selection_start.is_some() {
@@ -914,13 +1364,10 @@ impl OutputPane {
         }
 
         // Get the line to determine indent
-        let lines = self.get_all_lines();
-        if line_idx >= lines.len() {
-            return None;
-        }
-
-        let (line_text, is_header, _) = &lines[line_idx];
-        let indent = if *is_header { 2 } else { 4 };
+        let line = self.line_at(line_idx)?;
+        let line_text = self.line_text(line);
+        let indent = if matches!(line, PaneLine::Header(_)) { 2 } else { 4 };
+        let screen_col = screen_col.saturating_sub(self.output_start_col as usize);
 
         // Calculate column position considering horizontal scroll and indent
         let col = if screen_col < indent {
@@ -1042,10 +1489,7 @@ impl OutputPane {
                     self.scroll_offset.min(total_lines.saturating_sub(1))
                 };
                 self.cursor_line = (line_offset + self.viewport_height - 1).min(total_lines.saturating_sub(1));
-                let lines = self.get_all_lines();
-                if self.cursor_line < lines.len() {
-                    self.cursor_col = lines[self.cursor_line].0.chars().count();
-                }
+                self.cursor_col = self.get_line_length(self.cursor_line);
                 self.auto_scroll = false;
             }
             // Normal case: mouse is within the output pane
@@ -1072,12 +1516,11 @@ impl OutputPane {
 
     /// Select the word at the given position
     fn select_word_at(&mut self, line: usize, col: usize) {
-        let lines = self.get_all_lines();
-        if line >= lines.len() {
+        if line >= self.count_total_lines() {
             return;
         }
 
-        let line_text = &lines[line].0;
+        let line_text = self.row_text(line);
         let chars: Vec<char> = line_text.chars().collect();
 
         if col >= chars.len() {
@@ -1110,12 +1553,11 @@ impl OutputPane {
 
     /// Select the entire line at the given position
     fn select_line_at(&mut self, line: usize) {
-        let lines = self.get_all_lines();
-        if line >= lines.len() {
+        if line >= self.count_total_lines() {
             return;
         }
 
-        let line_text = &lines[line].0;
+        let line_text = self.row_text(line);
         let line_len = line_text.chars().count();
 
         // Select from start to end of line
@@ -1126,3 +1568,9 @@ impl OutputPane {
         self.ensure_cursor_visible();
     }
 }
+
+impl Drop for OutputPane {
+    fn drop(&mut self) {
+        self.remove_full_text_files();
+    }
+}
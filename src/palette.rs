@@ -0,0 +1,235 @@
+//! Command palette: search every action by name and run the one picked
+
+use crate::actions::{self, Action, Keymap};
+use crate::quick_open::fuzzy_match;
+use crate::theme::{self, Element};
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{self, Event, KeyCode, KeyModifiers},
+    execute,
+    style::{Attribute, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor},
+    terminal,
+};
+use std::io::{self, Write};
+use unicode_width::UnicodeWidthStr;
+
+struct Entry {
+    action: Action,
+    label: String, // "Category: Title"
+    keys: String,  // Bound keys, comma separated
+}
+
+pub struct CommandPalette {
+    entries: Vec<Entry>,
+    query: String,
+    matches: Vec<(usize, Vec<usize>)>, // Entry index and matched character positions, ranked
+    selected: usize,
+    scroll: usize,
+}
+
+impl CommandPalette {
+    pub fn new(keymap: &Keymap) -> Self {
+        let entries = actions::ACTIONS
+            .iter()
+            .filter(|info| info.action != Action::CommandPalette)
+            .map(|info| Entry {
+                action: info.action.clone(),
+                label: format!("{}: {}", info.category, info.title),
+                keys: keymap
+                    .keys_for(&info.action)
+                    .iter()
+                    .map(|key| key.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            })
+            .collect();
+
+        let mut palette = CommandPalette { entries, query: String::new(), matches: Vec::new(), selected: 0, scroll: 0 };
+        palette.rank();
+        palette
+    }
+
+    /// Show the palette until an action is picked or the user cancels
+    pub fn run<W: Write>(&mut self, writer: &mut W) -> io::Result<Option<Action>> {
+        loop {
+            self.draw(writer)?;
+
+            match event::read()? {
+                Event::Key(key) => {
+                    if key.kind == event::KeyEventKind::Release {
+                        continue;
+                    }
+
+                    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                    match key.code {
+                        KeyCode::Esc | KeyCode::F(1) => return Ok(None),
+                        KeyCode::Char('c') if ctrl => return Ok(None),
+                        KeyCode::Enter => {
+                            if let Some((idx, _)) = self.matches.get(self.selected) {
+                                return Ok(Some(self.entries[*idx].action.clone()));
+                            }
+                        }
+                        KeyCode::Up => self.move_selection(-1),
+                        KeyCode::Down => self.move_selection(1),
+                        KeyCode::Char('k') if ctrl => self.move_selection(-1),
+                        KeyCode::Char('j') if ctrl => self.move_selection(1),
+                        KeyCode::PageUp => self.move_selection(-10),
+                        KeyCode::PageDown => self.move_selection(10),
+                        KeyCode::Char('u') if ctrl => {
+                            self.query.clear();
+                            self.rank();
+                        }
+                        KeyCode::Backspace => {
+                            self.query.pop();
+                            self.rank();
+                        }
+                        KeyCode::Char(c) if !ctrl => {
+                            self.query.push(c);
+                            self.rank();
+                        }
+                        _ => {}
+                    }
+                }
+                Event::Resize(_, _) => {}
+                _ => continue,
+            }
+        }
+    }
+
+    /// Rank the entries against the query; with no query they keep their listed order
+    fn rank(&mut self) {
+        let query: Vec<char> = self.query.to_lowercase().chars().filter(|c| !c.is_whitespace()).collect();
+        let mut scored: Vec<(i64, usize, Vec<usize>)> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, entry)| fuzzy_match(&query, &entry.label).map(|(score, positions)| (score, idx, positions)))
+            .collect();
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+
+        self.matches = scored.into_iter().map(|(_, idx, positions)| (idx, positions)).collect();
+        self.selected = 0;
+        self.scroll = 0;
+    }
+
+    fn move_selection(&mut self, delta: i64) {
+        if self.matches.is_empty() {
+            return;
+        }
+        let last = self.matches.len() as i64 - 1;
+        self.selected = (self.selected as i64 + delta).clamp(0, last) as usize;
+    }
+
+    fn draw<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        let theme = theme::current();
+        let dialog = theme[Element::Dialog];
+        let (width, height) = terminal::size()?;
+        let box_width = (width as usize * 9 / 10).clamp(20.min(width as usize), 90);
+        let box_height = (height as usize * 3 / 4).clamp(8.min(height as usize), height as usize);
+        let x = (width as usize - box_width) as u16 / 2;
+        let y = (height as usize - box_height) as u16 / 3;
+        let inner = box_width.saturating_sub(2);
+        let list_rows = box_height.saturating_sub(4);
+
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + list_rows {
+            self.scroll = self.selected + 1 - list_rows;
+        }
+
+        execute!(writer, Hide, SetBackgroundColor(dialog.background()), SetForegroundColor(dialog.foreground()))?;
+
+        // Top border with the title
+        let title = " Commands ";
+        execute!(
+            writer,
+            MoveTo(x, y),
+            Print("╭─"),
+            SetAttribute(Attribute::Bold),
+            SetForegroundColor(theme[Element::DialogTitle].foreground()),
+            Print(title),
+            SetAttribute(Attribute::Reset),
+            SetBackgroundColor(dialog.background()),
+            SetForegroundColor(dialog.foreground()),
+            Print(format!("{}╮", "─".repeat(inner.saturating_sub(title.len() + 1))))
+        )?;
+
+        // Query line, with the match count on the right
+        let count = format!("{}/{}", self.matches.len(), self.entries.len());
+        let query_width = inner.saturating_sub(count.len() + 5);
+        let query: String = {
+            let chars: Vec<char> = self.query.chars().collect();
+            chars[chars.len().saturating_sub(query_width)..].iter().collect()
+        };
+        execute!(
+            writer,
+            MoveTo(x, y + 1),
+            Print("│ > "),
+            SetForegroundColor(theme[Element::DialogTitle].foreground()),
+            Print(format!("{:<width$}", query, width = query_width)),
+            SetForegroundColor(theme[Element::DialogDim].foreground()),
+            Print(format!(" {} ", count)),
+            SetForegroundColor(dialog.foreground()),
+            Print("│"),
+            MoveTo(x, y + 2),
+            Print(format!("├{}┤", "─".repeat(inner)))
+        )?;
+
+        for row in 0..list_rows {
+            execute!(writer, MoveTo(x, y + 3 + row as u16), SetBackgroundColor(dialog.background()), SetForegroundColor(dialog.foreground()), Print("│"))?;
+            self.draw_row(writer, self.scroll + row, inner)?;
+            execute!(writer, SetBackgroundColor(dialog.background()), SetForegroundColor(dialog.foreground()), Print("│"))?;
+        }
+
+        // Bottom border with the key help
+        let help = " Enter: Run  Esc: Cancel  ↑↓: Select ";
+        let help_x = x as usize + box_width.saturating_sub(help.chars().count() + 2);
+        execute!(
+            writer,
+            MoveTo(x, y + 3 + list_rows as u16),
+            Print(format!("╰{}╯", "─".repeat(inner))),
+            MoveTo(help_x as u16, y + 3 + list_rows as u16),
+            SetForegroundColor(theme[Element::DialogDim].foreground()),
+            Print(help),
+            ResetColor
+        )?;
+
+        // Cursor at the end of the query
+        execute!(writer, MoveTo(x + 4 + query.width() as u16, y + 1), Show)?;
+        writer.flush()
+    }
+
+    /// One action: its label with the matched characters highlighted, and its keys on the right
+    fn draw_row<W: Write>(&self, writer: &mut W, idx: usize, width: usize) -> io::Result<()> {
+        let Some((entry_idx, positions)) = self.matches.get(idx) else {
+            return execute!(writer, Print(" ".repeat(width)));
+        };
+        let entry = &self.entries[*entry_idx];
+        let theme = theme::current();
+        let dialog = theme[Element::Dialog];
+        let style = if idx == self.selected { theme[Element::DialogSelected].over(dialog) } else { dialog };
+        execute!(writer, SetBackgroundColor(style.background()), Print(" "))?;
+
+        let keys_width = entry.keys.width().min(width / 2);
+        let room = width.saturating_sub(keys_width + 3);
+        let mut used = 0;
+        for (pos, ch) in entry.label.chars().enumerate().take(room) {
+            let color = if positions.contains(&pos) {
+                theme[Element::DialogMatch].foreground()
+            } else {
+                style.foreground()
+            };
+            execute!(writer, SetForegroundColor(color), Print(ch))?;
+            used += 1;
+        }
+        let keys: String = entry.keys.chars().take(keys_width).collect();
+        execute!(
+            writer,
+            Print(" ".repeat(width.saturating_sub(used + keys_width + 2))),
+            SetForegroundColor(theme[Element::DialogDim].foreground()),
+            Print(keys),
+            Print(" "),
+            SetBackgroundColor(dialog.background())
+        )
+    }
+}
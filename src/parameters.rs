@@ -0,0 +1,121 @@
+//! Parameter injection for headless notebook runs (`sage --execute file.py -p key=value`)
+
+/// Tag on the delimiter line that marks the cell holding default parameter values
+pub const PARAMETERS_TAG: &str = "parameters";
+
+/// Tag written on the delimiter line of the generated override cell
+pub const INJECTED_PARAMETERS_TAG: &str = "injected-parameters";
+
+/// Parse a `key=value` command line argument
+pub fn parse_parameter_arg(arg: &str) -> Option<(String, String)> {
+    let (key, value) = arg.split_once('=')?;
+    let key = key.trim();
+
+    // The key has to be usable as a Python identifier
+    let mut chars = key.chars();
+    let first = chars.next()?;
+    if !(first.is_alphabetic() || first == '_') || !chars.all(|c| c.is_alphanumeric() || c == '_') {
+        return None;
+    }
+
+    Some((key.to_string(), value.to_string()))
+}
+
+/// Render a command line value as a Python literal
+/// Numbers, booleans and None are passed through, everything else becomes a string
+pub fn python_literal(value: &str) -> String {
+    let trimmed = value.trim();
+
+    if matches!(trimmed, "True" | "False" | "None") {
+        return trimmed.to_string();
+    }
+
+    // Only plain decimal numbers - Rust also accepts "inf"/"nan" which Python doesn't
+    let looks_numeric = !trimmed.is_empty()
+        && trimmed.chars().all(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E' | '_'))
+        && trimmed.chars().any(|c| c.is_ascii_digit());
+    // Python 3 rejects integers with leading zeros such as 007
+    let digits = trimmed.trim_start_matches(['-', '+']);
+    let leading_zero = trimmed.parse::<i64>().is_ok() && digits.starts_with('0') && digits != "0";
+    if looks_numeric && !leading_zero && (trimmed.parse::<i64>().is_ok() || trimmed.parse::<f64>().is_ok()) {
+        return trimmed.to_string();
+    }
+
+    let mut literal = String::with_capacity(value.len() + 2);
+    literal.push('\'');
+    for ch in value.chars() {
+        match ch {
+            '\\' => literal.push_str("\\\\"),
+            '\'' => literal.push_str("\\'"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            _ => literal.push(ch),
+        }
+    }
+    literal.push('\'');
+    literal
+}
+
+/// Build the source of the cell that overrides the notebook's default parameters
+pub fn parameter_override_code(params: &[(String, String)]) -> String {
+    let mut code = String::from("# Parameters\n");
+    for (key, value) in params {
+        code.push_str(&format!("{} = {}\n", key, python_literal(value)));
+    }
+    code
+}
+
+/// One-line summary of the parameter values used, for logging
+pub fn describe_parameters(params: &[(String, String)]) -> String {
+    params
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, python_literal(value)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parameter_keys_must_be_identifiers() {
+        assert_eq!(parse_parameter_arg("alpha=0.5"), Some(("alpha".to_string(), "0.5".to_string())));
+        assert_eq!(parse_parameter_arg(" name =a=b"), Some(("name".to_string(), "a=b".to_string())));
+        assert_eq!(parse_parameter_arg("_x="), Some(("_x".to_string(), String::new())));
+        assert_eq!(parse_parameter_arg("1x=2"), None);
+        assert_eq!(parse_parameter_arg("a-b=2"), None);
+        assert_eq!(parse_parameter_arg("=2"), None);
+        assert_eq!(parse_parameter_arg("novalue"), None);
+    }
+
+    #[test]
+    fn numbers_pass_through() {
+        assert_eq!(python_literal("42"), "42");
+        assert_eq!(python_literal("+5"), "+5");
+        assert_eq!(python_literal("-3"), "-3");
+        assert_eq!(python_literal("0"), "0");
+        assert_eq!(python_literal(".5"), ".5");
+        assert_eq!(python_literal("1e3"), "1e3");
+        assert_eq!(python_literal(" 2.5 "), "2.5");
+    }
+
+    #[test]
+    fn leading_zero_integers_are_strings() {
+        assert_eq!(python_literal("007"), "'007'");
+        assert_eq!(python_literal("-01"), "'-01'");
+        assert_eq!(python_literal("00"), "'00'");
+        assert_eq!(python_literal("0.5"), "0.5");
+    }
+
+    #[test]
+    fn other_values_are_quoted() {
+        assert_eq!(python_literal("inf"), "'inf'");
+        assert_eq!(python_literal("nan"), "'nan'");
+        assert_eq!(python_literal("True"), "True");
+        assert_eq!(python_literal("None"), "None");
+        assert_eq!(python_literal("it's"), "'it\\'s'");
+        assert_eq!(python_literal("a\\b\n"), "'a\\\\b\\n'");
+    }
+}
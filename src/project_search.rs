@@ -0,0 +1,704 @@
+//! Project search: find, and replace, a query in every file under the working directory
+
+use crate::find_replace::{Search, SearchOptions};
+use crate::quick_open;
+use crate::theme::{self, Element, Style};
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{self, Event, KeyCode, KeyModifiers},
+    execute,
+    style::{Attribute, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor},
+    terminal,
+};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use unicode_width::UnicodeWidthChar;
+
+/// Larger files are skipped
+const MAX_FILE_BYTES: u64 = 4 * 1024 * 1024;
+/// Stop searching after this many matched lines
+const MAX_MATCHED_LINES: usize = 10_000;
+/// Lines shown above and below each match
+const CONTEXT_LINES: usize = 1;
+/// Wait this long after the last key before searching again
+const SEARCH_DELAY: Duration = Duration::from_millis(150);
+
+/// A line with at least one match
+struct LineMatch {
+    line: usize,                 // Zero-based line number
+    text: String,                // The line, without its ending
+    ranges: Vec<(usize, usize)>, // Byte ranges of the matches in `text`
+    included: bool,              // Replaced when the replacements are applied
+}
+
+/// Everything found in one file
+struct FileMatches {
+    path: String,                  // Relative to the root, '/'-separated
+    lines: Vec<LineMatch>,
+    context: Vec<(usize, String)>, // Unmatched lines shown around the matches, by line number
+}
+
+/// A row of the result list
+#[derive(Clone, Copy)]
+enum Row {
+    File(usize),             // File header
+    Match(usize, usize),     // File and index into its matched lines
+    Context(usize, usize),   // File and index into its context lines
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+    Find,
+    Replace,
+    Results,
+}
+
+/// What the user chose in the panel
+pub enum ProjectSearchResult {
+    /// Open a file with a match selected: line, and byte range within the line
+    Open { path: PathBuf, line: usize, range: (usize, usize) },
+    /// Replace every match of `search` on the listed lines of each file
+    Replace { search: Search, replace: String, files: Vec<(PathBuf, Vec<usize>)> },
+}
+
+pub struct ProjectSearch {
+    root: PathBuf,
+    open_buffers: Arc<HashMap<String, String>>, // Text of open buffers by relative path, searched instead of the disk
+    query: String,
+    replace: String,
+    options: SearchOptions,
+    field: Field,
+    replacing: bool,                  // Show replacements; set once the replace field is visited
+    search: Option<Search>,           // The query as searched, for the replacement preview
+    error: Option<String>,            // Why the query can't be searched
+    files: Vec<FileMatches>,
+    rows: Vec<Row>,
+    selected: usize,                  // Row index; always a file header or a match
+    scroll: usize,
+    found_rx: Option<Receiver<FileMatches>>, // Results of the running search, until it finishes
+    cancel: Arc<AtomicBool>,                 // Tells the running search to stop
+    search_at: Option<Instant>,              // When to search for a changed query
+}
+
+impl ProjectSearch {
+    /// A panel searching under `root`; `open_buffers` are the paths and texts of open buffers
+    pub fn new(root: PathBuf, open_buffers: Vec<(PathBuf, String)>, replacing: bool) -> Self {
+        let root = fs::canonicalize(&root).unwrap_or(root);
+        let open_buffers = open_buffers
+            .into_iter()
+            .filter_map(|(path, text)| {
+                let path = fs::canonicalize(&path).ok()?;
+                let relative = path.strip_prefix(&root).ok()?;
+                Some((relative.to_string_lossy().replace('\\', "/"), text))
+            })
+            .collect();
+
+        ProjectSearch {
+            root,
+            open_buffers: Arc::new(open_buffers),
+            query: String::new(),
+            replace: String::new(),
+            options: SearchOptions::default(),
+            field: Field::Find,
+            replacing,
+            search: None,
+            error: None,
+            files: Vec::new(),
+            rows: Vec::new(),
+            selected: 0,
+            scroll: 0,
+            found_rx: None,
+            cancel: Arc::new(AtomicBool::new(false)),
+            search_at: None,
+        }
+    }
+
+    /// Start with a query, e.g. the selected text
+    pub fn set_query(&mut self, query: &str) {
+        self.query = query.to_string();
+        self.search_at = Some(Instant::now());
+    }
+
+    /// Show the panel until a result is opened, replacements are applied, or the user cancels
+    pub fn run<W: Write>(&mut self, writer: &mut W) -> io::Result<Option<ProjectSearchResult>> {
+        let mut needs_redraw = true;
+        let result = loop {
+            if self.search_at.is_some_and(|at| Instant::now() >= at) {
+                self.start_search();
+                needs_redraw = true;
+            }
+            if self.receive_results() {
+                needs_redraw = true;
+            }
+            if needs_redraw {
+                self.draw(writer)?;
+                needs_redraw = false;
+            }
+
+            // Poll so results keep appearing while idle
+            if !event::poll(Duration::from_millis(50))? {
+                continue;
+            }
+            match event::read()? {
+                Event::Key(key) => {
+                    if key.kind == event::KeyEventKind::Release {
+                        continue;
+                    }
+                    if let Some(result) = self.handle_key(key.code, key.modifiers) {
+                        break result;
+                    }
+                    needs_redraw = true;
+                }
+                Event::Resize(_, _) => needs_redraw = true,
+                _ => {}
+            }
+        };
+        self.cancel.store(true, Ordering::Relaxed);
+        Ok(result)
+    }
+
+    /// Handle a key; Some when the panel should close, with what was chosen
+    fn handle_key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> Option<Option<ProjectSearchResult>> {
+        let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+        let alt = modifiers.contains(KeyModifiers::ALT);
+        match code {
+            KeyCode::Esc => return Some(None),
+            KeyCode::Char('c') if ctrl => return Some(None),
+            KeyCode::Enter => return self.open_selected().map(Some),
+            KeyCode::Char('r') if ctrl && self.replacing => return self.replacements().map(Some),
+            KeyCode::Tab => self.set_field(match self.field {
+                Field::Find => Field::Replace,
+                Field::Replace => Field::Results,
+                Field::Results => Field::Find,
+            }),
+            KeyCode::BackTab => self.set_field(match self.field {
+                Field::Find => Field::Results,
+                Field::Replace => Field::Find,
+                Field::Results => Field::Replace,
+            }),
+            KeyCode::Up => self.move_selection(-1),
+            KeyCode::Down => self.move_selection(1),
+            KeyCode::PageUp => self.move_selection(-10),
+            KeyCode::PageDown => self.move_selection(10),
+            KeyCode::Char('r') if alt => self.toggle_option(|options| options.regex = !options.regex),
+            KeyCode::Char('c') if alt => self.toggle_option(|options| options.match_case = !options.match_case),
+            KeyCode::Char('w') if alt => self.toggle_option(|options| options.whole_word = !options.whole_word),
+            KeyCode::Char(' ') if self.field == Field::Results => self.toggle_included(),
+            _ => self.edit_field(code, ctrl || alt),
+        }
+        None
+    }
+
+    fn set_field(&mut self, field: Field) {
+        self.field = field;
+        self.replacing |= field == Field::Replace;
+    }
+
+    /// Type into the find or replace field
+    fn edit_field(&mut self, code: KeyCode, modified: bool) {
+        let text = match self.field {
+            Field::Find => &mut self.query,
+            Field::Replace => &mut self.replace,
+            Field::Results => return,
+        };
+        match code {
+            KeyCode::Char('u') if modified => text.clear(),
+            KeyCode::Backspace => {
+                text.pop();
+            }
+            KeyCode::Char(c) if !modified => text.push(c),
+            _ => return,
+        }
+        // The preview follows the replace text; only the query needs a new search
+        if self.field == Field::Find {
+            self.search_at = Some(Instant::now() + SEARCH_DELAY);
+        }
+    }
+
+    fn toggle_option(&mut self, toggle: impl FnOnce(&mut SearchOptions)) {
+        toggle(&mut self.options);
+        self.search_at = Some(Instant::now());
+    }
+
+    /// Stop any search under way and start one for the current query
+    fn start_search(&mut self) {
+        self.search_at = None;
+        self.cancel.store(true, Ordering::Relaxed);
+        self.files.clear();
+        self.rows.clear();
+        self.selected = 0;
+        self.scroll = 0;
+        self.found_rx = None;
+        self.error = None;
+        self.search = None;
+        if self.query.is_empty() {
+            return;
+        }
+
+        let search = match Search::new(&self.query, self.options) {
+            Ok(search) => search,
+            Err(e) => {
+                self.error = Some(e);
+                return;
+            }
+        };
+        self.search = Some(search.clone());
+
+        let (tx, rx) = mpsc::channel();
+        self.cancel = Arc::new(AtomicBool::new(false));
+        let cancel = self.cancel.clone();
+        let root = self.root.clone();
+        let open_buffers = self.open_buffers.clone();
+        std::thread::spawn(move || search_files(root, search, open_buffers, cancel, tx));
+        self.found_rx = Some(rx);
+    }
+
+    /// Take any files the search has found since last time; returns true if anything changed
+    fn receive_results(&mut self) -> bool {
+        let mut received = false;
+        while let Some(rx) = &self.found_rx {
+            match rx.try_recv() {
+                Ok(file) => {
+                    self.add_rows(self.files.len(), &file);
+                    self.files.push(file);
+                    received = true;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    // Search finished - redraw once more to drop the "searching" marker
+                    self.found_rx = None;
+                    received = true;
+                }
+            }
+        }
+        received
+    }
+
+    /// Rows for a file: its header, then matched and context lines in line order
+    fn add_rows(&mut self, file_idx: usize, file: &FileMatches) {
+        self.rows.push(Row::File(file_idx));
+        let (mut m, mut c) = (0, 0);
+        while m < file.lines.len() || c < file.context.len() {
+            let context_first = match (file.lines.get(m), file.context.get(c)) {
+                (Some(line), Some((context_line, _))) => *context_line < line.line,
+                (None, Some(_)) => true,
+                _ => false,
+            };
+            if context_first {
+                self.rows.push(Row::Context(file_idx, c));
+                c += 1;
+            } else {
+                self.rows.push(Row::Match(file_idx, m));
+                m += 1;
+            }
+        }
+    }
+
+    fn move_selection(&mut self, delta: i64) {
+        let selectable: Vec<usize> =
+            (0..self.rows.len()).filter(|&idx| !matches!(self.rows[idx], Row::Context(..))).collect();
+        if selectable.is_empty() {
+            return;
+        }
+        let current = selectable.iter().position(|&idx| idx == self.selected).unwrap_or(0) as i64;
+        let next = (current + delta).clamp(0, selectable.len() as i64 - 1) as usize;
+        self.selected = selectable[next];
+    }
+
+    /// Leave the selected line (or every line of the selected file) out of the replacements, or put it back
+    fn toggle_included(&mut self) {
+        match self.rows.get(self.selected) {
+            Some(Row::File(file)) => {
+                let lines = &mut self.files[*file].lines;
+                let include = !lines.iter().all(|line| line.included);
+                lines.iter_mut().for_each(|line| line.included = include);
+            }
+            Some(Row::Match(file, line)) => {
+                let line = &mut self.files[*file].lines[*line];
+                line.included = !line.included;
+            }
+            _ => {}
+        }
+    }
+
+    /// The file and match to open for the selected row
+    fn open_selected(&self) -> Option<ProjectSearchResult> {
+        let (file, line) = match self.rows.get(self.selected)? {
+            Row::File(file) => (*file, 0),
+            Row::Match(file, line) => (*file, *line),
+            Row::Context(..) => return None,
+        };
+        let file = &self.files[file];
+        let line = file.lines.get(line)?;
+        Some(ProjectSearchResult::Open {
+            path: self.root.join(&file.path),
+            line: line.line,
+            range: line.ranges.first().copied().unwrap_or_default(),
+        })
+    }
+
+    /// Everything that's included, for the event loop to apply
+    fn replacements(&self) -> Option<ProjectSearchResult> {
+        if self.found_rx.is_some() || self.search_at.is_some() {
+            return None; // Wait for the search to finish
+        }
+        let search = self.search.clone()?;
+        let files: Vec<(PathBuf, Vec<usize>)> = self
+            .files
+            .iter()
+            .map(|file| {
+                let lines = file.lines.iter().filter(|line| line.included).map(|line| line.line).collect();
+                (self.root.join(&file.path), lines)
+            })
+            .filter(|(_, lines): &(PathBuf, Vec<usize>)| !lines.is_empty())
+            .collect();
+        if files.is_empty() {
+            return None;
+        }
+        Some(ProjectSearchResult::Replace { search, replace: self.replace.clone(), files })
+    }
+
+    fn draw<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        let theme = theme::current();
+        let dialog = theme[Element::Dialog];
+        let (width, height) = terminal::size()?;
+        let box_width = (width as usize * 9 / 10).clamp(30.min(width as usize), 160);
+        let box_height = (height as usize * 4 / 5).clamp(10.min(height as usize), height as usize);
+        let x = (width as usize - box_width) as u16 / 2;
+        let y = (height as usize - box_height) as u16 / 2;
+        let inner = box_width.saturating_sub(2);
+        let list_rows = box_height.saturating_sub(5);
+
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + list_rows {
+            self.scroll = self.selected + 1 - list_rows;
+        }
+
+        execute!(writer, Hide, SetBackgroundColor(dialog.background()), SetForegroundColor(dialog.foreground()))?;
+
+        // Top border with the title and the search options, lit up when on
+        let title = " Search in Project ";
+        execute!(
+            writer,
+            MoveTo(x, y),
+            Print("╭─"),
+            SetAttribute(Attribute::Bold),
+            SetForegroundColor(theme[Element::DialogTitle].foreground()),
+            Print(title),
+            SetAttribute(Attribute::Reset),
+            SetBackgroundColor(dialog.background()),
+            SetForegroundColor(dialog.foreground()),
+        )?;
+        let mut used = 1 + title.len();
+        for (label, on) in [(".*", self.options.regex), ("Aa", self.options.match_case), ("Word", self.options.whole_word)] {
+            if used + label.len() + 3 > inner {
+                break;
+            }
+            let style = if on { theme[Element::DialogAccent] } else { theme[Element::DialogDim] };
+            execute!(
+                writer,
+                Print("─"),
+                SetForegroundColor(style.foreground()),
+                Print(format!(" {} ", label)),
+                SetForegroundColor(dialog.foreground()),
+            )?;
+            used += label.len() + 3;
+        }
+        execute!(writer, Print(format!("{}╮", "─".repeat(inner.saturating_sub(used)))))?;
+
+        // The two fields, with the result count beside the query
+        let line_count: usize = self.files.iter().map(|file| file.lines.len()).sum();
+        let match_count: usize = self.files.iter().flat_map(|file| &file.lines).map(|line| line.ranges.len()).sum();
+        let mut count = if self.query.is_empty() {
+            String::new()
+        } else {
+            format!("{} in {} files", match_count, self.files.len())
+        };
+        if line_count >= MAX_MATCHED_LINES {
+            count.push('+');
+        } else if self.found_rx.is_some() || self.search_at.is_some() {
+            count.push('…');
+        }
+        let field_width = inner.saturating_sub(count.chars().count() + 12);
+        let mut cursor = (x, y + 1);
+        for (row, label, text, field) in [(1, "Find:    ", &self.query, Field::Find), (2, "Replace: ", &self.replace, Field::Replace)] {
+            let active = self.field == field;
+            let label_color = if active { theme[Element::DialogTitle] } else { theme[Element::DialogInactive] };
+            let input = theme[if active { Element::DialogInput } else { Element::DialogInputInactive }];
+            let shown: String = {
+                let chars: Vec<char> = text.chars().collect();
+                chars[chars.len().saturating_sub(field_width.saturating_sub(1))..].iter().collect()
+            };
+            execute!(
+                writer,
+                MoveTo(x, y + row),
+                Print("│ "),
+                SetForegroundColor(label_color.foreground()),
+                Print(label),
+                SetBackgroundColor(input.background()),
+                SetForegroundColor(input.foreground()),
+                Print(quick_open::fit(&shown, field_width)),
+                SetBackgroundColor(dialog.background()),
+            )?;
+            let side = if row == 1 { count.clone() } else { String::new() };
+            execute!(
+                writer,
+                SetForegroundColor(theme[Element::DialogSuccess].foreground()),
+                Print(format!(" {:>width$} ", side, width = count.chars().count())),
+                SetForegroundColor(dialog.foreground()),
+                Print("│"),
+            )?;
+            if active {
+                let shown_width: usize = shown.chars().map(|c| c.width().unwrap_or(1)).sum();
+                cursor = (x + 11 + shown_width as u16, y + row);
+            }
+        }
+        execute!(writer, MoveTo(x, y + 3), Print(format!("├{}┤", "─".repeat(inner))))?;
+
+        // Results
+        for row in 0..list_rows {
+            execute!(
+                writer,
+                MoveTo(x, y + 4 + row as u16),
+                SetBackgroundColor(dialog.background()),
+                SetForegroundColor(dialog.foreground()),
+                Print("│")
+            )?;
+            self.draw_row(writer, self.scroll + row, inner)?;
+            execute!(writer, SetBackgroundColor(dialog.background()), SetForegroundColor(dialog.foreground()), Print("│"))?;
+        }
+
+        // Bottom border with the key help, or why the query can't be searched
+        let bottom_y = y + 4 + list_rows as u16;
+        let (note, note_color) = match &self.error {
+            Some(error) => (format!(" {} ", error), theme[Element::DialogError]),
+            None if self.replacing => {
+                (" Enter: Open  Tab: Field/results  Space: Include  Ctrl+R: Replace  Esc: Close ".to_string(), theme[Element::DialogDim])
+            }
+            None => (" Enter: Open  Tab: Replace  Alt+R/C/W: Options  Esc: Close ".to_string(), theme[Element::DialogDim]),
+        };
+        let note: String = note.chars().take(inner.saturating_sub(2)).collect();
+        let note_x = x as usize + box_width.saturating_sub(note.chars().count() + 2);
+        execute!(
+            writer,
+            MoveTo(x, bottom_y),
+            Print(format!("╰{}╯", "─".repeat(inner))),
+            MoveTo(note_x as u16, bottom_y),
+            SetForegroundColor(note_color.foreground()),
+            Print(note),
+            ResetColor
+        )?;
+
+        if self.field == Field::Results {
+            return writer.flush();
+        }
+        execute!(writer, MoveTo(cursor.0, cursor.1), Show)?;
+        writer.flush()
+    }
+
+    /// One row of the results: a file header, a matched line or a line of context
+    fn draw_row<W: Write>(&self, writer: &mut W, idx: usize, width: usize) -> io::Result<()> {
+        let theme = theme::current();
+        let dialog = theme[Element::Dialog];
+        let Some(row) = self.rows.get(idx) else {
+            return execute!(writer, Print(" ".repeat(width)));
+        };
+        let base = if idx == self.selected { theme[Element::DialogSelected].over(dialog) } else { dialog };
+        let dim = theme[Element::DialogDim].over(base);
+        let mut out = StyledRow::new(width);
+
+        match *row {
+            Row::File(file_idx) => {
+                let file = &self.files[file_idx];
+                if self.replacing {
+                    let included = file.lines.iter().filter(|line| line.included).count();
+                    let mark = match included {
+                        0 => "[ ] ",
+                        n if n == file.lines.len() => "[x] ",
+                        _ => "[-] ",
+                    };
+                    out.push(mark, base);
+                }
+                let matches: usize = file.lines.iter().map(|line| line.ranges.len()).sum();
+                let mut title = theme[Element::DialogTitle].over(base);
+                title.bold = true;
+                out.push(&file.path, title);
+                out.push(&format!("  {}", matches), dim);
+            }
+            Row::Match(file_idx, line_idx) => {
+                let line = &self.files[file_idx].lines[line_idx];
+                if self.replacing {
+                    out.push(if line.included { "  [x] " } else { "  [ ] " }, base);
+                } else {
+                    out.push("  ", base);
+                }
+                out.push(&format!("{:>5}: ", line.line + 1), dim);
+                self.push_line(&mut out, line, base);
+            }
+            Row::Context(file_idx, context_idx) => {
+                let (number, text) = &self.files[file_idx].context[context_idx];
+                out.push(if self.replacing { "      " } else { "  " }, base);
+                out.push(&format!("{:>5}  ", number + 1), dim);
+                out.push(text, dim);
+            }
+        }
+        out.finish(writer, base)
+    }
+
+    /// A matched line with its matches highlighted, and their replacements once replacing
+    fn push_line(&self, out: &mut StyledRow, line: &LineMatch, base: Style) {
+        let theme = theme::current();
+        let matched = theme[Element::DialogMatch].over(base);
+        let mut removed = theme[Element::DialogError].over(base);
+        removed.italic = true;
+        let added = theme[Element::DialogSuccess].over(base);
+
+        // Long lines start shortly before their first match
+        let first = line.ranges.first().map_or(0, |range| range.0);
+        let mut pos = line.text[..first]
+            .char_indices()
+            .rev()
+            .nth(out.room() / 3)
+            .map_or(0, |(offset, _)| offset);
+        if pos > 0 {
+            out.push("…", theme[Element::DialogDim].over(base));
+        }
+
+        for &(start, end) in &line.ranges {
+            out.push(&line.text[pos..start], base);
+            match (&self.search, line.included && self.replacing) {
+                (Some(search), true) => {
+                    out.push(&line.text[start..end], removed);
+                    out.push(&search.replacement(&line.text, start, &self.replace), added);
+                }
+                _ => out.push(&line.text[start..end], matched),
+            }
+            pos = end;
+        }
+        out.push(&line.text[pos..], base);
+    }
+}
+
+/// A row built up in styled pieces and cut to a width
+struct StyledRow {
+    width: usize,
+    used: usize,
+    pieces: Vec<(String, Style)>,
+}
+
+impl StyledRow {
+    fn new(width: usize) -> Self {
+        StyledRow { width, used: 0, pieces: Vec::new() }
+    }
+
+    fn room(&self) -> usize {
+        self.width.saturating_sub(self.used)
+    }
+
+    fn push(&mut self, text: &str, style: Style) {
+        let mut piece = String::new();
+        for ch in text.chars() {
+            let ch = if ch == '\t' { ' ' } else { ch };
+            if ch.is_control() {
+                continue;
+            }
+            let ch_width = ch.width().unwrap_or(1);
+            if self.used + ch_width > self.width {
+                break;
+            }
+            piece.push(ch);
+            self.used += ch_width;
+        }
+        if !piece.is_empty() {
+            self.pieces.push((piece, style));
+        }
+    }
+
+    fn finish<W: Write>(mut self, writer: &mut W, base: Style) -> io::Result<()> {
+        let padding = " ".repeat(self.room());
+        self.pieces.push((padding, base));
+        for (text, style) in self.pieces {
+            write!(writer, "{}{}", style.escape(), text)?;
+        }
+        Ok(())
+    }
+}
+
+/// Search every file the walk finds, sending those with matches
+fn search_files(root: PathBuf, search: Search, open_buffers: Arc<HashMap<String, String>>, cancel: Arc<AtomicBool>, tx: Sender<FileMatches>) {
+    let (walk_tx, walk_rx) = mpsc::channel();
+    let walk_root = root.clone();
+    std::thread::spawn(move || quick_open::walk(walk_root, walk_tx));
+
+    let mut matched_lines = 0;
+    for path in walk_rx.into_iter().flatten() {
+        if cancel.load(Ordering::Relaxed) {
+            return;
+        }
+        let text = match open_buffers.get(&path) {
+            Some(text) => text.clone(),
+            None => match read_text(&root.join(&path)) {
+                Some(text) => text,
+                None => continue,
+            },
+        };
+        let Some(file) = search_text(&search, path, &text, MAX_MATCHED_LINES - matched_lines) else {
+            continue;
+        };
+        matched_lines += file.lines.len();
+        if tx.send(file).is_err() || matched_lines >= MAX_MATCHED_LINES {
+            return;
+        }
+    }
+}
+
+/// A file's text, unless it's too large or not text
+fn read_text(path: &Path) -> Option<String> {
+    if fs::metadata(path).ok()?.len() > MAX_FILE_BYTES {
+        return None;
+    }
+    let text = fs::read_to_string(path).ok()?;
+    (!text.contains('\0')).then_some(text)
+}
+
+/// The matched lines of one file, at most `limit`, with their context; None without matches
+fn search_text(search: &Search, path: String, text: &str, limit: usize) -> Option<FileMatches> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut matches = Vec::new();
+    for (number, line) in lines.iter().enumerate() {
+        let ranges: Vec<(usize, usize)> = search.find_in_line(line).collect();
+        if !ranges.is_empty() {
+            matches.push(LineMatch { line: number, text: line.to_string(), ranges, included: true });
+            if matches.len() >= limit {
+                break;
+            }
+        }
+    }
+    if matches.is_empty() {
+        return None;
+    }
+
+    // Context lines around each match that aren't matches themselves
+    let mut context: Vec<(usize, String)> = Vec::new();
+    let mut next = 0; // First line not yet considered
+    for (idx, line) in matches.iter().enumerate() {
+        let from = line.line.saturating_sub(CONTEXT_LINES).max(next);
+        let to = (line.line + CONTEXT_LINES + 1).min(lines.len());
+        let following = matches.get(idx + 1).map(|next| next.line);
+        for (number, text) in lines.iter().enumerate().take(to).skip(from) {
+            if number != line.line && Some(number) != following {
+                context.push((number, text.to_string()));
+            }
+        }
+        next = to;
+    }
+    context.dedup_by_key(|(number, _)| *number);
+    Some(FileMatches { path, lines: matches, context })
+}
@@ -2,12 +2,14 @@ use crossterm::{
     cursor::{Hide, MoveTo, Show},
     event::{self, Event, KeyCode, KeyModifiers},
     execute,
-    style::{Attribute, Color, Print, SetAttribute, SetBackgroundColor, SetForegroundColor, ResetColor},
+    style::{Attribute, Print, SetAttribute, SetBackgroundColor, SetForegroundColor, ResetColor},
     terminal::{self, Clear, ClearType},
 };
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 
+use crate::theme::{self, Element};
+
 pub struct Prompt {
     title: String,
     input: String,
@@ -38,6 +40,7 @@ impl Prompt {
     
     /// Draw the complete prompt window (borders, title, etc.)
     fn draw_window(&mut self, stdout: &mut io::Stdout) -> io::Result<()> {
+        let theme = theme::current();
         let (width, height) = terminal::size()?;
         
         // Calculate prompt dimensions and position
@@ -51,7 +54,7 @@ impl Prompt {
             execute!(
                 stdout,
                 MoveTo(self.prompt_x + 2, (self.prompt_y as usize + y) as u16),
-                SetBackgroundColor(Color::Black),
+                SetBackgroundColor(theme[Element::DialogShadow].background()),
                 Print(" ".repeat(self.prompt_width))
             )?;
         }
@@ -61,8 +64,8 @@ impl Prompt {
             execute!(
                 stdout,
                 MoveTo(self.prompt_x, (self.prompt_y as usize + y) as u16),
-                SetBackgroundColor(Color::Rgb { r: 40, g: 40, b: 45 }),
-                SetForegroundColor(Color::Rgb { r: 200, g: 200, b: 205 }),
+                SetBackgroundColor(theme[Element::Dialog].background()),
+                SetForegroundColor(theme[Element::Dialog].foreground()),
             )?;
             
             if y == 0 {
@@ -85,11 +88,11 @@ impl Prompt {
                 execute!(
                     stdout,
                     SetAttribute(Attribute::Bold),
-                    SetForegroundColor(Color::White),
+                    SetForegroundColor(theme[Element::DialogTitle].foreground()),
                     Print(&self.title),
                     SetAttribute(Attribute::Reset),
-                    SetBackgroundColor(Color::Rgb { r: 40, g: 40, b: 45 }),
-                    SetForegroundColor(Color::Rgb { r: 200, g: 200, b: 205 }),
+                    SetBackgroundColor(theme[Element::Dialog].background()),
+                    SetForegroundColor(theme[Element::Dialog].foreground()),
                 )?;
                 let title_padding = self.prompt_width - 3 - self.title.len();
                 write!(stdout, "{:width$}│", "", width = title_padding)?;
@@ -103,10 +106,10 @@ impl Prompt {
                 write!(stdout, "│")?;
                 execute!(
                     stdout,
-                    SetForegroundColor(Color::Rgb { r: 130, g: 130, b: 140 }),
+                    SetForegroundColor(theme[Element::DialogDim].foreground()),
                     Print(format!("{:>width$}{}{:<width2$}", "", help_text, "", 
                         width = padding, width2 = self.prompt_width - 2 - padding - help_text.len())),
-                    SetForegroundColor(Color::Rgb { r: 200, g: 200, b: 205 }),
+                    SetForegroundColor(theme[Element::Dialog].foreground()),
                 )?;
                 write!(stdout, "│")?;
             } else {
@@ -121,12 +124,13 @@ impl Prompt {
     
     /// Update only the input field line
     fn update_input_field(&self, stdout: &mut io::Stdout) -> io::Result<()> {
+        let theme = theme::current();
         // Move to the input line
         execute!(
             stdout,
             MoveTo(self.prompt_x, self.prompt_y + 4),
-            SetBackgroundColor(Color::Rgb { r: 40, g: 40, b: 45 }),
-            SetForegroundColor(Color::Rgb { r: 200, g: 200, b: 205 }),
+            SetBackgroundColor(theme[Element::Dialog].background()),
+            SetForegroundColor(theme[Element::Dialog].foreground()),
         )?;
         
         // Draw the line with input field
@@ -150,11 +154,11 @@ impl Prompt {
         // Input field with distinct background
         execute!(
             stdout,
-            SetBackgroundColor(Color::Rgb { r: 20, g: 20, b: 25 }),
-            SetForegroundColor(Color::Rgb { r: 220, g: 220, b: 230 }),
+            SetBackgroundColor(theme[Element::DialogInput].background()),
+            SetForegroundColor(theme[Element::DialogInput].foreground()),
             Print(format!("{:<width$}", visible_input, width = input_width)),
-            SetBackgroundColor(Color::Rgb { r: 40, g: 40, b: 45 }),
-            SetForegroundColor(Color::Rgb { r: 200, g: 200, b: 205 }),
+            SetBackgroundColor(theme[Element::Dialog].background()),
+            SetForegroundColor(theme[Element::Dialog].foreground()),
         )?;
         
         write!(stdout, " │")?;
@@ -0,0 +1,661 @@
+//! Quick-open overlay: fuzzy find a file in the project and open it
+
+use crate::theme::{self, Element};
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{self, Event, KeyCode, KeyModifiers},
+    execute,
+    style::{Attribute, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor},
+    terminal,
+};
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::time::Duration;
+use unicode_width::UnicodeWidthChar;
+
+/// Stop walking after this many files
+const MAX_FILES: usize = 200_000;
+/// Files sent to the overlay at a time
+const WALK_BATCH: usize = 1000;
+/// Best matches kept for the list
+const MAX_RESULTS: usize = 1000;
+/// Most of a file read for its preview
+const PREVIEW_BYTES: u64 = 64 * 1024;
+
+/// Nearest directory at or above `start` that holds a `.git`, or `start` itself
+pub fn project_root(start: &Path) -> PathBuf {
+    let start = fs::canonicalize(start).unwrap_or_else(|_| start.to_path_buf());
+    start
+        .ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .map(Path::to_path_buf)
+        .unwrap_or(start)
+}
+
+struct Match {
+    file: usize,           // Index into the walked files
+    score: i64,
+    positions: Vec<usize>, // Matched character positions, for highlighting
+}
+
+pub struct QuickOpen {
+    root: PathBuf,
+    files: Vec<String>,                      // Paths relative to the root, '/'-separated
+    walk_rx: Option<Receiver<Vec<String>>>,  // Batches from the background walk, until it finishes
+    recent: Vec<String>,                     // Recently used files relative to the root, most recent first
+    query: String,
+    matches: Vec<Match>,                     // Best matches for the query, ranked
+    selected: usize,
+    scroll: usize,
+    preview: Option<(usize, Vec<String>)>,   // Previewed file and its first lines
+}
+
+impl QuickOpen {
+    /// Start walking the project; `recent` lists recently used files, most recent first
+    pub fn new(root: PathBuf, recent: &[PathBuf]) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let walk_root = root.clone();
+        std::thread::spawn(move || walk(walk_root, tx));
+
+        let recent = recent
+            .iter()
+            .filter_map(|path| {
+                let path = fs::canonicalize(path).ok()?;
+                let relative = path.strip_prefix(&root).ok()?;
+                Some(relative.to_string_lossy().replace('\\', "/"))
+            })
+            .collect();
+
+        QuickOpen {
+            root,
+            files: Vec::new(),
+            walk_rx: Some(rx),
+            recent,
+            query: String::new(),
+            matches: Vec::new(),
+            selected: 0,
+            scroll: 0,
+            preview: None,
+        }
+    }
+
+    /// Show the overlay until a file is picked (its full path) or the user cancels
+    pub fn run<W: Write>(&mut self, writer: &mut W) -> io::Result<Option<PathBuf>> {
+        let mut needs_redraw = true;
+        loop {
+            if self.receive_files() {
+                needs_redraw = true;
+            }
+            if needs_redraw {
+                self.draw(writer)?;
+                needs_redraw = false;
+            }
+
+            // Poll so batches from the walk keep appearing while idle
+            if !event::poll(Duration::from_millis(50))? {
+                continue;
+            }
+            match event::read()? {
+                Event::Key(key) => {
+                    // Windows: ignore key release events
+                    #[cfg(target_os = "windows")]
+                    if key.kind == event::KeyEventKind::Release {
+                        continue;
+                    }
+
+                    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                    match key.code {
+                        KeyCode::Esc => return Ok(None),
+                        KeyCode::Char('c') | KeyCode::Char('p') if ctrl => return Ok(None),
+                        KeyCode::Enter => {
+                            if let Some(m) = self.matches.get(self.selected) {
+                                return Ok(Some(self.root.join(&self.files[m.file])));
+                            }
+                        }
+                        KeyCode::Up => self.move_selection(-1),
+                        KeyCode::Down => self.move_selection(1),
+                        KeyCode::Char('k') if ctrl => self.move_selection(-1),
+                        KeyCode::Char('j') if ctrl => self.move_selection(1),
+                        KeyCode::PageUp => self.move_selection(-10),
+                        KeyCode::PageDown => self.move_selection(10),
+                        KeyCode::Char('u') if ctrl => {
+                            self.query.clear();
+                            self.rank_all();
+                        }
+                        KeyCode::Backspace => {
+                            self.query.pop();
+                            self.rank_all();
+                        }
+                        KeyCode::Char(c) if !ctrl => {
+                            self.query.push(c);
+                            self.rank_all();
+                        }
+                        _ => {}
+                    }
+                    needs_redraw = true;
+                }
+                Event::Resize(_, _) => needs_redraw = true,
+                _ => {}
+            }
+        }
+    }
+
+    /// Take any files the walk has found since last time; returns true if there were some
+    fn receive_files(&mut self) -> bool {
+        let mut received = false;
+        while let Some(rx) = &self.walk_rx {
+            match rx.try_recv() {
+                Ok(batch) => {
+                    let first = self.files.len();
+                    self.files.extend(batch);
+                    self.rank_range(first);
+                    received = true;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    // Walk finished - redraw once more to drop the "searching" marker
+                    self.walk_rx = None;
+                    received = true;
+                }
+            }
+        }
+        received
+    }
+
+    /// Rank every file against a new query
+    fn rank_all(&mut self) {
+        self.matches.clear();
+        self.selected = 0;
+        self.scroll = 0;
+        self.rank_range(0);
+    }
+
+    /// Rank the files from `first` on and merge them into the matches
+    fn rank_range(&mut self, first: usize) {
+        let query: Vec<char> = self.query.to_lowercase().chars().filter(|c| !c.is_whitespace()).collect();
+        for (offset, path) in self.files[first..].iter().enumerate() {
+            if let Some((score, positions)) = fuzzy_match(&query, path) {
+                let score = score + self.recency_bonus(path, query.is_empty());
+                self.matches.push(Match { file: first + offset, score, positions });
+            }
+        }
+
+        let files = &self.files;
+        self.matches
+            .sort_by(|a, b| b.score.cmp(&a.score).then_with(|| files[a.file].cmp(&files[b.file])));
+        self.matches.truncate(MAX_RESULTS);
+        self.selected = self.selected.min(self.matches.len().saturating_sub(1));
+    }
+
+    /// Bonus for recently used files - with no query they simply come first
+    fn recency_bonus(&self, path: &str, empty_query: bool) -> i64 {
+        match self.recent.iter().position(|recent| recent == path) {
+            Some(rank) if empty_query => 1000 - rank as i64,
+            Some(rank) => (30 - 3 * rank as i64).max(0),
+            None => 0,
+        }
+    }
+
+    fn move_selection(&mut self, delta: i64) {
+        if self.matches.is_empty() {
+            return;
+        }
+        let last = self.matches.len() as i64 - 1;
+        self.selected = (self.selected as i64 + delta).clamp(0, last) as usize;
+    }
+
+    /// First lines of the selected file, read once per selection
+    fn preview_lines(&mut self) -> &[String] {
+        let Some(file) = self.matches.get(self.selected).map(|m| m.file) else {
+            return &[];
+        };
+        if self.preview.as_ref().is_none_or(|(previewed, _)| *previewed != file) {
+            let lines = read_preview(&self.root.join(&self.files[file]));
+            self.preview = Some((file, lines));
+        }
+        self.preview.as_ref().map_or(&[], |(_, lines)| lines.as_slice())
+    }
+
+    fn draw<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        let theme = theme::current();
+        let dialog = theme[Element::Dialog];
+        let (width, height) = terminal::size()?;
+        let box_width = (width as usize * 9 / 10).clamp(20.min(width as usize), 140);
+        let box_height = (height as usize * 4 / 5).clamp(8.min(height as usize), height as usize);
+        let x = (width as usize - box_width) as u16 / 2;
+        let y = (height as usize - box_height) as u16 / 2;
+        let inner = box_width.saturating_sub(2);
+
+        // Narrow terminals get the list alone
+        let list_width = if box_width >= 60 { inner * 2 / 5 } else { inner };
+        let preview_width = inner.saturating_sub(list_width + 1);
+        let list_rows = box_height.saturating_sub(4);
+
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + list_rows {
+            self.scroll = self.selected + 1 - list_rows;
+        }
+
+        execute!(writer, Hide, SetBackgroundColor(dialog.background()), SetForegroundColor(dialog.foreground()))?;
+
+        // Top border with the title
+        let title = " Open File ";
+        execute!(
+            writer,
+            MoveTo(x, y),
+            Print("╭─"),
+            SetAttribute(Attribute::Bold),
+            SetForegroundColor(theme[Element::DialogTitle].foreground()),
+            Print(title),
+            SetAttribute(Attribute::Reset),
+            SetBackgroundColor(dialog.background()),
+            SetForegroundColor(dialog.foreground()),
+            Print(format!("{}╮", "─".repeat(inner.saturating_sub(title.len() + 1))))
+        )?;
+
+        // Query line, with the match count on the right
+        let count = if self.walk_rx.is_some() {
+            format!("{}/{}…", self.matches.len(), self.files.len())
+        } else {
+            format!("{}/{}", self.matches.len(), self.files.len())
+        };
+        let query_width = inner.saturating_sub(count.chars().count() + 5);
+        let query: String = {
+            let chars: Vec<char> = self.query.chars().collect();
+            chars[chars.len().saturating_sub(query_width)..].iter().collect()
+        };
+        execute!(
+            writer,
+            MoveTo(x, y + 1),
+            Print("│ > "),
+            SetForegroundColor(theme[Element::DialogTitle].foreground()),
+            Print(format!("{:<width$}", query, width = query_width)),
+            SetForegroundColor(theme[Element::DialogDim].foreground()),
+            Print(format!(" {} ", count)),
+            SetForegroundColor(dialog.foreground()),
+            Print("│")
+        )?;
+
+        // Separator between the query and the list/preview
+        let separator = if preview_width > 0 {
+            format!("├{}┬{}┤", "─".repeat(list_width), "─".repeat(preview_width))
+        } else {
+            format!("├{}┤", "─".repeat(inner))
+        };
+        execute!(writer, MoveTo(x, y + 2), Print(separator))?;
+
+        let preview: Vec<String> = if preview_width > 0 { self.preview_lines().to_vec() } else { Vec::new() };
+        let number_width = preview.len().max(1).to_string().len();
+        for row in 0..list_rows {
+            execute!(writer, MoveTo(x, y + 3 + row as u16), SetBackgroundColor(dialog.background()), Print("│"))?;
+            self.draw_list_row(writer, self.scroll + row, list_width)?;
+
+            if preview_width > 0 {
+                execute!(writer, SetBackgroundColor(dialog.background()), SetForegroundColor(dialog.foreground()), Print("│"))?;
+                match preview.get(row) {
+                    Some(line) => {
+                        let number = format!("{:>width$} ", row + 1, width = number_width);
+                        execute!(
+                            writer,
+                            SetForegroundColor(theme[Element::DialogDim].foreground()),
+                            Print(&number),
+                            SetForegroundColor(dialog.foreground()),
+                            Print(fit(line, preview_width.saturating_sub(number.len())))
+                        )?;
+                    }
+                    None => execute!(writer, Print(" ".repeat(preview_width)))?,
+                }
+            }
+            execute!(writer, SetForegroundColor(dialog.foreground()), Print("│"))?;
+        }
+
+        // Bottom border with the key help
+        let help = " Enter: Open  Esc: Cancel  ↑↓: Select ";
+        let bottom = if preview_width > 0 {
+            format!("╰{}┴{}╯", "─".repeat(list_width), "─".repeat(preview_width))
+        } else {
+            format!("╰{}╯", "─".repeat(inner))
+        };
+        let help_x = x as usize + box_width.saturating_sub(help.chars().count() + 2);
+        execute!(
+            writer,
+            MoveTo(x, y + 3 + list_rows as u16),
+            Print(bottom),
+            MoveTo(help_x as u16, y + 3 + list_rows as u16),
+            SetForegroundColor(theme[Element::DialogDim].foreground()),
+            Print(help),
+            ResetColor
+        )?;
+
+        // Cursor at the end of the query
+        let cursor_x = x as usize + 4 + query.chars().map(|c| c.width().unwrap_or(1)).sum::<usize>();
+        execute!(writer, MoveTo(cursor_x as u16, y + 1), Show)?;
+        writer.flush()
+    }
+
+    /// One row of the result list, with the matched characters highlighted
+    fn draw_list_row<W: Write>(&self, writer: &mut W, idx: usize, width: usize) -> io::Result<()> {
+        let Some(m) = self.matches.get(idx) else {
+            return execute!(writer, Print(" ".repeat(width)));
+        };
+        let theme = theme::current();
+        let dialog = theme[Element::Dialog];
+        let style = if idx == self.selected { theme[Element::DialogSelected].over(dialog) } else { dialog };
+        execute!(writer, SetBackgroundColor(style.background()), Print(" "))?;
+
+        // Long paths lose their start, keeping the file name visible
+        let chars: Vec<char> = self.files[m.file].chars().collect();
+        let room = width.saturating_sub(2);
+        let skip = chars.len().saturating_sub(room);
+        let mut used = 0;
+        if skip > 0 {
+            execute!(writer, SetForegroundColor(theme[Element::DialogDim].foreground()), Print("…"))?;
+            used += 1;
+        }
+        for (pos, &ch) in chars.iter().enumerate().skip(skip + usize::from(skip > 0)) {
+            let color = if m.positions.contains(&pos) {
+                theme[Element::DialogMatch].foreground()
+            } else {
+                style.foreground()
+            };
+            execute!(writer, SetForegroundColor(color), Print(ch))?;
+            used += 1;
+        }
+        execute!(writer, Print(" ".repeat(width.saturating_sub(used + 1))), SetBackgroundColor(dialog.background()))
+    }
+}
+
+/// Cut or pad a line to exactly `width` columns
+pub(crate) fn fit(line: &str, width: usize) -> String {
+    let mut out = String::new();
+    let mut used = 0;
+    for ch in line.chars() {
+        let ch = if ch == '\t' { ' ' } else { ch };
+        if ch.is_control() {
+            continue;
+        }
+        let ch_width = ch.width().unwrap_or(1);
+        if used + ch_width > width {
+            break;
+        }
+        out.push(ch);
+        used += ch_width;
+    }
+    out.push_str(&" ".repeat(width - used));
+    out
+}
+
+/// First lines of a file for the preview
+fn read_preview(path: &Path) -> Vec<String> {
+    let mut bytes = Vec::new();
+    match fs::File::open(path).and_then(|file| file.take(PREVIEW_BYTES).read_to_end(&mut bytes)) {
+        Ok(_) if bytes.contains(&0) => vec!["(binary file)".to_string()],
+        Ok(_) => String::from_utf8_lossy(&bytes).lines().map(|line| line.replace('\t', "    ")).collect(),
+        Err(e) => vec![format!("(can't read file: {})", e)],
+    }
+}
+
+/// Score how well a query (lowercase) matches a path, with the matched positions
+/// Matches in the file name, at word starts and in runs score higher; gaps cost
+pub(crate) fn fuzzy_match(query: &[char], path: &str) -> Option<(i64, Vec<usize>)> {
+    if query.is_empty() {
+        return Some((0, Vec::new()));
+    }
+    let chars: Vec<char> = path.chars().collect();
+    let lower: Vec<char> = chars.iter().map(|c| c.to_lowercase().next().unwrap_or(*c)).collect();
+    let name_start = chars.iter().rposition(|&c| c == '/').map_or(0, |i| i + 1);
+
+    // Try the file name alone first, then the whole path
+    let mut best: Option<(i64, Vec<usize>)> = None;
+    for from in [name_start, 0] {
+        if let Some(positions) = align(query, &lower, from) {
+            let score = score_positions(&chars, &positions, name_start);
+            if best.as_ref().is_none_or(|(best_score, _)| score > *best_score) {
+                best = Some((score, positions));
+            }
+        }
+    }
+    best
+}
+
+/// Match the query in order from `from`, then tighten the match by walking back from its end
+fn align(query: &[char], lower: &[char], from: usize) -> Option<Vec<usize>> {
+    let mut next = 0;
+    let mut end = None;
+    for (pos, ch) in lower.iter().enumerate().skip(from) {
+        if *ch == query[next] {
+            next += 1;
+            if next == query.len() {
+                end = Some(pos);
+                break;
+            }
+        }
+    }
+
+    let mut positions = Vec::with_capacity(query.len());
+    let mut remaining = query.len();
+    for pos in (from..=end?).rev() {
+        if remaining > 0 && lower[pos] == query[remaining - 1] {
+            positions.push(pos);
+            remaining -= 1;
+        }
+    }
+    positions.reverse();
+    Some(positions)
+}
+
+fn score_positions(chars: &[char], positions: &[usize], name_start: usize) -> i64 {
+    let mut score = 0;
+    for (i, &pos) in positions.iter().enumerate() {
+        score += 16;
+        let at_word_start = match pos.checked_sub(1).map(|prev| chars[prev]) {
+            None => true,
+            Some(prev) => matches!(prev, '/' | '_' | '-' | '.' | ' ') || (prev.is_lowercase() && chars[pos].is_uppercase()),
+        };
+        if at_word_start {
+            score += 10;
+        }
+        if pos >= name_start {
+            score += 4;
+        }
+        if i > 0 {
+            let gap = pos - positions[i - 1] - 1;
+            score += if gap == 0 { 8 } else { -(gap.min(10) as i64) };
+        }
+    }
+    // Prefer shorter paths among equal matches
+    score - chars.len() as i64 / 4
+}
+
+/// One line of a `.gitignore`
+struct IgnoreRule {
+    pattern: glob::Pattern,
+    negated: bool,  // `!pattern` re-includes
+    dir_only: bool, // `pattern/` matches directories only
+    anchored: bool, // Contains a slash, so it matches from the .gitignore's directory
+}
+
+/// Ignore rules in effect for a directory: its own `.gitignore` on top of its parents'
+struct IgnoreRules {
+    base: String, // Directory of the .gitignore relative to the root, ending in '/' (or empty)
+    rules: Vec<IgnoreRule>,
+    parent: Option<Rc<IgnoreRules>>,
+}
+
+impl IgnoreRules {
+    /// Read a `.gitignore` (or `.git/info/exclude`) on top of the parent's rules
+    fn load(path: &Path, base: &str, parent: Option<Rc<IgnoreRules>>) -> Option<Rc<IgnoreRules>> {
+        let rules: Vec<IgnoreRule> = match fs::read_to_string(path) {
+            Ok(text) => text.lines().filter_map(parse_ignore_line).collect(),
+            Err(_) => Vec::new(),
+        };
+        if rules.is_empty() {
+            return parent;
+        }
+        Some(Rc::new(IgnoreRules { base: base.to_string(), rules, parent }))
+    }
+
+    /// Check whether a path (relative to the root) is ignored
+    fn is_ignored(&self, path: &str, is_dir: bool) -> bool {
+        self.matched(path, is_dir).unwrap_or(false)
+    }
+
+    /// The last matching rule decides; nearer .gitignore files override their parents
+    fn matched(&self, path: &str, is_dir: bool) -> Option<bool> {
+        let options = glob::MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+        if let Some(relative) = path.strip_prefix(self.base.as_str()) {
+            let name = relative.rsplit('/').next().unwrap_or(relative);
+            for rule in self.rules.iter().rev() {
+                if rule.dir_only && !is_dir {
+                    continue;
+                }
+                let target = if rule.anchored { relative } else { name };
+                if rule.pattern.matches_with(target, options) {
+                    return Some(!rule.negated);
+                }
+            }
+        }
+        self.parent.as_ref().and_then(|parent| parent.matched(path, is_dir))
+    }
+}
+
+fn parse_ignore_line(line: &str) -> Option<IgnoreRule> {
+    let line = line.trim_end();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let (negated, line) = match line.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, line.strip_prefix('\\').unwrap_or(line)),
+    };
+    let (dir_only, line) = match line.strip_suffix('/') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    // A leading `**/` matches in any directory, like no slash at all
+    let line = line.strip_prefix("**/").filter(|rest| !rest.contains('/')).unwrap_or(line);
+    let anchored = line.contains('/');
+    let line = line.strip_prefix('/').unwrap_or(line);
+    let pattern = glob::Pattern::new(line).ok()?;
+    Some(IgnoreRule { pattern, negated, dir_only, anchored })
+}
+
+/// Walk the project breadth-first, sending file paths (relative to the root) in batches
+pub(crate) fn walk(root: PathBuf, tx: Sender<Vec<String>>) {
+    let exclude = IgnoreRules::load(&root.join(".git").join("info").join("exclude"), "", None);
+    let mut queue = VecDeque::from([(root, String::new(), exclude)]);
+    let mut batch = Vec::new();
+    let mut found = 0;
+
+    while let Some((dir, relative, parent_rules)) = queue.pop_front() {
+        let rules = IgnoreRules::load(&dir.join(".gitignore"), &relative, parent_rules);
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut entries: Vec<_> = entries.flatten().collect();
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name == ".git" {
+                continue;
+            }
+            // Follow symlinks to files but not to directories, which could loop
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let is_dir = file_type.is_dir();
+            if file_type.is_symlink() && !entry.path().is_file() {
+                continue;
+            }
+
+            let path = format!("{}{}", relative, name);
+            if rules.as_ref().is_some_and(|rules| rules.is_ignored(&path, is_dir)) {
+                continue;
+            }
+            if is_dir {
+                queue.push_back((entry.path(), format!("{}/", path), rules.clone()));
+                continue;
+            }
+
+            batch.push(path);
+            found += 1;
+            if batch.len() >= WALK_BATCH && tx.send(std::mem::take(&mut batch)).is_err() {
+                return; // Overlay closed
+            }
+            if found >= MAX_FILES {
+                let _ = tx.send(batch);
+                return;
+            }
+        }
+    }
+    let _ = tx.send(batch);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(line: &str) -> (String, bool, bool, bool) {
+        let rule = parse_ignore_line(line).unwrap();
+        (rule.pattern.as_str().to_string(), rule.negated, rule.dir_only, rule.anchored)
+    }
+
+    #[test]
+    fn parses_ignore_lines() {
+        assert!(parse_ignore_line("").is_none());
+        assert!(parse_ignore_line("   ").is_none());
+        assert!(parse_ignore_line("# comment").is_none());
+        assert_eq!(rule("*.pyc"), ("*.pyc".to_string(), false, false, false));
+        assert_eq!(rule("!keep.pyc  "), ("keep.pyc".to_string(), true, false, false));
+        assert_eq!(rule("\\#notes"), ("#notes".to_string(), false, false, false));
+        assert_eq!(rule("build/"), ("build".to_string(), false, true, false));
+        assert_eq!(rule("/target"), ("target".to_string(), false, false, true));
+        assert_eq!(rule("docs/*.html"), ("docs/*.html".to_string(), false, false, true));
+        assert_eq!(rule("**/cache/"), ("cache".to_string(), false, true, false));
+        assert_eq!(rule("**/a/b"), ("**/a/b".to_string(), false, false, true));
+    }
+
+    #[test]
+    fn last_matching_rule_wins() {
+        let root = Rc::new(IgnoreRules {
+            base: String::new(),
+            rules: ["*.log", "!keep.log", "build/", "/out"].into_iter().filter_map(parse_ignore_line).collect(),
+            parent: None,
+        });
+        let sub = IgnoreRules { base: "src/".to_string(), rules: parse_ignore_line("keep.log").into_iter().collect(), parent: Some(root.clone()) };
+        assert!(root.is_ignored("debug.log", false));
+        assert!(!root.is_ignored("keep.log", false));
+        assert!(root.is_ignored("a/build", true));
+        assert!(!root.is_ignored("a/build", false));
+        assert!(root.is_ignored("out", true));
+        assert!(!root.is_ignored("a/out", true));
+        // A nearer .gitignore overrides its parent's
+        assert!(sub.is_ignored("src/keep.log", false));
+        assert!(sub.is_ignored("src/debug.log", false));
+        assert!(!sub.is_ignored("keep.log", false));
+    }
+
+    fn score(query: &str, path: &str) -> Option<i64> {
+        fuzzy_match(&query.chars().collect::<Vec<_>>(), path).map(|(score, _)| score)
+    }
+
+    #[test]
+    fn fuzzy_match_prefers_file_names_and_runs() {
+        let query: Vec<char> = "main".chars().collect();
+        assert_eq!(fuzzy_match(&query, "src/Main.rs").map(|(_, positions)| positions), Some(vec![4, 5, 6, 7]));
+        assert_eq!(score("xyz", "src/main.rs"), None);
+        assert!(score("main", "src/main.rs") > score("main", "main/src/lib.rs"));
+        assert!(score("main", "src/main.rs") > score("main", "src/mxaxixn.rs"));
+        assert!(score("ed", "src/editor.rs") > score("ed", "src/modeled.rs"));
+    }
+}
@@ -0,0 +1,247 @@
+//! Offer to restore unsaved text found in a swap file when its file is opened
+
+use crate::theme::{self, Element, Style, Theme};
+use crate::undo_tree;
+use crate::undo_view::{diff_lines, print_fitted, DiffLine};
+use crossterm::{
+    cursor::{Hide, MoveTo},
+    event::{self, Event, KeyCode, KeyModifiers},
+    execute,
+    style::{Attribute, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor},
+    terminal::{self, Clear, ClearType},
+};
+use std::io::{self, Write};
+
+/// What to do with the recovered text
+#[derive(Clone, Copy, PartialEq)]
+pub enum Recovery {
+    Restore,
+    Discard,
+    Later, // Ask again next time the file is opened
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Button {
+    Restore,
+    Diff,
+    Discard,
+}
+
+const BUTTONS: [Button; 3] = [Button::Restore, Button::Diff, Button::Discard];
+
+pub struct RecoveryPrompt {
+    message: String,
+    diff: Vec<DiffLine>, // From the text on disk to the recovered text
+    selected: Button,
+    showing_diff: bool,
+    scroll: usize,
+}
+
+/// A button in its own colour when selected, otherwise as plain dialog text
+fn button_style(theme: &Theme, selected: bool, element: Element) -> Style {
+    if selected {
+        theme[element]
+    } else {
+        theme[Element::DialogInactive].over(theme[Element::Dialog])
+    }
+}
+
+impl RecoveryPrompt {
+    /// `name` is the file's, `text` what it holds now, and `recovered` the text from the
+    /// swap file written at `time`
+    pub fn new(name: &str, text: &str, recovered: &str, time: u64) -> Self {
+        let message = format!(
+            "Unsaved changes to {} from {} were left by a crash or a closed terminal.",
+            name,
+            undo_tree::age(time, undo_tree::now())
+        );
+        RecoveryPrompt { message, diff: diff_lines(text, recovered), selected: Button::Restore, showing_diff: false, scroll: 0 }
+    }
+
+    /// Show the prompt until the user decides
+    pub fn run<W: Write>(&mut self, writer: &mut W) -> io::Result<Recovery> {
+        loop {
+            self.draw(writer)?;
+
+            match event::read()? {
+                Event::Key(key) => {
+                    if key.kind == event::KeyEventKind::Release {
+                        continue;
+                    }
+
+                    let at = BUTTONS.iter().position(|&button| button == self.selected).unwrap_or(0);
+                    match key.code {
+                        KeyCode::Esc => return Ok(Recovery::Later),
+                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(Recovery::Later),
+                        KeyCode::Enter => match self.selected {
+                            Button::Restore => return Ok(Recovery::Restore),
+                            Button::Discard => return Ok(Recovery::Discard),
+                            Button::Diff => self.toggle_diff(writer)?,
+                        },
+                        KeyCode::Left | KeyCode::BackTab => self.selected = BUTTONS[(at + BUTTONS.len() - 1) % BUTTONS.len()],
+                        KeyCode::Right | KeyCode::Tab => self.selected = BUTTONS[(at + 1) % BUTTONS.len()],
+                        KeyCode::Char('r') | KeyCode::Char('R') => self.selected = Button::Restore,
+                        KeyCode::Char('s') | KeyCode::Char('S') => self.selected = Button::Diff,
+                        KeyCode::Char('d') | KeyCode::Char('D') => self.selected = Button::Discard,
+                        KeyCode::Up => self.scroll = self.scroll.saturating_sub(1),
+                        KeyCode::Down => self.scroll += 1,
+                        KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(10),
+                        KeyCode::PageDown => self.scroll += 10,
+                        _ => {}
+                    }
+                }
+                Event::Resize(_, _) => execute!(writer, Clear(ClearType::All))?,
+                _ => continue,
+            }
+        }
+    }
+
+    fn toggle_diff<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.showing_diff = !self.showing_diff;
+        self.scroll = 0;
+        if !self.showing_diff {
+            // The smaller box doesn't cover the diff; the editor is drawn again afterwards
+            execute!(writer, Clear(ClearType::All))?;
+        }
+        Ok(())
+    }
+
+    fn draw<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        let theme = theme::current();
+        let dialog = theme[Element::Dialog];
+        let (width, height) = terminal::size()?;
+        let box_width = (width as usize * 9 / 10).clamp(20.min(width as usize), 80);
+        let inner = box_width.saturating_sub(2);
+        let message = wrap(&self.message, inner.saturating_sub(2));
+        let diff_rows = if self.showing_diff {
+            (height as usize * 4 / 5).saturating_sub(message.len() + 6).max(3)
+        } else {
+            0
+        };
+        let box_height = (message.len() + 5 + diff_rows + usize::from(self.showing_diff)).min(height as usize);
+        let x = (width as usize).saturating_sub(box_width) as u16 / 2;
+        let y = (height as usize).saturating_sub(box_height) as u16 / 3;
+        self.scroll = self.scroll.min(self.diff.len().saturating_sub(diff_rows));
+
+        execute!(writer, Hide, SetBackgroundColor(dialog.background()), SetForegroundColor(dialog.foreground()))?;
+
+        // Top border with the title
+        let title = " Recover unsaved changes ";
+        execute!(
+            writer,
+            MoveTo(x, y),
+            Print("╭─"),
+            SetAttribute(Attribute::Bold),
+            SetForegroundColor(theme[Element::DialogTitle].foreground()),
+            Print(title),
+            SetAttribute(Attribute::Reset),
+            SetBackgroundColor(dialog.background()),
+            SetForegroundColor(dialog.foreground()),
+            Print(format!("{}╮", "─".repeat(inner.saturating_sub(title.len() + 1))))
+        )?;
+
+        let mut row = y + 1;
+        for line in std::iter::once("").chain(message.iter().map(String::as_str)) {
+            execute!(writer, MoveTo(x, row), Print("│ "))?;
+            print_fitted(writer, line, inner.saturating_sub(1))?;
+            execute!(writer, Print("│"))?;
+            row += 1;
+        }
+
+        if self.showing_diff {
+            let label = " Restoring changes ";
+            execute!(
+                writer,
+                MoveTo(x, row),
+                Print("├─"),
+                SetForegroundColor(theme[Element::DialogDim].foreground()),
+                Print(label),
+                SetForegroundColor(dialog.foreground()),
+                Print(format!("{}┤", "─".repeat(inner.saturating_sub(label.len() + 1))))
+            )?;
+            row += 1;
+            let added = theme[Element::GutterAdded].foreground();
+            let removed = theme[Element::GutterDeleted].foreground();
+            let dim = theme[Element::DialogDim].foreground();
+            for line in 0..diff_rows {
+                let (color, text) = match self.diff.get(self.scroll + line) {
+                    Some(DiffLine::Header(text)) => (dim, text.clone()),
+                    Some(DiffLine::Removed(text)) => (removed, format!("- {}", text)),
+                    Some(DiffLine::Added(text)) => (added, format!("+ {}", text)),
+                    None => (dialog.foreground(), String::new()),
+                };
+                execute!(writer, MoveTo(x, row), Print("│ "), SetForegroundColor(color))?;
+                print_fitted(writer, &text, inner.saturating_sub(1))?;
+                execute!(writer, SetForegroundColor(dialog.foreground()), Print("│"))?;
+                row += 1;
+            }
+        }
+
+        execute!(writer, MoveTo(x, row), Print(format!("│{}│", " ".repeat(inner))))?;
+        row += 1;
+        self.draw_buttons(writer, &theme, x, row, inner)?;
+        row += 1;
+
+        // Bottom border with the key help
+        let help = if self.showing_diff { " Esc: Later  ↑↓: Scroll " } else { " Esc: Later " };
+        let help_x = x as usize + box_width.saturating_sub(help.chars().count() + 2);
+        execute!(
+            writer,
+            MoveTo(x, row),
+            Print(format!("╰{}╯", "─".repeat(inner))),
+            MoveTo(help_x as u16, row),
+            SetForegroundColor(theme[Element::DialogDim].foreground()),
+            Print(help),
+            ResetColor
+        )?;
+        writer.flush()
+    }
+
+    /// The buttons, centred on one line
+    fn draw_buttons<W: Write>(&self, writer: &mut W, theme: &Theme, x: u16, y: u16, inner: usize) -> io::Result<()> {
+        let dialog = theme[Element::Dialog];
+        let diff_label = if self.showing_diff { " Hide diff " } else { " Show diff " };
+        let buttons = [
+            (Button::Restore, " Restore ", Element::ButtonSave),
+            (Button::Diff, diff_label, Element::ButtonCancel),
+            (Button::Discard, " Discard ", Element::ButtonDiscard),
+        ];
+        let buttons_width: usize = buttons.iter().map(|(_, label, _)| label.len()).sum::<usize>() + 2 * (buttons.len() - 1);
+        let padding = inner.saturating_sub(buttons_width) / 2;
+        execute!(writer, MoveTo(x, y), Print("│"), Print(" ".repeat(padding)))?;
+        for (idx, (button, label, element)) in buttons.iter().enumerate() {
+            if idx > 0 {
+                execute!(writer, Print("  "))?;
+            }
+            let style = button_style(theme, self.selected == *button, *element);
+            execute!(
+                writer,
+                SetBackgroundColor(style.background()),
+                SetForegroundColor(style.foreground()),
+                Print(label),
+                SetBackgroundColor(dialog.background()),
+                SetForegroundColor(dialog.foreground())
+            )?;
+        }
+        let remaining = inner.saturating_sub(padding + buttons_width);
+        execute!(writer, Print(" ".repeat(remaining)), Print("│"))
+    }
+}
+
+/// Break text into lines of at most `width` characters, between words where it can
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split(' ') {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    lines.push(line);
+    lines
+}
@@ -1,20 +1,26 @@
-use crate::editor::Editor;
+use crate::cell::{is_delimiter_line, tag_list_range, CellBadge};
+use crate::editor::{CellMark, CellState, DisplayLine, Editor};
+use crate::git::LineChange;
+use crate::layout::{Areas, Rect};
 use crate::syntax::{HighlightSpan, SyntaxState};
+use crate::theme::{self, Element};
 use crossterm::{
     cursor::{Hide, MoveTo, Show},
     execute,
     terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen, SetTitle},
 };
+use std::collections::HashMap;
 use std::io::{self, Write};
-use unicode_width::UnicodeWidthChar;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 pub struct Renderer {
     stdout: io::Stdout,
     last_size: (u16, u16),
-    last_screen: Vec<String>,  // Store what we last rendered
+    last_screen: HashMap<(u16, u16), String>, // What we last rendered, by the screen cell each line starts at
     last_status: String,        // Store last status line
     last_title: String,         // Store last terminal title
     last_cursor_style: CursorStyle, // Track cursor style to avoid redundant updates
+    last_areas: Areas,          // Layout used for the last frame
     #[cfg(target_os = "windows")]
     needs_full_redraw: bool,
 }
@@ -30,39 +36,41 @@ impl Renderer {
         let mut stdout = io::stdout();
         execute!(stdout, EnterAlternateScreen, Hide)?;
         
-        // Set initial cursor style and color
+        // Set initial cursor style; the cursor color and background come from the theme
         write!(stdout, "\x1b[2 q")?; // Steady block cursor
-        write!(stdout, "\x1b]12;#5F9EA0\x07")?; // Cadet blue - muted professional cyan
-        
-        // Alternative professional colors you can try:
-        // write!(stdout, "\x1b]12;#708090\x07")?; // Slate grey (very muted)
-        // write!(stdout, "\x1b]12;#4682B4\x07")?; // Steel blue (professional)
-        // write!(stdout, "\x1b]12;#5F8787\x07")?; // Muted teal
-        // write!(stdout, "\x1b]12;#6C8C8C\x07")?; // Steel blue-grey
-        // write!(stdout, "\x1b]12;#7B9FAF\x07")?; // Light slate blue
-        // write!(stdout, "\x1b]12;#8BA4B0\x07")?; // Muted sky blue
-        // write!(stdout, "\x1b]12;#6495ED\x07")?; // Cornflower blue
-        // write!(stdout, "\x1b]12;#B0C4DE\x07")?; // Light steel blue (very subtle)
         stdout.flush()?;
         
-        // Set consistent background color regardless of how we're launched
-        // This ensures the same appearance whether launched from terminal or Explorer
-        write!(stdout, "\x1b[48;2;30;30;30m")?; // Background color RGB(30,30,30)
-        execute!(stdout, Clear(ClearType::All))?;
-        write!(stdout, "\x1b[0m")?; // Reset after clear
-        
         let (width, height) = terminal::size()?;
         
-        Ok(Renderer {
+        let mut renderer = Renderer {
             stdout,
             last_size: (width, height),
-            last_screen: vec![String::new(); height as usize],
+            last_screen: HashMap::new(),
             last_status: String::new(),
             last_title: String::new(),
             last_cursor_style: CursorStyle::Block,
+            last_areas: Areas::editor_only(width, height),
             #[cfg(target_os = "windows")]
             needs_full_redraw: true,
-        })
+        };
+        renderer.apply_theme()?;
+        Ok(renderer)
+    }
+
+    /// Set the cursor color and clear the screen to the theme's background
+    /// Call again after the theme changes, along with `force_redraw`
+    pub fn apply_theme(&mut self) -> io::Result<()> {
+        let theme = theme::current();
+        match theme.cursor_escape() {
+            Some(escape) => write!(self.stdout, "{}", escape)?,
+            None => write!(self.stdout, "\x1b]112\x07")?, // Terminal's own cursor color
+        }
+        // Set consistent background color regardless of how we're launched
+        // This ensures the same appearance whether launched from terminal or Explorer
+        write!(self.stdout, "{}", theme[Element::Text].escape())?;
+        execute!(self.stdout, Clear(ClearType::All))?;
+        write!(self.stdout, "\x1b[0m")?; // Reset after clear
+        self.stdout.flush()
     }
     
     pub fn cleanup(&mut self) -> io::Result<()> {
@@ -76,12 +84,16 @@ impl Renderer {
         Ok(())
     }
     
+    /// Redraw the editor and status bar using the last frame's layout
     pub fn draw(&mut self, editor: &mut Editor) -> io::Result<()> {
-        self.draw_with_bottom_window(editor, 0)
+        let areas = self.last_areas;
+        self.draw_layout(editor, &areas)
     }
     
-    pub fn draw_with_bottom_window(&mut self, editor: &mut Editor, bottom_window_height: usize) -> io::Result<()> {
-        crate::debug_log("draw_with_bottom_window: start");
+    /// Draw the editor and status bar into their areas of the layout
+    /// Panes (output, find bar) draw themselves into their own areas afterwards
+    pub fn draw_layout(&mut self, editor: &mut Editor, areas: &Areas) -> io::Result<()> {
+        crate::debug_log("draw_layout: start");
         // Update cursor style based on selection
         // Note: Output pane handles its own cursor style when focused
         let desired_style = if editor.selection().is_some() {
@@ -99,13 +111,13 @@ impl Renderer {
             self.last_cursor_style = desired_style;
         }
 
-        crate::debug_log("draw_with_bottom_window: getting file_name");
+        crate::debug_log("draw_layout: getting file_name");
         // Update terminal title with filename and modified indicator
         let file_name = editor.file_name();
-        crate::debug_log("draw_with_bottom_window: getting is_modified");
+        crate::debug_log("draw_layout: getting is_modified");
         let modified_indicator = if editor.is_modified() { " *" } else { "" };
 
-        crate::debug_log("draw_with_bottom_window: formatting title");
+        crate::debug_log("draw_layout: formatting title");
         let title = if file_name == "[No Name]" {
             format!("No Name{}", modified_indicator)
         } else {
@@ -117,18 +129,18 @@ impl Renderer {
             self.last_title = title;
         }
 
-        crate::debug_log("draw_with_bottom_window: getting terminal size");
+        crate::debug_log("draw_layout: getting terminal size");
         let (width, height) = terminal::size()?;
 
         // Handle resize
         if (width, height) != self.last_size {
-            crate::debug_log("draw_with_bottom_window: handling resize");
+            crate::debug_log("draw_layout: handling resize");
             self.last_size = (width, height);
-            self.last_screen = vec![String::new(); height as usize];
+            self.last_screen.clear();
             self.last_status.clear();
             self.last_cursor_style = CursorStyle::Block; // Force cursor style refresh on resize
             // Maintain consistent background on resize
-            write!(self.stdout, "\x1b[48;2;30;30;30m")?; // Background color RGB(30,30,30)
+            write!(self.stdout, "{}", theme::current()[Element::Text].escape())?;
             execute!(self.stdout, Clear(ClearType::All))?;
             write!(self.stdout, "\x1b[0m")?; // Reset after clear
             #[cfg(target_os = "windows")]
@@ -137,42 +149,251 @@ impl Renderer {
             }
         }
 
-        crate::debug_log("draw_with_bottom_window: calculating content_height");
-        // Get viewport dimensions for rendering
-        let content_height = height.saturating_sub(1 + bottom_window_height as u16) as usize; // Reserve for status and bottom window
+        crate::debug_log("draw_layout: calculating content_height");
+        // A layout from before a resize no longer fits - fall back to the editor alone
+        let areas = if areas.status.y < height && areas.status.width <= width {
+            *areas
+        } else {
+            Areas::editor_only(width, height)
+        };
+        self.last_areas = areas;
+
+        // Draw the focused window
+        self.draw_lines(editor, areas.editor)?;
+        let buffer = editor.buffer();
+        let width = areas.status.width;
+
+        crate::debug_log("draw_layout: drawing loop completed, building status line");
+        // Divider between the editor and a pane on its right
+        if let Some(divider) = areas.divider {
+            let style = theme::current()[Element::Divider].escape();
+            for row in divider.y..divider.y + divider.height {
+                write!(self.stdout, "\x1b[{};{}H{}│\x1b[0m", row + 1, divider.x + 1, style)?;
+            }
+        }
+
+        // Build status line - position it above any bottom window
+        let status_row = areas.status.y as usize;
+        crate::debug_log("draw_layout: calling is_modified");
+        let modified_indicator = if editor.is_modified() { "*" } else { "" };
+        crate::debug_log("draw_layout: calling is_read_only");
+        let read_only_indicator = if editor.is_read_only() { " [RO]" } else { "" };
+        crate::debug_log("draw_layout: calling file_name");
+        let file_name = editor.file_name();
+        crate::debug_log("draw_layout: calling cursor_position");
+        let (line, col) = editor.cursor_position();
+        crate::debug_log("draw_layout: calling buffer.len_lines");
+        let total_lines = buffer.len_lines();
+        
+        // Check for status messages (errors)
+        let (status_msg, is_error) = if let Some((msg, is_err)) = &editor.status_message {
+            (msg.as_str(), *is_err)
+        } else {
+            ("", false)
+        };
+        
+        crate::debug_log("draw_layout: formatting left_status");
+        let left_status = if !status_msg.is_empty() {
+            // Show error message instead of filename
+            format!(" {} ", status_msg)
+        } else {
+            format!(" {}{}{} ", file_name, modified_indicator, read_only_indicator)
+        };
+
+        crate::debug_log("draw_layout: calling is_repl_mode");
+        // Add kernel info if in REPL mode
+        let mut kernel_info = if editor.is_repl_mode() {
+            crate::debug_log("draw_layout: in REPL mode, calling get_kernel_info");
+            if let Some(kernel_name) = editor.get_kernel_info() {
+                format!(" [{}] ", kernel_name)
+            } else {
+                " [No kernel] ".to_string()
+            }
+        } else {
+            String::new()
+        };
+        crate::debug_log("draw_layout: kernel_info formatted");
+
+        crate::debug_log("draw_layout: formatting right_status");
+        // Format the right status with fixed-width fields
+        // Right-align the entire row/total as one unit (19 chars) and column (4 chars)
+        // This accommodates up to 999,999,999 lines (9 digits + "/" + 9 digits)
+        let row_info = format!("{}/{}", line + 1, total_lines);
+        let cursor_count = match editor.extra_cursor_positions().len() {
+            0 => String::new(),
+            extra => format!("{} cursors ", extra + 1),
+        };
+        let right_status = format!(" {}{:>19}  {:>4} ",
+            cursor_count,
+            row_info,
+            col + 1
+        );
+
+        crate::debug_log("draw_layout: building full status_line");
+        // Calculate available space and truncate kernel_info if needed
+        let min_width = left_status.len() + right_status.len();
+        let max_kernel_width = if min_width < width as usize {
+            (width as usize).saturating_sub(min_width)
+        } else {
+            0
+        };
+
+        // Truncate kernel_info if it's too long
+        if kernel_info.len() > max_kernel_width {
+            if max_kernel_width > 4 {
+                // Truncate and add "..."
+                let truncate_to = max_kernel_width.saturating_sub(3);
+                kernel_info = kernel_info.chars().take(truncate_to).collect::<String>() + "...";
+            } else {
+                kernel_info.clear();
+            }
+        }
+
+        let mut status_line = String::with_capacity(width as usize);
+        status_line.push_str(&left_status);
+        status_line.push_str(&kernel_info);
+        // Calculate padding - ensure we never exceed width
+        let used_width = left_status.chars().count() + kernel_info.chars().count() + right_status.chars().count();
+        let padding = if used_width < width as usize {
+            width as usize - used_width
+        } else {
+            0
+        };
+        for _ in 0..padding {
+            status_line.push(' ');
+        }
+        status_line.push_str(&right_status);
+
+        // Final safety check: ensure status line doesn't exceed width
+        let status_chars: Vec<char> = status_line.chars().collect();
+        if status_chars.len() > width as usize {
+            status_line = status_chars.iter().take(width as usize).collect();
+        }
+
+        crate::debug_log("draw_layout: about to write status line to stdout");
+        // Only update status if it changed
+        #[cfg(target_os = "windows")]
+        let status_changed = self.needs_full_redraw || status_line != self.last_status;
+        #[cfg(not(target_os = "windows"))]
+        let status_changed = status_line != self.last_status;
+        if status_changed {
+            // Errors stand out from the normal status bar
+            let style = theme::current()[if is_error { Element::StatusError } else { Element::Status }];
+            write!(self.stdout, "\x1b[{};1H{}{}\x1b[0m", status_row + 1, style.escape(), status_line)?;
+            crate::debug_log("draw_layout: status line written");
+            self.last_status = status_line;
+        } else {
+            crate::debug_log("draw_layout: status line unchanged, skipping write");
+        }
+        #[cfg(target_os = "windows")]
+        {
+            self.needs_full_redraw = false;
+        }
+        crate::debug_log("draw_layout: status line complete");
+        
+        // Position cursor - map buffer position to screen position
+        // Only show cursor if find/replace is closed
+        if areas.find_bar.is_none() {
+            let viewport_offset = editor.viewport_offset();
+            let gutter = editor.gutter_width();
+            let (content_height, width) = (areas.editor.height as usize, areas.editor.width.saturating_sub(gutter as u16));
+            let (origin_x, origin_y) = (areas.editor.x, areas.editor.y);
+            let (logical_cursor_line, cursor_col) = editor.cursor_display_position(); // Counts the virtual lines before buffer
+            
+            if logical_cursor_line >= viewport_offset.0 && 
+               logical_cursor_line < viewport_offset.0 + content_height &&
+               cursor_col >= viewport_offset.1 &&
+               cursor_col < viewport_offset.1 + width as usize {
+                
+                let screen_row = logical_cursor_line - viewport_offset.0;
+                let screen_col = gutter + cursor_col - viewport_offset.1;
+                
+                let (screen_col, screen_row) = (origin_x as usize + screen_col, origin_y as usize + screen_row);
+
+                #[cfg(target_os = "windows")]
+                write!(self.stdout, "\x1b[{};{}H\x1b[?25h", 
+                    screen_row + 1, screen_col + 1)?;
+                
+                #[cfg(not(target_os = "windows"))]
+                execute!(
+                    self.stdout,
+                    MoveTo(screen_col as u16, screen_row as u16),
+                    Show
+                )?;
+            }
+        }
+        // If find/replace is open, cursor will be positioned by find_replace.draw()
+        
+        self.stdout.flush()?;
+        Ok(())
+    }
+    
+    /// Draw an unfocused split window - just its lines, without the status bar or cursor
+    pub fn draw_window(&mut self, editor: &mut Editor, area: Rect) -> io::Result<()> {
+        self.draw_lines(editor, area)
+    }
+
+    /// Draw a window's lines into its area of the screen
+    fn draw_lines(&mut self, editor: &mut Editor, area: Rect) -> io::Result<()> {
+        // Get viewport dimensions for rendering - lines are drawn across the window's area only
+        let content_height = area.height as usize;
+        let (origin_x, origin_y) = (area.x, area.y);
         // Note: viewport is only updated when cursor moves, not on every render
 
-        crate::debug_log("draw_with_bottom_window: about to update_syntax_viewport");
+        crate::debug_log("draw_layout: about to update_syntax_viewport");
         // Process syntax highlighting first (requires mutable borrow)
         // Update viewport for large files
         let viewport_height = content_height;
         editor.update_syntax_viewport(viewport_height);
-        crate::debug_log("draw_with_bottom_window: update_syntax_viewport complete");
+        crate::debug_log("draw_layout: update_syntax_viewport complete");
         // Only update syntax highlighting if we have work to do
-        crate::debug_log(&format!("draw_with_bottom_window: has_syntax_work = {}", editor.has_syntax_work()));
+        crate::debug_log(&format!("draw_layout: has_syntax_work = {}", editor.has_syntax_work()));
         if editor.has_syntax_work() {
-            crate::debug_log("draw_with_bottom_window: about to update_syntax_highlighting");
+            crate::debug_log("draw_layout: about to update_syntax_highlighting");
             editor.update_syntax_highlighting();
-            crate::debug_log("draw_with_bottom_window: update_syntax_highlighting complete");
+            crate::debug_log("draw_layout: update_syntax_highlighting complete");
         }
+        editor.ensure_git_changes();
+
+        // Text is drawn to the right of the gutter
+        let gutter = editor.gutter_width();
+        let width = area.width.saturating_sub(gutter as u16);
+        editor.refresh_wrap(width as usize);
 
-        crate::debug_log("draw_with_bottom_window: getting viewport_offset");
+        crate::debug_log("draw_layout: getting viewport_offset");
         // Now get all the data we need with immutable borrows
         let viewport_offset = editor.viewport_offset();
-        crate::debug_log("draw_with_bottom_window: getting selection");
+        crate::debug_log("draw_layout: getting selection");
         let selection = editor.selection();
-        crate::debug_log("draw_with_bottom_window: getting buffer");
+        let extra_selections = editor.extra_selections();
+        let extra_cursors = editor.extra_cursor_positions();
+        crate::debug_log("draw_layout: getting buffer");
         let buffer = editor.buffer();
-        crate::debug_log("draw_with_bottom_window: getting matching_brackets");
+        crate::debug_log("draw_layout: getting matching_brackets");
         let matching_brackets = editor.get_matching_brackets();
-        crate::debug_log("draw_with_bottom_window: getting matching_text_positions");
+        crate::debug_log("draw_layout: getting matching_text_positions");
         let matching_text_positions = editor.get_matching_text_positions();
-        crate::debug_log("draw_with_bottom_window: getting find_matches");
+        crate::debug_log("draw_layout: getting find_matches");
         let find_matches = editor.get_find_matches();
-        crate::debug_log("draw_with_bottom_window: getting current_find_match");
+        crate::debug_log("draw_layout: getting current_find_match");
         let current_find_match = editor.get_current_find_match();
+        let cell_badges: HashMap<usize, Vec<CellBadge>> = editor.cell_badges().into_iter().collect();
+        // Lines hidden by the fold starting on each line
+        let folds: HashMap<usize, usize> =
+            editor.folded_ranges().into_iter().map(|(first, last)| (first - 1, last - first + 1)).collect();
+        let display_lines = editor.display_lines(viewport_offset.0, content_height);
+        let visible_lines = display_lines.iter().filter_map(|line| match *line {
+            DisplayLine::Text(line) | DisplayLine::Wrap { line, .. } if line >= 2 => Some(line - 2),
+            _ => None,
+        });
+        let cell_marks = match (visible_lines.clone().min(), visible_lines.max()) {
+            (Some(first), Some(last)) if gutter > 0 => editor.cell_marks(first, last),
+            _ => HashMap::new(),
+        };
+        let theme = theme::current();
+        let text_style = theme[Element::Text];
 
-        crate::debug_log("draw_with_bottom_window: hiding cursor");
+        crate::debug_log("draw_layout: hiding cursor");
         // Hide cursor while drawing
         #[cfg(target_os = "windows")]
         write!(self.stdout, "\x1b[?25l")?;
@@ -180,33 +401,63 @@ impl Renderer {
         #[cfg(not(target_os = "windows"))]
         execute!(self.stdout, Hide)?;
 
-        crate::debug_log(&format!("draw_with_bottom_window: starting line drawing loop, content_height = {}", content_height));
+        crate::debug_log(&format!("draw_layout: starting line drawing loop, content_height = {}", content_height));
         // Draw all lines
-        for screen_row in 0..content_height {
+        for (screen_row, display_line) in display_lines.iter().enumerate() {
             if screen_row == 0 || screen_row == content_height - 1 || screen_row % 10 == 0 {
-                crate::debug_log(&format!("draw_with_bottom_window: drawing screen_row {}", screen_row));
+                crate::debug_log(&format!("draw_layout: drawing screen_row {}", screen_row));
             }
             let mut line_content = String::with_capacity(width as usize);
             
             // Calculate which logical line we're displaying
             // Logical lines: 0 and 1 are virtual, 2+ map to buffer lines 0+
-            let logical_line = viewport_offset.0 + screen_row;
+            // Inline output rows sit between buffer lines and aren't part of the buffer
+            // A soft-wrapped line takes a row for each part of it
+            let (logical_line, wrap_row, inline_row) = match *display_line {
+                DisplayLine::Text(line) => (line, 0, None),
+                DisplayLine::Wrap { line, row } => (line, row, None),
+                DisplayLine::Output { cell_idx, row } => (0, 0, Some((cell_idx, row))),
+            };
             
-            if logical_line < 2 {
+            if let Some((cell_idx, row)) = inline_row {
+                let (text, is_header, is_error) = editor.inline_output_row(cell_idx, row);
+                let row_style = if is_header {
+                    theme[Element::InlineOutputHeader]
+                } else if is_error {
+                    theme[Element::InlineOutputError]
+                } else {
+                    Default::default()
+                };
+                line_content.push_str(&row_style.over(theme[Element::InlineOutput]).escape());
+                let prefix = if is_header { "" } else { "  │ " };
+                let mut used = 0;
+                for ch in prefix.chars().chain(text.chars()) {
+                    let char_width = ch.width().unwrap_or(1);
+                    if used + char_width > width as usize {
+                        break;
+                    }
+                    line_content.push(ch);
+                    used += char_width;
+                }
+                while used < width as usize {
+                    line_content.push(' ');
+                    used += 1;
+                }
+                line_content.push_str("\x1b[0m");
+            } else if logical_line < 2 {
                 // Virtual lines before the buffer - respect horizontal scrolling
                 if viewport_offset.1 == 0 {
                     // Only show the ~ if we're not horizontally scrolled
-                    line_content.push_str("\x1b[48;2;30;30;30m"); // Consistent background
-                    line_content.push_str("\x1b[38;2;110;110;110m"); // Dim grey like comments
+                    line_content.push_str(&theme[Element::NonText].over(text_style).escape());
                     line_content.push('~');
-                    line_content.push_str("\x1b[39m"); // Reset foreground color
+                    line_content.push_str(&text_style.escape());
                     for _ in 1..width {
                         line_content.push(' ');
                     }
                     line_content.push_str("\x1b[0m");
                 } else {
                     // If horizontally scrolled, show all spaces
-                    line_content.push_str("\x1b[48;2;30;30;30m"); // Consistent background
+                    line_content.push_str(&text_style.escape());
                     for _ in 0..width {
                         line_content.push(' ');
                     }
@@ -226,27 +477,49 @@ impl Renderer {
                     
                     // Calculate byte positions for this line
                     let line_byte_start = buffer.line_to_byte(file_row);
+
+                    // The part of the line on this row, which is all of it unless soft wrap splits it
+                    let (row_start, row_end, (wrap_indent, wrap_indicator)) = editor.wrap_row(file_row, wrap_row);
+                    let is_last_row = row_end == line_display.len();
                     
                     // Get syntax highlighting for this line
                     let syntax_spans = editor.get_syntax_spans(file_row);
+
+                    // Tags on a cell delimiter line (`##$$ [skip, slow] title`)
+                    let tag_range = if is_delimiter_line(line_display) {
+                        tag_list_range(line_display)
+                    } else {
+                        None
+                    };
                     
                     // Build line with selection and syntax highlighting
                     let mut formatted_line = String::new();
                     // Start with the background color for the entire line
                     // Check if this is the current line
                     let is_current_line = file_row == editor.cursor_position().0;
-                    let line_bg_color = if is_current_line {
-                        "\x1b[48;2;40;40;40m" // Current line background RGB(40,40,40)
+                    let line_style = if is_current_line {
+                        theme[Element::CurrentLine].over(text_style)
                     } else {
-                        "\x1b[48;2;30;30;30m" // Normal background RGB(30,30,30)
+                        text_style
                     };
-                    formatted_line.push_str(line_bg_color); // Set line background
-                    let mut byte_pos = line_byte_start;
+                    formatted_line.push_str(&line_style.escape()); // Set line background
+                    let mut current_style = line_style; // Style the terminal is drawing with
+                    let mut byte_pos = line_byte_start + row_start;
                     let mut display_col = 0;  // Display column position (accounts for wide chars)
                     let mut screen_col = 0;    // Screen column position after horizontal scroll
-                    let mut line_byte_offset = 0;  // Byte offset within the line
+                    let mut line_byte_offset = row_start;  // Byte offset within the line
+
+                    // A continuation row starts at the line's indentation, after the wrap indicator
+                    if wrap_row > 0 {
+                        formatted_line.push_str(&" ".repeat(wrap_indent));
+                        let indicator = if wrap_indicator > 0 { editor.wrap_indicator() } else { "" };
+                        formatted_line.push_str(&theme[Element::NonText].over(line_style).escape());
+                        formatted_line.push_str(indicator);
+                        formatted_line.push_str(&line_style.escape());
+                        screen_col += wrap_indent + wrap_indicator;
+                    }
                     
-                    for ch in line_display.chars() {
+                    for ch in line_display[row_start..row_end].chars() {
                         // Get the display width of this character (0, 1, or 2 columns)
                         let char_width = ch.width().unwrap_or(1);
                         
@@ -262,11 +535,15 @@ impl Renderer {
                             if display_col < viewport_offset.1 && char_width > 1 {
                                 // Wide character is partially cut off by horizontal scroll
                                 // Skip it and add padding space
+                                if current_style != line_style {
+                                    formatted_line.push_str(&line_style.escape());
+                                    current_style = line_style;
+                                }
                                 formatted_line.push(' ');
                                 screen_col += 1;
                             } else {
                                 // Check if this character is selected
-                                let is_selected = selection.map_or(false, |(sel_start, sel_end)| {
+                                let is_selected = selection.iter().chain(&extra_selections).any(|&(sel_start, sel_end)| {
                                     byte_pos >= sel_start && byte_pos < sel_end
                                 });
 
@@ -280,11 +557,17 @@ impl Renderer {
                                     byte_pos >= *start && byte_pos < *end
                                 });
 
-                                // Check if this character is part of a find match
-                                let (is_find_match, is_current_find_match) = find_matches.iter().enumerate()
-                                    .find(|(_, (start, end))| byte_pos >= *start && byte_pos < *end)
-                                    .map(|(idx, _)| (true, current_find_match == Some(idx)))
-                                    .unwrap_or((false, false));
+                                // Check if this character is part of a find match (they're sorted, and can be many)
+                                let find_idx = find_matches.partition_point(|(_, end)| *end <= byte_pos);
+                                let (is_find_match, is_current_find_match) = match find_matches.get(find_idx) {
+                                    Some((start, _)) if *start <= byte_pos => (true, current_find_match == Some(find_idx)),
+                                    _ => (false, false),
+                                };
+
+                                // Check if this character is part of the cell tag list
+                                let in_tag_list = tag_range.is_some_and(|(start, end)| {
+                                    line_byte_offset >= start && line_byte_offset < end
+                                });
 
                                 // Check syntax highlighting for this character
                                 let mut syntax_state = SyntaxState::Normal;
@@ -297,165 +580,31 @@ impl Renderer {
                                     }
                                 }
                                 
-                                #[cfg(target_os = "windows")]
-                                {
-                                    if is_selected {
-                                        // Use same cadet blue as cursor (#5F9EA0) with black text
-                                        formatted_line.push_str("\x1b[48;2;95;158;160m\x1b[38;2;0;0;0m"); // RGB background + black foreground
-                                    } else if is_current_find_match {
-                                        // Bright orange background for current find match
-                                        formatted_line.push_str("\x1b[48;2;200;150;100m\x1b[38;2;0;0;0m"); // Bright orange + black text
-                                    } else if is_find_match {
-                                        // Dimmer orange background for other find matches
-                                        formatted_line.push_str("\x1b[48;2;120;90;60m"); // Dim orange background
-                                    } else if is_matching_bracket {
-                                        // Bright yellow for matching brackets
-                                        formatted_line.push_str("\x1b[38;2;220;220;120m\x1b[1m"); // Bright yellow + bold
-                                    } else if is_matching_text {
-                                        // Dim version of selection background for matching text
-                                        formatted_line.push_str("\x1b[48;2;50;80;82m"); // Dimmer version of selection color
-                                    } else {
-                                        // Apply syntax highlighting colors - muted and professional
-                                        match syntax_state {
-                                            SyntaxState::StringDouble | SyntaxState::StringSingle | SyntaxState::StringTriple | SyntaxState::StringTripleSingle => {
-                                                // Muted green for strings
-                                                formatted_line.push_str("\x1b[38;2;152;180;152m"); // #98B498
-                                            }
-                                            SyntaxState::LineComment | SyntaxState::BlockComment => {
-                                                // Dimmer grey for comments
-                                                formatted_line.push_str("\x1b[38;2;110;110;110m"); // #6E6E6E
-                                            }
-                                            SyntaxState::Keyword => {
-                                                // Muted blue for keywords
-                                                formatted_line.push_str("\x1b[38;2;135;160;180m"); // #87A0B4
-                                            }
-                                            SyntaxState::Type => {
-                                                // Muted teal for types
-                                                formatted_line.push_str("\x1b[38;2;132;170;170m"); // #84AAAA
-                                            }
-                                            SyntaxState::Function => {
-                                                // Muted yellow for functions
-                                                formatted_line.push_str("\x1b[38;2;200;190;150m"); // #C8BE96
-                                            }
-                                            SyntaxState::Number => {
-                                                // Muted orange for numbers
-                                                formatted_line.push_str("\x1b[38;2;200;170;140m"); // #C8AA8C
-                                            }
-                                            SyntaxState::Operator => {
-                                                // Lighter grey for operators
-                                                formatted_line.push_str("\x1b[38;2;160;160;160m"); // #A0A0A0
-                                            }
-                                            SyntaxState::Punctuation => {
-                                                // Default color for punctuation
-                                                // No need to set color
-                                            }
-                                            SyntaxState::MacroOrDecorator => {
-                                                // Muted purple for macros/decorators
-                                                formatted_line.push_str("\x1b[38;2;180;150;180m"); // #B496B4
-                                            }
-                                            SyntaxState::Normal => {
-                                                // Normal text - default foreground color
-                                                // No need to set color as it's already the default
-                                            }
-                                        }
-                                    }
-                                    formatted_line.push(ch);
-                                    if is_selected || is_current_find_match || is_find_match {
-                                        formatted_line.push_str("\x1b[0m");
-                                        formatted_line.push_str(line_bg_color); // Reset and restore line background
-                                    } else if is_matching_bracket {
-                                        // Reset bold and color
-                                        formatted_line.push_str("\x1b[0m");
-                                        formatted_line.push_str(line_bg_color); // Restore line background
-                                    } else if is_matching_text {
-                                        // Reset background
-                                        formatted_line.push_str("\x1b[49m");
-                                        formatted_line.push_str(line_bg_color); // Restore line background
-                                    } else if syntax_state != SyntaxState::Normal && syntax_state != SyntaxState::Punctuation {
-                                        // Reset color after syntax-highlighted character
-                                        formatted_line.push_str("\x1b[39m"); // Reset foreground only
-                                    }
-                                }
-                                
-                                #[cfg(not(target_os = "windows"))]
-                                {
-                                    if is_selected {
-                                        // Use same cadet blue as cursor (#5F9EA0) with black text
-                                        formatted_line.push_str("\x1b[48;2;95;158;160m\x1b[38;2;0;0;0m"); // RGB background + black foreground
-                                    } else if is_current_find_match {
-                                        // Bright orange background for current find match
-                                        formatted_line.push_str("\x1b[48;2;200;150;100m\x1b[38;2;0;0;0m"); // Bright orange + black text
-                                    } else if is_find_match {
-                                        // Dimmer orange background for other find matches
-                                        formatted_line.push_str("\x1b[48;2;120;90;60m"); // Dim orange background
-                                    } else if is_matching_bracket {
-                                        // Bright yellow for matching brackets
-                                        formatted_line.push_str("\x1b[38;2;220;220;120m\x1b[1m"); // Bright yellow + bold
-                                    } else if is_matching_text {
-                                        // Dim version of selection background for matching text
-                                        formatted_line.push_str("\x1b[48;2;50;80;82m"); // Dimmer version of selection color
-                                    } else {
-                                        // Apply syntax highlighting colors - muted and professional
-                                        match syntax_state {
-                                            SyntaxState::StringDouble | SyntaxState::StringSingle | SyntaxState::StringTriple | SyntaxState::StringTripleSingle => {
-                                                // Muted green for strings
-                                                formatted_line.push_str("\x1b[38;2;152;180;152m"); // #98B498
-                                            }
-                                            SyntaxState::LineComment | SyntaxState::BlockComment => {
-                                                // Dimmer grey for comments
-                                                formatted_line.push_str("\x1b[38;2;110;110;110m"); // #6E6E6E
-                                            }
-                                            SyntaxState::Keyword => {
-                                                // Muted blue for keywords
-                                                formatted_line.push_str("\x1b[38;2;135;160;180m"); // #87A0B4
-                                            }
-                                            SyntaxState::Type => {
-                                                // Muted teal for types
-                                                formatted_line.push_str("\x1b[38;2;132;170;170m"); // #84AAAA
-                                            }
-                                            SyntaxState::Function => {
-                                                // Muted yellow for functions
-                                                formatted_line.push_str("\x1b[38;2;200;190;150m"); // #C8BE96
-                                            }
-                                            SyntaxState::Number => {
-                                                // Muted orange for numbers
-                                                formatted_line.push_str("\x1b[38;2;200;170;140m"); // #C8AA8C
-                                            }
-                                            SyntaxState::Operator => {
-                                                // Lighter grey for operators
-                                                formatted_line.push_str("\x1b[38;2;160;160;160m"); // #A0A0A0
-                                            }
-                                            SyntaxState::Punctuation => {
-                                                // Default color for punctuation
-                                                // No need to set color
-                                            }
-                                            SyntaxState::MacroOrDecorator => {
-                                                // Muted purple for macros/decorators
-                                                formatted_line.push_str("\x1b[38;2;180;150;180m"); // #B496B4
-                                            }
-                                            SyntaxState::Normal => {
-                                                // Normal text - default foreground color
-                                                // No need to set color as it's already the default
-                                            }
-                                        }
-                                    }
-                                    formatted_line.push(ch);
-                                    if is_selected || is_current_find_match || is_find_match {
-                                        formatted_line.push_str("\x1b[0m");
-                                        formatted_line.push_str(line_bg_color); // Reset and restore line background
-                                    } else if is_matching_bracket {
-                                        // Reset bold and color
-                                        formatted_line.push_str("\x1b[0m");
-                                        formatted_line.push_str(line_bg_color); // Restore line background
-                                    } else if is_matching_text {
-                                        // Reset background
-                                        formatted_line.push_str("\x1b[49m");
-                                        formatted_line.push_str(line_bg_color); // Restore line background
-                                    } else if syntax_state != SyntaxState::Normal && syntax_state != SyntaxState::Punctuation {
-                                        // Reset color after syntax-highlighted character
-                                        formatted_line.push_str("\x1b[39m"); // Reset foreground only
-                                    }
+                                // Selection and find matches win over brackets, matching text,
+                                // cell tags and syntax colors
+                                let highlight = if extra_cursors.contains(&byte_pos) {
+                                    theme[Element::ExtraCursor]
+                                } else if is_selected {
+                                    theme[Element::Selection]
+                                } else if is_current_find_match {
+                                    theme[Element::FindMatchCurrent]
+                                } else if is_find_match {
+                                    theme[Element::FindMatch]
+                                } else if is_matching_bracket {
+                                    theme[Element::MatchingBracket]
+                                } else if is_matching_text {
+                                    theme[Element::MatchingText]
+                                } else if in_tag_list {
+                                    theme[Element::CellTags]
+                                } else {
+                                    theme.syntax(syntax_state)
+                                };
+                                let style = highlight.over(line_style);
+                                if style != current_style {
+                                    formatted_line.push_str(&style.escape());
+                                    current_style = style;
                                 }
+                                formatted_line.push(ch);
                                 
                                 screen_col += char_width;
                             }
@@ -466,6 +615,51 @@ impl Renderer {
                         display_col += char_width;
                     }
                     
+                    // An extra cursor at the end of the line sits on the space after it
+                    let line_end = line_byte_start + line_display.len();
+                    if byte_pos == line_end && extra_cursors.contains(&line_end)
+                        && display_col >= viewport_offset.1 && screen_col < width as usize
+                    {
+                        current_style = theme[Element::ExtraCursor].over(line_style);
+                        formatted_line.push_str(&current_style.escape());
+                        formatted_line.push(' ');
+                        screen_col += 1;
+                    }
+
+                    // Cell status badges, right-aligned on delimiter lines
+                    if current_style != line_style {
+                        formatted_line.push_str(&line_style.escape());
+                    }
+
+                    // How much a fold on this line hides
+                    if let Some(&count) = folds.get(&file_row).filter(|_| is_last_row) {
+                        let marker = format!("⋯ {} line{}", count, if count == 1 { "" } else { "s" });
+                        if screen_col + marker.width() + 3 <= width as usize {
+                            formatted_line.push(' ');
+                            formatted_line.push_str(&theme[Element::FoldMarker].over(line_style).escape());
+                            formatted_line.push_str(&format!(" {} ", marker));
+                            formatted_line.push_str(&line_style.escape());
+                            screen_col += marker.width() + 3;
+                        }
+                    }
+                    if let Some(badges) = cell_badges.get(&file_row).filter(|_| is_last_row && is_delimiter_line(line_display)) {
+                        let rendered: Vec<(String, Element)> = badges.iter().map(badge_style).collect();
+                        let badges_width: usize = rendered.iter().map(|(text, _)| text.width() + 1).sum();
+                        if screen_col + badges_width < width as usize {
+                            while screen_col < width as usize - badges_width {
+                                formatted_line.push(' ');
+                                screen_col += 1;
+                            }
+                            for (text, element) in rendered {
+                                formatted_line.push_str(&theme[element].over(line_style).escape());
+                                formatted_line.push_str(&text);
+                                formatted_line.push_str(&line_style.escape());
+                                formatted_line.push(' ');
+                                screen_col += text.width() + 1;
+                            }
+                        }
+                    }
+
                     // Pad the rest of the line with spaces (background already set)
                     while screen_col < width as usize {
                         formatted_line.push(' ');
@@ -478,17 +672,16 @@ impl Renderer {
                     // Virtual line after the buffer - respect horizontal scrolling
                     if viewport_offset.1 == 0 {
                         // Only show the ~ if we're not horizontally scrolled
-                        line_content.push_str("\x1b[48;2;30;30;30m"); // Consistent background
-                        line_content.push_str("\x1b[38;2;110;110;110m"); // Dim grey like comments
+                        line_content.push_str(&theme[Element::NonText].over(text_style).escape());
                         line_content.push('~');
-                        line_content.push_str("\x1b[39m"); // Reset foreground color
+                        line_content.push_str(&text_style.escape());
                         for _ in 1..width {
                             line_content.push(' ');
                         }
                         line_content.push_str("\x1b[0m");
                     } else {
                         // If horizontally scrolled, show all spaces
-                        line_content.push_str("\x1b[48;2;30;30;30m"); // Consistent background
+                        line_content.push_str(&text_style.escape());
                         for _ in 0..width {
                             line_content.push(' ');
                         }
@@ -497,215 +690,44 @@ impl Renderer {
                 }
             }
             
+            if gutter > 0 {
+                let file_row = match *display_line {
+                    DisplayLine::Text(line) if line >= 2 && line - 2 < buffer.len_lines() => Some(line - 2),
+                    _ => None,
+                };
+                line_content.insert_str(0, &gutter_text(editor, file_row, gutter, &cell_marks));
+            }
+
             // Only update if this line has changed
+            // Cache by where the line starts on screen, since windows may sit side by side
+            let term_row = origin_y as usize + screen_row;
             #[cfg(target_os = "windows")]
             {
-                if self.needs_full_redraw || self.last_screen.get(screen_row) != Some(&line_content) {
-                    write!(self.stdout, "\x1b[{};1H{}", screen_row + 1, line_content)?;
-                    if screen_row < self.last_screen.len() {
-                        self.last_screen[screen_row] = line_content;
-                    }
+                if self.needs_full_redraw || self.last_screen.get(&(origin_x, term_row as u16)) != Some(&line_content) {
+                    write!(self.stdout, "\x1b[{};{}H{}", term_row + 1, origin_x + 1, line_content)?;
+                    self.last_screen.insert((origin_x, term_row as u16), line_content);
                 }
             }
             
             #[cfg(not(target_os = "windows"))]
             {
-                if self.last_screen.get(screen_row) != Some(&line_content) {
-                    execute!(self.stdout, MoveTo(0, screen_row as u16))?;
+                if self.last_screen.get(&(origin_x, term_row as u16)) != Some(&line_content) {
+                    execute!(self.stdout, MoveTo(origin_x, term_row as u16))?;
                     print!("{}", line_content);
-                    if screen_row < self.last_screen.len() {
-                        self.last_screen[screen_row] = line_content;
-                    }
+                    self.last_screen.insert((origin_x, term_row as u16), line_content);
                 }
             }
         }
 
-        crate::debug_log("draw_with_bottom_window: drawing loop completed, building status line");
-        // Build status line - position it above any bottom window
-        let status_row = (height - 1 - bottom_window_height as u16) as usize;
-        crate::debug_log("draw_with_bottom_window: calling is_modified");
-        let modified_indicator = if editor.is_modified() { "*" } else { "" };
-        crate::debug_log("draw_with_bottom_window: calling is_read_only");
-        let read_only_indicator = if editor.is_read_only() { " [RO]" } else { "" };
-        crate::debug_log("draw_with_bottom_window: calling file_name");
-        let file_name = editor.file_name();
-        crate::debug_log("draw_with_bottom_window: calling cursor_position");
-        let (line, col) = editor.cursor_position();
-        crate::debug_log("draw_with_bottom_window: calling buffer.len_lines");
-        let total_lines = buffer.len_lines();
-        
-        // Check for status messages (errors)
-        let (status_msg, is_error) = if let Some((msg, is_err)) = &editor.status_message {
-            (msg.as_str(), *is_err)
-        } else {
-            ("", false)
-        };
-        
-        crate::debug_log("draw_with_bottom_window: formatting left_status");
-        let left_status = if !status_msg.is_empty() {
-            // Show error message instead of filename
-            format!(" {} ", status_msg)
-        } else {
-            format!(" {}{}{} ", file_name, modified_indicator, read_only_indicator)
-        };
-
-        crate::debug_log("draw_with_bottom_window: calling is_repl_mode");
-        // Add kernel info if in REPL mode
-        let mut kernel_info = if editor.is_repl_mode() {
-            crate::debug_log("draw_with_bottom_window: in REPL mode, calling get_kernel_info");
-            if let Some(kernel_name) = editor.get_kernel_info() {
-                format!(" [{}] ", kernel_name)
-            } else {
-                " [No kernel] ".to_string()
-            }
-        } else {
-            String::new()
-        };
-        crate::debug_log("draw_with_bottom_window: kernel_info formatted");
-
-        crate::debug_log("draw_with_bottom_window: formatting right_status");
-        // Format the right status with fixed-width fields
-        // Right-align the entire row/total as one unit (19 chars) and column (4 chars)
-        // This accommodates up to 999,999,999 lines (9 digits + "/" + 9 digits)
-        let row_info = format!("{}/{}", line + 1, total_lines);
-        let right_status = format!(" {:>19}  {:>4} ",
-            row_info,
-            col + 1
-        );
-
-        crate::debug_log("draw_with_bottom_window: building full status_line");
-        // Calculate available space and truncate kernel_info if needed
-        let min_width = left_status.len() + right_status.len();
-        let max_kernel_width = if min_width < width as usize {
-            (width as usize).saturating_sub(min_width)
-        } else {
-            0
-        };
-
-        // Truncate kernel_info if it's too long
-        if kernel_info.len() > max_kernel_width {
-            if max_kernel_width > 4 {
-                // Truncate and add "..."
-                let truncate_to = max_kernel_width.saturating_sub(3);
-                kernel_info = kernel_info.chars().take(truncate_to).collect::<String>() + "...";
-            } else {
-                kernel_info.clear();
-            }
-        }
-
-        let mut status_line = String::with_capacity(width as usize);
-        status_line.push_str(&left_status);
-        status_line.push_str(&kernel_info);
-        // Calculate padding - ensure we never exceed width
-        let used_width = left_status.chars().count() + kernel_info.chars().count() + right_status.chars().count();
-        let padding = if used_width < width as usize {
-            width as usize - used_width
-        } else {
-            0
-        };
-        for _ in 0..padding {
-            status_line.push(' ');
-        }
-        status_line.push_str(&right_status);
-
-        // Final safety check: ensure status line doesn't exceed width
-        let status_chars: Vec<char> = status_line.chars().collect();
-        if status_chars.len() > width as usize {
-            status_line = status_chars.iter().take(width as usize).collect();
-        }
-
-        crate::debug_log("draw_with_bottom_window: about to write status line to stdout");
-        // Only update status if it changed
-        #[cfg(target_os = "windows")]
-        {
-            if self.needs_full_redraw || status_line != self.last_status {
-                if is_error {
-                    // Red background for errors
-                    write!(self.stdout,
-                        "\x1b[{};1H\x1b[48;5;196m\x1b[38;5;15m{}\x1b[0m",
-                        height, status_line)?;
-                } else {
-                    // Normal dark grey background
-                    write!(self.stdout,
-                        "\x1b[{};1H\x1b[48;5;238m\x1b[38;5;15m{}\x1b[0m",
-                        height, status_line)?;
-                }
-                self.last_status = status_line;
-            }
-            self.needs_full_redraw = false;
-        }
-
-        #[cfg(not(target_os = "windows"))]
-        {
-            if status_line != self.last_status {
-                crate::debug_log("draw_with_bottom_window: status line changed, executing crossterm commands");
-                if is_error {
-                    // Red background for errors
-                    execute!(
-                        self.stdout,
-                        MoveTo(0, status_row as u16),
-                        crossterm::style::SetBackgroundColor(crossterm::style::Color::Red),
-                        crossterm::style::SetForegroundColor(crossterm::style::Color::White),
-                        crossterm::style::Print(&status_line),
-                        crossterm::style::ResetColor
-                    )?;
-                } else {
-                    // Normal dark grey background
-                    execute!(
-                        self.stdout,
-                        MoveTo(0, status_row as u16),
-                        crossterm::style::SetBackgroundColor(crossterm::style::Color::DarkGrey),
-                        crossterm::style::SetForegroundColor(crossterm::style::Color::White),
-                        crossterm::style::Print(&status_line),
-                        crossterm::style::ResetColor
-                    )?;
-                }
-                crate::debug_log("draw_with_bottom_window: status line written");
-                self.last_status = status_line;
-            } else {
-                crate::debug_log("draw_with_bottom_window: status line unchanged, skipping write");
-            }
-        }
-        crate::debug_log("draw_with_bottom_window: status line complete");
-        
-        // Position cursor - map buffer position to screen position
-        // Only show cursor if there's no bottom window (find/replace is closed)
-        if bottom_window_height == 0 {
-            let (cursor_line, cursor_col) = editor.cursor_position();
-            let logical_cursor_line = cursor_line + 2; // Add 2 for virtual lines before buffer
-            
-            if logical_cursor_line >= viewport_offset.0 && 
-               logical_cursor_line < viewport_offset.0 + content_height &&
-               cursor_col >= viewport_offset.1 &&
-               cursor_col < viewport_offset.1 + width as usize {
-                
-                let screen_row = logical_cursor_line - viewport_offset.0;
-                let screen_col = cursor_col - viewport_offset.1;
-                
-                #[cfg(target_os = "windows")]
-                write!(self.stdout, "\x1b[{};{}H\x1b[?25h", 
-                    screen_row + 1, screen_col + 1)?;
-                
-                #[cfg(not(target_os = "windows"))]
-                execute!(
-                    self.stdout,
-                    MoveTo(screen_col as u16, screen_row as u16),
-                    Show
-                )?;
-            }
-        }
-        // If find/replace is open, cursor will be positioned by find_replace.draw()
-        
-        self.stdout.flush()?;
         Ok(())
     }
     
     /// Force a complete redraw by clearing cached state
     pub fn force_redraw(&mut self) {
-        self.last_screen = vec![String::new(); self.last_size.1 as usize];
+        self.last_screen.clear();
         self.last_status.clear();
         self.last_title.clear();
-        // FIX 3: Don't reset cursor style here, let draw_with_bottom_window handle it properly
+        // FIX 3: Don't reset cursor style here, let draw_layout handle it properly
         #[cfg(target_os = "windows")]
         {
             self.needs_full_redraw = true;
@@ -714,8 +736,9 @@ impl Renderer {
 
     /// Reposition and show cursor at editor position (call after drawing output pane)
     pub fn reposition_cursor(&mut self, editor: &Editor) -> io::Result<()> {
-        let (width, height) = terminal::size()?;
-        let (cursor_line, cursor_col) = editor.cursor_position();
+        let gutter = editor.gutter_width();
+        let (width, height) = (self.last_areas.editor.width.saturating_sub(gutter as u16), self.last_areas.editor.height);
+        let (cursor_row, cursor_col) = editor.cursor_display_position();
         let (viewport_row, viewport_col) = editor.viewport_offset();
 
         // Update cursor style based on editor selection
@@ -733,13 +756,14 @@ impl Renderer {
             self.last_cursor_style = desired_style;
         }
 
-        // Calculate screen position (add 2 for virtual lines before buffer)
-        let logical_cursor_line = cursor_line + 2;
-        let screen_row = logical_cursor_line.saturating_sub(viewport_row);
+        // Calculate screen position (the display row counts the virtual lines before buffer)
+        let screen_row = cursor_row.saturating_sub(viewport_row);
         let screen_col = cursor_col.saturating_sub(viewport_col);
 
         // Only show cursor if it's within the visible area
         if screen_row < height as usize && screen_col < width as usize {
+            let screen_col = self.last_areas.editor.x as usize + gutter + screen_col;
+            let screen_row = self.last_areas.editor.y as usize + screen_row;
             #[cfg(target_os = "windows")]
             write!(self.stdout, "\x1b[{};{}H\x1b[?25h",
                 screen_row + 1, screen_col + 1)?;
@@ -756,3 +780,78 @@ impl Renderer {
         Ok(())
     }
 }
+
+/// The gutter beside a buffer line: its git mark, line number and cell mark.
+/// Rows that aren't buffer lines get a blank one
+fn gutter_text(editor: &Editor, line: Option<usize>, gutter: usize, cell_marks: &HashMap<usize, CellMark>) -> String {
+    let theme = theme::current();
+    let base = theme[Element::LineNumber].over(theme[Element::Text]);
+    let Some(line) = line else {
+        return format!("{}{}\x1b[0m", base.escape(), " ".repeat(gutter));
+    };
+
+    let (git_mark, git_style) = match editor.git_change(line) {
+        Some(LineChange::Added) => ('▎', theme[Element::GutterAdded]),
+        Some(LineChange::Modified) => ('▎', theme[Element::GutterModified]),
+        Some(LineChange::DeletedAbove) => ('▔', theme[Element::GutterDeleted]),
+        None => (' ', base),
+    };
+    let (number, is_current) = editor.line_number_label(line);
+    let number_style = if is_current { theme[Element::LineNumberCurrent] } else { base };
+    // Cell marks take the badge colours, without any badge background
+    let state_style = |state: CellState| {
+        let element = match state {
+            CellState::NotRun => Element::LineNumber,
+            CellState::Ok => Element::BadgeOk,
+            CellState::Failed => Element::BadgeError,
+            CellState::Running => Element::BadgeRunning,
+            CellState::Queued => Element::BadgeQueued,
+            CellState::Modified => Element::BadgeModified,
+            CellState::Stale => Element::BadgeStale,
+        };
+        theme::Style { bg: None, ..theme[element] }
+    };
+    let (cell_mark, cell_style) = match cell_marks.get(&line) {
+        Some(CellMark::Start(state)) => ('●', state_style(*state)),
+        Some(CellMark::Inside(state)) => ('│', state_style(*state)),
+        Some(CellMark::Error) => ('✗', theme[Element::GutterError]),
+        None => (' ', base),
+    };
+
+    format!(
+        "{}{}{}{:>digits$}{}{}{} \x1b[0m",
+        git_style.over(base).escape(),
+        git_mark,
+        number_style.over(base).escape(),
+        number,
+        cell_style.over(base).escape(),
+        cell_mark,
+        base.escape(),
+        digits = gutter - 3,
+    )
+}
+
+/// Text and theme element for a delimiter line badge
+fn badge_style(badge: &CellBadge) -> (String, Element) {
+    match badge {
+        CellBadge::Stale => (" stale ".to_string(), Element::BadgeStale),
+        CellBadge::Modified => ("modified".to_string(), Element::BadgeModified),
+        CellBadge::Running => ("[*]".to_string(), Element::BadgeRunning),
+        CellBadge::Queued => ("queued".to_string(), Element::BadgeQueued),
+        CellBadge::Count(count) => (format!("[{}]", count), Element::BadgeCount),
+        CellBadge::Elapsed(secs) => (format_elapsed(*secs), Element::BadgeElapsed),
+        CellBadge::Ok => ("ok".to_string(), Element::BadgeOk),
+        CellBadge::Error => ("error".to_string(), Element::BadgeError),
+    }
+}
+
+/// Short run time for a delimiter line badge: 0.042s, 12.3s, 2m05s
+fn format_elapsed(secs: f64) -> String {
+    if secs < 10.0 {
+        format!("{:.3}s", secs)
+    } else if secs < 60.0 {
+        format!("{:.1}s", secs)
+    } else {
+        format!("{}m{:02}s", secs as u64 / 60, secs as u64 % 60)
+    }
+}
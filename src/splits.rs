@@ -0,0 +1,303 @@
+//! Split editor windows
+
+use crate::editor::View;
+use crate::layout::Rect;
+use crate::theme::{self, Element};
+use crossterm::{
+    cursor, execute,
+    style::{Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+};
+use std::io::{self, Write};
+
+/// How a window is split in two
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitDirection {
+    /// One window above the other, with a separator row between them
+    Horizontal,
+    /// Side by side, with a separator column between them
+    Vertical,
+}
+
+/// Direction to move focus in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FocusDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+enum Node {
+    Window(usize),
+    Split { direction: SplitDirection, first: Box<Node>, second: Box<Node> },
+}
+
+/// A window onto one of the open buffers
+#[derive(Clone, Copy)]
+pub struct Window {
+    pub tab: usize, // Id of the tab whose buffer is shown
+    pub view: View, // Cursor and scroll position while not focused
+}
+
+pub struct Splits {
+    root: Node,
+    windows: Vec<Window>,
+    focused: usize,
+}
+
+impl Splits {
+    /// A single window showing the given tab
+    pub fn new(tab: usize) -> Self {
+        Splits {
+            root: Node::Window(0),
+            windows: vec![Window { tab, view: View::default() }],
+            focused: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.windows.len()
+    }
+
+    pub fn focused(&self) -> usize {
+        self.focused
+    }
+
+    pub fn window(&self, idx: usize) -> Window {
+        self.windows[idx]
+    }
+
+    /// Record the buffer and view of a window
+    pub fn set_window(&mut self, idx: usize, window: Window) {
+        self.windows[idx] = window;
+    }
+
+    /// Point the focused window at another buffer
+    pub fn show_tab(&mut self, tab: usize) {
+        self.windows[self.focused].tab = tab;
+    }
+
+    /// Point every window showing a closed buffer at another one
+    pub fn replace_tab(&mut self, closed: usize, tab: usize) {
+        for window in self.windows.iter_mut().filter(|window| window.tab == closed) {
+            window.tab = tab;
+        }
+    }
+
+    /// Make another window focused
+    pub fn focus(&mut self, idx: usize) {
+        if idx < self.windows.len() {
+            self.focused = idx;
+        }
+    }
+
+    /// Split the focused window in two; the new window shows the same buffer, starting from
+    /// the focused window's current view, and takes focus
+    pub fn split(&mut self, direction: SplitDirection, view: View) -> usize {
+        let new_idx = self.windows.len();
+        self.windows[self.focused].view = view;
+        self.windows.push(Window { tab: self.windows[self.focused].tab, view });
+        let focused = self.focused;
+        Self::replace_leaf(&mut self.root, focused, &mut |leaf| Node::Split {
+            direction,
+            first: Box::new(leaf),
+            second: Box::new(Node::Window(new_idx)),
+        });
+        self.focused = new_idx;
+        new_idx
+    }
+
+    /// Window that takes focus if the focused one is closed; None for the last window
+    pub fn successor(&self) -> Option<usize> {
+        Self::sibling_of(&self.root, self.focused).map(Self::first_window)
+    }
+
+    /// Close the focused window, giving its space to its sibling; returns false for the last window
+    pub fn close_focused(&mut self) -> bool {
+        let Some(successor) = self.successor() else {
+            return false;
+        };
+        let closing = self.focused;
+        Self::remove_leaf(&mut self.root, closing);
+        self.windows.remove(closing);
+        Self::renumber(&mut self.root, closing);
+
+        // Focus the window that took over the space
+        self.focused = if successor > closing { successor - 1 } else { successor };
+        true
+    }
+
+    /// Screen area of each window, indexed like the windows
+    pub fn window_rects(&self, area: Rect) -> Vec<Rect> {
+        let mut rects = vec![Rect::default(); self.windows.len()];
+        Self::layout_node(&self.root, area, &mut rects, &mut Vec::new());
+        rects
+    }
+
+    /// Screen area of one window
+    pub fn window_rect(&self, idx: usize, area: Rect) -> Rect {
+        self.window_rects(area).get(idx).copied().unwrap_or(area)
+    }
+
+    /// Window under a screen cell
+    pub fn window_at(&self, col: u16, row: u16, area: Rect) -> Option<usize> {
+        self.window_rects(area).iter().position(|rect| rect.contains(col, row))
+    }
+
+    /// Nearest window in a direction from the focused one
+    pub fn neighbour(&self, direction: FocusDirection, area: Rect) -> Option<usize> {
+        let rects = self.window_rects(area);
+        let from = rects[self.focused];
+        let centre = |rect: &Rect| (rect.x as i32 * 2 + rect.width as i32, rect.y as i32 * 2 + rect.height as i32);
+        let (from_x, from_y) = centre(&from);
+
+        rects
+            .iter()
+            .enumerate()
+            .filter(|(idx, rect)| {
+                *idx != self.focused
+                    && match direction {
+                        FocusDirection::Up => rect.y + rect.height <= from.y,
+                        FocusDirection::Down => rect.y >= from.y + from.height,
+                        FocusDirection::Left => rect.x + rect.width <= from.x,
+                        FocusDirection::Right => rect.x >= from.x + from.width,
+                    }
+            })
+            .min_by_key(|(_, rect)| {
+                let (x, y) = centre(rect);
+                (x - from_x).abs() + (y - from_y).abs()
+            })
+            .map(|(idx, _)| idx)
+    }
+
+    /// Draw the separators between windows
+    pub fn draw_separators<W: Write>(&self, writer: &mut W, area: Rect) -> io::Result<()> {
+        if self.windows.len() == 1 {
+            return Ok(());
+        }
+        let mut separators = Vec::new();
+        Self::layout_node(&self.root, area, &mut vec![Rect::default(); self.windows.len()], &mut separators);
+
+        let style = theme::current()[Element::Divider];
+        execute!(writer, SetBackgroundColor(style.background()), SetForegroundColor(style.foreground()))?;
+        for (rect, direction) in separators {
+            match direction {
+                SplitDirection::Horizontal => {
+                    execute!(writer, cursor::MoveTo(rect.x, rect.y), Print("─".repeat(rect.width as usize)))?;
+                }
+                SplitDirection::Vertical => {
+                    for row in rect.y..rect.y + rect.height {
+                        execute!(writer, cursor::MoveTo(rect.x, row), Print("│"))?;
+                    }
+                }
+            }
+        }
+        execute!(writer, ResetColor)?;
+        Ok(())
+    }
+
+    /// Work out the area of every window below a node, and the separators between them
+    fn layout_node(node: &Node, area: Rect, rects: &mut [Rect], separators: &mut Vec<(Rect, SplitDirection)>) {
+        match node {
+            Node::Window(idx) => rects[*idx] = area,
+            Node::Split { direction, first, second } => {
+                let (first_area, separator, second_area) = match direction {
+                    SplitDirection::Horizontal => {
+                        let top = area.height.saturating_sub(1) / 2;
+                        let bottom = area.height.saturating_sub(top + 1);
+                        (
+                            Rect::new(area.x, area.y, area.width, top),
+                            Rect::new(area.x, area.y + top, area.width, area.height.min(1)),
+                            Rect::new(area.x, area.y + top + 1, area.width, bottom),
+                        )
+                    }
+                    SplitDirection::Vertical => {
+                        let left = area.width.saturating_sub(1) / 2;
+                        let right = area.width.saturating_sub(left + 1);
+                        (
+                            Rect::new(area.x, area.y, left, area.height),
+                            Rect::new(area.x + left, area.y, area.width.min(1), area.height),
+                            Rect::new(area.x + left + 1, area.y, right, area.height),
+                        )
+                    }
+                };
+                separators.push((separator, *direction));
+                Self::layout_node(first, first_area, rects, separators);
+                Self::layout_node(second, second_area, rects, separators);
+            }
+        }
+    }
+
+    /// Replace the leaf for a window with whatever `f` builds from it
+    fn replace_leaf(node: &mut Node, idx: usize, f: &mut dyn FnMut(Node) -> Node) -> bool {
+        match node {
+            Node::Window(leaf) if *leaf == idx => {
+                let leaf = std::mem::replace(node, Node::Window(idx));
+                *node = f(leaf);
+                true
+            }
+            Node::Window(_) => false,
+            Node::Split { first, second, .. } => {
+                Self::replace_leaf(first, idx, f) || Self::replace_leaf(second, idx, f)
+            }
+        }
+    }
+
+    /// The other half of the split holding a window
+    fn sibling_of(node: &Node, idx: usize) -> Option<&Node> {
+        let Node::Split { first, second, .. } = node else {
+            return None;
+        };
+        if matches!(**first, Node::Window(leaf) if leaf == idx) {
+            Some(second)
+        } else if matches!(**second, Node::Window(leaf) if leaf == idx) {
+            Some(first)
+        } else {
+            Self::sibling_of(first, idx).or_else(|| Self::sibling_of(second, idx))
+        }
+    }
+
+    /// Remove the leaf for a window; its sibling takes the parent split's place
+    fn remove_leaf(node: &mut Node, idx: usize) -> bool {
+        let Node::Split { first, second, .. } = node else {
+            return false;
+        };
+        let sibling = if matches!(**first, Node::Window(leaf) if leaf == idx) {
+            Some(std::mem::replace(&mut **second, Node::Window(idx)))
+        } else if matches!(**second, Node::Window(leaf) if leaf == idx) {
+            Some(std::mem::replace(&mut **first, Node::Window(idx)))
+        } else {
+            None
+        };
+        match sibling {
+            Some(sibling) => {
+                *node = sibling;
+                true
+            }
+            None => Self::remove_leaf(first, idx) || Self::remove_leaf(second, idx),
+        }
+    }
+
+    /// Shift window indices down after one was removed from the list
+    fn renumber(node: &mut Node, removed: usize) {
+        match node {
+            Node::Window(idx) => {
+                if *idx > removed {
+                    *idx -= 1;
+                }
+            }
+            Node::Split { first, second, .. } => {
+                Self::renumber(first, removed);
+                Self::renumber(second, removed);
+            }
+        }
+    }
+
+    /// First window in a subtree - used to pick focus after closing
+    fn first_window(node: &Node) -> usize {
+        match node {
+            Node::Window(idx) => *idx,
+            Node::Split { first, .. } => Self::first_window(first),
+        }
+    }
+}
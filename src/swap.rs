@@ -0,0 +1,273 @@
+//! Swap files: unsaved text kept on disk, so a crash or a closed terminal doesn't lose it
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, Once};
+use std::thread;
+use std::time::{Duration, Instant};
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+use crate::config;
+use crate::editor::Editor;
+use crate::undo_history::stable_hash;
+use crate::undo_tree;
+
+/// Bumped whenever the layout of swap files changes, so older ones are ignored
+const FORMAT: u32 = 1;
+
+/// Seconds between swap file writes; 0 while they're turned off
+static INTERVAL: AtomicU64 = AtomicU64::new(0);
+static WRITER: Once = Once::new();
+static STATE: Mutex<SwapState> = Mutex::new(SwapState { pending: Vec::new(), written: Vec::new() });
+
+struct SwapState {
+    pending: Vec<(PathBuf, Option<Rope>)>, // Files and their text to write, or None to remove their swap file
+    written: Vec<PathBuf>,                 // Swap files this process has on disk
+}
+
+/// When buffers are saved to their files without being asked to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AutoSave {
+    /// Only when asked to
+    #[default]
+    Off,
+    /// When the terminal loses focus
+    Focus,
+    /// Once the text has been left alone for a while
+    Delay,
+}
+
+/// A swap file's contents
+#[derive(Serialize, Deserialize)]
+pub struct SwapFile {
+    format: u32,
+    pub path: PathBuf, // File the text belongs to
+    pub time: u64,     // When it was written, in seconds since the Unix epoch
+    pid: u32,          // Process that wrote it
+    pub text: String,
+}
+
+/// Full path of a file, for naming its swap file, even if it doesn't exist yet
+fn full_path(file: &Path) -> PathBuf {
+    fs::canonicalize(file)
+        .or_else(|_| std::path::absolute(file))
+        .unwrap_or_else(|_| file.to_path_buf())
+}
+
+/// Where a file's swap file goes; `file` is a full path
+fn swap_path(file: &Path) -> Option<PathBuf> {
+    let name = format!("{:016x}.json", stable_hash(file.to_string_lossy().as_bytes()));
+    Some(config::cache_dir()?.join("swap").join(name))
+}
+
+/// Whether the process that wrote a swap file may still be running. Only known on Linux,
+/// elsewhere every writer counts as gone
+pub fn still_running(pid: u32) -> bool {
+    cfg!(target_os = "linux") && Path::new(&format!("/proc/{}", pid)).exists()
+}
+
+/// Write a file's text to its swap file, or remove the swap file
+fn write_one(state: &mut SwapState, file: &Path, text: Option<&Rope>) -> io::Result<()> {
+    let Some(path) = swap_path(file) else {
+        return Ok(());
+    };
+    let Some(text) = text else {
+        state.written.retain(|written| *written != path);
+        return match fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let swap = SwapFile { format: FORMAT, path: file.to_path_buf(), time: undo_tree::now(), pid: std::process::id(), text: text.to_string() };
+    let json = serde_json::to_string(&swap).map_err(io::Error::other)?;
+    // Written beside it first, so a crash mid-write leaves the previous one whole
+    let partial = path.with_extension("json.partial");
+    fs::write(&partial, json)?;
+    fs::rename(partial, &path)?;
+    if !state.written.contains(&path) {
+        state.written.push(path);
+    }
+    Ok(())
+}
+
+/// Have a file's text written to its swap file, or the swap file removed with None
+fn queue(file: PathBuf, text: Option<Rope>) {
+    let Ok(mut state) = STATE.lock() else { return };
+    state.pending.retain(|(pending, _)| *pending != file);
+    state.pending.push((file, text));
+}
+
+/// Write everything waiting to be written now
+pub fn flush() {
+    // The lock is only poisoned if writing panicked, and then there's nothing more to save
+    let Ok(mut state) = STATE.lock() else { return };
+    write_pending(&mut state);
+}
+
+/// Write what's waiting from the panic hook. Skipped if the lock is held, which may be by the
+/// panicking thread itself, or poisoned
+pub fn flush_on_panic() {
+    let Ok(mut state) = STATE.try_lock() else { return };
+    write_pending(&mut state);
+}
+
+fn write_pending(state: &mut SwapState) {
+    for (file, text) in std::mem::take(&mut state.pending) {
+        let _ = write_one(state, &file, text.as_ref());
+    }
+}
+
+/// Write swap files every `seconds`, or stop writing them with 0. The first call with
+/// a non-zero interval starts the background writer
+pub fn set_interval(seconds: u64) {
+    INTERVAL.store(seconds, Ordering::Relaxed);
+    if seconds > 0 {
+        WRITER.call_once(|| {
+            thread::spawn(|| loop {
+                let seconds = INTERVAL.load(Ordering::Relaxed);
+                thread::sleep(Duration::from_secs(seconds.max(1)));
+                if seconds > 0 {
+                    flush();
+                }
+            });
+        });
+    }
+}
+
+/// Remove the swap files this process wrote, and anything still waiting; for quitting
+/// once the user has saved or discarded every buffer
+pub fn remove_own() {
+    let Ok(mut state) = STATE.lock() else { return };
+    state.pending.clear();
+    for path in std::mem::take(&mut state.written) {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// Text of a file left in its swap file by a session that ended without saving it, unless
+/// it's the text the buffer already has
+pub fn leftover(file: &Path, text: &str) -> Option<SwapFile> {
+    let path = swap_path(&full_path(file))?;
+    let swap: SwapFile = serde_json::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+    if swap.format != FORMAT || swap.path != full_path(file) || swap.pid == std::process::id() || still_running(swap.pid) {
+        return None;
+    }
+    if swap.text == text {
+        let _ = fs::remove_file(path);
+        return None;
+    }
+    Some(swap)
+}
+
+/// Remove a file's leftover swap file, once it's been restored or turned down
+pub fn discard(file: &Path) {
+    if let Some(path) = swap_path(&full_path(file)) {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// What was last handed over for a buffer
+struct Noted {
+    full_path: PathBuf,      // Names the swap file
+    version: u64,
+    modified: bool,
+    changed: Instant,        // When the version last changed, for auto-saving after a delay
+    auto_saved: Option<u64>, // Version last auto-saved, so a failing save isn't retried
+    seen: u64,               // Pass the buffer was last seen in, to notice it was closed
+}
+
+/// The event loop's side: which buffers changed since their text was last handed over
+#[derive(Default)]
+pub struct SwapFiles {
+    buffers: HashMap<PathBuf, Noted>, // By the file's path as the buffer has it
+    pass: u64,
+    checked: HashSet<PathBuf>,        // Files already looked at for a leftover swap file
+    put_off: HashSet<PathBuf>,        // Files whose leftover swap file is kept for next time
+}
+
+impl SwapFiles {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hand a buffer's text over if it changed, or have its swap file removed if it's no
+    /// longer modified. Call for every open buffer, then `end_pass`
+    pub fn note(&mut self, editor: &Editor) {
+        let Some(file) = editor.file_path() else { return };
+        let version = editor.buffer().version();
+        let modified = editor.is_modified();
+        let noted = self.buffers.entry(file.to_path_buf()).or_insert_with(|| Noted {
+            full_path: full_path(file),
+            version,
+            modified: false,
+            changed: Instant::now(),
+            auto_saved: None,
+            seen: self.pass,
+        });
+        noted.seen = self.pass;
+        if noted.version != version {
+            noted.version = version;
+            noted.changed = Instant::now();
+        } else if noted.modified == modified {
+            return;
+        }
+        let was_modified = std::mem::replace(&mut noted.modified, modified);
+        let off = INTERVAL.load(Ordering::Relaxed) == 0 || self.put_off.contains(&noted.full_path);
+        if off || (!modified && !was_modified) {
+            return;
+        }
+        queue(noted.full_path.clone(), modified.then(|| editor.buffer().rope().clone()));
+    }
+
+    /// Have the swap files of buffers closed since the last pass removed
+    pub fn end_pass(&mut self) {
+        let pass = self.pass;
+        let closed: Vec<PathBuf> = self.buffers.iter()
+            .filter(|(_, noted)| noted.seen != pass)
+            .map(|(file, _)| file.clone())
+            .collect();
+        for file in closed {
+            if let Some(noted) = self.buffers.remove(&file).filter(|noted| noted.modified) {
+                if !self.put_off.contains(&noted.full_path) {
+                    queue(noted.full_path, None);
+                }
+            }
+        }
+        self.pass += 1;
+    }
+
+    /// Whether a modified buffer has been left alone for `delay` and wasn't auto-saved as it is
+    /// yet. Answering yes counts as trying, so a save that fails isn't retried until the next edit
+    pub fn due_for_auto_save(&mut self, editor: &Editor, delay: Duration) -> bool {
+        let Some(noted) = editor.file_path().and_then(|file| self.buffers.get_mut(file)) else {
+            return false;
+        };
+        let due = noted.modified && noted.auto_saved != Some(noted.version) && noted.changed.elapsed() >= delay;
+        if due {
+            noted.auto_saved = Some(noted.version);
+        }
+        due
+    }
+
+    /// A swap file left behind for a buffer's file, the first time the buffer is asked about
+    pub fn unchecked_leftover(&mut self, editor: &Editor) -> Option<SwapFile> {
+        let file = editor.file_path()?;
+        if !self.checked.insert(file.to_path_buf()) {
+            return None;
+        }
+        leftover(file, &editor.buffer().to_string())
+    }
+
+    /// Keep a file's leftover swap file for the next session instead of writing over it; its
+    /// changes in this one aren't kept in a swap file
+    pub fn put_off(&mut self, file: &Path) {
+        self.put_off.insert(full_path(file));
+    }
+}
@@ -131,6 +131,11 @@ impl SyntaxHighlighter {
         }
     }
 
+    /// Language being highlighted
+    pub fn language(&self) -> Language {
+        self.language
+    }
+
     /// Set language from file path
     pub fn set_language_from_path(&mut self, path: &str) {
         if let Some(ext) = path.rsplit('.').next() {
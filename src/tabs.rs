@@ -0,0 +1,311 @@
+//! Open buffers and the tab bar along the top of the screen
+
+use crate::editor::Editor;
+use crate::kernel::Kernel;
+use crate::layout::Rect;
+use crate::theme::{self, Element};
+use crossterm::{
+    cursor, execute,
+    style::{Print, PrintStyledContent, ResetColor, SetBackgroundColor, SetForegroundColor},
+};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Most recently used files remembered for quick-open
+const MAX_RECENT: usize = 50;
+
+struct Tab {
+    id: usize,              // Stable id, kept while tabs move around
+    editor: Option<Editor>, // None for the active tab, whose editor is out in the event loop
+    own_kernel: bool,       // Keeps its own kernel instead of sharing
+}
+
+pub struct Tabs {
+    tabs: Vec<Tab>,
+    active: usize,
+    shared_kernel: Option<Box<dyn Kernel>>, // Shared kernel while a tab with its own kernel is active
+    tab_columns: Vec<(u16, u16)>,           // Screen columns of each tab from the last draw (start, end)
+    next_id: usize,
+    recent: Vec<PathBuf>, // Files shown in the active tab, most recent first
+}
+
+impl Tabs {
+    /// Tabs holding just the buffer that is already open
+    pub fn new() -> Self {
+        Tabs {
+            tabs: vec![Tab { id: 0, editor: None, own_kernel: false }],
+            active: 0,
+            shared_kernel: None,
+            tab_columns: Vec::new(),
+            next_id: 1,
+            recent: Vec::new(),
+        }
+    }
+
+    fn new_tab(&mut self, editor: Editor) -> Tab {
+        self.next_id += 1;
+        Tab { id: self.next_id - 1, editor: Some(editor), own_kernel: false }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tabs.len()
+    }
+
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    /// Id of the active tab
+    pub fn active_id(&self) -> usize {
+        self.tabs[self.active].id
+    }
+
+    /// Position of the tab with the given id
+    pub fn index_of(&self, id: usize) -> Option<usize> {
+        self.tabs.iter().position(|tab| tab.id == id)
+    }
+
+    /// Add a buffer after the last tab without switching to it
+    pub fn push(&mut self, editor: Editor) {
+        let tab = self.new_tab(editor);
+        self.tabs.push(tab);
+    }
+
+    /// Add a buffer right after the active tab and switch to it
+    pub fn open(&mut self, current: &mut Editor, editor: Editor) {
+        let tab = self.new_tab(editor);
+        self.tabs.insert(self.active + 1, tab);
+        self.switch_to(current, self.active + 1);
+    }
+
+    /// Make another tab active, swapping its editor into `current`
+    pub fn switch_to(&mut self, current: &mut Editor, idx: usize) {
+        if idx == self.active || idx >= self.tabs.len() {
+            return;
+        }
+
+        // The shared kernel leaves with a sharing tab and follows to the next one
+        if !self.tabs[self.active].own_kernel {
+            self.shared_kernel = current.take_kernel().or(self.shared_kernel.take());
+        }
+
+        let next = self.tabs[idx].editor.take().expect("inactive tab holds its editor");
+        self.tabs[self.active].editor = Some(std::mem::replace(current, next));
+        self.active = idx;
+        self.note_recent(current);
+
+        if !self.tabs[idx].own_kernel {
+            if let Some(kernel) = self.shared_kernel.take() {
+                current.set_kernel(kernel);
+            }
+        }
+    }
+
+    /// Switch to the next (or previous) tab, wrapping around
+    pub fn cycle(&mut self, current: &mut Editor, forward: bool) {
+        let count = self.tabs.len();
+        let idx = if forward { (self.active + 1) % count } else { (self.active + count - 1) % count };
+        self.switch_to(current, idx);
+    }
+
+    /// Move the active tab one place left or right; returns false at either end
+    pub fn move_active(&mut self, forward: bool) -> bool {
+        let target = if forward {
+            self.active + 1
+        } else {
+            match self.active.checked_sub(1) {
+                Some(target) => target,
+                None => return false,
+            }
+        };
+        if target >= self.tabs.len() {
+            return false;
+        }
+        self.tabs.swap(self.active, target);
+        self.active = target;
+        true
+    }
+
+    /// Close the active tab, switching to its neighbour; the last tab is replaced by an empty buffer
+    /// Returns the id of the tab that went away, if one did
+    pub fn close_active(&mut self, current: &mut Editor) -> Option<usize> {
+        let closing_own_kernel = self.tabs[self.active].own_kernel;
+
+        if self.tabs.len() == 1 {
+            let kernel = if closing_own_kernel { None } else { current.take_kernel() };
+            *current = Editor::new();
+            self.tabs[0].own_kernel = false;
+            if let Some(kernel) = kernel.or(self.shared_kernel.take()) {
+                current.set_kernel(kernel);
+            }
+            return None;
+        }
+
+        // Hand the shared kernel back before the tab goes away
+        if !closing_own_kernel {
+            self.shared_kernel = current.take_kernel().or(self.shared_kernel.take());
+        }
+
+        let closing = self.active;
+        let next = if closing + 1 < self.tabs.len() { closing + 1 } else { closing - 1 };
+        let next_editor = self.tabs[next].editor.take().expect("inactive tab holds its editor");
+        let mut closed = std::mem::replace(current, next_editor);
+        if let Some(mut kernel) = closed.take_kernel() {
+            let _ = kernel.disconnect();
+        }
+
+        let removed = self.tabs.remove(closing);
+        self.active = if next > closing { next - 1 } else { next };
+
+        if !self.tabs[self.active].own_kernel {
+            if let Some(kernel) = self.shared_kernel.take() {
+                current.set_kernel(kernel);
+            }
+        }
+        Some(removed.id)
+    }
+
+    /// Give the active tab its own kernel, or return it to the shared one; returns true if now separate
+    pub fn toggle_own_kernel(&mut self, current: &mut Editor) -> bool {
+        let tab = &mut self.tabs[self.active];
+        if tab.own_kernel {
+            // Drop the tab's kernel and pick the shared one back up
+            if let Some(mut kernel) = current.take_kernel() {
+                let _ = kernel.disconnect();
+            }
+            if let Some(kernel) = self.shared_kernel.take() {
+                current.set_kernel(kernel);
+            }
+        } else {
+            // The shared kernel stays with the other tabs; this one starts without a kernel
+            self.shared_kernel = current.take_kernel().or(self.shared_kernel.take());
+        }
+        tab.own_kernel = !tab.own_kernel;
+        tab.own_kernel
+    }
+
+    /// Indices of tabs with unsaved changes
+    pub fn modified_tabs(&self, current: &Editor) -> Vec<usize> {
+        (0..self.tabs.len())
+            .filter(|&idx| self.editor(idx, current).is_modified())
+            .collect()
+    }
+
+    /// File name shown for a tab
+    pub fn file_name<'a>(&'a self, idx: usize, current: &'a Editor) -> &'a str {
+        self.editor(idx, current).file_name()
+    }
+
+    /// Index of an open tab showing the given file
+    pub fn find_file(&self, path: &Path, current: &Editor) -> Option<usize> {
+        let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        (0..self.tabs.len()).find(|&idx| {
+            self.editor(idx, current)
+                .file_path()
+                .is_some_and(|open| std::fs::canonicalize(open).unwrap_or_else(|_| open.to_path_buf()) == path)
+        })
+    }
+
+    /// Paths and current text of every tab showing a file
+    pub fn file_texts(&self, current: &Editor) -> Vec<(PathBuf, String)> {
+        (0..self.tabs.len())
+            .filter_map(|idx| {
+                let editor = self.editor(idx, current);
+                Some((editor.file_path()?.to_path_buf(), editor.buffer().to_string()))
+            })
+            .collect()
+    }
+
+    /// Remember the active buffer's file as the most recently used
+    pub fn note_recent(&mut self, current: &Editor) {
+        if let Some(path) = current.file_path() {
+            let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+            self.recent.retain(|recent| *recent != path);
+            self.recent.insert(0, path);
+            self.recent.truncate(MAX_RECENT);
+        }
+    }
+
+    /// Recently used files, most recent first
+    pub fn recent_files(&self) -> &[PathBuf] {
+        &self.recent
+    }
+
+    fn editor<'a>(&'a self, idx: usize, current: &'a Editor) -> &'a Editor {
+        match &self.tabs[idx].editor {
+            Some(editor) => editor,
+            None => current,
+        }
+    }
+
+    /// Mutable access to any tab's editor - the active one is `current`
+    pub fn editor_mut<'a>(&'a mut self, idx: usize, current: &'a mut Editor) -> &'a mut Editor {
+        match &mut self.tabs[idx].editor {
+            Some(editor) => editor,
+            None => current,
+        }
+    }
+
+    /// Tab under a screen column of the tab bar
+    pub fn tab_at(&self, col: u16) -> Option<usize> {
+        self.tab_columns.iter().position(|(start, end)| col >= *start && col < *end)
+    }
+
+    /// Draw the tab bar, scrolled so the active tab is visible
+    pub fn draw<W: Write>(&mut self, writer: &mut W, current: &Editor, area: Rect) -> io::Result<()> {
+        let labels: Vec<String> = (0..self.tabs.len())
+            .map(|idx| {
+                let editor = self.editor(idx, current);
+                let modified = if editor.is_modified() { " *" } else { "" };
+                let kernel = if self.tabs[idx].own_kernel { " [k]" } else { "" };
+                format!(" {}{}{} ", editor.file_name(), modified, kernel)
+            })
+            .collect();
+
+        // Leftmost tab drawn - far enough right that the active tab fits
+        let width = area.width as usize;
+        let mut first = 0;
+        while first < self.active
+            && labels[first..=self.active].iter().map(|label| label.chars().count() + 1).sum::<usize>() > width
+        {
+            first += 1;
+        }
+
+        let theme = theme::current();
+        let bar = theme[Element::TabBar];
+        execute!(
+            writer,
+            cursor::MoveTo(area.x, area.y),
+            SetBackgroundColor(bar.background()),
+            Print(" ".repeat(width)),
+            cursor::MoveTo(area.x, area.y)
+        )?;
+
+        self.tab_columns = vec![(0, 0); self.tabs.len()];
+        let mut col = 0;
+        for (idx, label) in labels.iter().enumerate().skip(first) {
+            let label: String = label.chars().take(width.saturating_sub(col)).collect();
+            if label.is_empty() {
+                break;
+            }
+            let style = theme[if idx == self.active { Element::TabActive } else { Element::TabInactive }].over(bar);
+            let len = label.chars().count();
+            execute!(writer, PrintStyledContent(style.content().apply(label.as_str())))?;
+            self.tab_columns[idx] = (area.x + col as u16, area.x + (col + len) as u16);
+            col += len;
+
+            // Separator between tabs
+            if col < width {
+                execute!(
+                    writer,
+                    SetBackgroundColor(bar.background()),
+                    SetForegroundColor(bar.foreground()),
+                    Print("│")
+                )?;
+                col += 1;
+            }
+        }
+        execute!(writer, ResetColor)?;
+        Ok(())
+    }
+}
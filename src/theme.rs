@@ -0,0 +1,839 @@
+//! Colour themes for everything sage draws
+
+use crate::syntax::SyntaxState;
+use crossterm::style::{Attribute, Color, Colored, ContentStyle};
+use serde::de::{self, Deserializer, MapAccess, Visitor};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+/// Names of the built-in themes, the first being the default
+pub const BUILTIN_THEMES: &[&str] = &["dark", "light", "high-contrast"];
+
+/// The theme everything draws with; None until one is installed
+static CURRENT: RwLock<Option<Arc<Theme>>> = RwLock::new(None);
+
+/// Colours and attributes for one element
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Style {
+    pub fg: Option<Color>, // None lets whatever is underneath (or the terminal default) show
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub italic: bool,
+}
+
+impl Style {
+    const NONE: Style = Style { fg: None, bg: None, bold: false, italic: false };
+
+    const fn fg(color: Color) -> Style {
+        Style { fg: Some(color), ..Style::NONE }
+    }
+
+    const fn bg(color: Color) -> Style {
+        Style { bg: Some(color), ..Style::NONE }
+    }
+
+    const fn on(self, color: Color) -> Style {
+        Style { bg: Some(color), ..self }
+    }
+
+    const fn bold(self) -> Style {
+        Style { bold: true, ..self }
+    }
+
+    /// This style drawn on top of `base`: colours it leaves unset come from `base`
+    pub fn over(self, base: Style) -> Style {
+        Style {
+            fg: self.fg.or(base.fg),
+            bg: self.bg.or(base.bg),
+            bold: self.bold || base.bold,
+            italic: self.italic || base.italic,
+        }
+    }
+
+    /// Foreground for crossterm commands, the terminal's own when unset
+    pub fn foreground(&self) -> Color {
+        self.fg.unwrap_or(Color::Reset)
+    }
+
+    /// Background for crossterm commands, the terminal's own when unset
+    pub fn background(&self) -> Color {
+        self.bg.unwrap_or(Color::Reset)
+    }
+
+    /// Escape sequence that resets all attributes and then switches to this style
+    pub fn escape(&self) -> String {
+        let mut escape = String::from("\x1b[0");
+        if let Some(color) = self.fg {
+            escape.push_str(&format!(";{}", Colored::ForegroundColor(color)));
+        }
+        if let Some(color) = self.bg {
+            escape.push_str(&format!(";{}", Colored::BackgroundColor(color)));
+        }
+        if self.bold {
+            escape.push_str(";1");
+        }
+        if self.italic {
+            escape.push_str(";3");
+        }
+        escape.push('m');
+        escape
+    }
+
+    /// The same style for crossterm's styled printing
+    pub fn content(&self) -> ContentStyle {
+        let mut style = ContentStyle { foreground_color: self.fg, background_color: self.bg, ..ContentStyle::default() };
+        if self.bold {
+            style.attributes.set(Attribute::Bold);
+        }
+        if self.italic {
+            style.attributes.set(Attribute::Italic);
+        }
+        style
+    }
+}
+
+/// Everything a theme gives a style to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Element {
+    // Editor
+    Text,
+    CurrentLine,
+    NonText, // The `~` rows around the buffer
+    Selection,
+    MatchingText,
+    MatchingBracket,
+    FindMatch,
+    FindMatchCurrent,
+    CellTags,
+    FoldMarker, // `⋯ 12 lines` after the first line of a fold
+    LineNumber,
+    LineNumberCurrent,
+    GutterAdded, // Gutter marks for lines changed since git HEAD
+    GutterModified,
+    GutterDeleted,
+    GutterError, // The line the last traceback points at
+    BadgeStale,
+    BadgeModified,
+    BadgeRunning,
+    BadgeQueued,
+    BadgeCount,
+    BadgeElapsed,
+    BadgeOk,
+    BadgeError,
+    InlineOutput,
+    InlineOutputHeader,
+    InlineOutputError,
+    Cursor, // Only its foreground is used, as the terminal cursor colour
+    ExtraCursor, // Cursors besides the terminal's own, drawn as a coloured cell
+    Divider,
+    Status,
+    StatusError,
+    TabBar,
+    TabActive,
+    TabInactive,
+
+    // Output pane
+    Output,
+    OutputBorder,
+    OutputTitle,
+    OutputFindTitle,
+    OutputHeader,
+    OutputDim,
+    OutputError,
+    OutputSelection,
+
+    // Autocomplete popup
+    Completion,
+    CompletionSelected,
+
+    // Dialogs: prompts, the find bar, quick-open, the command palette, the kernel selector
+    Dialog,
+    DialogTitle,
+    DialogDim,
+    DialogInactive,
+    DialogSelected,
+    DialogMatch,
+    DialogInput,
+    DialogInputInactive,
+    DialogShadow,
+    DialogSuccess,
+    DialogAccent,
+    DialogError,
+    ButtonSave,
+    ButtonDiscard,
+    ButtonCancel,
+
+    // Syntax highlighting
+    String,
+    Comment,
+    Keyword,
+    Type,
+    Function,
+    Number,
+    Operator,
+    Punctuation,
+    Decorator,
+}
+
+/// Every element with the section and name it has in theme files, in declaration order
+const ELEMENTS: &[(Element, &str, &str)] = &[
+    (Element::Text, "ui", "text"),
+    (Element::CurrentLine, "ui", "current_line"),
+    (Element::NonText, "ui", "non_text"),
+    (Element::Selection, "ui", "selection"),
+    (Element::MatchingText, "ui", "matching_text"),
+    (Element::MatchingBracket, "ui", "matching_bracket"),
+    (Element::FindMatch, "ui", "find_match"),
+    (Element::FindMatchCurrent, "ui", "find_match_current"),
+    (Element::CellTags, "ui", "cell_tags"),
+    (Element::FoldMarker, "ui", "fold_marker"),
+    (Element::LineNumber, "ui", "line_number"),
+    (Element::LineNumberCurrent, "ui", "line_number_current"),
+    (Element::GutterAdded, "ui", "gutter_added"),
+    (Element::GutterModified, "ui", "gutter_modified"),
+    (Element::GutterDeleted, "ui", "gutter_deleted"),
+    (Element::GutterError, "ui", "gutter_error"),
+    (Element::BadgeStale, "ui", "badge_stale"),
+    (Element::BadgeModified, "ui", "badge_modified"),
+    (Element::BadgeRunning, "ui", "badge_running"),
+    (Element::BadgeQueued, "ui", "badge_queued"),
+    (Element::BadgeCount, "ui", "badge_count"),
+    (Element::BadgeElapsed, "ui", "badge_elapsed"),
+    (Element::BadgeOk, "ui", "badge_ok"),
+    (Element::BadgeError, "ui", "badge_error"),
+    (Element::InlineOutput, "ui", "inline_output"),
+    (Element::InlineOutputHeader, "ui", "inline_output_header"),
+    (Element::InlineOutputError, "ui", "inline_output_error"),
+    (Element::Cursor, "ui", "cursor"),
+    (Element::ExtraCursor, "ui", "extra_cursor"),
+    (Element::Divider, "ui", "divider"),
+    (Element::Status, "ui", "status"),
+    (Element::StatusError, "ui", "status_error"),
+    (Element::TabBar, "ui", "tab_bar"),
+    (Element::TabActive, "ui", "tab_active"),
+    (Element::TabInactive, "ui", "tab_inactive"),
+    (Element::Output, "ui", "output"),
+    (Element::OutputBorder, "ui", "output_border"),
+    (Element::OutputTitle, "ui", "output_title"),
+    (Element::OutputFindTitle, "ui", "output_find_title"),
+    (Element::OutputHeader, "ui", "output_header"),
+    (Element::OutputDim, "ui", "output_dim"),
+    (Element::OutputError, "ui", "output_error"),
+    (Element::OutputSelection, "ui", "output_selection"),
+    (Element::Completion, "ui", "completion"),
+    (Element::CompletionSelected, "ui", "completion_selected"),
+    (Element::Dialog, "ui", "dialog"),
+    (Element::DialogTitle, "ui", "dialog_title"),
+    (Element::DialogDim, "ui", "dialog_dim"),
+    (Element::DialogInactive, "ui", "dialog_inactive"),
+    (Element::DialogSelected, "ui", "dialog_selected"),
+    (Element::DialogMatch, "ui", "dialog_match"),
+    (Element::DialogInput, "ui", "dialog_input"),
+    (Element::DialogInputInactive, "ui", "dialog_input_inactive"),
+    (Element::DialogShadow, "ui", "dialog_shadow"),
+    (Element::DialogSuccess, "ui", "dialog_success"),
+    (Element::DialogAccent, "ui", "dialog_accent"),
+    (Element::DialogError, "ui", "dialog_error"),
+    (Element::ButtonSave, "ui", "button_save"),
+    (Element::ButtonDiscard, "ui", "button_discard"),
+    (Element::ButtonCancel, "ui", "button_cancel"),
+    (Element::String, "syntax", "string"),
+    (Element::Comment, "syntax", "comment"),
+    (Element::Keyword, "syntax", "keyword"),
+    (Element::Type, "syntax", "type"),
+    (Element::Function, "syntax", "function"),
+    (Element::Number, "syntax", "number"),
+    (Element::Operator, "syntax", "operator"),
+    (Element::Punctuation, "syntax", "punctuation"),
+    (Element::Decorator, "syntax", "decorator"),
+];
+
+/// How many colours the terminal can show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorDepth {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+}
+
+impl ColorDepth {
+    /// Work the depth out from the environment: `COLORTERM=truecolor` (or `24bit`) for
+    /// 24-bit colour, a `TERM` mentioning 256 colours for those, the 16 basic ones otherwise
+    pub fn detect() -> Self {
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default().to_lowercase();
+        let term = std::env::var("TERM").unwrap_or_default();
+        if colorterm.contains("truecolor") || colorterm.contains("24bit") || cfg!(windows) {
+            ColorDepth::TrueColor
+        } else if term.contains("256") {
+            ColorDepth::Ansi256
+        } else {
+            ColorDepth::Ansi16
+        }
+    }
+
+    /// Depth named in the config: "auto", "truecolor", "256" or "16"
+    pub fn from_setting(setting: &str) -> Option<Self> {
+        match setting {
+            "auto" => Some(ColorDepth::detect()),
+            "truecolor" | "24bit" => Some(ColorDepth::TrueColor),
+            "256" => Some(ColorDepth::Ansi256),
+            "16" => Some(ColorDepth::Ansi16),
+            _ => None,
+        }
+    }
+
+    /// The nearest colour this depth can show
+    fn adapt(self, color: Color) -> Color {
+        let Color::Rgb { r, g, b } = color else {
+            return color;
+        };
+        match self {
+            ColorDepth::TrueColor => color,
+            ColorDepth::Ansi256 => Color::AnsiValue(nearest_ansi256(r, g, b)),
+            ColorDepth::Ansi16 => nearest_ansi16(r, g, b),
+        }
+    }
+}
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2) as u32;
+    d(r1, r2) + d(g1, g2) + d(b1, b2)
+}
+
+/// Closest entry of the xterm 6x6x6 colour cube or its grey ramp
+fn nearest_ansi256(r: u8, g: u8, b: u8) -> u8 {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let level = |c: u8| (0..6).min_by_key(|&i| (LEVELS[i] as i32 - c as i32).abs()).unwrap_or(0);
+    let (ri, gi, bi) = (level(r), level(g), level(b));
+    let cube = (LEVELS[ri], LEVELS[gi], LEVELS[bi]);
+    let cube_index = 16 + 36 * ri + 6 * gi + bi;
+
+    let grey_step = ((r as u32 + g as u32 + b as u32) / 3).saturating_sub(8).min(238) / 10;
+    let grey_value = (8 + grey_step * 10) as u8;
+    let grey_index = 232 + grey_step as usize;
+
+    if distance((grey_value, grey_value, grey_value), (r, g, b)) < distance(cube, (r, g, b)) {
+        grey_index as u8
+    } else {
+        cube_index as u8
+    }
+}
+
+/// Closest of the 16 basic colours, using the usual xterm values
+fn nearest_ansi16(r: u8, g: u8, b: u8) -> Color {
+    const BASIC: [(Color, (u8, u8, u8)); 16] = [
+        (Color::Black, (0, 0, 0)),
+        (Color::DarkRed, (128, 0, 0)),
+        (Color::DarkGreen, (0, 128, 0)),
+        (Color::DarkYellow, (128, 128, 0)),
+        (Color::DarkBlue, (0, 0, 128)),
+        (Color::DarkMagenta, (128, 0, 128)),
+        (Color::DarkCyan, (0, 128, 128)),
+        (Color::Grey, (192, 192, 192)),
+        (Color::DarkGrey, (128, 128, 128)),
+        (Color::Red, (255, 0, 0)),
+        (Color::Green, (0, 255, 0)),
+        (Color::Yellow, (255, 255, 0)),
+        (Color::Blue, (0, 0, 255)),
+        (Color::Magenta, (255, 0, 255)),
+        (Color::Cyan, (0, 255, 255)),
+        (Color::White, (255, 255, 255)),
+    ];
+    BASIC.iter().min_by_key(|(_, rgb)| distance(*rgb, (r, g, b))).map(|(color, _)| *color).unwrap_or(Color::Reset)
+}
+
+const fn rgb(r: u8, g: u8, b: u8) -> Color {
+    Color::Rgb { r, g, b }
+}
+
+const fn grey(level: u8) -> Color {
+    Color::Rgb { r: level, g: level, b: level }
+}
+
+/// Styles for every element
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    styles: Vec<Style>, // Indexed by `Element`
+    cursor: Option<(u8, u8, u8)>, // Cursor colour before adapting to the terminal, for OSC 12
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::dark()
+    }
+}
+
+impl std::ops::Index<Element> for Theme {
+    type Output = Style;
+
+    fn index(&self, element: Element) -> &Style {
+        &self.styles[element as usize]
+    }
+}
+
+impl Theme {
+    fn from_styles(styles: &[(Element, Style)]) -> Self {
+        let mut theme = Theme { styles: vec![Style::NONE; ELEMENTS.len()], cursor: None };
+        for (element, style) in styles {
+            theme.set(*element, *style);
+        }
+        theme
+    }
+
+    fn set(&mut self, element: Element, style: Style) {
+        self.styles[element as usize] = style;
+        if element == Element::Cursor {
+            self.cursor = match style.fg {
+                Some(Color::Rgb { r, g, b }) => Some((r, g, b)),
+                _ => None,
+            };
+        }
+    }
+
+    /// A built-in theme by name
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Theme::dark()),
+            "light" => Some(Theme::light()),
+            "high-contrast" => Some(Theme::high_contrast()),
+            _ => None,
+        }
+    }
+
+    /// Style for a kind of syntax token, on top of the editor text
+    pub fn syntax(&self, state: SyntaxState) -> Style {
+        match state {
+            SyntaxState::StringDouble | SyntaxState::StringSingle | SyntaxState::StringTriple | SyntaxState::StringTripleSingle => {
+                self[Element::String]
+            }
+            SyntaxState::LineComment | SyntaxState::BlockComment => self[Element::Comment],
+            SyntaxState::Keyword => self[Element::Keyword],
+            SyntaxState::Type => self[Element::Type],
+            SyntaxState::Function => self[Element::Function],
+            SyntaxState::Number => self[Element::Number],
+            SyntaxState::Operator => self[Element::Operator],
+            SyntaxState::Punctuation => self[Element::Punctuation],
+            SyntaxState::MacroOrDecorator => self[Element::Decorator],
+            SyntaxState::Normal => Style::NONE,
+        }
+    }
+
+    /// Escape sequence setting the terminal cursor colour, if the theme has one
+    pub fn cursor_escape(&self) -> Option<String> {
+        self.cursor.map(|(r, g, b)| format!("\x1b]12;#{:02X}{:02X}{:02X}\x07", r, g, b))
+    }
+
+    /// The same theme with its colours converted for a terminal of the given depth
+    fn adapted(mut self, depth: ColorDepth) -> Self {
+        for style in &mut self.styles {
+            style.fg = style.fg.map(|color| depth.adapt(color));
+            style.bg = style.bg.map(|color| depth.adapt(color));
+        }
+        self
+    }
+
+    /// Muted colours on a near-black background - the original look
+    fn dark() -> Self {
+        let dialog = rgb(40, 40, 45);
+        Theme::from_styles(&[
+            (Element::Text, Style::bg(grey(30))),
+            (Element::CurrentLine, Style::bg(grey(40))),
+            (Element::NonText, Style::fg(grey(110))),
+            (Element::Selection, Style::fg(Color::Rgb { r: 0, g: 0, b: 0 }).on(rgb(95, 158, 160))),
+            (Element::MatchingText, Style::bg(rgb(50, 80, 82))),
+            (Element::MatchingBracket, Style::fg(rgb(220, 220, 120)).bold()),
+            (Element::FindMatch, Style::bg(rgb(120, 90, 60))),
+            (Element::FindMatchCurrent, Style::fg(grey(0)).on(rgb(200, 150, 100))),
+            (Element::CellTags, Style::fg(rgb(210, 180, 120)).on(rgb(55, 50, 40))),
+            (Element::FoldMarker, Style::fg(grey(140)).on(grey(40))),
+            (Element::LineNumber, Style::fg(grey(90))),
+            (Element::LineNumberCurrent, Style::fg(grey(180))),
+            (Element::GutterAdded, Style::fg(rgb(130, 180, 120))),
+            (Element::GutterModified, Style::fg(rgb(200, 170, 110))),
+            (Element::GutterDeleted, Style::fg(rgb(220, 110, 110))),
+            (Element::GutterError, Style::fg(rgb(220, 110, 110)).bold()),
+            (Element::BadgeStale, Style::fg(rgb(230, 180, 100)).on(rgb(70, 55, 30))),
+            (Element::BadgeModified, Style::fg(rgb(200, 170, 140))),
+            (Element::BadgeRunning, Style::fg(rgb(95, 158, 160)).bold()),
+            (Element::BadgeQueued, Style::fg(grey(110))),
+            (Element::BadgeCount, Style::fg(grey(160))),
+            (Element::BadgeElapsed, Style::fg(grey(110))),
+            (Element::BadgeOk, Style::fg(rgb(130, 180, 120))),
+            (Element::BadgeError, Style::fg(rgb(220, 110, 110))),
+            (Element::InlineOutput, Style::fg(grey(180)).on(grey(24))),
+            (Element::InlineOutputHeader, Style::fg(grey(110))),
+            (Element::InlineOutputError, Style::fg(rgb(220, 110, 110))),
+            (Element::Cursor, Style::fg(rgb(95, 158, 160))),
+            (Element::ExtraCursor, Style::fg(grey(0)).on(rgb(95, 158, 160))),
+            (Element::Divider, Style::fg(grey(80)).on(grey(30))),
+            (Element::Status, Style::fg(Color::White).on(Color::DarkGrey)),
+            (Element::StatusError, Style::fg(Color::White).on(Color::Red)),
+            (Element::TabBar, Style::fg(rgb(70, 70, 75)).on(dialog)),
+            (Element::TabActive, Style::fg(Color::White).on(grey(30))),
+            (Element::TabInactive, Style::fg(rgb(150, 150, 155)).on(dialog)),
+            (Element::OutputBorder, Style::fg(Color::DarkGrey)),
+            (Element::OutputTitle, Style::fg(Color::Cyan)),
+            (Element::OutputFindTitle, Style::fg(Color::Yellow)),
+            (Element::OutputHeader, Style::fg(Color::Green)),
+            (Element::OutputDim, Style::fg(Color::DarkGrey)),
+            (Element::OutputError, Style::fg(Color::Red)),
+            (Element::OutputSelection, Style::fg(Color::Black).on(Color::White)),
+            (Element::Completion, Style::fg(Color::White).on(Color::DarkGrey)),
+            (Element::CompletionSelected, Style::fg(Color::White).on(Color::DarkBlue)),
+            (Element::Dialog, Style::fg(rgb(200, 200, 205)).on(dialog)),
+            (Element::DialogTitle, Style::fg(Color::White).bold()),
+            (Element::DialogDim, Style::fg(rgb(130, 130, 140))),
+            (Element::DialogInactive, Style::fg(rgb(150, 150, 155))),
+            (Element::DialogSelected, Style::fg(Color::White).on(rgb(60, 60, 75))),
+            (Element::DialogMatch, Style::fg(rgb(230, 190, 90))),
+            (Element::DialogInput, Style::fg(rgb(220, 220, 230)).on(rgb(20, 20, 25))),
+            (Element::DialogInputInactive, Style::fg(rgb(220, 220, 230)).on(rgb(30, 30, 35))),
+            (Element::DialogShadow, Style::bg(Color::Black)),
+            (Element::DialogSuccess, Style::fg(rgb(150, 200, 150))),
+            (Element::DialogAccent, Style::fg(Color::Cyan)),
+            (Element::DialogError, Style::fg(Color::Red)),
+            (Element::ButtonSave, Style::fg(Color::White).on(rgb(60, 120, 60))),
+            (Element::ButtonDiscard, Style::fg(Color::White).on(rgb(120, 60, 60))),
+            (Element::ButtonCancel, Style::fg(Color::White).on(rgb(60, 60, 120))),
+            (Element::String, Style::fg(rgb(152, 180, 152))),
+            (Element::Comment, Style::fg(grey(110))),
+            (Element::Keyword, Style::fg(rgb(135, 160, 180))),
+            (Element::Type, Style::fg(rgb(132, 170, 170))),
+            (Element::Function, Style::fg(rgb(200, 190, 150))),
+            (Element::Number, Style::fg(rgb(200, 170, 140))),
+            (Element::Operator, Style::fg(grey(160))),
+            (Element::Decorator, Style::fg(rgb(180, 150, 180))),
+        ])
+    }
+
+    /// Dark text on an off-white background
+    fn light() -> Self {
+        let dialog = rgb(236, 236, 232);
+        let red = rgb(190, 40, 40);
+        let green = rgb(40, 130, 60);
+        let teal = rgb(30, 110, 140);
+        let selection = Style::fg(grey(0)).on(rgb(173, 214, 255));
+        Theme::from_styles(&[
+            (Element::Text, Style::fg(grey(40)).on(rgb(250, 250, 248))),
+            (Element::CurrentLine, Style::bg(rgb(238, 238, 232))),
+            (Element::NonText, Style::fg(grey(170))),
+            (Element::Selection, selection),
+            (Element::MatchingText, Style::bg(rgb(220, 235, 240))),
+            (Element::MatchingBracket, Style::fg(rgb(175, 90, 0)).bold()),
+            (Element::FindMatch, Style::bg(rgb(255, 228, 170))),
+            (Element::FindMatchCurrent, Style::fg(grey(0)).on(rgb(255, 170, 60))),
+            (Element::CellTags, Style::fg(rgb(130, 90, 30)).on(rgb(240, 230, 205))),
+            (Element::FoldMarker, Style::fg(grey(90)).on(grey(225))),
+            (Element::LineNumber, Style::fg(grey(165))),
+            (Element::LineNumberCurrent, Style::fg(grey(60))),
+            (Element::GutterAdded, Style::fg(green)),
+            (Element::GutterModified, Style::fg(rgb(190, 130, 20))),
+            (Element::GutterDeleted, Style::fg(red)),
+            (Element::GutterError, Style::fg(red).bold()),
+            (Element::BadgeStale, Style::fg(rgb(120, 80, 10)).on(rgb(250, 225, 170))),
+            (Element::BadgeModified, Style::fg(rgb(160, 100, 40))),
+            (Element::BadgeRunning, Style::fg(teal).bold()),
+            (Element::BadgeQueued, Style::fg(grey(150))),
+            (Element::BadgeCount, Style::fg(grey(110))),
+            (Element::BadgeElapsed, Style::fg(grey(150))),
+            (Element::BadgeOk, Style::fg(green)),
+            (Element::BadgeError, Style::fg(red)),
+            (Element::InlineOutput, Style::fg(grey(70)).on(rgb(242, 242, 238))),
+            (Element::InlineOutputHeader, Style::fg(grey(150))),
+            (Element::InlineOutputError, Style::fg(red)),
+            (Element::Cursor, Style::fg(rgb(40, 110, 180))),
+            (Element::ExtraCursor, Style::fg(grey(255)).on(rgb(40, 110, 180))),
+            (Element::Divider, Style::fg(grey(200)).on(rgb(250, 250, 248))),
+            (Element::Status, Style::fg(grey(30)).on(rgb(220, 220, 215))),
+            (Element::StatusError, Style::fg(grey(255)).on(rgb(200, 50, 50))),
+            (Element::TabBar, Style::fg(grey(190)).on(rgb(230, 230, 226))),
+            (Element::TabActive, Style::fg(grey(20)).on(rgb(250, 250, 248))),
+            (Element::TabInactive, Style::fg(grey(110)).on(rgb(230, 230, 226))),
+            (Element::Output, Style::fg(grey(40)).on(rgb(246, 246, 243))),
+            (Element::OutputBorder, Style::fg(grey(190))),
+            (Element::OutputTitle, Style::fg(teal)),
+            (Element::OutputFindTitle, Style::fg(rgb(160, 100, 0))),
+            (Element::OutputHeader, Style::fg(green)),
+            (Element::OutputDim, Style::fg(grey(150))),
+            (Element::OutputError, Style::fg(red)),
+            (Element::OutputSelection, selection),
+            (Element::Completion, Style::fg(grey(30)).on(rgb(230, 230, 226))),
+            (Element::CompletionSelected, selection),
+            (Element::Dialog, Style::fg(grey(50)).on(dialog)),
+            (Element::DialogTitle, Style::fg(grey(0)).bold()),
+            (Element::DialogDim, Style::fg(grey(130))),
+            (Element::DialogInactive, Style::fg(grey(110))),
+            (Element::DialogSelected, Style::fg(grey(0)).on(rgb(200, 220, 240))),
+            (Element::DialogMatch, Style::fg(rgb(170, 100, 0))),
+            (Element::DialogInput, Style::fg(grey(20)).on(grey(255))),
+            (Element::DialogInputInactive, Style::fg(grey(60)).on(rgb(245, 245, 242))),
+            (Element::DialogShadow, Style::bg(rgb(180, 180, 178))),
+            (Element::DialogSuccess, Style::fg(green)),
+            (Element::DialogAccent, Style::fg(teal)),
+            (Element::DialogError, Style::fg(red)),
+            (Element::ButtonSave, Style::fg(grey(255)).on(rgb(70, 150, 80))),
+            (Element::ButtonDiscard, Style::fg(grey(255)).on(rgb(190, 70, 70))),
+            (Element::ButtonCancel, Style::fg(grey(255)).on(rgb(80, 100, 180))),
+            (Element::String, Style::fg(rgb(60, 130, 60))),
+            (Element::Comment, Style::fg(rgb(140, 140, 130))),
+            (Element::Keyword, Style::fg(rgb(40, 80, 170))),
+            (Element::Type, Style::fg(rgb(30, 120, 130))),
+            (Element::Function, Style::fg(rgb(130, 90, 20))),
+            (Element::Number, Style::fg(rgb(170, 90, 30))),
+            (Element::Operator, Style::fg(grey(90))),
+            (Element::Decorator, Style::fg(rgb(140, 60, 150))),
+        ])
+    }
+
+    /// Pure colours on black, for low vision or washed-out displays
+    fn high_contrast() -> Self {
+        let black = grey(0);
+        let white = grey(255);
+        let yellow = rgb(255, 255, 0);
+        let cyan = rgb(0, 255, 255);
+        let red = rgb(255, 90, 90);
+        let green = rgb(90, 255, 90);
+        let inverse = Style::fg(black).on(white);
+        Theme::from_styles(&[
+            (Element::Text, Style::fg(white).on(black)),
+            (Element::CurrentLine, Style::bg(grey(28))),
+            (Element::NonText, Style::fg(grey(160))),
+            (Element::Selection, Style::fg(black).on(cyan)),
+            (Element::MatchingText, Style::bg(rgb(0, 70, 90))),
+            (Element::MatchingBracket, Style::fg(yellow).bold()),
+            (Element::FindMatch, Style::fg(black).on(rgb(200, 140, 0))),
+            (Element::FindMatchCurrent, Style::fg(black).on(yellow).bold()),
+            (Element::CellTags, Style::fg(black).on(yellow)),
+            (Element::FoldMarker, Style::fg(black).on(white)),
+            (Element::LineNumber, Style::fg(grey(200))),
+            (Element::LineNumberCurrent, Style::fg(yellow).bold()),
+            (Element::GutterAdded, Style::fg(green)),
+            (Element::GutterModified, Style::fg(yellow)),
+            (Element::GutterDeleted, Style::fg(red)),
+            (Element::GutterError, Style::fg(black).on(red)),
+            (Element::BadgeStale, Style::fg(black).on(yellow)),
+            (Element::BadgeModified, Style::fg(yellow)),
+            (Element::BadgeRunning, Style::fg(cyan).bold()),
+            (Element::BadgeQueued, Style::fg(grey(200))),
+            (Element::BadgeCount, Style::fg(white)),
+            (Element::BadgeElapsed, Style::fg(grey(200))),
+            (Element::BadgeOk, Style::fg(green)),
+            (Element::BadgeError, Style::fg(red).bold()),
+            (Element::InlineOutput, Style::fg(white).on(grey(20))),
+            (Element::InlineOutputHeader, Style::fg(grey(200))),
+            (Element::InlineOutputError, Style::fg(red)),
+            (Element::Cursor, Style::fg(yellow)),
+            (Element::ExtraCursor, Style::fg(black).on(yellow)),
+            (Element::Divider, Style::fg(white).on(black)),
+            (Element::Status, inverse),
+            (Element::StatusError, Style::fg(white).on(rgb(200, 0, 0)).bold()),
+            (Element::TabBar, Style::fg(grey(160)).on(black)),
+            (Element::TabActive, inverse.bold()),
+            (Element::TabInactive, Style::fg(white).on(black)),
+            (Element::Output, Style::fg(white).on(black)),
+            (Element::OutputBorder, Style::fg(white)),
+            (Element::OutputTitle, Style::fg(cyan).bold()),
+            (Element::OutputFindTitle, Style::fg(yellow).bold()),
+            (Element::OutputHeader, Style::fg(green)),
+            (Element::OutputDim, Style::fg(grey(180))),
+            (Element::OutputError, Style::fg(red)),
+            (Element::OutputSelection, inverse),
+            (Element::Completion, Style::fg(white).on(grey(40))),
+            (Element::CompletionSelected, Style::fg(black).on(cyan)),
+            (Element::Dialog, Style::fg(white).on(black)),
+            (Element::DialogTitle, Style::fg(yellow).bold()),
+            (Element::DialogDim, Style::fg(grey(190))),
+            (Element::DialogInactive, Style::fg(grey(190))),
+            (Element::DialogSelected, Style::fg(black).on(cyan)),
+            (Element::DialogMatch, Style::fg(yellow).bold()),
+            (Element::DialogInput, Style::fg(white).on(grey(40))),
+            (Element::DialogInputInactive, Style::fg(white).on(grey(20))),
+            (Element::DialogShadow, Style::bg(grey(60))),
+            (Element::DialogSuccess, Style::fg(green)),
+            (Element::DialogAccent, Style::fg(cyan)),
+            (Element::DialogError, Style::fg(red)),
+            (Element::ButtonSave, Style::fg(black).on(green)),
+            (Element::ButtonDiscard, Style::fg(black).on(red)),
+            (Element::ButtonCancel, Style::fg(black).on(cyan)),
+            (Element::String, Style::fg(green)),
+            (Element::Comment, Style::fg(grey(170))),
+            (Element::Keyword, Style::fg(cyan).bold()),
+            (Element::Type, Style::fg(rgb(120, 200, 255))),
+            (Element::Function, Style::fg(yellow)),
+            (Element::Number, Style::fg(rgb(255, 170, 60))),
+            (Element::Operator, Style::fg(white)),
+            (Element::Decorator, Style::fg(rgb(255, 120, 255))),
+        ])
+    }
+}
+
+/// The theme everything draws with
+pub fn current() -> Arc<Theme> {
+    if let Some(theme) = CURRENT.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        return Arc::clone(theme);
+    }
+    let theme = Arc::new(Theme::default().adapted(ColorDepth::detect()));
+    *CURRENT.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::clone(&theme));
+    theme
+}
+
+/// Draw with a theme from now on, converted for a terminal of the given depth
+pub fn install(theme: Theme, depth: ColorDepth) {
+    *CURRENT.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(theme.adapted(depth)));
+}
+
+/// A colour in a theme file: "#rrggbb", a basic colour name like "dark_grey", an index into
+/// the 256-colour palette, or "default" for the terminal's own
+struct ColorSpec(Option<Color>);
+
+impl<'de> Deserialize<'de> for ColorSpec {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ColorVisitor;
+
+        impl Visitor<'_> for ColorVisitor {
+            type Value = ColorSpec;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a colour like \"#87a0b4\", \"dark_grey\" or 0-255")
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<ColorSpec, E> {
+                u8::try_from(value)
+                    .map(|index| ColorSpec(Some(Color::AnsiValue(index))))
+                    .map_err(|_| E::custom(format!("colour index {} is not between 0 and 255", value)))
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<ColorSpec, E> {
+                if value == "default" || value == "none" {
+                    return Ok(ColorSpec(None));
+                }
+                if let Some(hex) = value.strip_prefix('#').filter(|hex| hex.len() == 6) {
+                    if let Ok(packed) = u32::from_str_radix(hex, 16) {
+                        return Ok(ColorSpec(Some(rgb((packed >> 16) as u8, (packed >> 8) as u8, packed as u8))));
+                    }
+                }
+                Color::try_from(value)
+                    .map(|color| ColorSpec(Some(color)))
+                    .map_err(|_| E::custom(format!("unknown colour '{}'", value)))
+            }
+        }
+
+        deserializer.deserialize_any(ColorVisitor)
+    }
+}
+
+/// An element in a theme file: just a foreground colour, or a table of
+/// `fg`, `bg`, `bold` and `italic`
+struct StyleSpec(Style);
+
+impl<'de> Deserialize<'de> for StyleSpec {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct StyleVisitor;
+
+        impl<'de> Visitor<'de> for StyleVisitor {
+            type Value = StyleSpec;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a colour or a table with fg, bg, bold and italic")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<StyleSpec, E> {
+                let ColorSpec(fg) = ColorSpec::deserialize(de::value::StrDeserializer::<E>::new(value))?;
+                Ok(StyleSpec(Style { fg, ..Style::NONE }))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<StyleSpec, A::Error> {
+                let mut style = Style::NONE;
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "fg" => style.fg = map.next_value::<ColorSpec>()?.0,
+                        "bg" => style.bg = map.next_value::<ColorSpec>()?.0,
+                        "bold" => style.bold = map.next_value()?,
+                        "italic" => style.italic = map.next_value()?,
+                        other => return Err(de::Error::unknown_field(other, &["fg", "bg", "bold", "italic"])),
+                    }
+                }
+                Ok(StyleSpec(style))
+            }
+        }
+
+        deserializer.deserialize_any(StyleVisitor)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+    base: Option<String>, // Built-in theme the file starts from, "dark" by default
+    #[serde(default)]
+    ui: BTreeMap<toml::Spanned<String>, StyleSpec>,
+    #[serde(default)]
+    syntax: BTreeMap<toml::Spanned<String>, StyleSpec>,
+}
+
+/// Line of a byte offset in a file, counting from 1
+pub fn line_at(text: &str, offset: usize) -> usize {
+    text[..offset.min(text.len())].matches('\n').count() + 1
+}
+
+/// Read a theme file
+fn parse_file(path: &Path, text: &str) -> Result<Theme, String> {
+    let file: ThemeFile = toml::from_str(text).map_err(|e| {
+        let message = e.message().lines().map(str::trim).filter(|line| !line.is_empty()).collect::<Vec<_>>().join(", ");
+        match e.span() {
+            Some(span) => format!("{}:{}: {}", path.display(), line_at(text, span.start), message),
+            None => format!("{}: {}", path.display(), message),
+        }
+    })?;
+
+    let base = file.base.as_deref().unwrap_or(BUILTIN_THEMES[0]);
+    let mut theme = Theme::builtin(base)
+        .ok_or_else(|| format!("{}: unknown base theme '{}', expected one of {}", path.display(), base, BUILTIN_THEMES.join(", ")))?;
+
+    for (section, entries) in [("ui", file.ui), ("syntax", file.syntax)] {
+        for (name, StyleSpec(style)) in entries {
+            let element = ELEMENTS
+                .iter()
+                .find(|(_, element_section, element_name)| *element_section == section && *element_name == name.get_ref())
+                .map(|(element, _, _)| *element)
+                .ok_or_else(|| format!("{}:{}: unknown {} element '{}'", path.display(), line_at(text, name.span().start), section, name.get_ref()))?;
+            theme.set(element, style);
+        }
+    }
+    Ok(theme)
+}
+
+/// Find a theme by name: a built-in one, `<name>.toml` in `themes_dir`, or a path to a
+/// theme file (relative ones from `themes_dir`'s parent, the config directory)
+/// Returns the theme and the file it came from, if any
+pub fn load(name: &str, themes_dir: Option<&Path>) -> Result<(Theme, Option<PathBuf>), String> {
+    if let Some(theme) = Theme::builtin(name) {
+        return Ok((theme, None));
+    }
+
+    let path = if name.ends_with(".toml") || name.contains('/') || name.contains('\\') {
+        let path = PathBuf::from(name);
+        match themes_dir.and_then(Path::parent) {
+            Some(config_dir) if path.is_relative() => config_dir.join(path),
+            _ => path,
+        }
+    } else {
+        match themes_dir {
+            Some(dir) => dir.join(format!("{}.toml", name)),
+            None => return Err(format!("unknown theme '{}'", name)),
+        }
+    };
+
+    let text = std::fs::read_to_string(&path).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            format!("unknown theme '{}', expected one of {} or a file in {}", name, BUILTIN_THEMES.join(", "),
+                themes_dir.map(|dir| dir.display().to_string()).unwrap_or_default())
+        } else {
+            format!("{}: {}", path.display(), e)
+        }
+    })?;
+    Ok((parse_file(&path, &text)?, Some(path)))
+}
@@ -0,0 +1,115 @@
+//! Undo history kept between sessions
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::undo_tree::UndoTree;
+use crate::config;
+
+/// Bumped whenever the layout of the history files changes, so older ones are ignored
+const FORMAT: u32 = 2;
+
+#[derive(Serialize, Deserialize)]
+struct HistoryFile {
+    format: u32,
+    path: PathBuf,     // File the history belongs to, in case two paths hash the same
+    content_hash: u64, // Hash of the text in the tree's current state
+    history: UndoTree,
+}
+
+impl HistoryFile {
+    /// The history, if it was saved for `file` (a full path) holding `text`
+    fn history_for(self, file: &Path, text: &str) -> Option<UndoTree> {
+        let matches = self.format == FORMAT
+            && self.path == file
+            && self.content_hash == stable_hash(text.as_bytes())
+            && self.history.is_valid();
+        matches.then_some(self.history)
+    }
+}
+
+/// FNV-1a, which unlike the standard library's hasher gives the same hash in every build
+pub fn stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// The file's full path, and where its history is kept
+fn history_path(file: &Path) -> Option<(PathBuf, PathBuf)> {
+    let file = file.canonicalize().ok()?;
+    let name = format!("{:016x}.json", stable_hash(file.to_string_lossy().as_bytes()));
+    Some((file, config::cache_dir()?.join("undo").join(name)))
+}
+
+/// History saved for a file, if the file still holds `text` as it did when it was saved
+pub fn load(file: &Path, text: &str) -> Option<UndoTree> {
+    let (file, history_path) = history_path(file)?;
+    let saved: HistoryFile = serde_json::from_str(&fs::read_to_string(history_path).ok()?).ok()?;
+    saved.history_for(&file, text)
+}
+
+/// Keep a file's history for the next session, trimmed to `max_bytes` of edited text.
+/// `text` is what the file now holds
+pub fn save(file: &Path, text: &str, mut history: UndoTree, max_bytes: usize) -> io::Result<()> {
+    let Some((file, history_path)) = history_path(file) else {
+        return Ok(());
+    };
+    history.trim_to(max_bytes);
+    if history.is_empty() {
+        return match fs::remove_file(&history_path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+
+    if let Some(parent) = history_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let saved = HistoryFile { format: FORMAT, path: file, content_hash: stable_hash(text.as_bytes()), history };
+    let json = serde_json::to_string(&saved).map_err(io::Error::other)?;
+    // Written beside it first, so a crash can't leave half a history to be loaded next time
+    let partial = history_path.with_extension("json.partial");
+    fs::write(&partial, json)?;
+    fs::rename(partial, history_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::undo_tree::UndoGroup;
+
+    #[test]
+    fn stable_hash_is_fnv1a() {
+        assert_eq!(stable_hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(stable_hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(stable_hash(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    fn saved(text: &str) -> HistoryFile {
+        let mut history = UndoTree::new();
+        history.push(UndoGroup::default());
+        HistoryFile { format: FORMAT, path: PathBuf::from("/notes/a.py"), content_hash: stable_hash(text.as_bytes()), history }
+    }
+
+    #[test]
+    fn history_is_only_used_for_the_text_it_was_saved_with() {
+        let file = Path::new("/notes/a.py");
+        assert!(saved("x = 1\n").history_for(file, "x = 1\n").is_some_and(|history| history.len() == 2));
+        assert!(saved("x = 1\n").history_for(file, "x = 2\n").is_none());
+        assert!(saved("x = 1\n").history_for(Path::new("/notes/b.py"), "x = 1\n").is_none());
+        let older = HistoryFile { format: FORMAT - 1, ..saved("x = 1\n") };
+        assert!(older.history_for(file, "x = 1\n").is_none());
+    }
+
+    #[test]
+    fn history_survives_a_round_trip() {
+        let json = serde_json::to_string(&saved("x = 1\n")).unwrap();
+        let read: HistoryFile = serde_json::from_str(&json).unwrap();
+        assert!(read.history_for(Path::new("/notes/a.py"), "x = 1\n").is_some());
+        // A tree that doesn't hang together is left alone
+        let broken: HistoryFile = serde_json::from_str(&json.replace("\"current\":1", "\"current\":9")).unwrap();
+        assert!(broken.history_for(Path::new("/notes/a.py"), "x = 1\n").is_none());
+    }
+}
@@ -0,0 +1,342 @@
+//! Undo history as a tree, so editing after an undo starts a new branch
+
+use std::collections::{BTreeSet, HashMap};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use crate::buffer::EditOp;
+
+/// Related edit operations undone and redone as one step (e.g., continuous typing)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UndoGroup {
+    pub ops: Vec<EditOp>,
+    pub cursor_before: usize,
+    pub cursor_after: usize,
+}
+
+impl UndoGroup {
+    /// Bytes of text the group's operations carry
+    fn text_len(&self) -> usize {
+        self.ops.iter()
+            .map(|op| match op {
+                EditOp::Insert { text, .. } | EditOp::Delete { text, .. } => text.len(),
+            })
+            .sum()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct UndoNode {
+    parent: Option<usize>,     // None only for the root, the text before any edit
+    redo_child: Option<usize>, // Child redo goes to: the newest, or the one last undone from or picked
+    group: UndoGroup,          // Edits from the parent's text to this node's; empty at the root
+    time: u64,                 // Seconds since the Unix epoch when the state was made
+}
+
+/// Every state the text has been in, and which one it's in now
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UndoTree {
+    nodes: Vec<UndoNode>,
+    current: usize,
+}
+
+impl Default for UndoTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Seconds since the Unix epoch
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// How long ago `time` was, e.g. "3 min ago"
+pub fn age(time: u64, now: u64) -> String {
+    match now.saturating_sub(time) {
+        0..=9 => "just now".to_string(),
+        secs @ 10..=59 => format!("{} s ago", secs),
+        secs @ 60..=3599 => format!("{} min ago", secs / 60),
+        secs @ 3600..=86_399 => format!("{} h ago", secs / 3600),
+        secs => format!("{} days ago", secs / 86_400),
+    }
+}
+
+/// Seconds in a span like "30s", "5m", "2h" or "1d"; a bare number is minutes
+pub fn parse_duration(text: &str) -> Option<u64> {
+    let text = text.trim().to_lowercase();
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let count: u64 = text[..split].parse().ok()?;
+    let unit = match text[split..].trim() {
+        "s" | "sec" | "secs" | "second" | "seconds" => 1,
+        "" | "m" | "min" | "mins" | "minute" | "minutes" => 60,
+        "h" | "hour" | "hours" => 3600,
+        "d" | "day" | "days" => 86_400,
+        _ => return None,
+    };
+    count.checked_mul(unit)
+}
+
+impl UndoTree {
+    pub fn new() -> Self {
+        let root = UndoNode { parent: None, redo_child: None, group: UndoGroup::default(), time: now() };
+        UndoTree { nodes: vec![root], current: 0 }
+    }
+
+    /// States in the tree, counting the root
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Whether there's nothing to undo or redo
+    pub fn is_empty(&self) -> bool {
+        self.nodes.len() == 1
+    }
+
+    /// The state the text is in
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn parent(&self, node: usize) -> Option<usize> {
+        self.nodes[node].parent
+    }
+
+    /// The child redo would go to from a state
+    pub fn redo_child(&self, node: usize) -> Option<usize> {
+        self.nodes[node].redo_child
+    }
+
+    /// Edits leading to a state from its parent
+    pub fn group(&self, node: usize) -> &UndoGroup {
+        &self.nodes[node].group
+    }
+
+    /// When a state was made, in seconds since the Unix epoch
+    pub fn time(&self, node: usize) -> u64 {
+        self.nodes[node].time
+    }
+
+    /// States reached from a state by one step, oldest first
+    pub fn children(&self, node: usize) -> Vec<usize> {
+        (node + 1..self.nodes.len()).filter(|&child| self.nodes[child].parent == Some(node)).collect()
+    }
+
+    /// Add a state reached from the current one by `group`, and move to it
+    pub fn push(&mut self, group: UndoGroup) {
+        let node = self.nodes.len();
+        self.nodes.push(UndoNode { parent: Some(self.current), redo_child: None, group, time: now() });
+        self.nodes[self.current].redo_child = Some(node);
+        self.current = node;
+    }
+
+    /// Make redo from a state go to another of its children; returns the child's position
+    /// among them, counting from 1, and how many there are
+    pub fn switch_branch(&mut self, node: usize, forward: bool) -> Option<(usize, usize)> {
+        let children = self.children(node);
+        let at = children.iter().position(|&child| Some(child) == self.nodes[node].redo_child)?;
+        let next = if forward { (at + 1) % children.len() } else { (at + children.len() - 1) % children.len() };
+        self.nodes[node].redo_child = Some(children[next]);
+        Some((next + 1, children.len()))
+    }
+
+    /// The way from the current state to another: the states whose edits have to be undone,
+    /// nearest first, then those whose edits have to be made, in order
+    pub fn path_to(&self, target: usize) -> (Vec<usize>, Vec<usize>) {
+        // Parents are numbered before their children, so the higher side steps up until they meet
+        let (mut up, mut down) = (Vec::new(), Vec::new());
+        let (mut from, mut to) = (self.current, target);
+        while from != to {
+            if from > to {
+                up.push(from);
+                from = self.nodes[from].parent.unwrap_or(0);
+            } else {
+                down.push(to);
+                to = self.nodes[to].parent.unwrap_or(0);
+            }
+        }
+        down.reverse();
+        (up, down)
+    }
+
+    /// Move to another state, returning the way there as `path_to` does. Redo then retraces
+    /// the way back up, and leads down towards `target` from above it
+    pub fn go_to(&mut self, target: usize) -> (Vec<usize>, Vec<usize>) {
+        let (up, down) = self.path_to(target);
+        for &node in &up {
+            if let Some(parent) = self.nodes[node].parent {
+                self.nodes[parent].redo_child = Some(node);
+            }
+        }
+        let mut node = target;
+        while let Some(parent) = self.nodes[node].parent {
+            self.nodes[parent].redo_child = Some(node);
+            node = parent;
+        }
+        self.current = target;
+        (up, down)
+    }
+
+    /// Newest state made at or before `time`, or the root if none was
+    pub fn state_at(&self, time: u64) -> usize {
+        (0..self.nodes.len()).rev().find(|&node| self.nodes[node].time <= time).unwrap_or(0)
+    }
+
+    /// Whether the tree hangs together, as one read from a file might not
+    pub fn is_valid(&self) -> bool {
+        let Some(root) = self.nodes.first() else {
+            return false;
+        };
+        root.parent.is_none()
+            && self.current < self.nodes.len()
+            && self.nodes.iter().enumerate().skip(1).all(|(idx, node)| node.parent.is_some_and(|parent| parent < idx))
+            && self.nodes.iter().enumerate().all(|(idx, node)| {
+                node.redo_child.is_none_or(|child| self.nodes.get(child).is_some_and(|child| child.parent == Some(idx)))
+            })
+    }
+
+    /// Drop states until the text their edits carry comes to at most `max_bytes`: first
+    /// branches off the way from the root to the current state, oldest first, then the oldest
+    /// states on that way, the first one left becoming the new root
+    pub fn trim_to(&mut self, max_bytes: usize) {
+        let mut total: usize = self.nodes.iter().map(|node| node.group.text_len()).sum();
+        if total <= max_bytes {
+            return;
+        }
+        let mut keep = vec![true; self.nodes.len()];
+        let mut on_path = vec![false; self.nodes.len()];
+        let mut node = Some(self.current);
+        while let Some(idx) = node {
+            on_path[idx] = true;
+            node = self.nodes[idx].parent;
+        }
+
+        // Leaves off the path, dropped oldest first; a parent left without children is next
+        let mut child_count = vec![0; self.nodes.len()];
+        for node in &self.nodes {
+            if let Some(parent) = node.parent {
+                child_count[parent] += 1;
+            }
+        }
+        let mut leaves: BTreeSet<usize> = (0..self.nodes.len()).filter(|&idx| child_count[idx] == 0 && !on_path[idx]).collect();
+        while total > max_bytes {
+            let Some(leaf) = leaves.pop_first() else { break };
+            keep[leaf] = false;
+            total -= self.nodes[leaf].group.text_len();
+            if let Some(parent) = self.nodes[leaf].parent {
+                child_count[parent] -= 1;
+                if child_count[parent] == 0 && !on_path[parent] {
+                    leaves.insert(parent);
+                }
+            }
+        }
+
+        // Then the oldest states on the path; the first one kept becomes the root
+        let mut root = 0;
+        while total > max_bytes && root != self.current {
+            keep[root] = false;
+            root = (0..self.nodes.len()).find(|&idx| keep[idx] && on_path[idx] && self.nodes[idx].parent == Some(root)).unwrap_or(self.current);
+            total -= self.nodes[root].group.text_len();
+        }
+
+        let renumbered: HashMap<usize, usize> = (0..self.nodes.len()).filter(|&idx| keep[idx]).enumerate().map(|(new, old)| (old, new)).collect();
+        let nodes = std::mem::take(&mut self.nodes);
+        self.nodes = nodes.into_iter()
+            .enumerate()
+            .filter(|(idx, _)| keep[*idx])
+            .map(|(idx, mut node)| {
+                node.parent = node.parent.and_then(|parent| renumbered.get(&parent).copied());
+                node.redo_child = node.redo_child.and_then(|child| renumbered.get(&child).copied());
+                if idx == root {
+                    node.group = UndoGroup::default();
+                }
+                node
+            })
+            .collect();
+        self.current = renumbered[&self.current];
+        // Redo from a state whose branch was dropped goes to its newest one left
+        let unset: Vec<bool> = self.nodes.iter().map(|node| node.redo_child.is_none()).collect();
+        for idx in 1..self.nodes.len() {
+            if let Some(parent) = self.nodes[idx].parent.filter(|&parent| unset[parent]) {
+                self.nodes[parent].redo_child = Some(idx);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_and_ages() {
+        assert_eq!(parse_duration("30s"), Some(30));
+        assert_eq!(parse_duration("5"), Some(300));
+        assert_eq!(parse_duration(" 2 Hours "), Some(7200));
+        assert_eq!(parse_duration("1d"), Some(86_400));
+        assert_eq!(parse_duration("m"), None);
+        assert_eq!(parse_duration("5 weeks"), None);
+        assert_eq!(age(100, 105), "just now");
+        assert_eq!(age(100, 145), "45 s ago");
+        assert_eq!(age(0, 7300), "2 h ago");
+        assert_eq!(age(0, 200_000), "2 days ago");
+        assert_eq!(age(200, 100), "just now");
+    }
+
+    fn insert(text: &str) -> UndoGroup {
+        UndoGroup { ops: vec![EditOp::Insert { pos: 0, text: text.to_string() }], cursor_before: 0, cursor_after: text.len() }
+    }
+
+    /// Root 0 with child 1, which has children 2 and 3; the current state is 3
+    fn branched() -> UndoTree {
+        let mut tree = UndoTree::new();
+        tree.push(insert("aaaaaaaaaa"));
+        tree.push(insert("bbbbbbbbbb"));
+        tree.go_to(1);
+        tree.push(insert("cccccccccc"));
+        tree
+    }
+
+    #[test]
+    fn go_to_crosses_branches() {
+        let mut tree = branched();
+        assert_eq!(tree.children(1), [2, 3]);
+        assert_eq!(tree.go_to(2), (vec![3], vec![2]));
+        assert_eq!(tree.current(), 2);
+        assert_eq!(tree.redo_child(1), Some(2));
+
+        // Redo leads back down the way it came
+        assert_eq!(tree.go_to(0), (vec![2, 1], vec![]));
+        assert_eq!(tree.redo_child(0), Some(1));
+        assert_eq!(tree.redo_child(1), Some(2));
+        assert_eq!(tree.go_to(3), (vec![], vec![1, 3]));
+        assert_eq!(tree.redo_child(1), Some(3));
+        assert!(tree.is_valid());
+    }
+
+    #[test]
+    fn trim_drops_other_branches_first() {
+        let mut tree = branched();
+        tree.trim_to(20);
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.current(), 2);
+        assert_eq!(tree.children(1), [2]);
+        assert_eq!(tree.redo_child(1), Some(2));
+        assert!(tree.is_valid());
+    }
+
+    #[test]
+    fn trim_moves_the_root_up_the_current_path() {
+        let mut tree = branched();
+        tree.trim_to(10);
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree.current(), 1);
+        assert_eq!(tree.parent(1), Some(0));
+        assert!(tree.group(0).ops.is_empty());
+        assert!(tree.is_valid());
+
+        tree.trim_to(0);
+        assert_eq!(tree.len(), 1);
+        assert_eq!(tree.current(), 0);
+        assert!(tree.is_valid());
+    }
+}
@@ -0,0 +1,294 @@
+//! Undo tree view: every state the buffer's text has been in, to jump to any of them
+
+use crate::buffer::{Buffer, EditOp};
+use crate::theme::{self, Element};
+use crate::undo_tree;
+use crossterm::{
+    cursor::{Hide, MoveTo},
+    event::{self, Event, KeyCode, KeyModifiers},
+    execute,
+    style::{Attribute, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor},
+    terminal,
+};
+use std::io::{self, Write};
+use unicode_width::UnicodeWidthChar;
+
+/// Characters of an edit's text shown in its summary
+const SUMMARY_CHARS: usize = 40;
+
+struct Row {
+    state: usize,
+    depth: usize,    // Branches off the first-child line, for the indentation
+    summary: String, // What the edit leading to the state did
+    age: String,
+}
+
+/// A line of the preview
+pub enum DiffLine {
+    Header(String),
+    Removed(String),
+    Added(String),
+}
+
+pub struct UndoTreeView<'a> {
+    buffer: &'a Buffer,
+    saved: usize, // State the file was last saved in
+    rows: Vec<Row>,
+    selected: usize,
+    scroll: usize,
+    preview: Option<(usize, Vec<DiffLine>)>, // Diff for the state it was worked out for
+}
+
+/// An edit's text on one line, cut short
+fn snippet(text: &str) -> String {
+    let mut snippet: String = text.chars().take(SUMMARY_CHARS).map(|ch| if ch == '\n' { '⏎' } else { ch }).collect();
+    if text.chars().count() > SUMMARY_CHARS {
+        snippet.push('…');
+    }
+    snippet
+}
+
+/// What an undo step did, e.g. `+"import numpy"`
+fn summarize(ops: &[EditOp]) -> String {
+    let inserted: String = ops.iter().filter_map(|op| match op {
+        EditOp::Insert { text, .. } => Some(text.as_str()),
+        EditOp::Delete { .. } => None,
+    }).collect();
+    let deleted: String = ops.iter().filter_map(|op| match op {
+        EditOp::Delete { text, .. } => Some(text.as_str()),
+        EditOp::Insert { .. } => None,
+    }).collect();
+    match (inserted.is_empty(), deleted.is_empty()) {
+        (true, true) => "before any edit kept".to_string(),
+        (false, true) => format!("+\"{}\"", snippet(&inserted)),
+        (true, false) => format!("-\"{}\"", snippet(&deleted)),
+        (false, false) => format!("-\"{}\" +\"{}\"", snippet(&deleted), snippet(&inserted)),
+    }
+}
+
+/// Lines that differ between two texts, after the lines they start and end with alike
+pub fn diff_lines(from: &str, to: &str) -> Vec<DiffLine> {
+    let from: Vec<&str> = from.lines().collect();
+    let to: Vec<&str> = to.lines().collect();
+    let prefix = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let suffix = from[prefix..].iter().rev().zip(to[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let removed = &from[prefix..from.len() - suffix];
+    let added = &to[prefix..to.len() - suffix];
+    if removed.is_empty() && added.is_empty() {
+        return vec![DiffLine::Header("Same text as now".to_string())];
+    }
+    std::iter::once(DiffLine::Header(format!("@@ line {} @@", prefix + 1)))
+        .chain(removed.iter().map(|line| DiffLine::Removed(line.to_string())))
+        .chain(added.iter().map(|line| DiffLine::Added(line.to_string())))
+        .collect()
+}
+
+/// Print text cut or padded to exactly `width` columns
+pub fn print_fitted<W: Write>(writer: &mut W, text: &str, width: usize) -> io::Result<()> {
+    let mut used = 0;
+    let mut fitted = String::new();
+    for ch in text.chars() {
+        let ch_width = ch.width().unwrap_or(1);
+        if used + ch_width > width {
+            break;
+        }
+        fitted.push(ch);
+        used += ch_width;
+    }
+    execute!(writer, Print(fitted), Print(" ".repeat(width - used)))
+}
+
+impl<'a> UndoTreeView<'a> {
+    pub fn new(buffer: &'a Buffer, saved: usize) -> Self {
+        let tree = buffer.undo_tree();
+        let now = undo_tree::now();
+        let mut depths = vec![0; tree.len()];
+        let mut has_child = vec![false; tree.len()];
+        for state in 1..tree.len() {
+            let parent = tree.parent(state).unwrap_or(0);
+            // States are numbered in the order they were made, so the first child comes first
+            depths[state] = depths[parent] + usize::from(has_child[parent]);
+            has_child[parent] = true;
+        }
+        let rows: Vec<Row> = (0..tree.len())
+            .rev()
+            .map(|state| Row {
+                state,
+                depth: depths[state],
+                summary: summarize(&tree.group(state).ops),
+                age: undo_tree::age(tree.time(state), now),
+            })
+            .collect();
+        let selected = rows.iter().position(|row| row.state == tree.current()).unwrap_or(0);
+        UndoTreeView { buffer, saved, rows, selected, scroll: 0, preview: None }
+    }
+
+    /// Show the view until a state is picked or the user cancels
+    pub fn run<W: Write>(&mut self, writer: &mut W) -> io::Result<Option<usize>> {
+        loop {
+            self.draw(writer)?;
+
+            match event::read()? {
+                Event::Key(key) => {
+                    if key.kind == event::KeyEventKind::Release {
+                        continue;
+                    }
+
+                    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                    match key.code {
+                        KeyCode::Esc => return Ok(None),
+                        KeyCode::Char('c') if ctrl => return Ok(None),
+                        KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::ALT) => return Ok(None),
+                        KeyCode::Enter => return Ok(self.rows.get(self.selected).map(|row| row.state)),
+                        KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+                        KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+                        KeyCode::PageUp => self.move_selection(-10),
+                        KeyCode::PageDown => self.move_selection(10),
+                        KeyCode::Home => self.move_selection(-(self.rows.len() as i64)),
+                        KeyCode::End => self.move_selection(self.rows.len() as i64),
+                        _ => {}
+                    }
+                }
+                Event::Resize(_, _) => {}
+                _ => continue,
+            }
+        }
+    }
+
+    fn move_selection(&mut self, delta: i64) {
+        let last = self.rows.len() as i64 - 1;
+        self.selected = (self.selected as i64 + delta).clamp(0, last.max(0)) as usize;
+    }
+
+    /// The selected state's diff against the current text, worked out again if it changed
+    fn preview(&mut self) -> &[DiffLine] {
+        let state = self.rows.get(self.selected).map_or(0, |row| row.state);
+        if self.preview.as_ref().is_none_or(|(previewed, _)| *previewed != state) {
+            let lines = diff_lines(&self.buffer.to_string(), &self.buffer.text_at_state(state));
+            self.preview = Some((state, lines));
+        }
+        self.preview.as_ref().map_or(&[], |(_, lines)| lines.as_slice())
+    }
+
+    fn draw<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        let theme = theme::current();
+        let dialog = theme[Element::Dialog];
+        let (width, height) = terminal::size()?;
+        let box_width = (width as usize * 9 / 10).clamp(20.min(width as usize), 100);
+        let box_height = (height as usize * 4 / 5).clamp(10.min(height as usize), height as usize);
+        let x = (width as usize - box_width) as u16 / 2;
+        let y = (height as usize - box_height) as u16 / 3;
+        let inner = box_width.saturating_sub(2);
+        let list_rows = box_height.saturating_sub(3) / 2;
+        let preview_rows = box_height.saturating_sub(3 + list_rows);
+
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + list_rows {
+            self.scroll = self.selected + 1 - list_rows;
+        }
+
+        execute!(writer, Hide, SetBackgroundColor(dialog.background()), SetForegroundColor(dialog.foreground()))?;
+
+        // Top border with the title
+        let title = " Undo tree ";
+        execute!(
+            writer,
+            MoveTo(x, y),
+            Print("╭─"),
+            SetAttribute(Attribute::Bold),
+            SetForegroundColor(theme[Element::DialogTitle].foreground()),
+            Print(title),
+            SetAttribute(Attribute::Reset),
+            SetBackgroundColor(dialog.background()),
+            SetForegroundColor(dialog.foreground()),
+            Print(format!("{}╮", "─".repeat(inner.saturating_sub(title.len() + 1))))
+        )?;
+
+        for row in 0..list_rows {
+            execute!(writer, MoveTo(x, y + 1 + row as u16), SetBackgroundColor(dialog.background()), SetForegroundColor(dialog.foreground()), Print("│"))?;
+            self.draw_row(writer, self.scroll + row, inner)?;
+            execute!(writer, SetBackgroundColor(dialog.background()), SetForegroundColor(dialog.foreground()), Print("│"))?;
+        }
+
+        // Divider, then the preview
+        let divider_y = y + 1 + list_rows as u16;
+        let label = " Going there changes ";
+        execute!(
+            writer,
+            MoveTo(x, divider_y),
+            Print("├─"),
+            SetForegroundColor(theme[Element::DialogDim].foreground()),
+            Print(label),
+            SetForegroundColor(dialog.foreground()),
+            Print(format!("{}┤", "─".repeat(inner.saturating_sub(label.len() + 1))))
+        )?;
+        let added = theme[Element::GutterAdded].foreground();
+        let removed = theme[Element::GutterDeleted].foreground();
+        let dim = theme[Element::DialogDim].foreground();
+        let foreground = dialog.foreground();
+        let preview = self.preview();
+        for row in 0..preview_rows {
+            let (color, text) = match preview.get(row) {
+                Some(DiffLine::Header(text)) => (dim, text.clone()),
+                Some(DiffLine::Removed(text)) => (removed, format!("- {}", text)),
+                Some(DiffLine::Added(text)) => (added, format!("+ {}", text)),
+                None => (foreground, String::new()),
+            };
+            execute!(writer, MoveTo(x, divider_y + 1 + row as u16), SetForegroundColor(foreground), Print("│ "), SetForegroundColor(color))?;
+            print_fitted(writer, &text, inner.saturating_sub(1))?;
+            execute!(writer, SetForegroundColor(foreground), Print("│"))?;
+        }
+
+        // Bottom border with the key help
+        let bottom_y = divider_y + 1 + preview_rows as u16;
+        let help = " Enter: Go there  Esc: Cancel  ↑↓: Select ";
+        let help_x = x as usize + box_width.saturating_sub(help.chars().count() + 2);
+        execute!(
+            writer,
+            MoveTo(x, bottom_y),
+            Print(format!("╰{}╯", "─".repeat(inner))),
+            MoveTo(help_x as u16, bottom_y),
+            SetForegroundColor(dim),
+            Print(help),
+            ResetColor
+        )?;
+        writer.flush()
+    }
+
+    /// One state: a marker indented by its branch, its number, what led to it and its age
+    fn draw_row<W: Write>(&self, writer: &mut W, idx: usize, width: usize) -> io::Result<()> {
+        let Some(row) = self.rows.get(idx) else {
+            return execute!(writer, Print(" ".repeat(width)));
+        };
+        let theme = theme::current();
+        let dialog = theme[Element::Dialog];
+        let style = if idx == self.selected { theme[Element::DialogSelected].over(dialog) } else { dialog };
+        let current = row.state == self.buffer.undo_tree().current();
+        let marker = if current { "●" } else { "○" };
+        let indent = "│ ".repeat(row.depth.min(width / 4));
+        let saved = if row.state == self.saved { " [saved]" } else { "" };
+        let age = format!("{}{} ", saved, row.age);
+        let age_width = age.chars().count().min(width / 2);
+
+        let marker_color = if current { theme[Element::DialogTitle].foreground() } else { style.foreground() };
+        execute!(
+            writer,
+            SetBackgroundColor(style.background()),
+            SetForegroundColor(theme[Element::DialogDim].foreground()),
+            Print(format!(" {}", indent)),
+            SetForegroundColor(marker_color),
+            Print(marker),
+            SetForegroundColor(style.foreground()),
+        )?;
+        let used = 2 + indent.chars().count();
+        print_fitted(writer, &format!(" #{} {}", row.state, row.summary), width.saturating_sub(used + age_width))?;
+        let age: String = age.chars().rev().take(age_width).collect::<Vec<_>>().into_iter().rev().collect();
+        execute!(
+            writer,
+            SetForegroundColor(theme[Element::DialogDim].foreground()),
+            Print(age),
+            SetBackgroundColor(dialog.background())
+        )
+    }
+}